@@ -415,7 +415,7 @@ pub(crate) fn expand(attr: TokenStream, item: TokenStream) -> TokenStream {
                     db.#data_field.kind(),
                     db.#data_field.kind_name(),
                     snapshot_data,
-                ))
+                ).with_durability(db.#data_field.durability()))
             },
         });
 
@@ -550,11 +550,15 @@ pub(crate) fn expand(attr: TokenStream, item: TokenStream) -> TokenStream {
                 let parent_runtime = picante::HasRuntime::runtime(db);
                 // Create a snapshot runtime that shares the parent's RuntimeId.
                 // This allows in-flight query deduplication to work across snapshots.
-                let runtime = picante::Runtime::new_for_snapshot(parent_runtime.id());
+                let runtime = picante::Runtime::new_for_snapshot(parent_runtime.id())
+                    .with_clock(parent_runtime.clock().clone());
                 // Set the snapshot's revision to match the database's current revision.
                 // This ensures cached query results (which have verified_at from the db's revision)
                 // are considered valid in the snapshot.
-                runtime.set_current_revision(parent_runtime.current_revision());
+                runtime
+                    .restore_revision(parent_runtime.current_revision())
+                    .expect("a fresh snapshot runtime accepts any revision");
+                runtime.set_max_query_depth(parent_runtime.max_query_depth());
                 let mut snapshot = Self {
                     runtime,
                     ingredients: picante::IngredientRegistry::new(),
@@ -566,6 +570,12 @@ pub(crate) fn expand(attr: TokenStream, item: TokenStream) -> TokenStream {
                 // Without this, `db.ingredient(dep.kind)` in revalidation will return None for
                 // snapshots and all revalidation attempts will fail (forcing recomputation).
                 #(#snapshot_registers)*
+                for ingredient in snapshot.ingredients.persistable_ingredients() {
+                    snapshot.runtime.register_kind(
+                        picante::persist::PersistableIngredient::kind(ingredient),
+                        picante::persist::PersistableIngredient::kind_name(ingredient),
+                    );
+                }
 
                 snapshot
             }
@@ -638,6 +648,13 @@ pub(crate) fn expand(attr: TokenStream, item: TokenStream) -> TokenStream {
 
                 #(#ctor_lets)*
                 #(#ctor_registers)*
+                // Name every kind, so events and errors can be labelled in logs.
+                for ingredient in ingredients.persistable_ingredients() {
+                    runtime.register_kind(
+                        picante::persist::PersistableIngredient::kind(ingredient),
+                        picante::persist::PersistableIngredient::kind_name(ingredient),
+                    );
+                }
 
                 Self {
                     runtime,
@@ -697,11 +714,7 @@ pub(crate) fn expand(attr: TokenStream, item: TokenStream) -> TokenStream {
             ///
             /// db.save_to_cache_with_options(
             ///     "cache.bin",
-            ///     &CacheSaveOptions {
-            ///         max_bytes: Some(4096),
-            ///         max_records_per_section: None,
-            ///         max_record_bytes: None,
-            ///     }
+            ///     &CacheSaveOptions::default().with_max_bytes(4096),
             /// ).await?;
             /// ```
             #vis async fn save_to_cache_with_options(
@@ -857,11 +870,7 @@ pub(crate) fn expand(attr: TokenStream, item: TokenStream) -> TokenStream {
             /// let new_revision = db.compact_wal_with_options(
             ///     "cache.bin",
             ///     "cache.wal",
-            ///     &picante::persist::CacheSaveOptions {
-            ///         max_bytes: Some(10_000_000),
-            ///         max_records_per_section: None,
-            ///         max_record_bytes: None,
-            ///     },
+            ///     &picante::persist::CacheSaveOptions::default().with_max_bytes(10_000_000),
             ///     true,
             /// ).await?;
             /// ```
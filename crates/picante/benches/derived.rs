@@ -2,6 +2,7 @@ use divan::{Bencher, black_box};
 use picante::db::{DynIngredient, IngredientLookup, IngredientRegistry};
 use picante::ingredient::{DerivedIngredient, InputIngredient};
 use picante::key::QueryKindId;
+use picante::persist::PersistableIngredient;
 use picante::runtime::{HasRuntime, Runtime};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -67,6 +68,110 @@ fn derived_get_hit(bencher: Bencher) {
     });
 }
 
+/// Large keys on the recompute path: the key handed to `get` is moved into compute rather
+/// than cloned or decoded back from its encoded bytes.
+#[divan::bench(args = [1024, 64 * 1024])]
+fn derived_recompute_large_key(bencher: Bencher, key_len: usize) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let db = Db::default();
+    let input: Arc<InputIngredient<String, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Seed"));
+    input.set(&db, String::new(), 0);
+
+    let input_for_compute = input.clone();
+    let derived: Arc<DerivedIngredient<Db, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "KeyLen",
+        move |db, key| {
+            let input = input_for_compute.clone();
+            Box::pin(async move {
+                let seed = input.get(db, &String::new())?.unwrap_or_default();
+                Ok(key.len() as u64 + seed)
+            })
+        },
+    ));
+
+    let key = "k".repeat(key_len);
+    let mut seed = 0;
+    bencher
+        .with_inputs(|| {
+            seed += 1;
+            input.set(&db, String::new(), seed);
+            key.clone()
+        })
+        .bench_local_values(|key| {
+            rt.block_on(async {
+                let v = derived.get(&db, key).await.unwrap();
+                black_box(v);
+            })
+        });
+}
+
+/// Large keys on the hit path: the key is encoded once per lookup.
+#[divan::bench(args = [1024, 64 * 1024])]
+fn derived_get_hit_large_key(bencher: Bencher, key_len: usize) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let db = Db::default();
+    let derived: Arc<DerivedIngredient<Db, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "KeyLen",
+        |_db, key| Box::pin(async move { Ok(key.len() as u64) }),
+    ));
+
+    let key = "k".repeat(key_len);
+    rt.block_on(async {
+        let _ = derived.get(&db, key.clone()).await.unwrap();
+    });
+
+    bencher
+        .with_inputs(|| key.clone())
+        .bench_local_values(|key| {
+            rt.block_on(async {
+                let v = derived.get(&db, key).await.unwrap();
+                black_box(v);
+            })
+        });
+}
+
+/// Cold-start decoding of a derived section. Large sections are decoded on several threads.
+#[divan::bench(args = [1_000, 100_000])]
+fn derived_load_records(bencher: Bencher, records: usize) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let make = || -> DerivedIngredient<Db, String, u64> {
+        DerivedIngredient::new(QueryKindId(2), "KeyLen", |_db, key| {
+            Box::pin(async move { Ok(key.len() as u64) })
+        })
+    };
+
+    let db = Db::default();
+    let source = make();
+    let saved = rt.block_on(async {
+        for i in 0..records {
+            let _ = source.get(&db, format!("key-{i}")).await.unwrap();
+        }
+        source.save_records().await.unwrap()
+    });
+
+    bencher
+        .with_inputs(|| (make(), saved.clone()))
+        .bench_local_values(|(target, saved)| {
+            target.load_records(saved).unwrap();
+            black_box(target);
+        });
+}
+
 fn main() {
     divan::main();
 }
@@ -0,0 +1,44 @@
+use divan::{Bencher, black_box};
+use picante::ingredient::InternedIngredient;
+use picante::key::QueryKindId;
+
+/// `n` distinct symbols, each repeated once, as a batch load would see them.
+fn symbols(n: usize) -> Vec<String> {
+    (0..n).chain(0..n).map(|i| format!("symbol-{i}")).collect()
+}
+
+#[divan::bench(args = [1_000, 100_000])]
+fn intern_loop(bencher: Bencher, n: usize) {
+    bencher
+        .with_inputs(|| {
+            (
+                InternedIngredient::<String>::new(QueryKindId(1), "Symbol"),
+                symbols(n),
+            )
+        })
+        .bench_local_values(|(interned, values)| {
+            for value in values {
+                black_box(interned.intern(value).unwrap());
+            }
+            interned
+        });
+}
+
+#[divan::bench(args = [1_000, 100_000])]
+fn intern_many(bencher: Bencher, n: usize) {
+    bencher
+        .with_inputs(|| {
+            (
+                InternedIngredient::<String>::new(QueryKindId(1), "Symbol"),
+                symbols(n),
+            )
+        })
+        .bench_local_values(|(interned, values)| {
+            black_box(interned.intern_many(values).unwrap());
+            interned
+        });
+}
+
+fn main() {
+    divan::main();
+}
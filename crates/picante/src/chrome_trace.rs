@@ -0,0 +1,122 @@
+//! Chrome Trace Event output (see [`crate::Runtime::start_chrome_trace`]).
+//!
+//! Writes the JSON object format (`{"traceEvents":[...]}`) understood by `chrome://tracing`
+//! and Perfetto, one complete (`"ph":"X"`) event per query computation.
+
+use crate::key::QueryKindId;
+use crate::revision::Revision;
+use std::collections::HashMap;
+use std::io::{self, BufWriter, Write};
+use std::time::Instant;
+
+/// One finished computation, as reported by the runtime's running-query guard.
+pub(crate) struct ComputeSpan {
+    pub(crate) kind: QueryKindId,
+    pub(crate) kind_name: &'static str,
+    pub(crate) key_hash: u64,
+    pub(crate) revision: Revision,
+    pub(crate) depth: usize,
+    pub(crate) task: Option<tokio::task::Id>,
+    pub(crate) start: Instant,
+    pub(crate) end: Instant,
+}
+
+/// An open trace file.
+pub(crate) struct ChromeTrace {
+    out: BufWriter<Box<dyn Write + Send>>,
+    origin: Instant,
+    /// Tokio task ids mapped to small, stable thread ids; computations outside a task use 0.
+    tids: HashMap<tokio::task::Id, u64>,
+    events: usize,
+    /// First write error; reported by [`Self::finish`].
+    error: Option<io::Error>,
+}
+
+impl std::fmt::Debug for ChromeTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChromeTrace")
+            .field("events", &self.events)
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ChromeTrace {
+    pub(crate) fn start(out: Box<dyn Write + Send>, origin: Instant) -> io::Result<Self> {
+        let mut out = BufWriter::new(out);
+        out.write_all(b"{\"traceEvents\":[")?;
+        Ok(Self {
+            out,
+            origin,
+            tids: HashMap::new(),
+            events: 0,
+            error: None,
+        })
+    }
+
+    pub(crate) fn record(&mut self, span: &ComputeSpan) {
+        if self.error.is_some() {
+            return;
+        }
+        let next_tid = self.tids.len() as u64 + 1;
+        let tid = match span.task {
+            Some(task) => *self.tids.entry(task).or_insert(next_tid),
+            None => 0,
+        };
+        let ts = span.start.saturating_duration_since(self.origin).as_nanos();
+        let dur = span.end.saturating_duration_since(span.start).as_nanos();
+        let separator = if self.events == 0 { "\n" } else { ",\n" };
+        let result = write!(
+            self.out,
+            "{separator}{{\"name\":\"{}\",\"cat\":\"picante\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\
+             \"pid\":1,\"tid\":{tid},\"args\":{{\"kind\":{},\"key_hash\":\"{:016x}\",\
+             \"revision\":{},\"depth\":{}}}}}",
+            JsonEscaped(span.kind_name),
+            Micros(ts),
+            Micros(dur),
+            span.kind.0,
+            span.key_hash,
+            span.revision.0,
+            span.depth,
+        );
+        match result {
+            Ok(()) => self.events += 1,
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// Close the JSON document and flush it, returning the first error seen while writing.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+        self.out.write_all(b"\n]}\n")?;
+        self.out.flush()
+    }
+}
+
+/// Nanoseconds printed as fractional microseconds, the unit of `ts` and `dur`.
+struct Micros(u128);
+
+impl std::fmt::Display for Micros {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:03}", self.0 / 1000, self.0 % 1000)
+    }
+}
+
+struct JsonEscaped<'a>(&'a str);
+
+impl std::fmt::Display for JsonEscaped<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use std::fmt::Write as _;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//! Time sources for time-based runtime features.
+//!
+//! Runtime features that deal in elapsed time ([`Runtime::in_flight`], cache save/load
+//! timings in [`Runtime::cache_report`]) read it from [`Runtime::clock`] rather than calling
+//! [`Instant::now`] directly, and features that wait (compute timeouts and retry backoff,
+//! see [`DerivedIngredient::with_timeout`] and [`DerivedIngredient::with_retry`]) sleep on
+//! it too. Production runtimes use [`SystemClock`]; tests can install a [`ManualClock`] and
+//! step time forward explicitly, without `tokio::time::pause`.
+//!
+//! [`Runtime::in_flight`]: crate::runtime::Runtime::in_flight
+//! [`Runtime::cache_report`]: crate::runtime::Runtime::cache_report
+//! [`Runtime::clock`]: crate::runtime::Runtime::clock
+//! [`DerivedIngredient::with_timeout`]: crate::ingredient::DerivedIngredient::with_timeout
+//! [`DerivedIngredient::with_retry`]: crate::ingredient::DerivedIngredient::with_retry
+
+use futures::future::{BoxFuture, Either};
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// A source of monotonic time.
+pub trait Clock: Send + Sync + Debug {
+    /// The current instant. Must never go backwards.
+    fn now(&self) -> Instant;
+
+    /// Wait until `duration` has passed on this clock.
+    ///
+    /// Defaults to [`tokio::time::sleep`], which needs a tokio runtime with the time driver
+    /// enabled.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// Run `future` until it finishes or `limit` passes on `clock`, whichever comes first.
+///
+/// Returns `None` on timeout, dropping `future`.
+pub async fn timeout<F: Future>(
+    clock: &dyn Clock,
+    limit: Duration,
+    future: F,
+) -> Option<F::Output> {
+    match futures::future::select(std::pin::pin!(future), clock.sleep(limit)).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(((), _)) => None,
+    }
+}
+
+/// The real clock: [`Instant::now`]. The default for every runtime.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to, for deterministic tests.
+///
+/// ```
+/// use picante::clock::{Clock, ManualClock};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let start = clock.now();
+/// clock.advance(Duration::from_secs(30));
+/// assert_eq!(clock.now() - start, Duration::from_secs(30));
+/// ```
+///
+/// [`Clock::sleep`] on a manual clock finishes once [`ManualClock::advance`] has moved it far
+/// enough, however much real time that takes.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: watch::Sender<Instant>,
+}
+
+impl ManualClock {
+    /// A clock frozen at the current instant.
+    pub fn new() -> Self {
+        Self {
+            now: watch::Sender::new(Instant::now()),
+        }
+    }
+
+    /// Move the clock forward by `by`, waking the sleeps that have run out.
+    pub fn advance(&self, by: Duration) {
+        self.now.send_modify(|now| *now += by);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.borrow()
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        let mut now = self.now.subscribe();
+        let deadline = *now.borrow() + duration;
+        Box::pin(async move {
+            // A dropped clock never advances again.
+            if now.wait_for(|now| *now >= deadline).await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        })
+    }
+}
@@ -1,10 +1,11 @@
 //! Database integration traits used by Picante for precise revalidation.
 
 use crate::error::PicanteResult;
-use crate::key::{Key, QueryKindId};
+use crate::key::{Dep, Key, QueryKindId};
 use crate::persist::PersistableIngredient;
-use crate::revision::Revision;
+use crate::revision::{Durability, Revision};
 use futures::future::BoxFuture;
+use std::any::Any;
 use std::collections::HashMap;
 use std::sync::Arc;
 
@@ -19,6 +20,45 @@ pub struct Touch {
 pub trait DynIngredient<DB>: PersistableIngredient {
     /// Ensure the `(kind, key)` is valid at `db.runtime().current_revision()` and return metadata.
     fn touch<'a>(&'a self, db: &'a DB, key: Key) -> BoxFuture<'a, PicanteResult<Touch>>;
+
+    /// If `key` names a memoized cell that has not been verified at `revision` yet, return the
+    /// dependencies that must be validated before it.
+    ///
+    /// Used by [`ValidationStrategy::Iterative`](crate::ingredient::ValidationStrategy) to walk
+    /// deep dependency graphs without recursing. Never blocks: a cell that is currently locked
+    /// is reported as `None`.
+    fn stale_deps(&self, _key: &Key, _revision: Revision) -> Option<Arc<[Dep]>> {
+        None
+    }
+
+    /// Drop the memoized cell for `key`, if any, without bumping the revision, so the next
+    /// access recomputes it.
+    ///
+    /// Used to throw away results computed from a provisional value during fixed-point
+    /// iteration (see `DerivedIngredient::with_fixed_point`). Ingredients without memoized
+    /// cells ignore it.
+    fn discard_cell(&self, _db: &DB, _key: &Key) {}
+
+    /// The value memoized for `key` if it is verified at the current revision, type-erased
+    /// (downcast it to the ingredient's value type), without recording a dependency or
+    /// waiting on a lock.
+    ///
+    /// Used to attach values to
+    /// [`RuntimeEvent::EagerOutputRecomputed`](crate::runtime::RuntimeEvent::EagerOutputRecomputed).
+    /// Ingredients without memoized cells return `None`.
+    fn cached_value(&self, _db: &DB, _key: &Key) -> Option<Arc<dyn Any + Send + Sync>> {
+        None
+    }
+
+    /// How durable the value of `key` is: for an input, the durability it was declared
+    /// with; for a memoized cell, the lowest durability among its dependencies.
+    ///
+    /// Derived queries take the minimum over their dependencies to decide which input
+    /// changes they can skip validation for. The default, [`Durability::Low`], never
+    /// skips.
+    fn durability(&self, _key: &Key) -> Durability {
+        Durability::Low
+    }
 }
 
 /// A simple registry of ingredients keyed by [`QueryKindId`].
@@ -41,11 +81,27 @@ impl<DB> IngredientRegistry<DB> {
     }
 
     /// Register an ingredient (overwrites any previous registration for the same kind id).
+    ///
+    /// # Panics
+    ///
+    /// If an ingredient with a different `kind_name` is already registered under the same kind
+    /// id: two distinct kinds collided (e.g. two [`QueryKindId::for_types`] ids for the same
+    /// key and value types), and their cached values would be mixed up.
     pub fn register<I>(&mut self, ingredient: Arc<I>)
     where
         I: DynIngredient<DB> + 'static,
     {
         let kind = ingredient.kind();
+        if let Some(existing) = self.ingredients.get(&kind) {
+            assert!(
+                existing.kind_name() == ingredient.kind_name(),
+                "query kind id collision: `{}` and `{}` both use kind id {}; \
+                 give one of them an explicit QueryKindId",
+                existing.kind_name(),
+                ingredient.kind_name(),
+                kind.0,
+            );
+        }
         self.ingredients.insert(kind, ingredient);
     }
 
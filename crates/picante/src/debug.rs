@@ -46,6 +46,8 @@ pub struct DependencyGraph {
     pub forward_deps: HashMap<DynKey, Vec<Dep>>,
     /// Reverse dependencies: for each query, what depends on it?
     pub reverse_deps: HashMap<DynKey, Vec<DynKey>>,
+    /// Names registered with the runtime (see `Runtime::register_kind`), used as node labels.
+    pub kind_names: HashMap<QueryKindId, &'static str>,
 }
 
 impl DependencyGraph {
@@ -70,6 +72,7 @@ impl DependencyGraph {
         Self {
             forward_deps,
             reverse_deps,
+            kind_names: runtime.kind_names(),
         }
     }
 
@@ -80,7 +83,8 @@ impl DependencyGraph {
     /// dot -Tpng deps.dot -o deps.png
     /// ```
     ///
-    /// Nodes are labeled with `kind_id:key_hash` for uniqueness.
+    /// Nodes are labeled with the kind's registered name (or `kind_<id>` if it has none) and
+    /// the key hash.
     /// Edges point from queries to their dependencies.
     pub fn write_dot<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
         let mut file = std::fs::File::create(path)?;
@@ -109,7 +113,11 @@ impl DependencyGraph {
         // Write node declarations
         for node in &all_nodes {
             let node_id = format!("{}_{:x}", node.kind.0, node.key.hash());
-            let label = format!("kind_{}\\nkey_{:x}", node.kind.0, node.key.hash());
+            let kind = match self.kind_names.get(&node.kind) {
+                Some(name) => name.to_string(),
+                None => format!("kind_{}", node.kind.0),
+            };
+            let label = format!("{kind}\\nkey_{:x}", node.key.hash());
             writeln!(writer, "  {} [label=\"{}\"];", node_id, label)?;
         }
 
@@ -375,6 +383,16 @@ impl TraceCollector {
                         revision,
                         timestamp,
                     },
+                    RuntimeEvent::BatchCommitted { to, .. } => TraceEvent::RevisionBumped {
+                        revision: to,
+                        timestamp,
+                    },
+                    RuntimeEvent::EagerOutputRecomputed { .. }
+                    | RuntimeEvent::QueryRecomputed { .. } => {
+                        // Value changes are already reported as QueryChanged
+                        continue;
+                    }
+                    RuntimeEvent::ShuttingDown { .. } | RuntimeEvent::Lagged { .. } => continue,
                     RuntimeEvent::RevisionSet { .. } => {
                         // Skip RevisionSet as it's primarily for cache loading
                         continue;
@@ -0,0 +1,81 @@
+//! Push-mode recomputation of eager outputs.
+//!
+//! Picante is pull-based: derived queries only recompute when read. Queries registered with
+//! [`Runtime::register_eager_output`](crate::runtime::Runtime::register_eager_output) are the
+//! exception: an input change schedules every eager output that transitively depends on it,
+//! and the functions here bring those outputs up to date and announce them with
+//! [`RuntimeEvent::EagerOutputRecomputed`](crate::runtime::RuntimeEvent::EagerOutputRecomputed).
+//!
+//! Recomputation goes through the normal revalidation path, so outputs whose dependencies did
+//! not actually change are only revalidated, and unchanged values keep their `changed_at`.
+
+use crate::db::IngredientLookup;
+use tracing::debug;
+
+/// Recompute every eager output scheduled so far, returning how many were processed.
+///
+/// Outputs that fail to compute are skipped (the error stays cached in the cell and is
+/// returned to the next reader).
+pub async fn recompute_eager_outputs<DB>(db: &DB) -> usize
+where
+    DB: IngredientLookup,
+{
+    let pending = db.runtime().take_pending_eager_outputs();
+
+    for query in &pending {
+        let Some(ingredient) = db.ingredient(query.kind) else {
+            debug!(kind = query.kind.0, "eager output has no registered ingredient");
+            continue;
+        };
+
+        let revision = db.runtime().current_revision();
+        match ingredient.touch(db, query.key.clone()).await {
+            Ok(touch) => {
+                let value = ingredient.cached_value(db, &query.key);
+                db.runtime().notify_eager_output_recomputed(
+                    revision,
+                    query.clone(),
+                    touch.changed_at,
+                    value,
+                );
+            }
+            Err(error) => {
+                debug!(
+                    kind = query.kind.0,
+                    key_hash = %format!("{:016x}", query.key.hash()),
+                    %error,
+                    "eager output failed"
+                );
+            }
+        }
+    }
+
+    pending.len()
+}
+
+/// Recompute eager outputs as they get scheduled, until the runtime shuts down.
+///
+/// Run this on a dedicated task; it returns once
+/// [`Runtime::begin_shutdown`](crate::runtime::Runtime::begin_shutdown) is called (dropping or
+/// aborting it also stops push mode). Outputs are recomputed one batch at a time;
+/// invalidations that arrive while a batch runs coalesce into the next batch, so a burst of
+/// input changes costs at most one extra recomputation per output rather than one per change.
+pub async fn drive_eager_outputs<DB>(db: &DB)
+where
+    DB: IngredientLookup,
+{
+    let shutdown = db.runtime().shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = db.runtime().eager_outputs_scheduled() => {}
+        }
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = recompute_eager_outputs(db) => {}
+        }
+    }
+    debug!("eager output driver stopped (shutdown)");
+}
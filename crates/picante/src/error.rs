@@ -1,8 +1,10 @@
 //! Error types used throughout Picante.
 
 use crate::key::{DynKey, QueryKindId};
+use crate::revision::Revision;
 use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Result type used by Picante APIs.
 pub type PicanteResult<T> = std::result::Result<T, Arc<PicanteError>>;
@@ -24,6 +26,10 @@ pub enum PicanteError {
         what: &'static str,
         /// Human-readable error message.
         message: String,
+        /// Kind id of the ingredient involved, when known.
+        kind: Option<QueryKindId>,
+        /// Debug name of the ingredient involved, when known.
+        kind_name: Option<&'static str>,
     },
 
     /// Failed to decode a value using `facet-postcard`.
@@ -32,6 +38,10 @@ pub enum PicanteError {
         what: &'static str,
         /// Human-readable error message.
         message: String,
+        /// Kind id of the ingredient involved, when known.
+        kind: Option<QueryKindId>,
+        /// Debug name of the ingredient involved, when known.
+        kind_name: Option<&'static str>,
     },
 
     /// Cache I/O or format errors.
@@ -48,6 +58,28 @@ pub enum PicanteError {
         id: u32,
     },
 
+    /// An interned id was minted before the interning table was last cleared (e.g. by
+    /// loading a cache), so it may now name a different value.
+    StaleInternId {
+        /// Kind id of the interned ingredient.
+        kind: QueryKindId,
+        /// The stale id.
+        id: u32,
+        /// Generation the id was minted in.
+        generation: u32,
+        /// Current generation of the ingredient.
+        current: u32,
+    },
+
+    /// A new value could not be interned because the table is full (see
+    /// `InternedIngredient::with_max_entries`).
+    InternCapacityExceeded {
+        /// Kind id of the interned ingredient.
+        kind: QueryKindId,
+        /// The configured maximum number of entries.
+        max: usize,
+    },
+
     /// An input value was requested but is not present (missing or removed).
     MissingInputValue {
         /// Kind id of the input ingredient.
@@ -56,6 +88,92 @@ pub enum PicanteError {
         key_hash: u64,
     },
 
+    /// A query kept finishing after the revision had already moved on, and gave up after
+    /// its configured number of retries (see `DerivedIngredient::with_max_stale_retries`).
+    Contended {
+        /// Kind id of the query.
+        kind: QueryKindId,
+        /// Stable hash of the encoded key bytes (for diagnostics).
+        key_hash: u64,
+        /// Number of consecutive retries performed.
+        retries: u32,
+    },
+
+    /// An attempt to move a runtime's revision backwards (e.g. loading a stale cache into a
+    /// database that has already advanced).
+    RevisionRegression {
+        /// The runtime's current revision.
+        current: Revision,
+        /// The (lower) revision that was requested.
+        requested: Revision,
+    },
+
+    /// A read pinned to a [`Snapshot`](crate::runtime::Snapshot) found the query's value
+    /// replaced since, so the value at the pinned revision is gone.
+    SnapshotExpired {
+        /// Kind id of the query.
+        kind: QueryKindId,
+        /// Stable hash of the encoded key bytes (for diagnostics).
+        key_hash: u64,
+        /// The pinned revision.
+        snapshot: Revision,
+        /// The runtime's current revision.
+        current: Revision,
+    },
+
+    /// A compute gave up at a [`checkpoint`](crate::frame::checkpoint) because the revision
+    /// moved on while it was running. The query is recomputed at the new revision.
+    Cancelled {
+        /// Kind id of the query.
+        kind: QueryKindId,
+        /// Stable hash of the encoded key bytes (for diagnostics).
+        key_hash: u64,
+        /// Revision the compute started at.
+        started_at: Revision,
+        /// The runtime's revision when it was cancelled.
+        current: Revision,
+    },
+
+    /// A query would have computed deeper than the runtime's limit (see
+    /// `Runtime::set_max_query_depth`).
+    DepthExceeded {
+        /// Kind id of the query.
+        kind: QueryKindId,
+        /// Stable hash of the encoded key bytes (for diagnostics).
+        key_hash: u64,
+        /// The configured maximum depth.
+        limit: usize,
+    },
+
+    /// Fixed-point iteration over a cycle didn't settle within the configured number of
+    /// rounds (see `DerivedIngredient::with_fixed_point`).
+    CycleDidNotConverge {
+        /// Kind id of the query the cycle was iterated on.
+        kind: QueryKindId,
+        /// Stable hash of the encoded key bytes (for diagnostics).
+        key_hash: u64,
+        /// Number of compute rounds performed.
+        iterations: u32,
+    },
+
+    /// A compute ran longer than its ingredient allows (see `DerivedIngredient::with_timeout`)
+    /// and was abandoned.
+    Timeout {
+        /// Kind id of the query.
+        kind: QueryKindId,
+        /// Stable hash of the encoded key bytes (for diagnostics).
+        key_hash: u64,
+        /// How long the compute ran before it was abandoned (the configured timeout).
+        elapsed: Duration,
+    },
+
+    /// The runtime's forward and reverse dependency indexes disagree (see
+    /// `Runtime::debug_verify_dep_index`).
+    DepIndexMismatch {
+        /// One description per missing edge.
+        problems: Vec<String>,
+    },
+
     /// A query panicked during execution (caught to avoid poisoning the runtime).
     Panic {
         /// Human-readable panic message (best effort).
@@ -71,12 +189,7 @@ impl fmt::Display for PicanteError {
 
                 // Show the full stack path
                 for (i, query) in stack.iter().enumerate() {
-                    write!(
-                        f,
-                        "  → kind_{}, key_{:016x}",
-                        query.kind.0,
-                        query.key.hash()
-                    )?;
+                    write!(f, "  → {query}")?;
                     if i == 0 {
                         writeln!(f, "  (initial)")?;
                     } else {
@@ -85,29 +198,207 @@ impl fmt::Display for PicanteError {
                 }
 
                 // Show the requested query that creates the cycle
-                write!(
-                    f,
-                    "  → kind_{}, key_{:016x}  ← cycle (already in stack)",
-                    requested.kind.0,
-                    requested.key.hash()
-                )?;
+                write!(f, "  → {requested}  ← cycle (already in stack)")?;
 
                 Ok(())
             }
-            PicanteError::Encode { what, message } => write!(f, "encode {what} failed: {message}"),
-            PicanteError::Decode { what, message } => write!(f, "decode {what} failed: {message}"),
+            PicanteError::Encode {
+                what,
+                message,
+                kind,
+                kind_name,
+            } => {
+                write!(f, "encode {what} failed")?;
+                write_ingredient(f, *kind, *kind_name)?;
+                write!(f, ": {message}")
+            }
+            PicanteError::Decode {
+                what,
+                message,
+                kind,
+                kind_name,
+            } => {
+                write!(f, "decode {what} failed")?;
+                write_ingredient(f, *kind, *kind_name)?;
+                write!(f, ": {message}")
+            }
             PicanteError::Cache { message } => write!(f, "cache error: {message}"),
             PicanteError::MissingInternedValue { kind, id } => {
                 write!(f, "missing interned value (kind {}, id {id})", kind.0)
             }
+            PicanteError::StaleInternId {
+                kind,
+                id,
+                generation,
+                current,
+            } => write!(
+                f,
+                "stale interned id (kind {}, id {id}, generation {generation}, current {current})",
+                kind.0
+            ),
+            PicanteError::InternCapacityExceeded { kind, max } => {
+                write!(f, "interned table full (kind {}, max {max} entries)", kind.0)
+            }
             PicanteError::MissingInputValue { kind, key_hash } => write!(
                 f,
                 "missing input value (kind {}, key {:016x})",
                 kind.0, key_hash
             ),
+            PicanteError::Contended {
+                kind,
+                key_hash,
+                retries,
+            } => write!(
+                f,
+                "query (kind {}, key {:016x}) gave up after {retries} retries: inputs kept changing",
+                kind.0, key_hash
+            ),
+            PicanteError::RevisionRegression { current, requested } => write!(
+                f,
+                "revision regression: runtime is at revision {}, refusing to go back to {}",
+                current.0, requested.0
+            ),
+            PicanteError::SnapshotExpired {
+                kind,
+                key_hash,
+                snapshot,
+                current,
+            } => write!(
+                f,
+                "query (kind {}, key {:016x}) changed after snapshot revision {} (now at {})",
+                kind.0, key_hash, snapshot.0, current.0
+            ),
+            PicanteError::Cancelled {
+                kind,
+                key_hash,
+                started_at,
+                current,
+            } => write!(
+                f,
+                "query (kind {}, key {:016x}) cancelled: started at revision {}, runtime is at {}",
+                kind.0, key_hash, started_at.0, current.0
+            ),
+            PicanteError::DepthExceeded {
+                kind,
+                key_hash,
+                limit,
+            } => write!(
+                f,
+                "query (kind {}, key {:016x}) exceeds the maximum query depth of {limit}",
+                kind.0, key_hash
+            ),
+            PicanteError::CycleDidNotConverge {
+                kind,
+                key_hash,
+                iterations,
+            } => write!(
+                f,
+                "cycle through query (kind {}, key {:016x}) did not converge after {iterations} \
+                 iterations",
+                kind.0, key_hash
+            ),
+            PicanteError::Timeout {
+                kind,
+                key_hash,
+                elapsed,
+            } => write!(
+                f,
+                "query (kind {}, key {:016x}) timed out after {elapsed:?}",
+                kind.0, key_hash
+            ),
+            PicanteError::DepIndexMismatch { problems } => write!(
+                f,
+                "dependency index mismatch ({} problems): {}",
+                problems.len(),
+                problems.join("; ")
+            ),
             PicanteError::Panic { message } => write!(f, "query panicked: {message}"),
         }
     }
 }
 
+fn write_ingredient(
+    f: &mut fmt::Formatter<'_>,
+    kind: Option<QueryKindId>,
+    kind_name: Option<&'static str>,
+) -> fmt::Result {
+    match (kind_name, kind) {
+        (Some(name), Some(kind)) => write!(f, " in `{name}` (kind {})", kind.0),
+        (Some(name), None) => write!(f, " in `{name}`"),
+        (None, Some(kind)) => write!(f, " in kind {}", kind.0),
+        (None, None) => Ok(()),
+    }
+}
+
+impl PicanteError {
+    /// The ingredient kinds this error mentions, in order of first mention.
+    ///
+    /// `Runtime::display_error` uses them to label the error with ingredient names.
+    pub fn kinds(&self) -> Vec<QueryKindId> {
+        let mut kinds = Vec::new();
+        match self {
+            PicanteError::Cycle { requested, stack } => {
+                for query in stack.iter().chain(std::iter::once(requested)) {
+                    if !kinds.contains(&query.kind) {
+                        kinds.push(query.kind);
+                    }
+                }
+            }
+            PicanteError::Encode { kind, .. } | PicanteError::Decode { kind, .. } => {
+                kinds.extend(*kind);
+            }
+            PicanteError::MissingInternedValue { kind, .. }
+            | PicanteError::StaleInternId { kind, .. }
+            | PicanteError::InternCapacityExceeded { kind, .. }
+            | PicanteError::MissingInputValue { kind, .. }
+            | PicanteError::Contended { kind, .. }
+            | PicanteError::SnapshotExpired { kind, .. }
+            | PicanteError::Cancelled { kind, .. }
+            | PicanteError::DepthExceeded { kind, .. }
+            | PicanteError::CycleDidNotConverge { kind, .. }
+            | PicanteError::Timeout { kind, .. } => kinds.push(*kind),
+            PicanteError::Cache { .. }
+            | PicanteError::RevisionRegression { .. }
+            | PicanteError::DepIndexMismatch { .. }
+            | PicanteError::Panic { .. } => {}
+        }
+        kinds
+    }
+
+    /// Attribute an encode/decode error to an ingredient, unless it already names one.
+    ///
+    /// Other errors are returned unchanged.
+    pub(crate) fn in_ingredient(
+        err: Arc<PicanteError>,
+        kind: QueryKindId,
+        kind_name: &'static str,
+    ) -> Arc<PicanteError> {
+        match &*err {
+            PicanteError::Encode {
+                what,
+                message,
+                kind: None,
+                ..
+            } => Arc::new(PicanteError::Encode {
+                what: *what,
+                message: message.clone(),
+                kind: Some(kind),
+                kind_name: Some(kind_name),
+            }),
+            PicanteError::Decode {
+                what,
+                message,
+                kind: None,
+                ..
+            } => Arc::new(PicanteError::Decode {
+                what: *what,
+                message: message.clone(),
+                kind: Some(kind),
+                kind_name: Some(kind_name),
+            }),
+            _ => err,
+        }
+    }
+}
+
 impl std::error::Error for PicanteError {}
@@ -1,17 +1,71 @@
 //! Tokio task-local query frames used for dependency recording and cycle detection.
 
-use crate::key::{Dep, DynKey};
+use crate::error::{PicanteError, PicanteResult};
+use crate::inflight::ArcAny;
+use crate::key::{Dep, DynKey, Key, QueryKindId};
 use crate::revision::Revision;
 use parking_lot::Mutex;
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::future::Future;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::watch;
 use tracing::trace;
 
 tokio::task_local! {
     static ACTIVE_STACK: RefCell<Vec<ActiveFrameHandle>>;
 }
 
+static CALL_TRACE: AtomicBool = AtomicBool::new(false);
+
+/// Record an ordered call trace for every computation that starts from now on.
+///
+/// Unlike the dependency list, which is deduplicated, a call trace keeps every read a
+/// compute makes, in order and with repetitions: input and interned reads as they happen,
+/// derived queries when they return, together with how they were answered. Read it back
+/// with `DerivedIngredient::last_call_trace`. Off by default; while off, tracing costs one
+/// relaxed atomic load per frame. The switch is process-wide.
+pub fn enable_call_trace() {
+    CALL_TRACE.store(true, Ordering::Relaxed);
+}
+
+/// Stop recording call traces for computations that start from now on.
+pub fn disable_call_trace() {
+    CALL_TRACE.store(false, Ordering::Relaxed);
+}
+
+/// Whether [`enable_call_trace`] is in effect.
+pub fn call_trace_enabled() -> bool {
+    CALL_TRACE.load(Ordering::Relaxed)
+}
+
+/// One read made by a computation, as recorded by [`enable_call_trace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TracedCall {
+    /// Kind of the ingredient that was read.
+    pub kind: QueryKindId,
+    /// Encoded key that was read.
+    pub key: Key,
+    /// How the read was answered.
+    pub outcome: CallOutcome,
+}
+
+/// How a [`TracedCall`] was answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallOutcome {
+    /// A plain read of an input, interned value or other non-derived ingredient.
+    Read,
+    /// A derived query answered from a value already verified at this revision.
+    Hit,
+    /// A derived query whose cached value was revalidated against its dependencies.
+    Validated,
+    /// A derived query that ran its compute function.
+    Recomputed,
+    /// A derived query that failed.
+    Error,
+}
+
 /// A cheap, clonable handle for the currently-running query frame.
 #[derive(Clone)]
 pub struct ActiveFrameHandle(Arc<ActiveFrameInner>);
@@ -19,7 +73,23 @@ pub struct ActiveFrameHandle(Arc<ActiveFrameInner>);
 struct ActiveFrameInner {
     dyn_key: DynKey,
     started_at: Revision,
-    deps: Mutex<Vec<Dep>>,
+    deps: Mutex<RecordedDeps>,
+    /// The runtime's live revision, for [`checkpoint`].
+    revisions: Option<watch::Receiver<Revision>>,
+    /// Present when [`enable_call_trace`] was on when the frame was created.
+    calls: Option<Mutex<Vec<TracedCall>>>,
+    cycle: Mutex<CycleState>,
+}
+
+/// Fixed-point bookkeeping for a frame whose query was read cyclically (see
+/// `DerivedIngredient::with_cycle_recovery`).
+#[derive(Default)]
+struct CycleState {
+    /// What cyclic reads of the frame's query get: the recovery seed, then the result of the
+    /// previous fixed-point iteration.
+    provisional: Option<ArcAny>,
+    /// Queries that were computing above the frame when it was read cyclically.
+    participants: Vec<DynKey>,
 }
 
 impl ActiveFrameHandle {
@@ -28,7 +98,27 @@ impl ActiveFrameHandle {
         Self(Arc::new(ActiveFrameInner {
             dyn_key,
             started_at,
-            deps: Mutex::new(Vec::new()),
+            deps: Mutex::new(RecordedDeps::default()),
+            revisions: None,
+            calls: call_trace_enabled().then(Mutex::default),
+            cycle: Mutex::default(),
+        }))
+    }
+
+    /// Like [`Self::new`], but also watching the runtime's live revision so that
+    /// [`checkpoint`] can tell when the frame's work has gone stale.
+    pub fn new_watched(
+        dyn_key: DynKey,
+        started_at: Revision,
+        revisions: watch::Receiver<Revision>,
+    ) -> Self {
+        Self(Arc::new(ActiveFrameInner {
+            dyn_key,
+            started_at,
+            deps: Mutex::new(RecordedDeps::default()),
+            revisions: Some(revisions),
+            calls: call_trace_enabled().then(Mutex::default),
+            cycle: Mutex::default(),
         }))
     }
 
@@ -42,10 +132,55 @@ impl ActiveFrameHandle {
         self.0.started_at
     }
 
+    /// The runtime's live revision if it has moved past [`Self::started_at`].
+    fn stale_against(&self) -> Option<Revision> {
+        let current = *self.0.revisions.as_ref()?.borrow();
+        (current > self.0.started_at).then_some(current)
+    }
+
     /// Drain the recorded dependency list.
+    ///
+    /// Each distinct dependency appears once, in the order it was first read.
     pub fn take_deps(&self) -> Vec<Dep> {
         let mut deps = self.0.deps.lock();
-        std::mem::take(&mut *deps)
+        std::mem::take(&mut *deps).order
+    }
+
+    /// Drain the recorded call trace, or `None` if the frame wasn't tracing calls.
+    pub fn take_call_trace(&self) -> Option<Vec<TracedCall>> {
+        let calls = self.0.calls.as_ref()?;
+        Some(std::mem::take(&mut *calls.lock()))
+    }
+
+    /// Drain the queries recorded as taking part in a cycle through this frame's query.
+    pub(crate) fn take_cycle_participants(&self) -> Vec<DynKey> {
+        std::mem::take(&mut self.0.cycle.lock().participants)
+    }
+
+    /// The value cyclic reads of this frame's query currently get, if any.
+    pub(crate) fn cycle_provisional(&self) -> Option<ArcAny> {
+        self.0.cycle.lock().provisional.clone()
+    }
+
+    /// Set the value cyclic reads of this frame's query get from now on.
+    pub(crate) fn set_cycle_provisional(&self, value: ArcAny) {
+        self.0.cycle.lock().provisional = Some(value);
+    }
+}
+
+/// Dependencies recorded by a frame, deduplicated so that a compute reading the same key in a
+/// loop stores (and later validates and persists) it once.
+#[derive(Default)]
+struct RecordedDeps {
+    order: Vec<Dep>,
+    seen: HashSet<Dep>,
+}
+
+impl RecordedDeps {
+    fn push(&mut self, dep: Dep) {
+        if self.seen.insert(dep.clone()) {
+            self.order.push(dep);
+        }
     }
 }
 
@@ -80,6 +215,18 @@ where
     }
 }
 
+/// Run `fut` on a private copy of the current task-local query stack.
+///
+/// Lets several queries be awaited concurrently on one task without their frames interleaving
+/// on a shared stack: each still sees the caller's frames (so reads record dependencies on the
+/// caller's query and cycles are detected), but pushes its own onto its copy.
+pub(crate) async fn fork_scope<Fut: Future>(fut: Fut) -> Fut::Output {
+    let stack = ACTIVE_STACK
+        .try_with(|stack| stack.borrow().clone())
+        .unwrap_or_default();
+    ACTIVE_STACK.scope(RefCell::new(stack), fut).await
+}
+
 /// Returns `true` if there is a current query frame.
 pub fn has_active_frame() -> bool {
     ACTIVE_STACK
@@ -87,15 +234,70 @@ pub fn has_active_frame() -> bool {
         .unwrap_or(false)
 }
 
+/// Whether a query of `kind` is being computed on this task.
+pub(crate) fn is_computing_kind(kind: QueryKindId) -> bool {
+    ACTIVE_STACK
+        .try_with(|stack| stack.borrow().iter().any(|f| f.dyn_key().kind == kind))
+        .unwrap_or(false)
+}
+
+/// Number of frames on the task-local stack (0 outside any query).
+pub(crate) fn depth() -> usize {
+    ACTIVE_STACK
+        .try_with(|stack| stack.borrow().len())
+        .unwrap_or(0)
+}
+
 /// Record a dependency on the current top-of-stack frame, if any.
+///
+/// When the frame traces calls, the read is also appended to its trace as a
+/// [`CallOutcome::Read`].
 pub fn record_dep(dep: Dep) {
     let _ = ACTIVE_STACK.try_with(|stack| {
         if let Some(top) = stack.borrow().last() {
+            if let Some(calls) = &top.0.calls {
+                calls.lock().push(TracedCall {
+                    kind: dep.kind,
+                    key: dep.key.clone(),
+                    outcome: CallOutcome::Read,
+                });
+            }
             top.0.deps.lock().push(dep);
         }
     });
 }
 
+/// Like [`record_dep`], leaving the call trace to a later [`trace_call`] (derived queries
+/// trace themselves once they know their outcome).
+pub(crate) fn record_dep_untraced(dep: Dep) {
+    let _ = ACTIVE_STACK.try_with(|stack| {
+        if let Some(top) = stack.borrow().last() {
+            top.0.deps.lock().push(dep);
+        }
+    });
+}
+
+/// Append a call to the current frame's trace, if it is tracing calls.
+pub(crate) fn trace_call(call: TracedCall) {
+    let _ = ACTIVE_STACK.try_with(|stack| {
+        if let Some(calls) = stack.borrow().last().and_then(|top| top.0.calls.as_ref()) {
+            calls.lock().push(call);
+        }
+    });
+}
+
+/// Number of distinct dependencies the current query has recorded so far, or `None` outside a
+/// query.
+///
+/// Cheap enough to call in a loop; lets a compute with highly variable fan-in switch to a
+/// coarser strategy once it has read too many fine-grained inputs.
+pub fn current_dep_count() -> Option<usize> {
+    ACTIVE_STACK
+        .try_with(|stack| stack.borrow().last().map(|top| top.0.deps.lock().order.len()))
+        .ok()
+        .flatten()
+}
+
 /// If `requested` already exists in the task-local stack, returns the full stack of `DynKey`s.
 pub fn find_cycle(requested: &DynKey) -> Option<Vec<DynKey>> {
     ACTIVE_STACK
@@ -111,6 +313,101 @@ pub fn find_cycle(requested: &DynKey) -> Option<Vec<DynKey>> {
         .flatten()
 }
 
+/// Like [`find_cycle`], for a cyclic read that is going to be answered instead of failing.
+///
+/// Records every query above `requested` on the stack as a participant of the cycle (see
+/// [`ActiveFrameHandle::take_cycle_participants`]) and returns the full stack together with
+/// `requested`'s frame.
+pub(crate) fn enter_cycle(requested: &DynKey) -> Option<(Vec<DynKey>, ActiveFrameHandle)> {
+    ACTIVE_STACK
+        .try_with(|stack| {
+            let stack = stack.borrow();
+            let pos = stack.iter().position(|f| f.dyn_key() == requested)?;
+            let head = stack[pos].clone();
+            {
+                let mut cycle = head.0.cycle.lock();
+                for frame in &stack[pos + 1..] {
+                    if !cycle.participants.contains(frame.dyn_key()) {
+                        cycle.participants.push(frame.dyn_key().clone());
+                    }
+                }
+            }
+            Some((stack.iter().map(|f| f.dyn_key().clone()).collect(), head))
+        })
+        .ok()
+        .flatten()
+}
+
+/// Cooperative cancellation point for long-running computes.
+///
+/// Yields to the executor, then fails with [`PicanteError::Cancelled`] if the revision has
+/// moved on since the current query started: its result would be discarded anyway, and the
+/// query is recomputed at the new revision. Outside a query (or in a frame that doesn't watch
+/// the revision) it only yields.
+///
+/// ```ignore
+/// for chunk in chunks {
+///     picante::frame::checkpoint().await?;
+///     process(chunk);
+/// }
+/// ```
+pub async fn checkpoint() -> PicanteResult<()> {
+    tokio::task::yield_now().await;
+    check_cancelled()
+}
+
+/// Whether the current query has been cancelled: the revision moved on since it started, so
+/// its result would be discarded. `false` outside a query.
+///
+/// A cheap synchronous check for CPU-bound loops that don't await; a compute that sees `true`
+/// should return early, with [`check_cancelled`]'s error so that the query is retried cleanly.
+pub fn is_cancelled() -> bool {
+    ACTIVE_STACK
+        .try_with(|stack| {
+            stack
+                .borrow()
+                .last()
+                .is_some_and(|top| top.stale_against().is_some())
+        })
+        .unwrap_or(false)
+}
+
+/// Like [`checkpoint`] without yielding: fails with [`PicanteError::Cancelled`] if the current
+/// query has been cancelled (see [`is_cancelled`]).
+///
+/// A cancelled compute leaves no trace in its cell: the previous value (if any) is restored
+/// as a stale value, so the recompute at the new revision still gets early cutoff.
+pub fn check_cancelled() -> PicanteResult<()> {
+    let stale = ACTIVE_STACK
+        .try_with(|stack| {
+            let stack = stack.borrow();
+            let top = stack.last()?;
+            let current = top.stale_against()?;
+            Some((top.dyn_key().clone(), top.started_at(), current))
+        })
+        .ok()
+        .flatten();
+
+    match stale {
+        Some((query, started_at, current)) => {
+            trace!(
+                kind = query.kind.0,
+                key_hash = %format!("{:016x}", query.key.hash()),
+                started_at = started_at.0,
+                current = current.0,
+                "checkpoint: stale, cancelling"
+            );
+            Err(Arc::new(PicanteError::Cancelled {
+                kind: query.kind,
+                key_hash: query.key.hash(),
+                started_at,
+                current,
+            }))
+        }
+        None => Ok(()),
+    }
+}
+
 /// Push a frame onto the task-local stack. Requires an active scope (see [`scope_if_needed`]).
 pub fn push_frame(frame: ActiveFrameHandle) -> FrameGuard {
     let _ = ACTIVE_STACK.try_with(|stack| {
@@ -9,7 +9,7 @@ use crate::key::{Dep, Key, QueryKindId};
 use crate::revision::Revision;
 use crate::runtime::RuntimeId;
 use dashmap::DashMap;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use tokio::sync::Notify;
@@ -25,6 +25,12 @@ pub(crate) type ArcAny = Arc<dyn std::any::Any + Send + Sync>;
 static IN_FLIGHT_REGISTRY: std::sync::LazyLock<DashMap<InFlightKey, Arc<InFlightEntry>>> =
     std::sync::LazyLock::new(DashMap::new);
 
+/// [`IN_FLIGHT_REGISTRY`] indexed by query, with the revisions each query is computing at,
+/// so [`running_at_older_revision`] doesn't scan every computation in the process.
+static IN_FLIGHT_BY_QUERY: std::sync::LazyLock<
+    DashMap<QueryId, BTreeMap<Revision, Arc<InFlightEntry>>>,
+> = std::sync::LazyLock::new(DashMap::new);
+
 // ============================================================================
 // Shared completed-result cache (cross-snapshot memoization)
 // ============================================================================
@@ -120,6 +126,16 @@ pub(crate) fn shared_cache_put(
     SHARED_CACHE.insert(k, record);
 }
 
+pub(crate) fn shared_cache_remove(runtime_id: RuntimeId, kind: QueryKindId, key: &Key) {
+    let k = SharedCacheKey {
+        runtime_id,
+        kind,
+        key: key.clone(),
+    };
+    // The stale order-queue entry no longer matches anything and is skipped on eviction.
+    SHARED_CACHE.remove(&k);
+}
+
 #[doc(hidden)]
 pub fn __test_shared_cache_clear() {
     SHARED_CACHE.clear();
@@ -148,6 +164,34 @@ pub(crate) struct InFlightKey {
     pub key: Key,
 }
 
+/// An [`InFlightKey`] without the revision.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct QueryId {
+    runtime_id: RuntimeId,
+    kind: QueryKindId,
+    key: Key,
+}
+
+impl From<&InFlightKey> for QueryId {
+    fn from(key: &InFlightKey) -> Self {
+        Self {
+            runtime_id: key.runtime_id,
+            kind: key.kind,
+            key: key.key.clone(),
+        }
+    }
+}
+
+/// Remove a finished computation from the registry and its index.
+fn unregister(key: &InFlightKey) {
+    IN_FLIGHT_REGISTRY.remove(key);
+    let query = QueryId::from(key);
+    IN_FLIGHT_BY_QUERY.remove_if_mut(&query, |_, revisions| {
+        revisions.remove(&key.revision);
+        revisions.is_empty()
+    });
+}
+
 /// State of an in-flight computation.
 #[derive(Debug, Clone)]
 pub(crate) enum InFlightState {
@@ -241,14 +285,14 @@ impl InFlightGuard {
         self.completed = true;
         // Entry stays in registry briefly so followers can read the result,
         // then we remove it.
-        IN_FLIGHT_REGISTRY.remove(&self.key);
+        unregister(&self.key);
     }
 
     /// Mark the computation as failed.
     pub(crate) fn fail(mut self, error: Arc<PicanteError>) {
         self.entry.fail(error);
         self.completed = true;
-        IN_FLIGHT_REGISTRY.remove(&self.key);
+        unregister(&self.key);
     }
 }
 
@@ -258,7 +302,7 @@ impl Drop for InFlightGuard {
             // Leader was dropped without completing (likely cancelled/panicked).
             // Mark as cancelled so followers can retry.
             self.entry.cancel();
-            IN_FLIGHT_REGISTRY.remove(&self.key);
+            unregister(&self.key);
         }
     }
 }
@@ -276,6 +320,10 @@ pub(crate) fn try_lead(key: InFlightKey) -> TryLeadResult {
         Entry::Vacant(vacant) => {
             // We're the first - become the leader.
             let entry = Arc::new(InFlightEntry::new());
+            IN_FLIGHT_BY_QUERY
+                .entry(QueryId::from(&key))
+                .or_default()
+                .insert(key.revision, entry.clone());
             vacant.insert(entry.clone());
             TryLeadResult::Leader(InFlightGuard {
                 key,
@@ -285,3 +333,77 @@ pub(crate) fn try_lead(key: InFlightKey) -> TryLeadResult {
         }
     }
 }
+
+/// Find a computation of the same query that is still running at a revision older than
+/// `revision`, returning the newest such revision and its entry.
+///
+/// Used by callers that are about to compute at `revision`: waiting for the older computation
+/// and then revalidating its result (via the shared completed-result cache) is cheaper than
+/// running the same compute twice when the inputs it read didn't change in between.
+pub(crate) fn running_at_older_revision(
+    runtime_id: RuntimeId,
+    kind: QueryKindId,
+    key: &Key,
+    revision: Revision,
+) -> Option<(Revision, Arc<InFlightEntry>)> {
+    let query = QueryId {
+        runtime_id,
+        kind,
+        key: key.clone(),
+    };
+    let revisions = IN_FLIGHT_BY_QUERY.get(&query)?;
+    revisions
+        .range(..revision)
+        .rev()
+        .find(|(_, entry)| matches!(entry.state(), InFlightState::Running))
+        .map(|(rev, entry)| (*rev, entry.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::Runtime;
+
+    fn lead(runtime_id: RuntimeId, revision: u64) -> InFlightGuard {
+        let key = InFlightKey {
+            runtime_id,
+            revision: Revision(revision),
+            kind: QueryKindId(1),
+            key: Key::from_bytes(vec![7]),
+        };
+        match try_lead(key) {
+            TryLeadResult::Leader(guard) => guard,
+            TryLeadResult::Follower(_) => panic!("expected to lead"),
+        }
+    }
+
+    fn older(runtime_id: RuntimeId, revision: u64) -> Option<Revision> {
+        let key = Key::from_bytes(vec![7]);
+        running_at_older_revision(runtime_id, QueryKindId(1), &key, Revision(revision))
+            .map(|(rev, _)| rev)
+    }
+
+    #[test]
+    fn test_older_running_revision_is_found_through_the_index() {
+        let runtime_id = Runtime::new().id();
+        let at_2 = lead(runtime_id, 2);
+        let at_5 = lead(runtime_id, 5);
+
+        assert_eq!(older(runtime_id, 2), None);
+        assert_eq!(older(runtime_id, 3), Some(Revision(2)));
+        assert_eq!(older(runtime_id, 9), Some(Revision(5)));
+        assert_eq!(older(Runtime::new().id(), 9), None);
+
+        at_5.fail(Arc::new(PicanteError::Cache {
+            message: "failed".into(),
+        }));
+        assert_eq!(older(runtime_id, 9), Some(Revision(2)));
+        drop(at_2);
+        assert_eq!(older(runtime_id, 9), None);
+        assert!(
+            !IN_FLIGHT_BY_QUERY
+                .iter()
+                .any(|e| e.key().runtime_id == runtime_id)
+        );
+    }
+}
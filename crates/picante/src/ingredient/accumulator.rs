@@ -0,0 +1,262 @@
+use crate::db::{DynIngredient, Touch};
+use crate::error::{PicanteError, PicanteResult};
+use crate::frame;
+use crate::key::{Dep, Key, QueryKindId};
+use crate::persist::{PersistableIngredient, SectionType, yield_point};
+use crate::revision::Revision;
+use crate::runtime::HasRuntime;
+use facet::Facet;
+use futures::future::BoxFuture;
+use parking_lot::RwLock;
+use std::hash::Hash;
+use std::sync::Arc;
+use tracing::{debug, trace};
+
+/// The items accumulated under one key of an [`AccumulatorIngredient`].
+#[derive(Clone)]
+struct AccumulatedEntry<T>
+where
+    T: Clone,
+{
+    /// Items in push order.
+    items: im::Vector<T>,
+    /// The last revision at which an item was pushed or the key was cleared.
+    changed_at: Revision,
+}
+
+/// An append-only input ingredient: each key collects the items pushed under it.
+///
+/// Meant for side outputs such as diagnostics: a query (or a loader) pushes lints for a file
+/// and a parent query reads them all back with [`get`](Self::get). Every push bumps the
+/// revision and invalidates the readers of that key only; [`clear_key`](Self::clear_key)
+/// starts the key over, e.g. before re-running whatever produced its items.
+pub struct AccumulatorIngredient<K, T>
+where
+    K: Clone + Eq + Hash,
+    T: Clone,
+{
+    kind: QueryKindId,
+    kind_name: &'static str,
+    entries: RwLock<im::HashMap<K, AccumulatedEntry<T>>>,
+}
+
+impl<K, T> AccumulatorIngredient<K, T>
+where
+    K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+    T: Clone + Facet<'static> + Send + Sync + 'static,
+{
+    /// Create an empty accumulator ingredient.
+    pub fn new(kind: QueryKindId, kind_name: &'static str) -> Self {
+        Self {
+            kind,
+            kind_name,
+            entries: RwLock::new(im::HashMap::new()),
+        }
+    }
+
+    /// The stable kind id.
+    pub fn kind(&self) -> QueryKindId {
+        self.kind
+    }
+
+    /// Debug name for this ingredient.
+    pub fn kind_name(&self) -> &'static str {
+        self.kind_name
+    }
+
+    /// Append `item` to the items for `key`.
+    ///
+    /// Always bumps the runtime revision: pushing an item equal to one already present adds
+    /// it again.
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
+    pub fn push<DB: HasRuntime>(&self, db: &DB, key: K, item: T) -> Revision {
+        let rev = {
+            let mut entries = self.entries.write();
+            let rev = db.runtime().bump_revision();
+            let entry = entries
+                .entry(key.clone())
+                .or_insert_with(|| AccumulatedEntry {
+                    items: im::Vector::new(),
+                    changed_at: rev,
+                });
+            entry.items.push_back(item);
+            entry.changed_at = rev;
+            rev
+        };
+
+        self.notify_changed(db, rev, &key);
+        rev
+    }
+
+    /// Drop every item accumulated under `key`.
+    ///
+    /// Bumps the runtime revision only if there was an item to drop.
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
+    pub fn clear_key<DB: HasRuntime>(&self, db: &DB, key: &K) -> Revision {
+        let rev = {
+            let mut entries = self.entries.write();
+            let Some(entry) = entries.get_mut(key) else {
+                trace!(kind = self.kind.0, "accumulator clear no-op (missing key)");
+                return Revision(0);
+            };
+            if entry.items.is_empty() {
+                trace!(
+                    kind = self.kind.0,
+                    changed_at = entry.changed_at.0,
+                    "accumulator clear no-op (already empty)"
+                );
+                return entry.changed_at;
+            }
+
+            let rev = db.runtime().bump_revision();
+            entry.items.clear();
+            // Keep the empty entry so its `changed_at` still reflects the clear.
+            entry.changed_at = rev;
+            rev
+        };
+
+        self.notify_changed(db, rev, key);
+        rev
+    }
+
+    /// Read the items for `key` (empty if there are none), in push order.
+    ///
+    /// If there's an active query frame, records a dependency edge on all of them.
+    #[tracing::instrument(level = "trace", skip_all, fields(kind = self.kind.0))]
+    pub fn get<DB: HasRuntime>(&self, _db: &DB, key: &K) -> PicanteResult<Vec<T>> {
+        if frame::has_active_frame() {
+            let encoded_key = Key::encode_for(key, self.kind, self.kind_name)?;
+            trace!(kind = self.kind.0, key_hash = %format!("{:016x}", encoded_key.hash()), "accumulator dep");
+            frame::record_dep(Dep {
+                kind: self.kind,
+                key: encoded_key,
+            });
+        }
+
+        let entries = self.entries.read();
+        Ok(entries
+            .get(key)
+            .map(|e| e.items.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// The last revision at which the items for `key` changed.
+    pub fn changed_at(&self, key: &K) -> Option<Revision> {
+        let entries = self.entries.read();
+        entries.get(key).map(|e| e.changed_at)
+    }
+
+    fn notify_changed<DB: HasRuntime>(&self, db: &DB, rev: Revision, key: &K) {
+        if let Ok(encoded_key) = Key::encode_facet(key) {
+            db.runtime().notify_input_set(rev, self.kind, encoded_key);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Facet)]
+struct AccumulatorRecord<K, T> {
+    key: K,
+    items: Vec<T>,
+    changed_at: u64,
+}
+
+impl<K, T> PersistableIngredient for AccumulatorIngredient<K, T>
+where
+    K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+    T: Clone + Facet<'static> + Send + Sync + 'static,
+{
+    fn kind(&self) -> QueryKindId {
+        self.kind
+    }
+
+    fn kind_name(&self) -> &'static str {
+        self.kind_name
+    }
+
+    fn section_type(&self) -> SectionType {
+        SectionType::Input
+    }
+
+    fn clear(&self) {
+        let mut entries = self.entries.write();
+        *entries = im::HashMap::new();
+    }
+
+    fn save_records(&self) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        self.save_records_yielding(0)
+    }
+
+    fn save_records_yielding(
+        &self,
+        yield_every: usize,
+    ) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        Box::pin(async move {
+            let entries = self.entries.read().clone();
+            let mut records = Vec::with_capacity(entries.len());
+            for (i, (key, entry)) in entries.iter().enumerate() {
+                yield_point(i, yield_every).await;
+                let rec = AccumulatorRecord::<K, T> {
+                    key: key.clone(),
+                    items: entry.items.iter().cloned().collect(),
+                    changed_at: entry.changed_at.0,
+                };
+                let bytes = facet_postcard::to_vec(&rec).map_err(|e| {
+                    Arc::new(PicanteError::Encode {
+                        what: "accumulator record",
+                        message: format!("{e:?}"),
+                        kind: Some(self.kind),
+                        kind_name: Some(self.kind_name),
+                    })
+                })?;
+                records.push(bytes);
+            }
+            debug!(
+                kind = self.kind.0,
+                records = records.len(),
+                "save_records (accumulator)"
+            );
+            Ok(records)
+        })
+    }
+
+    fn load_records(&self, records: Vec<Vec<u8>>) -> PicanteResult<()> {
+        let mut entries = self.entries.write();
+        for bytes in records {
+            let rec: AccumulatorRecord<K, T> = facet_postcard::from_slice(&bytes).map_err(|e| {
+                Arc::new(PicanteError::Decode {
+                    what: "accumulator record",
+                    message: format!("{e:?}"),
+                    kind: Some(self.kind),
+                    kind_name: Some(self.kind_name),
+                })
+            })?;
+            entries.insert(
+                rec.key,
+                AccumulatedEntry {
+                    items: rec.items.into_iter().collect(),
+                    changed_at: Revision(rec.changed_at),
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+impl<DB, K, T> DynIngredient<DB> for AccumulatorIngredient<K, T>
+where
+    DB: HasRuntime + Send + Sync + 'static,
+    K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+    T: Clone + Facet<'static> + Send + Sync + 'static,
+{
+    fn touch<'a>(&'a self, _db: &'a DB, key: Key) -> BoxFuture<'a, PicanteResult<Touch>> {
+        Box::pin(async move {
+            let key: K = key.decode_for(self.kind, self.kind_name)?;
+            let entries = self.entries.read();
+            let changed_at = entries
+                .get(&key)
+                .map(|e| e.changed_at)
+                .unwrap_or(Revision(0));
+            Ok(Touch { changed_at })
+        })
+    }
+}
@@ -1,23 +1,34 @@
+use crate::clock;
 use crate::db::{DynIngredient, IngredientLookup, Touch};
 use crate::error::{PicanteError, PicanteResult};
-use crate::frame::{self, ActiveFrameHandle};
+use crate::frame::{self, ActiveFrameHandle, CallOutcome, TracedCall};
 use crate::inflight::{self, InFlightKey, InFlightState, SharedCacheRecord, TryLeadResult};
+use crate::ingredient::InputIngredient;
 use crate::key::{Dep, DynKey, Key, QueryKindId};
-use crate::persist::{PersistableIngredient, SectionType};
-use crate::revision::Revision;
+use crate::persist::{PersistableIngredient, SectionType, decode_all, yield_point};
+use crate::report::AccessOutcome;
+use crate::revision::{Durability, Revision};
+use crate::runtime::{RuntimeId, Snapshot};
 use facet::Facet;
 use futures::FutureExt;
 use futures::future::BoxFuture;
 use parking_lot::RwLock;
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 use std::marker::PhantomData;
-use std::sync::Arc;
-use tokio::sync::{Mutex, Notify};
-use tracing::{debug, trace};
+use std::sync::{Arc, LazyLock};
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::{Mutex, MutexGuard, Notify, Semaphore, watch};
+use tracing::{Instrument, debug, debug_span, field, trace, warn};
 
 type ComputeFuture<'db, V> = BoxFuture<'db, PicanteResult<V>>;
 type ComputeFn<DB, K, V> = dyn for<'db> Fn(&'db DB, K) -> ComputeFuture<'db, V> + Send + Sync;
+type CycleRecoveryFn<DB, K, V> = dyn Fn(&DB, &K, &[DynKey]) -> V + Send + Sync;
+type TransientErrorFn = dyn Fn(&PicanteError) -> bool + Send + Sync;
+type EvictFn = dyn Fn(&DynKey, &ArcAny) + Send + Sync;
 
 // ============================================================================
 // Type-erased compute infrastructure (for dyn dispatch)
@@ -26,19 +37,45 @@ type ComputeFn<DB, K, V> = dyn for<'db> Fn(&'db DB, K) -> ComputeFuture<'db, V>
 /// Type-erased Arc<dyn Any> for storing values without knowing V
 type ArcAny = Arc<dyn Any + Send + Sync>;
 
+/// The value shared by every cell of every `()`-valued derived query.
+static UNIT: LazyLock<ArcAny> = LazyLock::new(|| Arc::new(()));
+
+/// Box a computed or loaded value for storage in a cell.
+///
+/// Unit-valued queries exist for their dependencies alone, so they share [`UNIT`] instead of
+/// allocating per cell; being pointer-equal, their results also backdate without a compare.
+fn erase_value<V: Send + Sync + 'static>(value: V) -> ArcAny {
+    if TypeId::of::<V>() == TypeId::of::<()>() {
+        return UNIT.clone();
+    }
+    Arc::new(value)
+}
+
 /// Type-erased compute future that returns ArcAny
 type ComputeFut<'a> = BoxFuture<'a, PicanteResult<ArcAny>>;
 
 /// Function pointer for deep equality check without knowing V
 type EqErasedFn = fn(&dyn Any, &dyn Any) -> bool;
 
+/// Function pointer that sends a value (or `None`) to a type-erased
+/// `watch::Sender<Option<V>>`, returning whether anyone is still subscribed.
+type WatchSendFn = fn(&(dyn Any + Send + Sync), Option<&ArcAny>) -> bool;
+
 /// Trait for type-erased compute function (dyn dispatch)
 ///
 /// This trait allows the state machine to call compute() without being generic
 /// over the closure/future type F. Each query implements this via TypedCompute<DB,K,V>.
 trait ErasedCompute<DB>: Send + Sync {
-    /// Compute the value for a given key, returning type-erased result
-    fn compute<'a>(&'a self, db: &'a DB, key: Key) -> ComputeFut<'a>;
+    /// Compute the value for a given key, returning type-erased result.
+    ///
+    /// `typed_key` is the caller's `Option<K>` slot: when it still holds the key, it is moved
+    /// into the compute call instead of decoding `key`.
+    fn compute<'a>(
+        &'a self,
+        db: &'a DB,
+        key: Key,
+        typed_key: &mut (dyn Any + Send),
+    ) -> ComputeFut<'a>;
 }
 
 /// Typed adapter that implements ErasedCompute for a specific (DB, K, V)
@@ -56,15 +93,41 @@ where
     K: Facet<'static> + Send + Sync + 'static,
     V: Send + Sync + 'static,
 {
-    fn compute<'a>(&'a self, db: &'a DB, key: Key) -> ComputeFut<'a> {
+    fn compute<'a>(
+        &'a self,
+        db: &'a DB,
+        key: Key,
+        typed_key: &mut (dyn Any + Send),
+    ) -> ComputeFut<'a> {
+        let typed_key = typed_key
+            .downcast_mut::<Option<K>>()
+            .and_then(Option::take);
         Box::pin(async move {
-            let k: K = key.decode_facet()?;
+            let k: K = match typed_key {
+                Some(k) => k,
+                None => key.decode_facet()?,
+            };
             let v: V = (self.f)(db, k).await?;
-            Ok(Arc::new(v) as ArcAny)
+            Ok(erase_value(v))
         })
     }
 }
 
+/// The error for a missing input value read by [`DerivedIngredient::zip`].
+fn missing_input<K, V>(input: &InputIngredient<K, V>, key: &K) -> Arc<PicanteError>
+where
+    K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+    V: Clone + Facet<'static> + Send + Sync + 'static,
+{
+    match Key::encode_for(key, input.kind(), input.kind_name()) {
+        Ok(encoded) => Arc::new(PicanteError::MissingInputValue {
+            kind: input.kind(),
+            key_hash: encoded.hash(),
+        }),
+        Err(e) => e,
+    }
+}
+
 /// Deep equality helper for type-erased values
 ///
 /// Uses autoref specialization to prefer PartialEq when available,
@@ -77,6 +140,126 @@ where
     crate::facet_eq::facet_eq::<V>(a, b)
 }
 
+/// Typed [`WatchSendFn`] for values of type `V`.
+fn watch_send_for<V>(sender: &(dyn Any + Send + Sync), value: Option<&ArcAny>) -> bool
+where
+    V: Clone + Send + Sync + 'static,
+{
+    let Some(sender) = sender.downcast_ref::<watch::Sender<Option<V>>>() else {
+        return false;
+    };
+    sender.send_replace(value.and_then(|value| value.downcast_ref::<V>().cloned()));
+    !sender.is_closed()
+}
+
+/// Consecutive stale recomputations of one cell after which a warning is logged.
+const STALE_RETRY_WARN_THRESHOLD: u32 = 8;
+
+/// How a stale cell validates its dependencies before deciding whether to recompute.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ValidationStrategy {
+    /// Touch each dependency in order; derived dependencies validate their own
+    /// dependencies from inside that call.
+    ///
+    /// Simple and lazy (stops at the first changed dependency), but the async call depth
+    /// grows with the depth of the dependency graph, which can overflow the stack on long
+    /// chains of derived queries. This is the default.
+    #[default]
+    Recursive,
+    /// Walk the stale part of the dependency graph with an explicit work stack first, then
+    /// validate it bottom-up so that every nested validation finds its dependencies already
+    /// verified.
+    ///
+    /// Shared dependencies (diamonds) are visited once and cycles are cut by the visited set.
+    /// Because the whole stale subgraph is validated eagerly, this may revalidate dependencies
+    /// that the recursive strategy would have skipped after finding an earlier change, so
+    /// only opt into it for dependency chains too deep for [`ValidationStrategy::Recursive`].
+    Iterative,
+}
+
+/// What a derived query does with a computation that failed.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ErrorPolicy {
+    /// Keep the error until the revision changes: every reader at the same revision gets it
+    /// without recomputing.
+    #[default]
+    Cache,
+    /// Recompute a failed cell up to `max` more times within the same revision before
+    /// keeping its error, so transient failures (e.g. flaky IO) heal without an input
+    /// change while persistent ones stop being retried.
+    ///
+    /// Each reader that finds the cell failed fewer than `max + 1` times at the current
+    /// revision recomputes it; the count lives in the cell, so concurrent readers share it
+    /// (one of them recomputes, the others wait for that attempt). Once exhausted, readers
+    /// get the error of the last attempt. The count starts over at the next revision.
+    RetryWithinRevision {
+        /// Extra attempts allowed after the first failure.
+        max: u32,
+    },
+    /// Never keep an error: every reader that finds the cell failed recomputes it, so a
+    /// failure lasts only as long as the attempt that produced it.
+    ///
+    /// For IO-bound queries where one failed read shouldn't stick until the next input
+    /// change. Concurrent readers still share an attempt (and its error). Combine with
+    /// [`DerivedIngredient::with_transient_errors`] to keep errors that retrying won't fix.
+    Retry,
+}
+
+impl ErrorPolicy {
+    /// Whether a cell that already failed `failures` times at this revision is recomputed.
+    fn retries(self, failures: u32) -> bool {
+        match self {
+            ErrorPolicy::Cache => false,
+            ErrorPolicy::RetryWithinRevision { max } => failures <= max,
+            ErrorPolicy::Retry => true,
+        }
+    }
+}
+
+/// What a derived query does with a computation that panicked.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum PanicPolicy {
+    /// Record the panic as [`PicanteError::Panic`] in the cell, which the [`ErrorPolicy`]
+    /// then treats like any other failure.
+    #[default]
+    Poison,
+    /// Put back the cell's previous value, if it had one, and return it in place of the
+    /// panic, which is only logged. The value keeps its old `verified_at`, so the next read
+    /// revalidates and recomputes it. A cell that never had a value is poisoned as usual.
+    ///
+    /// For aggregations where a stale answer beats none. Other readers waiting on the
+    /// panicked attempt read the restored value and recompute it themselves.
+    KeepLastGood,
+}
+
+/// How a derived query re-runs a compute that returned an error (see
+/// [`DerivedIngredient::with_retry`]).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// Compute runs in total, including the first; 0 and 1 both mean no retries.
+    pub max_attempts: u32,
+    /// Sleep before the first retry; each later retry waits twice as long as the previous.
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// The sleep before the attempt following failed attempt number `attempt` (1-based).
+    fn delay_after(self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(1u32.checked_shl(attempt - 1).unwrap_or(u32::MAX))
+    }
+}
+
+/// One entry of the explicit work stack used by [`ValidationStrategy::Iterative`].
+struct ValidationFrame {
+    /// The dependency this frame expands (`None` for the root being validated).
+    dep: Option<Dep>,
+    /// Stale dependencies of `dep`.
+    deps: Arc<[Dep]>,
+    /// Index of the next entry in `deps` to visit.
+    next: usize,
+}
+
 // ============================================================================
 // Non-generic core: state machine compiled ONCE
 // ============================================================================
@@ -89,15 +272,439 @@ struct DerivedCore {
     kind: QueryKindId,
     kind_name: &'static str,
     cells: RwLock<im::HashMap<DynKey, Arc<ErasedCell>>>,
+    validation: ValidationStrategy,
+    error_policy: ErrorPolicy,
+    panic_policy: PanicPolicy,
+    /// Errors the error policy may retry; `None` retries any.
+    transient_errors: Option<Arc<TransientErrorFn>>,
+    max_stale_retries: Option<u32>,
+    stale_retries: AtomicU64,
+    logic_version: u32,
+    /// Keys protected from capacity eviction, whether or not they have a cell.
+    pinned: RwLock<HashSet<Key>>,
+    /// Call trace of each key's latest computation, while call tracing is enabled.
+    call_traces: RwLock<HashMap<DynKey, Arc<[TracedCall]>>>,
+    /// Most cells kept before least-recently-used ones are evicted; `None` keeps them all.
+    capacity: Option<usize>,
+    /// How long a single compute may run before it is abandoned.
+    timeout: Option<Duration>,
+    /// How often a failed compute is re-run before the cell is poisoned.
+    retry: Option<RetryPolicy>,
+    /// Most computations running at once, with the semaphore handing out their permits.
+    concurrency: Option<(usize, Semaphore)>,
+    /// Called with each value that leaves a cell (see `DerivedIngredient::on_evict`).
+    on_evict: Option<Arc<EvictFn>>,
+    /// Most compute rounds of fixed-point iteration over a cycle; `None` runs compute once.
+    max_cycle_iterations: Option<u32>,
+    /// Coarse access clock stamped on cells for LRU eviction (only advanced with a capacity).
+    lru_clock: AtomicU64,
+    evictions: AtomicU64,
+    stats: QueryCounters,
+    /// Subscribers to each key's value: a type-erased `watch::Sender<Option<V>>` per key,
+    /// fed through `watch_send`.
+    watchers: RwLock<HashMap<DynKey, Box<dyn Any + Send + Sync>>>,
+    watch_send: WatchSendFn,
+    #[cfg(feature = "lock-metrics")]
+    lock_wait: crate::metrics::LockWaitRecorder,
 }
 
 impl DerivedCore {
-    fn new(kind: QueryKindId, kind_name: &'static str) -> Self {
+    fn new(kind: QueryKindId, kind_name: &'static str, watch_send: WatchSendFn) -> Self {
         Self {
             kind,
             kind_name,
             cells: RwLock::new(im::HashMap::new()),
+            validation: ValidationStrategy::default(),
+            error_policy: ErrorPolicy::default(),
+            panic_policy: PanicPolicy::default(),
+            transient_errors: None,
+            max_stale_retries: None,
+            stale_retries: AtomicU64::new(0),
+            logic_version: 0,
+            pinned: RwLock::new(HashSet::new()),
+            call_traces: RwLock::new(HashMap::new()),
+            capacity: None,
+            timeout: None,
+            retry: None,
+            concurrency: None,
+            on_evict: None,
+            max_cycle_iterations: None,
+            lru_clock: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            stats: QueryCounters::default(),
+            watchers: RwLock::new(HashMap::new()),
+            watch_send,
+            #[cfg(feature = "lock-metrics")]
+            lock_wait: crate::metrics::LockWaitRecorder::new(),
+        }
+    }
+
+    /// Whether the cell for `key` must be skipped by capacity eviction.
+    fn is_pinned(&self, key: &Key) -> bool {
+        self.pinned.read().contains(key)
+    }
+
+    /// Stamp `cell` as the most recently used one, when eviction is enabled.
+    fn note_use(&self, cell: &ErasedCell) {
+        if self.capacity.is_some() {
+            let tick = self.lru_clock.fetch_add(1, Ordering::Relaxed) + 1;
+            cell.last_used.store(tick, Ordering::Relaxed);
+        }
+    }
+
+    /// Evict least-recently-used cells once `cells` holds more than `capacity`.
+    ///
+    /// Evicts down to 1/16 below the bound, so the full scan is paid once per batch of new
+    /// keys rather than on every insert. Only settled (`Ready`/`Poisoned`) cells whose lock
+    /// is free are candidates: running and freshly created cells stay, as do pinned keys.
+    /// A task still holding an evicted cell finishes with it normally; the next access of
+    /// the key recomputes into a new cell. Returns the evicted cells.
+    fn evict_over_capacity(
+        &self,
+        cells: &mut im::HashMap<DynKey, Arc<ErasedCell>>,
+        capacity: usize,
+    ) -> Vec<(DynKey, Arc<ErasedCell>)> {
+        if cells.len() <= capacity {
+            return Vec::new();
+        }
+        let excess = cells.len() - (capacity - capacity / 16);
+        let mut candidates: Vec<(u64, DynKey)> = {
+            let pinned = self.pinned.read();
+            cells
+                .iter()
+                .filter(|(key, cell)| !pinned.contains(&key.key) && cell.is_settled())
+                .map(|(key, cell)| (cell.last_used.load(Ordering::Relaxed), key.clone()))
+                .collect()
+        };
+        if candidates.len() > excess {
+            candidates.select_nth_unstable_by_key(excess, |(tick, _)| *tick);
+            candidates.truncate(excess);
+        }
+
+        let mut call_traces = self.call_traces.write();
+        let mut evicted = Vec::with_capacity(candidates.len());
+        for (_, key) in candidates {
+            call_traces.remove(&key);
+            if let Some(cell) = cells.remove(&key) {
+                evicted.push((key, cell));
+            }
         }
+        self.evictions
+            .fetch_add(evicted.len() as u64, Ordering::Relaxed);
+        debug!(
+            kind = self.kind.0,
+            evicted = evicted.len(),
+            cells = cells.len(),
+            "evicted least-recently-used cells"
+        );
+        evicted
+    }
+
+    /// Hand the value of `old`, a cell state being dropped, to the `on_evict` hook, unless
+    /// `replacement` is that very value.
+    fn retire(&self, requested: &DynKey, old: Option<&ErasedState>, replacement: Option<&ArcAny>) {
+        let (Some(on_evict), Some(ErasedState::Ready { value, .. })) = (&self.on_evict, old) else {
+            return;
+        };
+        if replacement.is_some_and(|new| Arc::ptr_eq(new, value)) {
+            return;
+        }
+        on_evict(requested, value);
+    }
+
+    /// [`Self::retire`] the value of a cell removed from the map.
+    ///
+    /// A cell whose lock is held is being computed (its displaced value is retired once the
+    /// computation replaces it) or briefly read, and is skipped.
+    fn retire_cell(&self, requested: &DynKey, cell: &ErasedCell) {
+        if self.on_evict.is_none() {
+            return;
+        }
+        if let Ok(state) = cell.state.try_lock() {
+            self.retire(requested, Some(&*state), None);
+        }
+    }
+
+    /// Account for a computation that finished after the revision moved on.
+    ///
+    /// Warns once a single access has retried [`STALE_RETRY_WARN_THRESHOLD`] times in a row, and
+    /// fails with [`PicanteError::Contended`] once `max_stale_retries` is exceeded.
+    fn note_stale_retry(&self, retries: &mut u32, key_hash: u64) -> PicanteResult<()> {
+        *retries += 1;
+        self.stale_retries.fetch_add(1, Ordering::Relaxed);
+
+        if *retries == STALE_RETRY_WARN_THRESHOLD {
+            warn!(
+                kind = self.kind.0,
+                kind_name = self.kind_name,
+                key_hash = %format!("{:016x}", key_hash),
+                retries = *retries,
+                "query keeps recomputing: inputs change faster than it completes"
+            );
+        }
+
+        match self.max_stale_retries {
+            Some(max) if *retries > max => Err(Arc::new(PicanteError::Contended {
+                kind: self.kind,
+                key_hash,
+                retries: *retries,
+            })),
+            _ => Ok(()),
+        }
+    }
+
+    fn record_access<DB: IngredientLookup>(&self, db: &DB, outcome: AccessOutcome) {
+        let counter = match outcome {
+            AccessOutcome::Hit => &self.stats.hits,
+            AccessOutcome::Revalidated => &self.stats.revalidations,
+            AccessOutcome::Recomputed => &self.stats.recomputes,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        db.runtime()
+            .tallies()
+            .record_access(self.kind, self.kind_name, outcome);
+    }
+
+    /// The value of `requested`'s cell if it is `Ready` at the current revision, without
+    /// blocking: a cell whose state lock is held elsewhere counts as not ready.
+    fn try_ready_value<DB: IngredientLookup>(
+        &self,
+        db: &DB,
+        requested: &DynKey,
+    ) -> Option<ArcAny> {
+        let (cell, value) = self.peek_ready_value(db, requested)?;
+        self.record_access(db, AccessOutcome::Hit);
+        self.note_use(&cell);
+        cell.note_read(db.runtime().current_revision());
+        Some(value)
+    }
+
+    /// The value of a cell verified at the current revision, touching nothing: no tallies,
+    /// no read bookkeeping, no waiting on the cell's lock.
+    fn peek_ready_value<DB: IngredientLookup>(
+        &self,
+        db: &DB,
+        requested: &DynKey,
+    ) -> Option<(Arc<ErasedCell>, ArcAny)> {
+        let cell = self.cells.read().get(requested).cloned()?;
+        let rev = db.runtime().current_revision();
+        let value = match &*cell.state.try_lock().ok()? {
+            ErasedState::Ready {
+                value, verified_at, ..
+            } if *verified_at == rev => value.clone(),
+            _ => return None,
+        };
+        Some((cell, value))
+    }
+
+    /// The value of `requested`'s cell if it was the query's value at `revision`: `Ready`,
+    /// last changed at or before `revision` and verified at or after it.
+    async fn value_at(&self, requested: &DynKey, revision: Revision) -> Option<ArcAny> {
+        let cell = self.cells.read().get(requested).cloned()?;
+        match &*cell.state.lock().await {
+            ErasedState::Ready {
+                value,
+                verified_at,
+                changed_at,
+                ..
+            } if *changed_at <= revision && revision <= *verified_at => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Get or create the cell for `requested`, evicting old cells if that goes over capacity.
+    fn cell_for(&self, requested: &DynKey) -> Arc<ErasedCell> {
+        // Fast path: read lock
+        let existing = self.cells.read().get(requested).cloned();
+        if let Some(cell) = existing {
+            self.note_use(&cell);
+            return cell;
+        }
+        // Slow path: write lock, double-check after acquiring lock
+        let mut cells = self.cells.write();
+        if let Some(cell) = cells.get(requested) {
+            self.note_use(cell);
+            return cell.clone();
+        }
+        let cell = Arc::new(ErasedCell::new());
+        self.note_use(&cell);
+        cells.insert(requested.clone(), cell.clone());
+        let Some(capacity) = self.capacity else {
+            return cell;
+        };
+        let evicted = self.evict_over_capacity(&mut cells, capacity);
+        // Run the hook outside the map lock, in case it reads this ingredient.
+        drop(cells);
+        for (key, evicted) in &evicted {
+            self.retire_cell(key, evicted);
+        }
+        cell
+    }
+
+    /// Send `value` to the subscribers of `requested`, if any, forgetting the channel once
+    /// they have all gone.
+    fn notify_watchers(&self, requested: &DynKey, value: Option<&ArcAny>) {
+        let subscribed = match self.watchers.read().get(requested) {
+            Some(sender) => (self.watch_send)(sender.as_ref(), value),
+            None => return,
+        };
+        if !subscribed {
+            self.watchers.write().remove(requested);
+        }
+    }
+
+    /// Drop the cells of `queries` (in whatever ingredients they live) without bumping the
+    /// revision, so their next access recomputes.
+    fn discard_cells<DB: IngredientLookup>(&self, db: &DB, queries: &[DynKey]) {
+        for query in queries {
+            if let Some(ingredient) = db.ingredient(query.kind) {
+                ingredient.discard_cell(db, &query.key);
+            }
+        }
+    }
+
+    /// Drop the cell for `requested` and its shared-cache entry, if any.
+    fn discard_cell(&self, runtime_id: RuntimeId, requested: &DynKey) -> bool {
+        let Some(cell) = self.cells.write().remove(requested) else {
+            return false;
+        };
+        self.retire_cell(requested, &cell);
+        self.call_traces.write().remove(requested);
+        inflight::shared_cache_remove(runtime_id, self.kind, &requested.key);
+        true
+    }
+
+    /// Whether `cell` is still the one stored for `requested`, i.e. it hasn't been
+    /// invalidated since it was looked up.
+    fn is_current_cell(&self, requested: &DynKey, cell: &Arc<ErasedCell>) -> bool {
+        self.cells
+            .read()
+            .get(requested)
+            .is_some_and(|current| Arc::ptr_eq(current, cell))
+    }
+
+    /// Publish a finished result to the cross-snapshot cache, unless the cell was
+    /// invalidated while computing.
+    ///
+    /// Checked again after the put: an `invalidate` that slipped in between removes the
+    /// cell before clearing the shared entry, so one of the two sides always drops it.
+    fn share_result(
+        &self,
+        runtime_id: RuntimeId,
+        requested: &DynKey,
+        cell: &Arc<ErasedCell>,
+        record: SharedCacheRecord,
+    ) {
+        if !self.is_current_cell(requested, cell) {
+            return;
+        }
+        inflight::shared_cache_put(runtime_id, self.kind, requested.key.clone(), record);
+        if !self.is_current_cell(requested, cell) {
+            inflight::shared_cache_remove(runtime_id, self.kind, &requested.key);
+        }
+    }
+
+    /// Acquire a cell's state lock, recording the wait when `lock-metrics` is enabled.
+    async fn lock_state<'a>(&self, cell: &'a ErasedCell) -> MutexGuard<'a, ErasedState> {
+        #[cfg(feature = "lock-metrics")]
+        {
+            let start = std::time::Instant::now();
+            let guard = cell.state.lock().await;
+            self.lock_wait.record(start.elapsed());
+            guard
+        }
+        #[cfg(not(feature = "lock-metrics"))]
+        {
+            cell.state.lock().await
+        }
+    }
+
+    /// Run [`Self::access_untraced`] inside a `picante.query` span.
+    ///
+    /// The span's fields follow `tracing-opentelemetry` conventions so exported traces show
+    /// query execution directly: `outcome` is `hit`, `validated`, `recomputed` or `error`,
+    /// `duration_us` is how long the access took, and a failed access sets
+    /// `otel.status_code = "ERROR"` with the error as `otel.status_message`. Queries read
+    /// while computing get child spans.
+    async fn access_scoped_erased<DB>(
+        &self,
+        db: &DB,
+        requested: DynKey,
+        typed_key: &mut (dyn Any + Send),
+        mode: AccessMode,
+        compute: &dyn ErasedCompute<DB>,
+        eq_erased: EqErasedFn,
+    ) -> PicanteResult<ErasedAccessResult>
+    where
+        DB: IngredientLookup + Send + Sync + 'static,
+    {
+        // Only reads made by a compute belong in its call trace, not revalidation touches.
+        let traced_key = (mode.want_value && frame::call_trace_enabled())
+            .then(|| requested.key.clone());
+        let result = self
+            .access_in_span(db, requested, typed_key, mode, compute, eq_erased)
+            .await;
+        if let Some(key) = traced_key {
+            frame::trace_call(TracedCall {
+                kind: self.kind,
+                key,
+                outcome: match &result {
+                    Ok(access) => access.outcome.call_outcome(),
+                    Err(_) => CallOutcome::Error,
+                },
+            });
+        }
+        result
+    }
+
+    /// Run [`Self::access_untraced`] inside a `picante.query` span recording its outcome.
+    async fn access_in_span<DB>(
+        &self,
+        db: &DB,
+        requested: DynKey,
+        typed_key: &mut (dyn Any + Send),
+        mode: AccessMode,
+        compute: &dyn ErasedCompute<DB>,
+        eq_erased: EqErasedFn,
+    ) -> PicanteResult<ErasedAccessResult>
+    where
+        DB: IngredientLookup + Send + Sync + 'static,
+    {
+        let span = debug_span!(
+            "picante.query",
+            kind = self.kind.0,
+            kind_name = self.kind_name,
+            key_hash = %format!("{:016x}", requested.key.hash()),
+            outcome = field::Empty,
+            duration_us = field::Empty,
+            otel.status_code = field::Empty,
+            otel.status_message = field::Empty,
+        );
+        if span.is_disabled() {
+            return self
+                .access_untraced(db, requested, typed_key, mode, compute, eq_erased)
+                .await;
+        }
+
+        let started = db.runtime().clock().now();
+        let result = self
+            .access_untraced(db, requested, typed_key, mode, compute, eq_erased)
+            .instrument(span.clone())
+            .await;
+
+        let elapsed = db.runtime().clock().now().saturating_duration_since(started);
+        span.record("duration_us", elapsed.as_micros() as u64);
+        match &result {
+            Ok(access) => {
+                span.record("outcome", access.outcome.as_str());
+            }
+            Err(err) => {
+                span.record("outcome", "error");
+                span.record("otel.status_code", "ERROR");
+                span.record("otel.status_message", field::display(err));
+            }
+        }
+        result
     }
 
     /// Type-erased state machine implementation (compiled ONCE per DB type).
@@ -107,11 +714,12 @@ impl DerivedCore {
     ///
     /// Runtime cost: one vtable call + one BoxFuture allocation per compute.
     /// Compile-time win: 50+ copies reduced to ~2 copies (DB + DatabaseSnapshot).
-    async fn access_scoped_erased<DB>(
+    async fn access_untraced<DB>(
         &self,
         db: &DB,
         requested: DynKey,
-        want_value: bool,
+        typed_key: &mut (dyn Any + Send),
+        mode: AccessMode,
         compute: &dyn ErasedCompute<DB>,
         eq_erased: EqErasedFn,
     ) -> PicanteResult<ErasedAccessResult>
@@ -119,6 +727,17 @@ impl DerivedCore {
         DB: IngredientLookup + Send + Sync + 'static,
     {
         let key_hash = requested.key.hash();
+        let AccessMode {
+            want_value,
+            propagate_panics,
+        } = mode;
+        // A caller that wants panics resumed recomputes a panicked cell rather than reading
+        // the recorded `PicanteError::Panic`, so that the panic happens on its own task.
+        let rerun_poisoned = |error: &PicanteError, failures: u32| {
+            (propagate_panics && matches!(error, PicanteError::Panic { .. }))
+                || (self.error_policy.retries(failures)
+                    && self.transient_errors.as_ref().is_none_or(|f| f(error)))
+        };
 
         if let Some(stack) = frame::find_cycle(&requested) {
             return Err(Arc::new(PicanteError::Cycle {
@@ -126,6 +745,8 @@ impl DerivedCore {
                 stack,
             }));
         }
+        db.runtime()
+            .check_query_depth(&requested, frame::depth() + 1)?;
 
         // 0) record dependency into parent frame (if any)
         if want_value && frame::has_active_frame() {
@@ -134,31 +755,18 @@ impl DerivedCore {
                 key_hash = %format!("{:016x}", key_hash),
                 "derived dep"
             );
-            frame::record_dep(Dep {
+            frame::record_dep_untraced(Dep {
                 kind: self.kind,
                 key: requested.key.clone(),
             });
         }
 
-        // Get or create the cell for this key
-        let cell = {
-            // Fast path: read lock
-            if let Some(cell) = self.cells.read().get(&requested) {
-                cell.clone()
-            } else {
-                // Slow path: write lock, double-check after acquiring lock
-                let mut cells = self.cells.write();
-                if let Some(cell) = cells.get(&requested) {
-                    cell.clone()
-                } else {
-                    let cell = Arc::new(ErasedCell::new());
-                    cells.insert(requested.clone(), cell.clone());
-                    cell
-                }
-            }
-        };
+        // Consecutive computations of this cell that finished at an outdated revision.
+        let mut stale_retries = 0;
 
         loop {
+            // Looked up on every pass: `invalidate` may have replaced the cell meanwhile.
+            let cell = self.cell_for(&requested);
             let rev = db.runtime().current_revision();
             // Create this before inspecting state to avoid missing a notification
             // between observing `Running` and awaiting.
@@ -176,13 +784,15 @@ impl DerivedCore {
                 },
                 StaleReady {
                     deps: Arc<[Dep]>,
+                    verified_at: Revision,
                     changed_at: Revision,
+                    durability: Durability,
                 },
                 StaleOther,
             }
 
             let observed = {
-                let state = cell.state.lock().await;
+                let state = self.lock_state(&cell).await;
                 match &*state {
                     ErasedState::Ready {
                         value,
@@ -193,17 +803,26 @@ impl DerivedCore {
                         value: want_value.then(|| value.clone()),
                         changed_at: *changed_at,
                     },
-                    ErasedState::Poisoned { error, verified_at } if *verified_at == rev => {
+                    ErasedState::Poisoned {
+                        error,
+                        verified_at,
+                        failures,
+                    } if *verified_at == rev && !rerun_poisoned(error, *failures) => {
                         ErasedObserved::Error(error.clone())
                     }
                     ErasedState::Running { started_at } => ErasedObserved::Running {
                         started_at: *started_at,
                     },
                     ErasedState::Ready {
-                        deps, changed_at, ..
+                        deps,
+                        verified_at,
+                        changed_at,
+                        ..
                     } => ErasedObserved::StaleReady {
                         deps: deps.clone(),
+                        verified_at: *verified_at,
                         changed_at: *changed_at,
+                        durability: cell.durability(),
                     },
                     _ => ErasedObserved::StaleOther,
                 }
@@ -213,7 +832,13 @@ impl DerivedCore {
                 ErasedObserved::Ready { value, changed_at } => {
                     // Ensure we return a value consistent with *now*.
                     if db.runtime().current_revision() == rev {
-                        return Ok(ErasedAccessResult { value, changed_at });
+                        self.record_access(db, AccessOutcome::Hit);
+                        cell.note_read(rev);
+                        return Ok(ErasedAccessResult {
+                            value,
+                            changed_at,
+                            outcome: AccessOutcome::Hit,
+                        });
                     }
                     continue;
                 }
@@ -224,6 +849,9 @@ impl DerivedCore {
                     continue;
                 }
                 ErasedObserved::Running { started_at } => {
+                    // Wait even if `started_at < rev`: once the compute lands, the cell is
+                    // either Ready at `rev` (nothing changed) or StaleReady, and then
+                    // revalidating its deps decides whether a recompute is needed at all.
                     trace!(
                         kind = self.kind.0,
                         key_hash = %format!("{:016x}", key_hash),
@@ -233,10 +861,19 @@ impl DerivedCore {
                     notified.await;
                     continue;
                 }
-                ErasedObserved::StaleReady { deps, changed_at } => {
-                    if self
-                        .try_revalidate(db, &requested, rev, &deps, changed_at)
-                        .await?
+                ErasedObserved::StaleReady {
+                    deps,
+                    verified_at,
+                    changed_at,
+                    durability,
+                } => {
+                    // Nothing this cell can depend on changed since it was verified: skip
+                    // the dependency walk.
+                    let durable = db.runtime().last_changed(durability) <= verified_at;
+                    if durable
+                        || self
+                            .try_revalidate(db, &requested, rev, &deps, changed_at)
+                            .await?
                     {
                         let mut state = cell.state.lock().await;
                         match &mut *state {
@@ -252,9 +889,12 @@ impl DerivedCore {
                                 drop(state);
 
                                 if db.runtime().current_revision() == rev {
+                                    self.record_access(db, AccessOutcome::Revalidated);
+                                    cell.note_read(rev);
                                     return Ok(ErasedAccessResult {
                                         value: out_value,
                                         changed_at: out_changed_at,
+                                        outcome: AccessOutcome::Revalidated,
                                     });
                                 }
                                 continue;
@@ -270,28 +910,54 @@ impl DerivedCore {
                 ErasedObserved::StaleOther => {}
             }
 
+            // With a concurrency limit, wait for a permit before claiming the cell, so readers
+            // of a key queued here don't hold it `Running` meanwhile. Nested computations of
+            // this query on the same task run under their outermost one's permit; waiting for
+            // another would deadlock once every permit is held by a parent.
+            let _permit = match &self.concurrency {
+                Some((_, semaphore)) if !frame::is_computing_kind(self.kind) => {
+                    semaphore.acquire().await.ok()
+                }
+                _ => None,
+            };
+
             // 2) attempt to start computation
-            let (started, prev) = {
+            // `failures` counts the attempts that already failed at `rev` (see `ErrorPolicy`).
+            let (started, prev, displaced, failures) = {
                 let mut prev: Option<(Arc<dyn std::any::Any + Send + Sync>, Revision)> = None;
-                let mut state = cell.state.lock().await;
+                let mut state = self.lock_state(&cell).await;
                 match &*state {
-                    ErasedState::Ready { verified_at, .. } if *verified_at == rev => (false, None), // raced
-                    ErasedState::Poisoned { verified_at, .. } if *verified_at == rev => {
-                        (false, None)
+                    ErasedState::Ready { verified_at, .. } if *verified_at == rev => {
+                        (false, None, None, 0)
+                    } // raced
+                    ErasedState::Poisoned {
+                        error,
+                        verified_at,
+                        failures,
+                    } if *verified_at == rev && !rerun_poisoned(error, *failures) => {
+                        (false, None, None, 0)
                     } // raced
-                    ErasedState::Running { .. } => (false, None), // someone else started
+                    ErasedState::Running { .. } => (false, None, None, 0), // someone else started
                     _ => {
                         let old = std::mem::replace(
                             &mut *state,
                             ErasedState::Running { started_at: rev },
                         );
-                        if let ErasedState::Ready {
-                            value, changed_at, ..
-                        } = old
-                        {
-                            prev = Some((value, changed_at));
-                        }
-                        (true, prev)
+                        let failures = match &old {
+                            ErasedState::Ready {
+                                value, changed_at, ..
+                            } => {
+                                prev = Some((value.clone(), *changed_at));
+                                0
+                            }
+                            ErasedState::Poisoned {
+                                verified_at,
+                                failures,
+                                ..
+                            } if *verified_at == rev => *failures,
+                            _ => 0,
+                        };
+                        (true, prev, Some(old), failures)
                     }
                 }
             };
@@ -318,7 +984,9 @@ impl DerivedCore {
                         .update_query_deps(requested.clone(), record.deps.clone());
 
                     // Mark the adopted cell as verified at the *current* revision.
+                    let durability = self.deps_durability(db, &record.deps);
                     let mut state = cell.state.lock().await;
+                    cell.set_durability(durability);
                     *state = ErasedState::Ready {
                         value: record.value.clone(),
                         verified_at: rev,
@@ -327,12 +995,16 @@ impl DerivedCore {
                     };
                     drop(state);
                     cell.notify.notify_waiters();
+                    self.retire(&requested, displaced.as_ref(), Some(&record.value));
+                    if record.changed_at == rev {
+                        self.notify_watchers(&requested, Some(&record.value));
+                    }
 
                     // Update the shared cache's verified_at so future lookups can skip revalidation.
-                    inflight::shared_cache_put(
+                    self.share_result(
                         db.runtime().id(),
-                        self.kind,
-                        requested.key.clone(),
+                        &requested,
+                        &cell,
                         SharedCacheRecord {
                             value: record.value.clone(),
                             deps: record.deps.clone(),
@@ -343,10 +1015,13 @@ impl DerivedCore {
                     );
 
                     if db.runtime().current_revision() == rev {
+                        self.record_access(db, AccessOutcome::Revalidated);
+                        cell.note_read(rev);
                         let out_value = want_value.then(|| record.value.clone());
                         return Ok(ErasedAccessResult {
                             value: out_value,
                             changed_at: record.changed_at,
+                            outcome: AccessOutcome::Revalidated,
                         });
                     }
 
@@ -355,6 +1030,42 @@ impl DerivedCore {
                 }
             }
 
+            // 3) If another snapshot is still computing this key at an *older* revision, wait
+            //    for it instead of starting a second compute. Once it finishes, the next
+            //    iteration finds its result in the shared cache and adopts it if its deps are
+            //    unchanged at `rev`; only if a dep changed (or the older compute failed or was
+            //    cancelled, leaving nothing to adopt) do we compute ourselves.
+            if let Some((older_rev, entry)) = inflight::running_at_older_revision(
+                db.runtime().id(),
+                self.kind,
+                &requested.key,
+                rev,
+            ) {
+                trace!(
+                    kind = self.kind.0,
+                    key_hash = %format!("{:016x}", key_hash),
+                    rev = rev.0,
+                    older_rev = older_rev.0,
+                    "inflight: waiting for compute at older revision"
+                );
+
+                // Put back what we displaced so the previous value still serves early cutoff.
+                {
+                    let mut state = cell.state.lock().await;
+                    *state = displaced.unwrap_or(ErasedState::Vacant);
+                }
+                cell.notify.notify_waiters();
+
+                loop {
+                    let notified = entry.notified();
+                    if !matches!(entry.state(), InFlightState::Running) {
+                        break;
+                    }
+                    notified.await;
+                }
+                continue;
+            }
+
             // 3) Check global in-flight registry for cross-snapshot deduplication.
             //    This allows concurrent queries from different snapshots to share work.
             let inflight_key = InFlightKey {
@@ -380,6 +1091,7 @@ impl DerivedCore {
                         let mut state = cell.state.lock().await;
                         *state = ErasedState::Vacant;
                     }
+                    self.retire(&requested, displaced.as_ref(), None);
 
                     // Wait for the leader to complete.
                     loop {
@@ -405,7 +1117,9 @@ impl DerivedCore {
                                 );
 
                                 let out_value = want_value.then(|| value.clone());
+                                let durability = self.deps_durability(db, &deps);
                                 let mut state = cell.state.lock().await;
+                                cell.set_durability(durability);
                                 *state = ErasedState::Ready {
                                     value: value.clone(),
                                     verified_at: rev,
@@ -421,13 +1135,14 @@ impl DerivedCore {
                                     .update_query_deps(requested.clone(), deps.clone());
                                 if changed_at == rev {
                                     db.runtime().notify_query_changed(rev, requested.clone());
+                                    self.notify_watchers(&requested, Some(&value));
                                 }
 
                                 // Store in shared completed-result cache for future snapshots.
-                                inflight::shared_cache_put(
+                                self.share_result(
                                     db.runtime().id(),
-                                    self.kind,
-                                    requested.key.clone(),
+                                    &requested,
+                                    &cell,
                                     SharedCacheRecord {
                                         value: value.clone(),
                                         deps: deps.clone(),
@@ -438,9 +1153,12 @@ impl DerivedCore {
                                 );
 
                                 if db.runtime().current_revision() == rev {
+                                    self.record_access(db, AccessOutcome::Revalidated);
+                                    cell.note_read(rev);
                                     return Ok(ErasedAccessResult {
                                         value: out_value,
                                         changed_at,
+                                        outcome: AccessOutcome::Revalidated,
                                     });
                                 }
                                 // Revision changed, retry the main loop.
@@ -459,6 +1177,7 @@ impl DerivedCore {
                                 *state = ErasedState::Poisoned {
                                     error: err.clone(),
                                     verified_at: rev,
+                                    failures: failures.saturating_add(1),
                                 };
                                 drop(state);
                                 cell.notify.notify_waiters();
@@ -494,7 +1213,17 @@ impl DerivedCore {
                     );
 
                     // Run compute under an active frame.
-                    let frame = ActiveFrameHandle::new(requested.clone(), rev);
+                    let frame = ActiveFrameHandle::new_watched(
+                        requested.clone(),
+                        rev,
+                        db.runtime().subscribe_revisions(),
+                    );
+                    let _running = db.runtime().track_running(
+                        &requested,
+                        self.kind_name,
+                        rev,
+                        frame::depth(),
+                    );
                     let _frame_guard = frame::push_frame(frame.clone());
 
                     debug!(
@@ -504,13 +1233,60 @@ impl DerivedCore {
                         "compute: start"
                     );
 
-                    // Call compute through trait object (dyn dispatch)
-                    let result =
-                        std::panic::AssertUnwindSafe(compute.compute(db, requested.key.clone()))
-                            .catch_unwind()
+                    self.record_access(db, AccessOutcome::Recomputed);
+                    db.runtime().notify_query_recomputed(rev, requested.clone());
+
+                    let mut rounds = 0;
+                    let result = loop {
+                        let result = self
+                            .run_compute(db, &requested, &mut *typed_key, compute, rev)
                             .await;
+                        rounds += 1;
+
+                        // Fixed-point iteration: while the result read through a cycle
+                        // differs from the value the cycle was seeded with, discard the
+                        // participants' provisional results and run again.
+                        let Some(max_rounds) = self.max_cycle_iterations else {
+                            break result;
+                        };
+                        let participants = frame.take_cycle_participants();
+                        let Ok(Ok(out)) = &result else {
+                            self.discard_cells(db, &participants);
+                            break result;
+                        };
+                        if participants.is_empty() {
+                            break result;
+                        }
+                        let converged = frame.cycle_provisional().is_some_and(|provisional| {
+                            Arc::ptr_eq(&provisional, out)
+                                || eq_erased(provisional.as_ref(), out.as_ref())
+                        });
+                        if converged {
+                            trace!(
+                                kind = self.kind.0,
+                                key_hash = %format!("{:016x}", key_hash),
+                                rounds,
+                                "cycle: converged"
+                            );
+                            break result;
+                        }
+                        self.discard_cells(db, &participants);
+                        if rounds >= max_rounds {
+                            break Ok(Err(Arc::new(PicanteError::CycleDidNotConverge {
+                                kind: self.kind,
+                                key_hash,
+                                iterations: rounds,
+                            })));
+                        }
+                        frame.set_cycle_provisional(out.clone());
+                    };
 
                     let deps: Arc<[Dep]> = frame.take_deps().into();
+                    if let Some(calls) = frame.take_call_trace() {
+                        self.call_traces
+                            .write()
+                            .insert(requested.clone(), calls.into());
+                    }
 
                     // 4) finalize
                     match result {
@@ -536,7 +1312,9 @@ impl DerivedCore {
                             let out_value = want_value.then(|| out.clone());
 
                             // Update local cell.
+                            let durability = self.deps_durability(db, &deps);
                             let mut state = cell.state.lock().await;
+                            cell.set_durability(durability);
                             *state = ErasedState::Ready {
                                 value: out.clone(),
                                 verified_at: rev,
@@ -545,12 +1323,16 @@ impl DerivedCore {
                             };
                             drop(state);
                             cell.notify.notify_waiters();
+                            self.retire(&requested, displaced.as_ref(), Some(&out));
+                            if changed_at == rev {
+                                self.notify_watchers(&requested, Some(&out));
+                            }
 
                             // Store in shared completed-result cache for future snapshots.
-                            inflight::shared_cache_put(
+                            self.share_result(
                                 db.runtime().id(),
-                                self.kind,
-                                requested.key.clone(),
+                                &requested,
+                                &cell,
                                 SharedCacheRecord {
                                     value: out.clone(),
                                     deps: deps.clone(),
@@ -572,21 +1354,57 @@ impl DerivedCore {
 
                             // 5) stale check
                             if db.runtime().current_revision() == rev {
+                                cell.note_read(rev);
                                 return Ok(ErasedAccessResult {
                                     value: out_value,
                                     changed_at,
+                                    outcome: AccessOutcome::Recomputed,
                                 });
                             }
+                            self.note_stale_retry(&mut stale_retries, key_hash)?;
+                            continue;
+                        }
+                        Ok(Err(err))
+                            if matches!(
+                                &*err,
+                                PicanteError::Cancelled { started_at, .. } if *started_at == rev
+                            ) =>
+                        {
+                            // Cancelled at a checkpoint: put back what we displaced, so the
+                            // recompute at the new revision still has the previous value for
+                            // early cutoff. Followers see the in-flight entry cancelled and
+                            // retry.
+                            {
+                                let mut state = cell.state.lock().await;
+                                *state = displaced.unwrap_or(ErasedState::Vacant);
+                            }
+                            cell.notify.notify_waiters();
+                            drop(guard);
+
+                            debug!(
+                                kind = self.kind.0,
+                                key_hash = %format!("{:016x}", key_hash),
+                                rev = rev.0,
+                                "compute: cancelled"
+                            );
+
+                            if db.runtime().current_revision() == rev {
+                                return Err(err);
+                            }
+                            self.note_stale_retry(&mut stale_retries, key_hash)?;
                             continue;
                         }
                         Ok(Err(err)) => {
+                            self.stats.errors.fetch_add(1, Ordering::Relaxed);
                             let mut state = cell.state.lock().await;
                             *state = ErasedState::Poisoned {
                                 error: err.clone(),
                                 verified_at: rev,
+                                failures: failures.saturating_add(1),
                             };
                             drop(state);
                             cell.notify.notify_waiters();
+                            self.retire(&requested, displaced.as_ref(), None);
 
                             // Fail the global in-flight entry so followers get the error.
                             guard.fail(err.clone());
@@ -601,20 +1419,61 @@ impl DerivedCore {
                             if db.runtime().current_revision() == rev {
                                 return Err(err);
                             }
+                            self.note_stale_retry(&mut stale_retries, key_hash)?;
                             continue;
                         }
                         Err(panic_payload) => {
+                            self.stats.panics.fetch_add(1, Ordering::Relaxed);
                             let err = Arc::new(PicanteError::Panic {
-                                message: panic_message(panic_payload),
+                                message: panic_message(&*panic_payload),
                             });
 
+                            if self.panic_policy == PanicPolicy::KeepLastGood
+                                && let Some(ErasedState::Ready {
+                                    value, changed_at, ..
+                                }) = &displaced
+                            {
+                                let (value, changed_at) = (value.clone(), *changed_at);
+                                {
+                                    let mut state = cell.state.lock().await;
+                                    *state = displaced.unwrap_or(ErasedState::Vacant);
+                                }
+                                cell.notify.notify_waiters();
+                                // Followers see the in-flight entry cancelled and retry.
+                                drop(guard);
+
+                                warn!(
+                                    kind = self.kind.0,
+                                    key_hash = %format!("{:016x}", key_hash),
+                                    rev = rev.0,
+                                    error = %err,
+                                    "compute: panic, keeping last good value"
+                                );
+
+                                if propagate_panics {
+                                    std::panic::resume_unwind(panic_payload);
+                                }
+
+                                if db.runtime().current_revision() == rev {
+                                    return Ok(ErasedAccessResult {
+                                        value: want_value.then_some(value),
+                                        changed_at,
+                                        outcome: AccessOutcome::Recomputed,
+                                    });
+                                }
+                                self.note_stale_retry(&mut stale_retries, key_hash)?;
+                                continue;
+                            }
+
                             let mut state = cell.state.lock().await;
                             *state = ErasedState::Poisoned {
                                 error: err.clone(),
                                 verified_at: rev,
+                                failures: failures.saturating_add(1),
                             };
                             drop(state);
                             cell.notify.notify_waiters();
+                            self.retire(&requested, displaced.as_ref(), None);
 
                             // Fail the global in-flight entry so followers get the panic error.
                             guard.fail(err.clone());
@@ -626,9 +1485,16 @@ impl DerivedCore {
                                 "compute: panic"
                             );
 
+                            // The cell is already poisoned for everyone else; only this
+                            // caller sees the original panic.
+                            if propagate_panics {
+                                std::panic::resume_unwind(panic_payload);
+                            }
+
                             if db.runtime().current_revision() == rev {
                                 return Err(err);
                             }
+                            self.note_stale_retry(&mut stale_retries, key_hash)?;
                             continue;
                         }
                     }
@@ -637,6 +1503,74 @@ impl DerivedCore {
         }
     }
 
+    /// Run compute once for `requested`, applying the ingredient's timeout, and again with
+    /// backoff after failures its retry policy covers.
+    ///
+    /// Retries happen while the caller still owns the `Running` cell, so concurrent readers
+    /// wait for the final attempt instead of retrying on their own.
+    async fn run_compute<DB>(
+        &self,
+        db: &DB,
+        requested: &DynKey,
+        typed_key: &mut (dyn Any + Send),
+        compute: &dyn ErasedCompute<DB>,
+        rev: Revision,
+    ) -> std::thread::Result<PicanteResult<ArcAny>>
+    where
+        DB: IngredientLookup + Send + Sync + 'static,
+    {
+        let key_hash = requested.key.hash();
+        let mut attempt = 1;
+        loop {
+            // Call compute through trait object (dyn dispatch)
+            let computing = std::panic::AssertUnwindSafe(compute.compute(
+                db,
+                requested.key.clone(),
+                &mut *typed_key,
+            ))
+            .catch_unwind();
+            let result = match self.timeout {
+                None => computing.await,
+                // Dropping the compute future on timeout cancels it; the cell is poisoned
+                // like any other failure.
+                Some(limit) => clock::timeout(&**db.runtime().clock(), limit, computing)
+                    .await
+                    .unwrap_or_else(|| {
+                        Ok(Err(Arc::new(PicanteError::Timeout {
+                            kind: self.kind,
+                            key_hash,
+                            elapsed: limit,
+                        })))
+                    }),
+            };
+
+            let Some(retry) = self.retry else {
+                return result;
+            };
+            match &result {
+                Ok(Err(err))
+                    if attempt < retry.max_attempts
+                        && !matches!(&**err, PicanteError::Cancelled { .. })
+                        && self.transient_errors.as_ref().is_none_or(|f| f(err))
+                        && db.runtime().current_revision() == rev =>
+                {
+                    let delay = retry.delay_after(attempt);
+                    debug!(
+                        kind = self.kind.0,
+                        key_hash = %format!("{:016x}", key_hash),
+                        rev = rev.0,
+                        attempt,
+                        delay_ms = delay.as_millis() as u64,
+                        "compute: failed, retrying"
+                    );
+                    db.runtime().clock().sleep(delay).await;
+                    attempt += 1;
+                }
+                _ => return result,
+            }
+        }
+    }
+
     async fn try_revalidate<DB>(
         &self,
         db: &DB,
@@ -658,6 +1592,10 @@ impl DerivedCore {
         let frame = ActiveFrameHandle::new(requested.clone(), rev);
         let _guard = frame::push_frame(frame);
 
+        if self.validation == ValidationStrategy::Iterative {
+            self.prevalidate_deps(db, rev, deps).await;
+        }
+
         for dep in deps.iter() {
             let Some(ingredient) = db.ingredient(dep.kind) else {
                 return Ok(false);
@@ -671,6 +1609,123 @@ impl DerivedCore {
 
         Ok(true)
     }
+
+    /// Validate the stale derived dependencies below `deps` deepest-first.
+    ///
+    /// Afterwards every reachable derived dependency is either verified at `rev` or
+    /// poisoned at `rev`, so the in-order walk in [`Self::try_revalidate`] only hits fast
+    /// paths and never nests more than one level deep. Errors are deliberately swallowed
+    /// here: the in-order walk surfaces them only for dependencies it actually reaches.
+    async fn prevalidate_deps<DB>(&self, db: &DB, rev: Revision, deps: &Arc<[Dep]>)
+    where
+        DB: IngredientLookup + Send + Sync + 'static,
+    {
+        let mut visited: HashSet<DynKey> = HashSet::new();
+        let mut post_order: Vec<Dep> = Vec::new();
+        let mut stack = vec![ValidationFrame {
+            dep: None,
+            deps: deps.clone(),
+            next: 0,
+        }];
+
+        loop {
+            let Some(top) = stack.last_mut() else {
+                break;
+            };
+            let child = top.deps.get(top.next).cloned();
+            top.next += 1;
+
+            let Some(child) = child else {
+                if let Some(dep) = stack.pop().and_then(|frame| frame.dep) {
+                    post_order.push(dep);
+                }
+                continue;
+            };
+
+            let child_key = DynKey {
+                kind: child.kind,
+                key: child.key.clone(),
+            };
+            if !visited.insert(child_key) {
+                continue;
+            }
+
+            let Some(ingredient) = db.ingredient(child.kind) else {
+                continue;
+            };
+            if let Some(grandchildren) = ingredient.stale_deps(&child.key, rev) {
+                stack.push(ValidationFrame {
+                    dep: Some(child),
+                    deps: grandchildren,
+                    next: 0,
+                });
+            }
+        }
+
+        trace!(
+            kind = self.kind.0,
+            stale = post_order.len(),
+            "revalidate: iterative prewalk"
+        );
+
+        for dep in post_order {
+            let Some(ingredient) = db.ingredient(dep.kind) else {
+                continue;
+            };
+            if let Err(error) = ingredient.touch(db, dep.key.clone()).await {
+                trace!(
+                    kind = dep.kind.0,
+                    key_hash = %format!("{:016x}", dep.key.hash()),
+                    %error,
+                    "revalidate: deferred dependency error"
+                );
+            }
+        }
+    }
+
+    /// The lowest durability among `deps`; [`Durability::High`] without any.
+    fn deps_durability<DB>(&self, db: &DB, deps: &[Dep]) -> Durability
+    where
+        DB: IngredientLookup + Send + Sync + 'static,
+    {
+        deps.iter()
+            .map(|dep| {
+                db.ingredient(dep.kind)
+                    .map_or(Durability::Low, |ingredient| {
+                        ingredient.durability(&dep.key)
+                    })
+            })
+            .min()
+            .unwrap_or(Durability::High)
+    }
+
+    /// Durability of the cell for `key`, [`Durability::Low`] if there is none.
+    fn durability(&self, key: &Key) -> Durability {
+        let dyn_key = DynKey {
+            kind: self.kind,
+            key: key.clone(),
+        };
+        self.cells
+            .read()
+            .get(&dyn_key)
+            .map_or(Durability::Low, |cell| cell.durability())
+    }
+
+    /// Dependencies of the cell for `key` if it is `Ready` but not verified at `revision`.
+    fn stale_deps(&self, key: &Key, revision: Revision) -> Option<Arc<[Dep]>> {
+        let dyn_key = DynKey {
+            kind: self.kind,
+            key: key.clone(),
+        };
+        let cell = self.cells.read().get(&dyn_key).cloned()?;
+        let state = cell.state.try_lock().ok()?;
+        match &*state {
+            ErasedState::Ready {
+                verified_at, deps, ..
+            } if *verified_at != revision => Some(deps.clone()),
+            _ => None,
+        }
+    }
 }
 
 // ============================================================================
@@ -694,6 +1749,8 @@ where
     compute: Arc<dyn ErasedCompute<DB>>,
     /// Deep equality function for detecting value changes
     eq_erased: EqErasedFn,
+    /// Seeds the value of a key read while it is already being computed on the same task.
+    cycle_recovery: Option<Arc<CycleRecoveryFn<DB, K, V>>>,
 }
 
 impl<DB, K, V> DerivedIngredient<DB, K, V>
@@ -716,13 +1773,87 @@ where
         let compute_erased: Arc<dyn ErasedCompute<DB>> = Arc::new(typed_compute);
 
         Self {
-            core: DerivedCore::new(kind, kind_name),
+            core: DerivedCore::new(kind, kind_name, watch_send_for::<V>),
             _phantom: PhantomData,
             compute: compute_erased,
             eq_erased: eq_erased_for::<V>,
+            cycle_recovery: None,
         }
     }
 
+    /// Answer cyclic reads with `recover` instead of failing with [`PicanteError::Cycle`].
+    ///
+    /// When a computation of this query (directly or through other queries) reads the very
+    /// key it is computing, that inner read returns `recover(db, key, stack)` instead of an
+    /// error, and the outer computation carries on with it; `stack` lists the queries being
+    /// computed on the task, outermost first, so the seed can depend on where the cycle
+    /// entered. The seed is neither cached nor recorded as a dependency of the reader, but
+    /// inputs `recover` reads through `db` are. Only value reads (`get` and its variants)
+    /// recover; `touch` still reports the cycle.
+    pub fn with_cycle_recovery(
+        mut self,
+        recover: impl Fn(&DB, &K, &[DynKey]) -> V + Send + Sync + 'static,
+    ) -> Self {
+        self.cycle_recovery = Some(Arc::new(recover));
+        self
+    }
+
+    /// Iterate cycles through this query to a fixed point, running compute at most
+    /// `max_iterations` times per cycle.
+    ///
+    /// Needs [`Self::with_cycle_recovery`] for the first round's seed. When a computation of a
+    /// key reads that key cyclically, its result is compared (like early cutoff, by value) with
+    /// what the cyclic read returned; while they differ, the results of the cycle's other
+    /// queries are discarded and the key is computed again, with cyclic reads now returning
+    /// the previous round's result. Once a round reproduces its input, that result is cached.
+    /// If `max_iterations` rounds don't converge, the access fails with
+    /// [`PicanteError::CycleDidNotConverge`]. Only the queries on the cycle are recomputed
+    /// each round; a query off the cycle that reads one of them mid-iteration keeps what it
+    /// saw.
+    pub fn with_fixed_point(mut self, max_iterations: u32) -> Self {
+        self.core.max_cycle_iterations = Some(max_iterations.max(1));
+        self
+    }
+
+    /// Choose how stale cells validate their dependencies (see [`ValidationStrategy`]).
+    pub fn with_validation_strategy(mut self, strategy: ValidationStrategy) -> Self {
+        self.core.validation = strategy;
+        self
+    }
+
+    /// Choose what happens to failed computations (see [`ErrorPolicy`]).
+    pub fn with_error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.core.error_policy = policy;
+        self
+    }
+
+    /// Choose what happens to computations that panic (see [`PanicPolicy`]).
+    pub fn with_panic_policy(mut self, policy: PanicPolicy) -> Self {
+        self.core.panic_policy = policy;
+        self
+    }
+
+    /// Only retry errors `is_transient` accepts under [`ErrorPolicy::Retry`] and
+    /// [`ErrorPolicy::RetryWithinRevision`]; other errors are kept until the revision changes,
+    /// as with [`ErrorPolicy::Cache`].
+    pub fn with_transient_errors(
+        mut self,
+        is_transient: impl Fn(&PicanteError) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.core.transient_errors = Some(Arc::new(is_transient));
+        self
+    }
+
+    /// The error policy used by this ingredient.
+    pub fn error_policy(&self) -> ErrorPolicy {
+        self.core.error_policy
+    }
+
+    /// The panic policy used by this ingredient.
+    pub fn panic_policy(&self) -> PanicPolicy {
+        self.core.panic_policy
+    }
+
     /// The stable kind id.
     pub fn kind(&self) -> QueryKindId {
         self.core.kind
@@ -733,22 +1864,410 @@ where
         self.core.kind_name
     }
 
-    /// Get the value for `key` at the database's current revision.
-    pub async fn get(&self, db: &DB, key: K) -> PicanteResult<V> {
-        // Encode key once (avoids re-encoding on every lookup)
-        let dyn_key = DynKey {
-            kind: self.core.kind,
-            key: Key::encode_facet(&key)?,
-        };
+    /// The dependency validation strategy used by this ingredient.
+    pub fn validation_strategy(&self) -> ValidationStrategy {
+        self.core.validation
+    }
+
+    /// Bound how many times a single `get`/`touch` recomputes a cell whose result was already
+    /// outdated (because an input changed during the computation) before giving up with
+    /// [`PicanteError::Contended`].
+    ///
+    /// By default there is no bound: the access keeps recomputing until a computation finishes
+    /// at the current revision.
+    pub fn with_max_stale_retries(mut self, retries: u32) -> Self {
+        self.core.max_stale_retries = Some(retries);
+        self
+    }
+
+    /// Tag the compute function's logic with a version, persisted with the cache.
+    ///
+    /// Bump it when the compute function changes behavior without changing its types: cached
+    /// values saved under another version are discarded on load, so only this query starts
+    /// cold. Defaults to 0.
+    pub fn with_logic_version(mut self, version: u32) -> Self {
+        self.core.logic_version = version;
+        self
+    }
+
+    /// The logic version set with [`Self::with_logic_version`].
+    pub fn logic_version(&self) -> u32 {
+        self.core.logic_version
+    }
+
+    /// Keep at most `max_cells` cells, evicting the least recently used ones beyond that.
+    ///
+    /// The bound is checked whenever a new key gets a cell; cells added in bulk
+    /// (`load_records`, promotion, snapshots) only count towards it from then on. Evicted keys
+    /// simply recompute on their next access, and since the new value then counts as changed,
+    /// so do queries that read them. Running cells and pinned keys (see [`Self::pin`]) are
+    /// never evicted. A `max_cells` of 0 is treated as 1.
+    pub fn with_capacity(mut self, max_cells: usize) -> Self {
+        self.core.capacity = Some(max_cells.max(1));
+        self
+    }
+
+    /// The bound set with [`Self::with_capacity`], if any.
+    pub fn capacity(&self) -> Option<usize> {
+        self.core.capacity
+    }
+
+    /// Abandon a compute that hasn't finished after `timeout`.
+    ///
+    /// The compute future is dropped when the timeout elapses and the cell is poisoned with
+    /// [`PicanteError::Timeout`], which waiters see and which is cached for the revision like
+    /// any other error (see [`Self::with_error_policy`] to retry instead). Time is measured
+    /// with the runtime's [`Clock`](crate::clock::Clock); the default one needs a tokio
+    /// runtime with the time driver enabled. A compute that blocks the thread without
+    /// awaiting can't be interrupted.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.core.timeout = Some(timeout);
+        self
+    }
+
+    /// Call `hook` with each value that leaves a cell of this query, e.g. to release resources
+    /// tied to cached results.
+    ///
+    /// Fires once per value: when a recomputation replaces it (even with an equal value) or
+    /// fails, and when the cell is removed by [`Self::invalidate`],
+    /// [`Self::invalidate_all`] or capacity eviction. Errors never reach the hook, nor do
+    /// values that are only set aside while their key recomputes. Bulk loads
+    /// ([`Self::load_cells`], cache loading) replace cells without calling it. Values are
+    /// shared with snapshots and followers, so the hook marks the end of the cell's use of a
+    /// value, not necessarily its drop.
+    pub fn on_evict(mut self, hook: impl Fn(&K, &V) + Send + Sync + 'static) -> Self {
+        let kind_name = self.core.kind_name;
+        self.core.on_evict = Some(Arc::new(move |dyn_key: &DynKey, value: &ArcAny| {
+            let Some(value) = value.downcast_ref::<V>() else {
+                return;
+            };
+            match dyn_key.key.decode_facet::<K>() {
+                Ok(key) => hook(&key, value),
+                Err(err) => warn!(kind_name, error = %err, "on_evict: failed to decode key"),
+            }
+        }));
+        self
+    }
+
+    /// Run at most `limit` computations of this query at once, e.g. to stay within a
+    /// connection pool when a revision invalidates many keys that are then read together.
+    ///
+    /// Readers that need to compute wait for one of `limit` permits before claiming their
+    /// cell; readers of a key already being computed wait for it as usual. A computation
+    /// that (directly or through other queries) reads keys of this same query computes them
+    /// under its own permit. A `limit` of 0 is treated as 1.
+    pub fn with_concurrency_limit(mut self, limit: usize) -> Self {
+        let limit = limit.max(1);
+        self.core.concurrency = Some((limit, Semaphore::new(limit)));
+        self
+    }
+
+    /// The limit set with [`Self::with_concurrency_limit`], if any.
+    pub fn concurrency_limit(&self) -> Option<usize> {
+        self.core.concurrency.as_ref().map(|(limit, _)| *limit)
+    }
+
+    /// Re-run compute when it returns an error, sleeping with exponential backoff in between,
+    /// and only poison the cell once `policy.max_attempts` runs have failed.
+    ///
+    /// The retries happen within the one computation of the key, so concurrent readers wait
+    /// for the final outcome rather than retrying themselves. Panics and cancellations are
+    /// not retried, nor are errors rejected by [`Self::with_transient_errors`]; a timeout
+    /// (see [`Self::with_timeout`]) applies to each attempt. Retrying stops early once the
+    /// revision moves on, as the result would be discarded anyway. The backoff sleeps on the
+    /// runtime's [`Clock`](crate::clock::Clock); the default one needs a tokio runtime with
+    /// the time driver enabled.
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.core.retry = Some(policy);
+        self
+    }
+
+    /// The retry policy set with [`Self::with_retry`], if any.
+    pub fn retry_policy(&self) -> Option<RetryPolicy> {
+        self.core.retry
+    }
+
+    /// The timeout set with [`Self::with_timeout`], if any.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.core.timeout
+    }
+
+    /// Number of cells evicted to stay within [`Self::capacity`] since creation.
+    pub fn evictions(&self) -> u64 {
+        self.core.evictions.load(Ordering::Relaxed)
+    }
+
+    /// Access counters since creation or the last [`Self::reset_stats`].
+    ///
+    /// Useful for spotting queries that recompute far more often than their inputs change.
+    pub fn stats(&self) -> QueryStats {
+        self.core.stats.snapshot()
+    }
+
+    /// Zero the counters reported by [`Self::stats`].
+    pub fn reset_stats(&self) {
+        self.core.stats.reset();
+    }
+
+    /// Total number of stale recomputations across all keys since creation.
+    ///
+    /// A steadily climbing value means inputs change faster than this query completes.
+    pub fn stale_retries(&self) -> u64 {
+        self.core.stale_retries.load(Ordering::Relaxed)
+    }
+
+    /// Time spent waiting for cell state locks on the `get` path, since creation.
+    #[cfg(feature = "lock-metrics")]
+    pub fn lock_wait_histogram(&self) -> crate::metrics::LockWaitHistogram {
+        self.core.lock_wait.snapshot()
+    }
+
+    /// Derive a new query that applies `f` to this query's value for the same key.
+    ///
+    /// The returned ingredient reads `self` through [`Self::get`], so the dependency is recorded
+    /// like any other, and its own output is compared before being published: when `f` maps a
+    /// changed value to an equal result, downstream queries stay backdated.
+    ///
+    /// `kind` must be unique and stable like any other kind id; [`QueryKindId::child`] derives
+    /// one from this ingredient's kind (`source.kind().child("len")`). Register the returned
+    /// ingredient with the database like any other, so queries depending on it can revalidate.
+    pub fn map<W, F>(
+        self: &Arc<Self>,
+        kind: QueryKindId,
+        kind_name: &'static str,
+        f: F,
+    ) -> DerivedIngredient<DB, K, W>
+    where
+        W: Clone + Facet<'static> + Send + Sync + 'static,
+        F: Fn(V) -> W + Send + Sync + 'static,
+    {
+        let source = Arc::clone(self);
+        let f = Arc::new(f);
+        DerivedIngredient::new(kind, kind_name, move |db, key| {
+            let source = source.clone();
+            let f = f.clone();
+            Box::pin(async move { Ok(f(source.get(db, key).await?)) })
+        })
+    }
+
+    /// Like [`Self::map`], for an asynchronous, fallible step.
+    ///
+    /// The future returned by `f` must not borrow the database; reading other queries belongs
+    /// in a regular [`DerivedIngredient::new`] compute function.
+    pub fn and_then<W, F, Fut>(
+        self: &Arc<Self>,
+        kind: QueryKindId,
+        kind_name: &'static str,
+        f: F,
+    ) -> DerivedIngredient<DB, K, W>
+    where
+        W: Clone + Facet<'static> + Send + Sync + 'static,
+        F: Fn(V) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = PicanteResult<W>> + Send + 'static,
+    {
+        let source = Arc::clone(self);
+        let f = Arc::new(f);
+        DerivedIngredient::new(kind, kind_name, move |db, key| {
+            let source = source.clone();
+            let f = f.clone();
+            Box::pin(async move { f(source.get(db, key).await?).await })
+        })
+    }
+
+    /// Derive a query combining two inputs stored under the same key.
+    ///
+    /// The compute function reads `input_a` and `input_b` at `key` and applies `combine`, so
+    /// the query depends on both and recomputes when either changes; an equal combined result
+    /// stays backdated. Both inputs are read before either is checked, so a query that failed
+    /// on a missing value still reruns when the other input is set. If a value is missing, the
+    /// query fails with [`PicanteError::MissingInputValue`] naming that input's kind (`input_a`
+    /// first when both are).
+    pub fn zip<A, B, F>(
+        kind: QueryKindId,
+        kind_name: &'static str,
+        input_a: Arc<InputIngredient<K, A>>,
+        input_b: Arc<InputIngredient<K, B>>,
+        combine: F,
+    ) -> Self
+    where
+        A: Clone + Facet<'static> + Send + Sync + 'static,
+        B: Clone + Facet<'static> + Send + Sync + 'static,
+        F: Fn(A, B) -> V + Send + Sync + 'static,
+    {
+        let combine = Arc::new(combine);
+        Self::new(kind, kind_name, move |db, key| {
+            let input_a = input_a.clone();
+            let input_b = input_b.clone();
+            let combine = combine.clone();
+            Box::pin(async move {
+                let a = input_a.get(db, &key)?;
+                let b = input_b.get(db, &key)?;
+                let a = a.ok_or_else(|| missing_input(&input_a, &key))?;
+                let b = b.ok_or_else(|| missing_input(&input_b, &key))?;
+                Ok(combine(a, b))
+            })
+        })
+    }
+
+    /// Get the value for `key` at the database's current revision.
+    pub async fn get(&self, db: &DB, key: K) -> PicanteResult<V> {
+        // Encode key once (avoids re-encoding on every lookup); the typed key is handed to
+        // compute on a miss so it never has to be decoded back from the bytes.
+        let encoded = Key::encode_for(&key, self.core.kind, self.core.kind_name)?;
+        self.get_encoded(db, encoded, Some(key)).await
+    }
+
+    /// Get the values for `keys` at the current revision, computing them concurrently.
+    ///
+    /// Values come back in the order of `keys`; a key listed more than once is fetched once.
+    /// Inside a query, every distinct key is recorded as a dependency like with [`Self::get`]
+    /// (in the order the fetches finish). All fetches settle before this returns; if any
+    /// failed, the error of the first failing key in `keys` is returned.
+    pub async fn get_many(&self, db: &DB, keys: Vec<K>) -> PicanteResult<Vec<V>> {
+        let mut slots = Vec::with_capacity(keys.len());
+        let mut distinct: HashMap<Key, usize> = HashMap::new();
+        let mut fetches = Vec::new();
+        for key in keys {
+            let encoded = Key::encode_for(&key, self.core.kind, self.core.kind_name)?;
+            let slot = *distinct.entry(encoded.clone()).or_insert_with(|| {
+                // Each fetch gets its own copy of the query stack, so concurrent computes
+                // don't push their frames onto each other's.
+                fetches.push(frame::fork_scope(self.get_encoded(db, encoded, Some(key))));
+                fetches.len() - 1
+            });
+            slots.push(slot);
+        }
+        let results = futures::future::join_all(fetches).await;
+        slots.into_iter().map(|slot| results[slot].clone()).collect()
+    }
+
+    /// Get the value `key` had at `snapshot`'s revision, for batches of reads that must agree
+    /// with each other while writers move on.
+    ///
+    /// A cached value is returned as long as it still stands at the pinned revision, i.e. it
+    /// last changed at or before it (backdated recomputes and revalidations at later
+    /// revisions keep it available). While the pinned revision is current, a missing or
+    /// stale value is brought up to date first. Otherwise inputs no longer hold their old
+    /// values, so nothing can be recomputed and the read fails with
+    /// [`PicanteError::SnapshotExpired`]. Meant for reads outside queries: no dependency is
+    /// recorded.
+    pub async fn get_at(&self, db: &DB, key: K, snapshot: &Snapshot) -> PicanteResult<V> {
+        let dyn_key = DynKey {
+            kind: self.core.kind,
+            key: Key::encode_for(&key, self.core.kind, self.core.kind_name)?,
+        };
+        let pinned = snapshot.revision();
+        if let Some(value) = self.core.value_at(&dyn_key, pinned).await {
+            return self.downcast_value(value, "get_at");
+        }
+
+        if db.runtime().current_revision() == pinned {
+            self.prefetch(db, key).await?;
+            // If the revision moved on meanwhile, the fresh value may still date from before.
+            if let Some(value) = self.core.value_at(&dyn_key, pinned).await {
+                return self.downcast_value(value, "get_at");
+            }
+        }
+        Err(Arc::new(PicanteError::SnapshotExpired {
+            kind: self.core.kind,
+            key_hash: dyn_key.key.hash(),
+            snapshot: pinned,
+            current: db.runtime().current_revision(),
+        }))
+    }
+
+    /// Like [`Self::get`], looking the key up by a borrowed form, e.g. a `&str` for a
+    /// `String`-keyed query, without allocating an owned key on a cache hit.
+    ///
+    /// Keys are identified by their encoded bytes, so `Q` must encode exactly like the `K`
+    /// it borrows from (as `str` does for `String`, or `[T]` for `Vec<T>`); a `Q` with a
+    /// different encoding silently looks up a different key. On a miss, the owned key handed
+    /// to compute is decoded from those bytes.
+    pub async fn get_borrowed<Q>(&self, db: &DB, key: &Q) -> PicanteResult<V>
+    where
+        K: Borrow<Q>,
+        Q: Facet<'static> + ?Sized,
+    {
+        let encoded = Key::encode_for(key, self.core.kind, self.core.kind_name)?;
+        self.get_encoded(db, encoded, None).await
+    }
+
+    /// Like [`Self::get`], but a panic in compute unwinds into this call instead of being
+    /// returned as [`PicanteError::Panic`].
+    ///
+    /// Useful in tests and under a debugger, where the original panic (with its location and
+    /// backtrace) is more helpful than its message. The cell is still poisoned at the current
+    /// revision, so other readers see the usual `PicanteError::Panic`; a cell that already
+    /// holds a panic at this revision is recomputed so the panic can be observed again.
+    /// If another task is computing the key, this call waits for it like [`Self::get`].
+    pub async fn get_propagate_panics(&self, db: &DB, key: K) -> PicanteResult<V> {
+        let encoded = Key::encode_for(&key, self.core.kind, self.core.kind_name)?;
+        let mode = AccessMode {
+            propagate_panics: true,
+            ..AccessMode::GET
+        };
+        self.access_value(db, encoded, Some(key), mode).await
+    }
+
+    /// Like [`Self::get`], for a key that is already encoded.
+    ///
+    /// `typed_key`, when provided, must be the key `key` was encoded from.
+    pub(crate) async fn get_encoded(
+        &self,
+        db: &DB,
+        key: Key,
+        typed_key: Option<K>,
+    ) -> PicanteResult<V> {
+        self.access_value(db, key, typed_key, AccessMode::GET).await
+    }
+
+    async fn access_value(
+        &self,
+        db: &DB,
+        key: Key,
+        mut typed_key: Option<K>,
+        mode: AccessMode,
+    ) -> PicanteResult<V> {
+        let dyn_key = DynKey {
+            kind: self.core.kind,
+            key,
+        };
+
+        let cycle = self
+            .cycle_recovery
+            .as_ref()
+            .and_then(|recover| Some((recover, frame::enter_cycle(&dyn_key)?)));
+        if let Some((recover, (stack, head))) = cycle {
+            // During fixed-point iteration, later rounds read the previous round's result.
+            if let Some(provisional) = head.cycle_provisional() {
+                return self.downcast_value(provisional, "get");
+            }
+            let key = match typed_key {
+                Some(key) => key,
+                None => dyn_key.key.decode_facet()?,
+            };
+            trace!(
+                kind = self.core.kind.0,
+                key_hash = %format!("{:016x}", dyn_key.key.hash()),
+                depth = stack.len(),
+                "cycle: recovering"
+            );
+            let seed = recover(db, &key, &stack);
+            head.set_cycle_provisional(erase_value(seed.clone()));
+            return Ok(seed);
+        }
+
+        let key_slot: &mut (dyn Any + Send) = &mut typed_key;
 
         // Ensure we have a task-local query stack (required for cycle detection + dep tracking).
-        let result = frame::scope_if_needed(|| async {
+        let result = frame::scope_if_needed(|| async move {
             // Call type-erased core with trait object (dyn dispatch)
             self.core
                 .access_scoped_erased(
                     db,
-                    dyn_key.clone(),
-                    true,
+                    dyn_key,
+                    key_slot,
+                    mode,
                     self.compute.as_ref(),
                     self.eq_erased,
                 )
@@ -759,15 +2278,23 @@ where
         // Downcast at the boundary - MUST succeed due to type safety
         let arc_any = result.value.ok_or_else(|| {
             Arc::new(PicanteError::Panic {
-                message: format!("[BUG] expected value but got None for key {:?}", dyn_key),
+                message: format!(
+                    "[BUG] expected value but got None in get() for ingredient {}",
+                    self.core.kind_name
+                ),
             })
         })?;
 
+        self.downcast_value(arc_any, "get")
+    }
+
+    /// Recover a `V` from a cell value.
+    fn downcast_value(&self, arc_any: ArcAny, caller: &str) -> PicanteResult<V> {
         // Downcast Arc<dyn Any> → Arc<V>
         let arc_v = arc_any.downcast::<V>().map_err(|any| {
             Arc::new(PicanteError::Panic {
                 message: format!(
-                    "[BUG] type mismatch in get() for ingredient {}: expected {}, got TypeId {:?}",
+                    "[BUG] type mismatch in {caller}() for ingredient {}: expected {}, got TypeId {:?}",
                     self.core.kind_name,
                     std::any::type_name::<V>(),
                     (&*any as &dyn std::any::Any).type_id()
@@ -776,24 +2303,111 @@ where
         })?;
 
         // Extract V from Arc (try_unwrap if sole owner, else clone)
-        let value = Arc::try_unwrap(arc_v).unwrap_or_else(|arc| (*arc).clone());
+        Ok(Arc::try_unwrap(arc_v).unwrap_or_else(|arc| (*arc).clone()))
+    }
+
+    /// Return the cached value for `key` if it is already verified at the current revision,
+    /// without waiting or computing.
+    ///
+    /// Returns `Ok(None)` when the cell is missing, stale, poisoned, being computed, or
+    /// momentarily locked by another task; never blocks. Meant for latency-sensitive callers
+    /// (e.g. a render loop) that would rather skip a value than wait for it. Inside a query, a
+    /// dependency on `key` is recorded either way, so the calling query is revalidated (and
+    /// the value brought up to date) once the database changes.
+    pub fn get_if_ready(&self, db: &DB, key: &K) -> PicanteResult<Option<V>> {
+        let key = Key::encode_for(key, self.core.kind, self.core.kind_name)?;
+        self.get_if_ready_encoded(db, key)
+    }
+
+    /// Like [`Self::get_if_ready`], looking the key up by a borrowed form (see
+    /// [`Self::get_borrowed`] for the contract on `Q`).
+    pub fn get_if_ready_borrowed<Q>(&self, db: &DB, key: &Q) -> PicanteResult<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Facet<'static> + ?Sized,
+    {
+        let key = Key::encode_for(key, self.core.kind, self.core.kind_name)?;
+        self.get_if_ready_encoded(db, key)
+    }
+
+    pub(crate) fn get_if_ready_encoded(&self, db: &DB, key: Key) -> PicanteResult<Option<V>> {
+        if frame::has_active_frame() {
+            frame::record_dep(Dep {
+                kind: self.core.kind,
+                key: key.clone(),
+            });
+        }
+
+        let requested = DynKey {
+            kind: self.core.kind,
+            key,
+        };
+        match self.core.try_ready_value(db, &requested) {
+            Some(value) => self.downcast_value(value, "get_if_ready").map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Return the cached value for `key` if it is verified at the current revision, leaving
+    /// the incremental graph untouched.
+    ///
+    /// Unlike [`Self::get_if_ready`] this never records a dependency, even inside a query,
+    /// and doesn't count towards cache tallies, so it is safe to poll from outside the
+    /// database (e.g. a UI thread) as often as needed. Returns `Ok(None)` in the same cases
+    /// as `get_if_ready`.
+    pub fn get_if_cached(&self, db: &DB, key: &K) -> PicanteResult<Option<V>> {
+        let key = Key::encode_for(key, self.core.kind, self.core.kind_name)?;
+        self.get_if_cached_encoded(db, key)
+    }
+
+    /// Like [`Self::get_if_cached`], looking the key up by a borrowed form (see
+    /// [`Self::get_borrowed`] for the contract on `Q`).
+    pub fn get_if_cached_borrowed<Q>(&self, db: &DB, key: &Q) -> PicanteResult<Option<V>>
+    where
+        K: Borrow<Q>,
+        Q: Facet<'static> + ?Sized,
+    {
+        let key = Key::encode_for(key, self.core.kind, self.core.kind_name)?;
+        self.get_if_cached_encoded(db, key)
+    }
 
-        Ok(value)
+    pub(crate) fn get_if_cached_encoded(&self, db: &DB, key: Key) -> PicanteResult<Option<V>> {
+        let requested = DynKey {
+            kind: self.core.kind,
+            key,
+        };
+        match self.core.peek_ready_value(db, &requested) {
+            Some((_, value)) => self.downcast_value(value, "get_if_cached").map(Some),
+            None => Ok(None),
+        }
     }
 
     /// Ensure the value is valid at the current revision and return its `changed_at`.
     pub async fn touch(&self, db: &DB, key: K) -> PicanteResult<Revision> {
         // Encode key once
+        let key = Key::encode_for(&key, self.core.kind, self.core.kind_name)?;
+        self.touch_encoded(db, key).await
+    }
+
+    /// Like [`Self::touch`], for a key that is already encoded.
+    pub(crate) async fn touch_encoded(&self, db: &DB, key: Key) -> PicanteResult<Revision> {
         let dyn_key = DynKey {
             kind: self.core.kind,
-            key: Key::encode_facet(&key)?,
+            key,
         };
 
         // Ensure we have a task-local query stack (required for cycle detection + dep tracking).
         // Note: touch may still compute/revalidate; it just doesn't return the value to the caller.
         let result = frame::scope_if_needed(|| async {
             self.core
-                .access_scoped_erased(db, dyn_key, false, self.compute.as_ref(), self.eq_erased)
+                .access_scoped_erased(
+                    db,
+                    dyn_key,
+                    &mut None::<K>,
+                    AccessMode::TOUCH,
+                    self.compute.as_ref(),
+                    self.eq_erased,
+                )
                 .await
         })
         .await?;
@@ -801,6 +2415,75 @@ where
         Ok(result.changed_at)
     }
 
+    /// Watch the value of `key`.
+    ///
+    /// The receiver starts with the latest value computed for `key`, if any (it may be stale),
+    /// and is sent each new value this ingredient computes for it, or takes over from a runtime
+    /// sharing its identity (a snapshot, or its parent database): a recompute that reproduces
+    /// the previous value (early cutoff) sends nothing, nor does a failed one. Invalidating the
+    /// key (see [`Self::invalidate`]) sends `None`. Nothing is computed on subscribing, so
+    /// something still has to `get` the key for values to flow. All receivers of a key share
+    /// one channel, which is dropped once they are all gone.
+    pub fn subscribe(&self, key: &K) -> PicanteResult<watch::Receiver<Option<V>>> {
+        let dyn_key = DynKey {
+            kind: self.core.kind,
+            key: Key::encode_for(key, self.core.kind, self.core.kind_name)?,
+        };
+        let mut watchers = self.core.watchers.write();
+        if let Some(sender) = watchers
+            .get(&dyn_key)
+            .and_then(|sender| sender.downcast_ref::<watch::Sender<Option<V>>>())
+        {
+            return Ok(sender.subscribe());
+        }
+
+        let latest = self.core.cells.read().get(&dyn_key).and_then(|cell| {
+            match &*cell.state.try_lock().ok()? {
+                ErasedState::Ready { value, .. } => value.downcast_ref::<V>().cloned(),
+                _ => None,
+            }
+        });
+        let (sender, receiver) = watch::channel(latest);
+        watchers.insert(dyn_key, Box::new(sender));
+        Ok(receiver)
+    }
+
+    /// Bring `key`'s cell up to date without returning its value, so a later [`Self::get`]
+    /// is a hit.
+    ///
+    /// For warming likely-needed queries ahead of time: the returned future can be spawned
+    /// with an owned database handle (e.g. a snapshot). Does nothing if the cell is already
+    /// verified at the current revision; otherwise it revalidates or computes like
+    /// [`Self::touch`], and a failure is cached and returned like for `get`. Called from
+    /// inside a query, it doesn't make that query depend on `key`.
+    pub async fn prefetch(&self, db: &DB, key: K) -> PicanteResult<()> {
+        let dyn_key = DynKey {
+            kind: self.core.kind,
+            key: Key::encode_for(&key, self.core.kind, self.core.kind_name)?,
+        };
+        if self.core.peek_ready_value(db, &dyn_key).is_some() {
+            return Ok(());
+        }
+
+        // Hand compute the typed key so it isn't decoded back from the bytes.
+        let mut typed_key = Some(key);
+        let key_slot: &mut (dyn Any + Send) = &mut typed_key;
+        frame::scope_if_needed(|| async move {
+            self.core
+                .access_scoped_erased(
+                    db,
+                    dyn_key,
+                    key_slot,
+                    AccessMode::TOUCH,
+                    self.compute.as_ref(),
+                    self.eq_erased,
+                )
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
     /// Create a snapshot of this ingredient's cells.
     ///
     /// This is an O(1) operation due to structural sharing in `im::HashMap`.
@@ -822,18 +2505,163 @@ where
     pub fn cell_for_key(&self, key: &K) -> PicanteResult<Option<Arc<ErasedCell>>> {
         let dyn_key = DynKey {
             kind: self.core.kind,
-            key: Key::encode_facet(key)?,
+            key: Key::encode_for(key, self.core.kind, self.core.kind_name)?,
         };
         Ok(self.core.cells.read().get(&dyn_key).cloned())
     }
 
+    /// Read statistics for `key`'s cell, or `None` if the key was never requested or loaded.
+    ///
+    /// Reads are counted on every successful [`Self::get`] / [`Self::touch`] (including cache
+    /// hits) with two relaxed atomics, so this is cheap enough to leave on in production.
+    pub async fn access_info(&self, key: &K) -> PicanteResult<Option<AccessInfo>> {
+        match self.cell_for_key(key)? {
+            Some(cell) => Ok(Some(cell.access_info().await)),
+            None => Ok(None),
+        }
+    }
+
+    /// The lifecycle state of `key`'s cell, or `None` if the key was never requested or
+    /// loaded.
+    ///
+    /// Holds the cell's lock only long enough to read the state; it records no dependency and
+    /// computes nothing. A `Ready` or `Poisoned` cell verified before the current revision is
+    /// stale and will be revalidated on its next access.
+    pub async fn peek_state(&self, key: &K) -> PicanteResult<Option<CellState>> {
+        match self.cell_for_key(key)? {
+            Some(cell) => Ok(Some(cell.state().await)),
+            None => Ok(None),
+        }
+    }
+
+    /// The dependencies recorded by the latest computation of `key`, or `None` unless its cell
+    /// holds a value.
+    ///
+    /// Each distinct read appears once, in first-read order. The deps of a value that is
+    /// stale but not yet revalidated are returned as they were; for the transitive graph
+    /// across all ingredients see [`crate::debug::DependencyGraph`].
+    pub async fn dependencies(&self, key: &K) -> PicanteResult<Option<Vec<Dep>>> {
+        match self.cell_for_key(key)? {
+            Some(cell) => Ok(cell.ready_record().await.map(|record| record.deps.to_vec())),
+            None => Ok(None),
+        }
+    }
+
+    /// The ordered call trace of the latest computation of `key`, or `None` if it hasn't
+    /// been computed since [`frame::enable_call_trace`] was called.
+    ///
+    /// Each entry is one read the compute made, in order and with repetitions (see
+    /// [`TracedCall`]); a failed computation keeps its trace too. Derived reads are listed
+    /// when they return, so a compute that awaits several queries concurrently sees them in
+    /// completion order.
+    pub fn last_call_trace(&self, key: &K) -> PicanteResult<Option<Arc<[TracedCall]>>> {
+        let dyn_key = DynKey {
+            kind: self.core.kind,
+            key: Key::encode_for(key, self.core.kind, self.core.kind_name)?,
+        };
+        Ok(self.core.call_traces.read().get(&dyn_key).cloned())
+    }
+
+    /// Drop the cached value for `key` so its next access recomputes, and bump the revision
+    /// so dependents re-evaluate.
+    ///
+    /// For results that depend on something outside the database (a file, a remote service)
+    /// that changed out of band. A computation of `key` already in flight keeps running, but
+    /// its result is discarded: callers waiting on it, and the computation itself once it
+    /// notices the new revision, recompute instead. The fresh value counts as changed, so
+    /// dependents that read `key` recompute too. Pins are kept.
+    ///
+    /// Returns `true` if a cell was dropped; the revision is only bumped in that case.
+    pub fn invalidate(&self, db: &DB, key: &K) -> PicanteResult<bool> {
+        let key = Key::encode_for(key, self.core.kind, self.core.kind_name)?;
+        Ok(self.invalidate_encoded(db, key))
+    }
+
+    /// Like [`Self::invalidate`], looking the key up by a borrowed form (see
+    /// [`Self::get_borrowed`] for the contract on `Q`).
+    pub fn invalidate_borrowed<Q>(&self, db: &DB, key: &Q) -> PicanteResult<bool>
+    where
+        K: Borrow<Q>,
+        Q: Facet<'static> + ?Sized,
+    {
+        let key = Key::encode_for(key, self.core.kind, self.core.kind_name)?;
+        Ok(self.invalidate_encoded(db, key))
+    }
+
+    pub(crate) fn invalidate_encoded(&self, db: &DB, key: Key) -> bool {
+        let dyn_key = DynKey {
+            kind: self.core.kind,
+            key,
+        };
+        if !self.core.discard_cell(db.runtime().id(), &dyn_key) {
+            return false;
+        }
+        self.core.notify_watchers(&dyn_key, None);
+        db.runtime().bump_revision();
+        true
+    }
+
+    /// [`Self::invalidate`] every cached key at once, bumping the revision a single time.
+    ///
+    /// Returns the number of cells dropped; the revision is left alone when there were none.
+    pub fn invalidate_all(&self, db: &DB) -> usize {
+        let dropped = self.discard_all(db);
+        if dropped > 0 {
+            db.runtime().bump_revision();
+        }
+        dropped
+    }
+
+    /// Drop every cell like [`Self::invalidate_all`] without bumping the revision.
+    pub(crate) fn discard_all(&self, db: &DB) -> usize {
+        let cells = std::mem::take(&mut *self.core.cells.write());
+        if cells.is_empty() {
+            return 0;
+        }
+        self.core.call_traces.write().clear();
+        for (dyn_key, cell) in cells.iter() {
+            inflight::shared_cache_remove(db.runtime().id(), self.core.kind, &dyn_key.key);
+            self.core.retire_cell(dyn_key, cell);
+            self.core.notify_watchers(dyn_key, None);
+        }
+        cells.len()
+    }
+
+    /// Protect `key`'s cell from capacity eviction.
+    ///
+    /// Pinning only affects eviction: a pinned cell is still invalidated and recomputed like
+    /// any other. Pins are kept per key, so a key can be pinned before it is first computed
+    /// and stays pinned across recomputations until [`Self::unpin`]. They are not persisted.
+    /// Returns `true` if the key wasn't pinned already.
+    pub fn pin(&self, key: &K) -> PicanteResult<bool> {
+        let key = Key::encode_for(key, self.core.kind, self.core.kind_name)?;
+        Ok(self.core.pinned.write().insert(key))
+    }
+
+    /// Make `key`'s cell evictable again. Returns `true` if it was pinned.
+    pub fn unpin(&self, key: &K) -> PicanteResult<bool> {
+        let key = Key::encode_for(key, self.core.kind, self.core.kind_name)?;
+        Ok(self.core.pinned.write().remove(&key))
+    }
+
+    /// Whether `key` is pinned (see [`Self::pin`]).
+    pub fn is_pinned(&self, key: &K) -> PicanteResult<bool> {
+        let key = Key::encode_for(key, self.core.kind, self.core.kind_name)?;
+        Ok(self.core.is_pinned(&key))
+    }
+
+    /// Number of pinned keys.
+    pub fn pinned_count(&self) -> usize {
+        self.core.pinned.read().len()
+    }
+
     /// Insert a ready cell record into this ingredient (overwriting any existing cell).
     ///
     /// This is intended for cache promotion (e.g. from a snapshot back into a live DB).
     pub fn insert_ready_record(&self, key: &K, record: ErasedReadyRecord) -> PicanteResult<()> {
         let dyn_key = DynKey {
             kind: self.core.kind,
-            key: Key::encode_facet(key)?,
+            key: Key::encode_for(key, self.core.kind, self.core.kind_name)?,
         };
         let cell = Arc::new(ErasedCell::new_ready(
             record.value,
@@ -869,6 +2697,98 @@ where
         Ok(true)
     }
 
+    /// Decode one persisted record into the cell it describes (without inserting it).
+    pub(crate) fn decode_record(&self, bytes: &[u8]) -> PicanteResult<(DynKey, Arc<ErasedCell>)> {
+        self.record_into_cell(self.decode_typed_record(bytes)?)
+    }
+
+    fn decode_typed_record(&self, bytes: &[u8]) -> PicanteResult<DerivedRecord<K, V>> {
+        facet_postcard::from_slice(bytes).map_err(|e| {
+            Arc::new(PicanteError::Decode {
+                what: "derived record",
+                message: format!("{e:?}"),
+                kind: Some(self.core.kind),
+                kind_name: Some(self.core.kind_name),
+            })
+        })
+    }
+
+    fn record_into_cell(
+        &self,
+        rec: DerivedRecord<K, V>,
+    ) -> PicanteResult<(DynKey, Arc<ErasedCell>)> {
+        let deps: Arc<[Dep]> = rec
+            .deps
+            .into_iter()
+            .map(DepRecord::into_dep)
+            .collect::<Vec<_>>()
+            .into();
+
+        // Create DynKey from K
+        let dyn_key = DynKey {
+            kind: self.core.kind,
+            key: Key::encode_for(&rec.key, self.core.kind, self.core.kind_name)?,
+        };
+
+        // Wrap value as Arc<dyn Any>
+        let erased_value = erase_value(rec.value);
+
+        let cell = Arc::new(ErasedCell::new_ready(
+            erased_value,
+            Revision(rec.verified_at),
+            Revision(rec.changed_at),
+            deps,
+        ));
+        Ok((dyn_key, cell))
+    }
+
+    /// Like [`PersistableIngredient::load_records`], skipping records whose key and value
+    /// `filter` rejects.
+    ///
+    /// Use it to discard cached entries that decode fine but break an invariant (e.g. after a
+    /// subtle change in what a value means) without throwing away the whole section. A
+    /// skipped key simply stays cold and is computed on its next access. Like
+    /// `load_records`, nothing is inserted if any record fails to decode. `filter` may be
+    /// called from several threads for large sections. Returns the number of records
+    /// skipped.
+    pub fn load_records_with_filter<F>(
+        &self,
+        records: Vec<Vec<u8>>,
+        filter: F,
+    ) -> PicanteResult<usize>
+    where
+        F: Fn(&K, &V) -> bool + Sync,
+    {
+        let decoded = decode_all(&records, |bytes| {
+            let rec = self.decode_typed_record(bytes)?;
+            if !filter(&rec.key, &rec.value) {
+                return Ok(None);
+            }
+            self.record_into_cell(rec).map(Some)
+        })?;
+        let mut loaded = 0usize;
+        {
+            let mut cells = self.core.cells.write();
+            for (dyn_key, cell) in decoded.into_iter().flatten() {
+                cells.insert(dyn_key, cell);
+                loaded += 1;
+            }
+        }
+        let skipped = records.len() - loaded;
+        debug!(
+            kind = self.core.kind.0,
+            loaded,
+            skipped,
+            "load_records_with_filter (derived)"
+        );
+        Ok(skipped)
+    }
+
+    /// Insert a decoded cell (see [`Self::decode_record`]), replacing any existing cell.
+    pub(crate) fn insert_cell(&self, dyn_key: DynKey, cell: Arc<ErasedCell>) {
+        self.core.cells.write().insert(dyn_key, cell);
+    }
+
     /// Create a deep snapshot of this ingredient's cells.
     ///
     /// Unlike `snapshot()` which shares `Arc<Cell>` references, this method
@@ -930,6 +2850,14 @@ where
 pub struct ErasedCell {
     state: Mutex<ErasedState>,
     notify: Notify,
+    /// Revision of the latest successful read (meaningful once `read_count > 0`).
+    last_read: AtomicU64,
+    read_count: AtomicU64,
+    /// Owning ingredient's LRU clock at the latest access (see `DerivedCore::note_use`).
+    last_used: AtomicU64,
+    /// Lowest durability among the dependencies of the `Ready` value (index into
+    /// [`Durability::ALL`]); only written under the state lock.
+    durability: AtomicU8,
 }
 
 /// Type-erased state (not generic over V).
@@ -955,6 +2883,8 @@ enum ErasedState {
     Poisoned {
         error: Arc<PicanteError>,
         verified_at: Revision,
+        /// Failed attempts at `verified_at` (saturating), for the retrying [`ErrorPolicy`]s.
+        failures: u32,
     },
 }
 
@@ -963,6 +2893,10 @@ impl ErasedCell {
         Self {
             state: Mutex::new(ErasedState::Vacant),
             notify: Notify::new(),
+            last_read: AtomicU64::new(0),
+            read_count: AtomicU64::new(0),
+            last_used: AtomicU64::new(0),
+            durability: AtomicU8::new(0),
         }
     }
 
@@ -980,6 +2914,65 @@ impl ErasedCell {
                 deps,
             }),
             notify: Notify::new(),
+            last_read: AtomicU64::new(0),
+            read_count: AtomicU64::new(0),
+            last_used: AtomicU64::new(0),
+            durability: AtomicU8::new(0),
+        }
+    }
+
+    fn durability(&self) -> Durability {
+        Durability::ALL[usize::from(self.durability.load(Ordering::Relaxed))]
+    }
+
+    fn set_durability(&self, durability: Durability) {
+        self.durability
+            .store(durability.index() as u8, Ordering::Relaxed);
+    }
+
+    /// Record a successful read at `rev`. Lock-free, called on every answered access.
+    fn note_read(&self, rev: Revision) {
+        self.last_read.fetch_max(rev.0, Ordering::Relaxed);
+        self.read_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Whether the cell holds a finished result and nobody holds its lock.
+    fn is_settled(&self) -> bool {
+        matches!(
+            self.state.try_lock().as_deref(),
+            Ok(ErasedState::Ready { .. } | ErasedState::Poisoned { .. })
+        )
+    }
+
+    /// Where this cell is in its lifecycle (see [`CellState`]).
+    pub async fn state(&self) -> CellState {
+        match &*self.state.lock().await {
+            ErasedState::Vacant => CellState::Vacant,
+            ErasedState::Running { started_at } => CellState::Running {
+                started_at: *started_at,
+            },
+            ErasedState::Ready { verified_at, .. } => CellState::Ready {
+                verified_at: *verified_at,
+            },
+            ErasedState::Poisoned { verified_at, .. } => CellState::Poisoned {
+                verified_at: *verified_at,
+            },
+        }
+    }
+
+    /// Read statistics for this cell (see [`AccessInfo`]).
+    pub async fn access_info(&self) -> AccessInfo {
+        let verified_at = match &*self.state.lock().await {
+            ErasedState::Ready { verified_at, .. } | ErasedState::Poisoned { verified_at, .. } => {
+                Some(*verified_at)
+            }
+            ErasedState::Vacant | ErasedState::Running { .. } => None,
+        };
+        let read_count = self.read_count.load(Ordering::Relaxed);
+        AccessInfo {
+            last_read: (read_count > 0).then(|| Revision(self.last_read.load(Ordering::Relaxed))),
+            verified_at,
+            read_count,
         }
     }
 
@@ -1006,6 +2999,99 @@ impl ErasedCell {
     }
 }
 
+/// Lifecycle state of a derived cell, without its value (see
+/// [`DerivedIngredient::peek_state`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellState {
+    /// Never computed, or its last computation was cancelled.
+    Vacant,
+    /// Being computed.
+    Running {
+        /// Revision the computation started at.
+        started_at: Revision,
+    },
+    /// Holds a value.
+    Ready {
+        /// Revision at which the value was last verified.
+        verified_at: Revision,
+    },
+    /// Holds the error of a failed computation.
+    Poisoned {
+        /// Revision at which the computation failed.
+        verified_at: Revision,
+    },
+}
+
+/// Read statistics of a derived cell, for eviction tuning and finding hot queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccessInfo {
+    /// Revision of the latest successful `get`/`touch`, or `None` if the cell was never read
+    /// (e.g. it was only loaded from a cache file).
+    pub last_read: Option<Revision>,
+    /// Revision at which the cell's value (or error) was last verified, or `None` while it is
+    /// empty or being computed.
+    pub verified_at: Option<Revision>,
+    /// Number of successful `get`/`touch` calls answered by this cell, including the touches
+    /// made while revalidating queries that depend on it.
+    pub read_count: u64,
+}
+
+/// Access counters of a derived ingredient (see [`DerivedIngredient::stats`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryStats {
+    /// Accesses answered from a cell already verified at the current revision.
+    pub hits: u64,
+    /// Accesses answered by revalidating dependencies (backdating) instead of recomputing.
+    pub revalidations: u64,
+    /// Times the compute function ran.
+    pub recomputes: u64,
+    /// Computes that returned an error (timeouts and unconverged cycles included, but not
+    /// cancellations).
+    pub errors: u64,
+    /// Computes that panicked.
+    pub panics: u64,
+}
+
+/// Live counters behind [`QueryStats`].
+#[derive(Debug, Default)]
+struct QueryCounters {
+    hits: AtomicU64,
+    revalidations: AtomicU64,
+    recomputes: AtomicU64,
+    errors: AtomicU64,
+    panics: AtomicU64,
+}
+
+impl QueryCounters {
+    fn counters(&self) -> [&AtomicU64; 5] {
+        [
+            &self.hits,
+            &self.revalidations,
+            &self.recomputes,
+            &self.errors,
+            &self.panics,
+        ]
+    }
+
+    fn snapshot(&self) -> QueryStats {
+        let [hits, revalidations, recomputes, errors, panics] =
+            self.counters().map(|c| c.load(Ordering::Relaxed));
+        QueryStats {
+            hits,
+            revalidations,
+            recomputes,
+            errors,
+            panics,
+        }
+    }
+
+    fn reset(&self) {
+        for counter in self.counters() {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
 /// A type-erased derived-cell record that can be re-inserted into another runtime.
 #[derive(Clone)]
 pub struct ErasedReadyRecord {
@@ -1023,21 +3109,51 @@ pub struct ErasedReadyRecord {
 struct ErasedAccessResult {
     value: Option<Arc<dyn std::any::Any + Send + Sync>>,
     changed_at: Revision,
+    outcome: AccessOutcome,
+}
+
+/// How a single erased access behaves.
+#[derive(Clone, Copy)]
+struct AccessMode {
+    /// Return the value and record a dependency, rather than only reporting `changed_at`.
+    want_value: bool,
+    /// Resume a compute panic in the caller instead of returning [`PicanteError::Panic`].
+    propagate_panics: bool,
+}
+
+impl AccessMode {
+    const GET: Self = Self {
+        want_value: true,
+        propagate_panics: false,
+    };
+    const TOUCH: Self = Self {
+        want_value: false,
+        propagate_panics: false,
+    };
 }
 
 #[derive(Debug, Clone, Facet)]
-struct DepRecord {
-    kind_id: u32,
-    key_bytes: Vec<u8>,
+pub(crate) struct DepRecord {
+    pub(crate) kind_id: u32,
+    pub(crate) key_bytes: Vec<u8>,
+}
+
+impl DepRecord {
+    pub(crate) fn into_dep(self) -> Dep {
+        Dep {
+            kind: QueryKindId(self.kind_id),
+            key: Key::from_bytes(self.key_bytes),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Facet)]
-struct DerivedRecord<K, V> {
-    key: K,
-    value: V,
-    verified_at: u64,
-    changed_at: u64,
-    deps: Vec<DepRecord>,
+pub(crate) struct DerivedRecord<K, V> {
+    pub(crate) key: K,
+    pub(crate) value: V,
+    pub(crate) verified_at: u64,
+    pub(crate) changed_at: u64,
+    pub(crate) deps: Vec<DepRecord>,
 }
 
 impl<DB, K, V> PersistableIngredient for DerivedIngredient<DB, K, V>
@@ -1058,12 +3174,24 @@ where
         SectionType::Derived
     }
 
+    fn logic_version(&self) -> u32 {
+        self.core.logic_version
+    }
+
     fn clear(&self) {
         let mut cells = self.core.cells.write();
         *cells = im::HashMap::new();
+        self.core.call_traces.write().clear();
     }
 
     fn save_records(&self) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        self.save_records_yielding(0)
+    }
+
+    fn save_records_yielding(
+        &self,
+        yield_every: usize,
+    ) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
         Box::pin(async move {
             // Collect snapshot under lock, then release before async work
             let snapshot: Vec<(DynKey, Arc<ErasedCell>)> = {
@@ -1072,7 +3200,9 @@ where
             };
             let mut records = Vec::with_capacity(snapshot.len());
 
-            for (dyn_key, cell) in snapshot {
+            for (i, (dyn_key, cell)) in snapshot.into_iter().enumerate() {
+                yield_point(i, yield_every).await;
+
                 // Decode DynKey back to K (we're in DerivedIngredient<DB, K, V> so we know K!)
                 let key: K = dyn_key.key.decode_facet().map_err(|e| {
                     Arc::new(PicanteError::Panic {
@@ -1127,6 +3257,8 @@ where
                     Arc::new(PicanteError::Encode {
                         what: "derived record",
                         message: format!("{e:?}"),
+                        kind: Some(self.core.kind),
+                        kind_name: Some(self.core.kind_name),
                     })
                 })?;
                 records.push(bytes);
@@ -1141,40 +3273,11 @@ where
     }
 
     fn load_records(&self, records: Vec<Vec<u8>>) -> PicanteResult<()> {
-        for bytes in records {
-            let rec: DerivedRecord<K, V> = facet_postcard::from_slice(&bytes).map_err(|e| {
-                Arc::new(PicanteError::Decode {
-                    what: "derived record",
-                    message: format!("{e:?}"),
-                })
-            })?;
-
-            let deps: Arc<[Dep]> = rec
-                .deps
-                .into_iter()
-                .map(|d| Dep {
-                    kind: QueryKindId(d.kind_id),
-                    key: Key::from_bytes(d.key_bytes),
-                })
-                .collect::<Vec<_>>()
-                .into();
-
-            // Create DynKey from K
-            let dyn_key = DynKey {
-                kind: self.core.kind,
-                key: Key::encode_facet(&rec.key)?,
-            };
-
-            // Wrap value as Arc<dyn Any>
-            let erased_value = Arc::new(rec.value) as Arc<dyn std::any::Any + Send + Sync>;
-
-            let cell = Arc::new(ErasedCell::new_ready(
-                erased_value,
-                Revision(rec.verified_at),
-                Revision(rec.changed_at),
-                deps,
-            ));
-            let mut cells = self.core.cells.write();
+        // Decode everything first: the lock is only taken for the (cheap) inserts, and a bad
+        // record leaves the ingredient untouched.
+        let decoded = decode_all(&records, |bytes| self.decode_record(bytes))?;
+        let mut cells = self.core.cells.write();
+        for (dyn_key, cell) in decoded {
             cells.insert(dyn_key, cell);
         }
         Ok(())
@@ -1279,6 +3382,8 @@ where
                     Arc::new(PicanteError::Encode {
                         what: "derived key",
                         message: format!("{e:?}"),
+                        kind: Some(self.core.kind),
+                        kind_name: Some(self.core.kind_name),
                     })
                 })?;
 
@@ -1286,6 +3391,8 @@ where
                     Arc::new(PicanteError::Encode {
                         what: "derived record",
                         message: format!("{e:?}"),
+                        kind: Some(self.core.kind),
+                        kind_name: Some(self.core.kind_name),
                     })
                 })?;
 
@@ -1313,6 +3420,8 @@ where
             Arc::new(PicanteError::Decode {
                 what: "derived key from WAL",
                 message: format!("{e:?}"),
+                kind: Some(self.core.kind),
+                kind_name: Some(self.core.kind_name),
             })
         })?;
 
@@ -1323,6 +3432,8 @@ where
                     Arc::new(PicanteError::Decode {
                         what: "derived record from WAL",
                         message: format!("{e:?}"),
+                        kind: Some(self.core.kind),
+                        kind_name: Some(self.core.kind_name),
                     })
                 })?;
 
@@ -1339,11 +3450,11 @@ where
             // Create DynKey from K
             let dyn_key = DynKey {
                 kind: self.core.kind,
-                key: Key::encode_facet(&rec.key)?,
+                key: Key::encode_for(&rec.key, self.core.kind, self.core.kind_name)?,
             };
 
             // Wrap value as Arc<dyn Any>
-            let erased_value = Arc::new(rec.value) as Arc<dyn std::any::Any + Send + Sync>;
+            let erased_value = erase_value(rec.value);
 
             let cell = Arc::new(ErasedCell::new_ready(
                 erased_value,
@@ -1358,7 +3469,7 @@ where
             // Delete operation - remove the key from cells
             let dyn_key = DynKey {
                 kind: self.core.kind,
-                key: Key::encode_facet(&key)?,
+                key: Key::encode_for(&key, self.core.kind, self.core.kind_name)?,
             };
 
             let mut cells = self.core.cells.write();
@@ -1377,14 +3488,39 @@ where
 {
     fn touch<'a>(&'a self, db: &'a DB, key: Key) -> BoxFuture<'a, PicanteResult<Touch>> {
         Box::pin(async move {
-            let key: K = key.decode_facet()?;
-            let changed_at = self.touch(db, key).await?;
+            let changed_at = self.touch_encoded(db, key).await?;
             Ok(Touch { changed_at })
         })
     }
+
+    fn stale_deps(&self, key: &Key, revision: Revision) -> Option<Arc<[Dep]>> {
+        self.core.stale_deps(key, revision)
+    }
+
+    fn durability(&self, key: &Key) -> Durability {
+        self.core.durability(key)
+    }
+
+    fn discard_cell(&self, db: &DB, key: &Key) {
+        let dyn_key = DynKey {
+            kind: self.core.kind,
+            key: key.clone(),
+        };
+        self.core.discard_cell(db.runtime().id(), &dyn_key);
+    }
+
+    fn cached_value(&self, db: &DB, key: &Key) -> Option<ArcAny> {
+        let dyn_key = DynKey {
+            kind: self.core.kind,
+            key: key.clone(),
+        };
+        self.core
+            .peek_ready_value(db, &dyn_key)
+            .map(|(_, value)| value)
+    }
 }
 
-fn panic_message(panic: Box<dyn std::any::Any + Send>) -> String {
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
     if let Some(s) = panic.downcast_ref::<&str>() {
         (*s).to_string()
     } else if let Some(s) = panic.downcast_ref::<String>() {
@@ -0,0 +1,259 @@
+use crate::db::{DynIngredient, Touch};
+use crate::error::{PicanteError, PicanteResult};
+use crate::frame;
+use crate::ingredient::InputRecord;
+use crate::key::{Dep, Key, QueryKindId};
+use crate::persist::{PersistableIngredient, SectionType, yield_point};
+use crate::revision::Revision;
+use crate::runtime::HasRuntime;
+use futures::future::BoxFuture;
+use parking_lot::RwLock;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, trace};
+
+#[derive(Clone)]
+struct ExternalEntry {
+    /// Last seen modification time (nanoseconds since the Unix epoch), `None` if missing.
+    mtime: Option<u64>,
+    changed_at: Revision,
+}
+
+/// An input backed by the filesystem: it tracks the modification time of files.
+///
+/// Derived queries call [`ExternalInput::track`] before reading a file, which records a
+/// dependency on the path. Whenever such a query is revalidated, the path is stat'ed again and
+/// a different mtime (or the file appearing/disappearing) bumps the revision, so the query
+/// recomputes.
+///
+/// Revalidation only happens once the revision has moved, so a database whose inputs are
+/// otherwise idle won't notice file changes by itself: call [`ExternalInput::refresh`] (e.g.
+/// from a file watcher, or before each build) to re-check every tracked path.
+pub struct ExternalInput {
+    kind: QueryKindId,
+    kind_name: &'static str,
+    entries: RwLock<im::HashMap<String, ExternalEntry>>,
+}
+
+impl ExternalInput {
+    /// Create an empty external input.
+    pub fn new(kind: QueryKindId, kind_name: &'static str) -> Self {
+        Self {
+            kind,
+            kind_name,
+            entries: RwLock::new(im::HashMap::new()),
+        }
+    }
+
+    /// The stable kind id.
+    pub fn kind(&self) -> QueryKindId {
+        self.kind
+    }
+
+    /// Debug name for this ingredient.
+    pub fn kind_name(&self) -> &'static str {
+        self.kind_name
+    }
+
+    /// Track `path` and return its current modification time (`None` if it doesn't exist).
+    ///
+    /// If there's an active query frame, records a dependency edge.
+    #[tracing::instrument(level = "trace", skip_all, fields(kind = self.kind.0))]
+    pub async fn track<DB: HasRuntime>(
+        &self,
+        db: &DB,
+        path: &str,
+    ) -> PicanteResult<Option<SystemTime>> {
+        if frame::has_active_frame() {
+            let encoded_key = Key::encode_for(&path.to_string(), self.kind, self.kind_name)?;
+            trace!(kind = self.kind.0, key_hash = %format!("{:016x}", encoded_key.hash()), "external dep");
+            frame::record_dep(Dep {
+                kind: self.kind,
+                key: encoded_key,
+            });
+        }
+
+        let (mtime, _) = self.sync(db, path).await;
+        Ok(mtime.map(|nanos| UNIX_EPOCH + Duration::from_nanos(nanos)))
+    }
+
+    /// Re-check every tracked path, bumping the revision for each one that changed.
+    ///
+    /// Returns how many paths changed.
+    pub async fn refresh<DB: HasRuntime>(&self, db: &DB) -> usize {
+        let paths: Vec<String> = self.entries.read().keys().cloned().collect();
+        let mut changed = 0;
+        for path in paths {
+            let before = self.changed_at(&path);
+            let (_, after) = self.sync(db, &path).await;
+            if Some(after) != before {
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    /// The modification time last observed for `path`, without touching the filesystem.
+    pub fn last_seen(&self, path: &str) -> Option<SystemTime> {
+        let entries = self.entries.read();
+        entries
+            .get(path)
+            .and_then(|e| e.mtime)
+            .map(|nanos| UNIX_EPOCH + Duration::from_nanos(nanos))
+    }
+
+    /// The last revision at which `path` was seen to change.
+    pub fn changed_at(&self, path: &str) -> Option<Revision> {
+        let entries = self.entries.read();
+        entries.get(path).map(|e| e.changed_at)
+    }
+
+    /// Compare the live mtime of `path` against the stored one, bumping the revision if it
+    /// changed. Returns the live mtime and the entry's `changed_at`.
+    async fn sync<DB: HasRuntime>(&self, db: &DB, path: &str) -> (Option<u64>, Revision) {
+        let live = stat_mtime(path).await;
+
+        {
+            let entries = self.entries.read();
+            if let Some(entry) = entries.get(path)
+                && entry.mtime == live
+            {
+                return (live, entry.changed_at);
+            }
+        }
+
+        let (changed_at, bumped) = {
+            let mut entries = self.entries.write();
+            let (changed_at, bumped) = match entries.get(path) {
+                // Raced with another sync that already recorded this mtime.
+                Some(entry) if entry.mtime == live => return (live, entry.changed_at),
+                Some(_) => (db.runtime().bump_revision(), true),
+                // First sight: nothing can have depended on an older state of this path.
+                None => (db.runtime().current_revision(), false),
+            };
+            entries.insert(
+                path.to_string(),
+                ExternalEntry {
+                    mtime: live,
+                    changed_at,
+                },
+            );
+            (changed_at, bumped)
+        };
+
+        if bumped {
+            debug!(
+                kind = self.kind.0,
+                path,
+                rev = changed_at.0,
+                "external input changed"
+            );
+            if let Ok(key) = Key::encode_facet(&path.to_string()) {
+                db.runtime().notify_input_set(changed_at, self.kind, key);
+            }
+        }
+
+        (live, changed_at)
+    }
+}
+
+/// Modification time of `path` in nanoseconds since the Unix epoch, or `None` if it can't be
+/// stat'ed (the query reading the file will report the actual error).
+async fn stat_mtime(path: &str) -> Option<u64> {
+    let modified = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(u64::try_from(since_epoch.as_nanos()).unwrap_or(u64::MAX))
+}
+
+impl PersistableIngredient for ExternalInput {
+    fn kind(&self) -> QueryKindId {
+        self.kind
+    }
+
+    fn kind_name(&self) -> &'static str {
+        self.kind_name
+    }
+
+    fn section_type(&self) -> SectionType {
+        SectionType::Input
+    }
+
+    fn clear(&self) {
+        let mut entries = self.entries.write();
+        *entries = im::HashMap::new();
+    }
+
+    fn save_records(&self) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        self.save_records_yielding(0)
+    }
+
+    fn save_records_yielding(
+        &self,
+        yield_every: usize,
+    ) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        Box::pin(async move {
+            let entries = self.entries.read().clone();
+            let mut records = Vec::with_capacity(entries.len());
+            for (i, (path, entry)) in entries.iter().enumerate() {
+                yield_point(i, yield_every).await;
+                // Same layout as an `InputIngredient<String, u64>` section.
+                let rec = InputRecord::<String, u64> {
+                    key: path.clone(),
+                    value: entry.mtime,
+                    changed_at: entry.changed_at.0,
+                };
+                let bytes = facet_postcard::to_vec(&rec).map_err(|e| {
+                    Arc::new(PicanteError::Encode {
+                        what: "external input record",
+                        message: format!("{e:?}"),
+                        kind: Some(self.kind),
+                        kind_name: Some(self.kind_name),
+                    })
+                })?;
+                records.push(bytes);
+            }
+            debug!(
+                kind = self.kind.0,
+                records = records.len(),
+                "save_records (external)"
+            );
+            Ok(records)
+        })
+    }
+
+    fn load_records(&self, records: Vec<Vec<u8>>) -> PicanteResult<()> {
+        let mut entries = self.entries.write();
+        for bytes in records {
+            let rec: InputRecord<String, u64> =
+                facet_postcard::from_slice(&bytes).map_err(|e| {
+                    Arc::new(PicanteError::Decode {
+                        what: "external input record",
+                        message: format!("{e:?}"),
+                        kind: Some(self.kind),
+                        kind_name: Some(self.kind_name),
+                    })
+                })?;
+            entries.insert(
+                rec.key,
+                ExternalEntry {
+                    mtime: rec.value,
+                    changed_at: Revision(rec.changed_at),
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+impl<DB> DynIngredient<DB> for ExternalInput
+where
+    DB: HasRuntime + Send + Sync + 'static,
+{
+    fn touch<'a>(&'a self, db: &'a DB, key: Key) -> BoxFuture<'a, PicanteResult<Touch>> {
+        Box::pin(async move {
+            let path: String = key.decode_for(self.kind, self.kind_name)?;
+            let (_, changed_at) = self.sync(db, &path).await;
+            Ok(Touch { changed_at })
+        })
+    }
+}
@@ -2,14 +2,15 @@ use crate::db::{DynIngredient, Touch};
 use crate::error::{PicanteError, PicanteResult};
 use crate::frame;
 use crate::key::{Dep, Key, QueryKindId};
-use crate::persist::{PersistableIngredient, SectionType};
-use crate::revision::Revision;
-use crate::runtime::HasRuntime;
+use crate::persist::{PersistableIngredient, SectionType, yield_point};
+use crate::revision::{Durability, Revision};
+use crate::runtime::{HasRuntime, Runtime, Undo};
 use facet::Facet;
 use futures::future::BoxFuture;
 use parking_lot::RwLock;
 use std::hash::Hash;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use tracing::{debug, trace};
 
 /// Entry in an input ingredient, containing the value and its change revision.
@@ -40,9 +41,18 @@ where
 {
     kind: QueryKindId,
     kind_name: &'static str,
-    entries: RwLock<im::HashMap<K, InputEntry<V>>>,
+    entries: Arc<RwLock<im::HashMap<K, InputEntry<V>>>>,
+    durability: Durability,
+    /// The latest revision at which `entries` changed, for
+    /// [`PersistableIngredient::is_dirty`]. [`UNKNOWN_CHANGE`] when the contents don't come
+    /// from a load or from writes alone.
+    dirty_at: Arc<AtomicU64>,
 }
 
+/// [`InputIngredient::dirty_at`] for contents no revision accounts for: cleared ingredients,
+/// copies of snapshots, and records loaded on top of existing ones.
+const UNKNOWN_CHANGE: u64 = u64::MAX;
+
 impl<K, V> InputIngredient<K, V>
 where
     K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
@@ -53,10 +63,26 @@ where
         Self {
             kind,
             kind_name,
-            entries: RwLock::new(im::HashMap::new()),
+            entries: Arc::new(RwLock::new(im::HashMap::new())),
+            durability: Durability::default(),
+            dirty_at: Arc::new(AtomicU64::new(0)),
         }
     }
 
+    /// Declare how rarely this ingredient's values change (default [`Durability::Low`]).
+    ///
+    /// Writes bump the revision as changes of that durability, so derived queries that read
+    /// only more durable inputs skip validating their dependencies across them.
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// The durability of this ingredient's values.
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
     /// The stable kind id.
     pub fn kind(&self) -> QueryKindId {
         self.kind
@@ -70,8 +96,22 @@ where
     /// Set an input value.
     ///
     /// Bumps the runtime revision only if the value actually changed.
-    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
     pub fn set<DB: HasRuntime>(&self, db: &DB, key: K, value: V) -> Revision {
+        self.set_inner(db, key, value).0
+    }
+
+    /// Set an input value and report whether it changed.
+    ///
+    /// Like [`Self::set`], writing a value equal (by facet structural equality) to the current
+    /// one is a no-op: no revision bump, no events, no invalidation. Returns `true` if the
+    /// value was written, which lets code that re-sets inputs wholesale (e.g. after re-reading
+    /// every file) tell which keys actually changed.
+    pub fn set_if_changed<DB: HasRuntime>(&self, db: &DB, key: K, value: V) -> bool {
+        self.set_inner(db, key, value).1
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
+    fn set_inner<DB: HasRuntime>(&self, db: &DB, key: K, value: V) -> (Revision, bool) {
         // Check if value is unchanged (read lock)
         {
             let entries = self.entries.read();
@@ -84,15 +124,16 @@ where
                     changed_at = existing.changed_at.0,
                     "input set no-op (same value)"
                 );
-                return existing.changed_at;
+                return (existing.changed_at, false);
             }
         }
 
         // Value changed, take write lock
         let encoded_key = Key::encode_facet(&key).ok();
-        let rev = db.runtime().bump_revision();
+        let rev = db.runtime().bump_revision_for(self.durability);
         {
             let mut entries = self.entries.write();
+            self.record_undo(db.runtime(), &entries);
             entries.insert(
                 key,
                 InputEntry {
@@ -100,11 +141,76 @@ where
                     changed_at: rev,
                 },
             );
+            self.mark_dirty(rev);
         }
         if let Some(encoded_key) = encoded_key {
             db.runtime().notify_input_set(rev, self.kind, encoded_key);
         }
-        rev
+        (rev, true)
+    }
+
+    /// Update the value for `key` in place and return the new value, or `None` (changing
+    /// nothing) if the key has no value.
+    ///
+    /// The read, `f` and the write happen under the ingredient's write lock, so concurrent
+    /// `modify` calls can't lose each other's updates the way a `get` followed by a `set`
+    /// can. `f` must not call back into this ingredient. As with [`Self::set`], the revision
+    /// is bumped (once) only if the value actually changed.
+    pub fn modify<DB: HasRuntime>(&self, db: &DB, key: K, f: impl FnOnce(&mut V)) -> Option<V> {
+        self.modify_inner(db, key, |current| {
+            let mut value = current?;
+            f(&mut value);
+            Some(value)
+        })
+    }
+
+    /// Like [`Self::modify`], starting from `V::default()` if the key has no value.
+    pub fn modify_or_insert<DB: HasRuntime>(&self, db: &DB, key: K, f: impl FnOnce(&mut V)) -> V
+    where
+        V: Default,
+    {
+        self.modify_inner(db, key, |current| {
+            let mut value = current.unwrap_or_default();
+            f(&mut value);
+            Some(value)
+        })
+        .expect("modify_or_insert always produces a value")
+    }
+
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
+    fn modify_inner<DB: HasRuntime>(
+        &self,
+        db: &DB,
+        key: K,
+        update: impl FnOnce(Option<V>) -> Option<V>,
+    ) -> Option<V> {
+        let encoded_key = Key::encode_facet(&key).ok();
+        let (rev, value) = {
+            let mut entries = self.entries.write();
+            let current = entries.get(&key).and_then(|e| e.value.clone());
+            let value = update(current.clone())?;
+            if current.is_some_and(|current| crate::facet_eq::facet_eq_direct(&current, &value)) {
+                trace!(kind = self.kind.0, "input modify no-op (same value)");
+                return Some(value);
+            }
+
+            // Bump while holding the write lock so concurrent writers can't interleave.
+            self.record_undo(db.runtime(), &entries);
+            let rev = db.runtime().bump_revision_for(self.durability);
+            entries.insert(
+                key,
+                InputEntry {
+                    value: Some(value.clone()),
+                    changed_at: rev,
+                },
+            );
+            self.mark_dirty(rev);
+            (rev, value)
+        };
+        if let Some(encoded_key) = encoded_key {
+            db.runtime().notify_input_set(rev, self.kind, encoded_key);
+        }
+        Some(value)
     }
 
     /// Remove an input value.
@@ -134,9 +240,10 @@ where
 
         // Need to remove, take write lock
         let encoded_key = Key::encode_facet(key).ok();
-        let rev = db.runtime().bump_revision();
+        let rev = db.runtime().bump_revision_for(self.durability);
         {
             let mut entries = self.entries.write();
+            self.record_undo(db.runtime(), &entries);
             entries.insert(
                 key.clone(),
                 InputEntry {
@@ -144,6 +251,7 @@ where
                     changed_at: rev,
                 },
             );
+            self.mark_dirty(rev);
         }
         if let Some(encoded_key) = encoded_key {
             db.runtime()
@@ -152,13 +260,133 @@ where
         rev
     }
 
+    /// Set many input values, as one change.
+    ///
+    /// Like a sequence of [`Self::set`] calls, but every value that differs from the stored
+    /// one is written under a single revision bump, so dependents revalidate once for the
+    /// whole batch instead of once per key. One `InputSet` event (and its invalidation) is
+    /// emitted per changed key; keys not in `entries` are left alone. If a key appears
+    /// several times in `entries`, the last value wins.
+    ///
+    /// Returns the new revision, or `None` if nothing changed (no bump, no events).
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
+    pub fn set_many<DB: HasRuntime>(
+        &self,
+        db: &DB,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Option<Revision> {
+        self.write_batch(db, entries.into_iter().collect(), false)
+    }
+
+    /// Replace the whole contents of this ingredient with `entries`, as one change.
+    ///
+    /// Keys whose value is unchanged are left alone, keys missing from `entries` are removed,
+    /// and everything else is set, all under a single revision bump: readers see either the
+    /// old contents or the new ones, never a mix. `InputSet` / `InputRemoved` events (and
+    /// invalidation) are emitted only for keys that actually changed. If a key appears
+    /// several times in `entries`, the last value wins.
+    ///
+    /// Returns the new revision, or `None` if nothing changed (no bump, no events).
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
+    pub fn replace_all<DB: HasRuntime>(
+        &self,
+        db: &DB,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Option<Revision> {
+        self.write_batch(db, entries.into_iter().collect(), true)
+    }
+
+    /// Write the values in `new` that differ from the stored ones (and, with
+    /// `remove_missing`, remove every key not in `new`) under one revision bump, then emit
+    /// the events for the keys that changed.
+    fn write_batch<DB: HasRuntime>(
+        &self,
+        db: &DB,
+        new: std::collections::HashMap<K, V>,
+        remove_missing: bool,
+    ) -> Option<Revision> {
+        let (rev, set, removed) = {
+            let mut current = self.entries.write();
+
+            let removed: Vec<K> = if remove_missing {
+                current
+                    .iter()
+                    .filter(|(key, entry)| entry.value.is_some() && !new.contains_key(*key))
+                    .map(|(key, _)| key.clone())
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            let changed: Vec<(K, V)> = new
+                .into_iter()
+                .filter(
+                    |(key, value)| match current.get(key).and_then(|e| e.value.as_ref()) {
+                        Some(existing) => !crate::facet_eq::facet_eq_direct(existing, value),
+                        None => true,
+                    },
+                )
+                .collect();
+
+            if changed.is_empty() && removed.is_empty() {
+                trace!(kind = self.kind.0, "input batch no-op (same contents)");
+                return None;
+            }
+
+            // Bump while holding the write lock so concurrent writers can't interleave.
+            self.record_undo(db.runtime(), &current);
+            let rev = db.runtime().bump_revision_for(self.durability);
+            for key in &removed {
+                current.insert(
+                    key.clone(),
+                    InputEntry {
+                        value: None,
+                        changed_at: rev,
+                    },
+                );
+            }
+            let mut set = Vec::with_capacity(changed.len());
+            for (key, value) in changed {
+                set.push(key.clone());
+                current.insert(
+                    key,
+                    InputEntry {
+                        value: Some(value),
+                        changed_at: rev,
+                    },
+                );
+            }
+            self.mark_dirty(rev);
+            (rev, set, removed)
+        };
+
+        debug!(
+            kind = self.kind.0,
+            rev = rev.0,
+            set = set.len(),
+            removed = removed.len(),
+            "input batch write"
+        );
+        for key in &set {
+            if let Ok(encoded_key) = Key::encode_facet(key) {
+                db.runtime().notify_input_set(rev, self.kind, encoded_key);
+            }
+        }
+        for key in &removed {
+            if let Ok(encoded_key) = Key::encode_facet(key) {
+                db.runtime()
+                    .notify_input_removed(rev, self.kind, encoded_key);
+            }
+        }
+        Some(rev)
+    }
+
     /// Read an input value.
     ///
     /// If there's an active query frame, records a dependency edge.
     #[tracing::instrument(level = "trace", skip_all, fields(kind = self.kind.0))]
     pub fn get<DB: HasRuntime>(&self, _db: &DB, key: &K) -> PicanteResult<Option<V>> {
         if frame::has_active_frame() {
-            let encoded_key = Key::encode_facet(key)?;
+            let encoded_key = Key::encode_for(key, self.kind, self.kind_name)?;
             trace!(kind = self.kind.0, key_hash = %format!("{:016x}", encoded_key.hash()), "input dep");
             frame::record_dep(Dep {
                 kind: self.kind,
@@ -176,6 +404,19 @@ where
         entries.get(key).map(|e| e.changed_at)
     }
 
+    /// Every key that currently has a value, with that value, in no particular order.
+    ///
+    /// Taken from one consistent view of the map, so a concurrent write is either fully in it
+    /// or not at all. Records no dependency: meant for walking all inputs from outside a query
+    /// (e.g. to rebuild downstream state after a cache load), not for computing from them.
+    pub fn get_all(&self) -> Vec<(K, V)> {
+        let entries = self.entries.read().clone();
+        entries
+            .into_iter()
+            .filter_map(|(key, entry)| Some((key, entry.value?)))
+            .collect()
+    }
+
     /// Create a snapshot of this ingredient's data.
     ///
     /// This is an O(1) operation due to structural sharing in `im::HashMap`.
@@ -185,6 +426,25 @@ where
         self.entries.read().clone()
     }
 
+    /// Record a change to `entries` at `rev`; call with the write lock held.
+    fn mark_dirty(&self, rev: Revision) {
+        self.dirty_at.fetch_max(rev.0, Ordering::Release);
+    }
+
+    /// Let the runtime's open checkpoints keep `entries` (the contents before a write) for
+    /// [`Runtime::rollback`]; call with the write lock held.
+    fn record_undo(&self, runtime: &Runtime, entries: &im::HashMap<K, InputEntry<V>>) {
+        runtime.record_undo(self.kind, || -> Undo {
+            let saved = entries.clone();
+            let live = self.entries.clone();
+            let dirty_at = self.dirty_at.clone();
+            let kind = self.kind;
+            Box::new(move |runtime: &Runtime, rev: Revision| {
+                roll_back(runtime, rev, kind, &live, &dirty_at, saved)
+            })
+        });
+    }
+
     /// Create a new ingredient initialized from a snapshot.
     ///
     /// This is used when creating database snapshots. The returned ingredient
@@ -197,16 +457,88 @@ where
         Self {
             kind,
             kind_name,
-            entries: RwLock::new(entries),
+            entries: Arc::new(RwLock::new(entries)),
+            durability: Durability::default(),
+            dirty_at: Arc::new(AtomicU64::new(UNKNOWN_CHANGE)),
+        }
+    }
+}
+
+/// Write back every entry of `live` that differs from `saved` at `rev`, then emit the events
+/// for the keys that changed.
+fn roll_back<K, V>(
+    runtime: &Runtime,
+    rev: Revision,
+    kind: QueryKindId,
+    live: &RwLock<im::HashMap<K, InputEntry<V>>>,
+    dirty_at: &AtomicU64,
+    saved: im::HashMap<K, InputEntry<V>>,
+) where
+    K: Clone + Eq + Hash + Facet<'static>,
+    V: Clone,
+{
+    let mut changes = Vec::new();
+    {
+        let mut current = live.write();
+        // An entry untouched since the checkpoint still has the `changed_at` it had then.
+        let changed: Vec<K> = current
+            .iter()
+            .filter(|(key, entry)| {
+                saved
+                    .get(*key)
+                    .is_none_or(|old| old.changed_at != entry.changed_at)
+            })
+            .map(|(key, _)| key.clone())
+            .chain(
+                saved
+                    .keys()
+                    .filter(|key| !current.contains_key(*key))
+                    .cloned(),
+            )
+            .collect();
+        for key in changed {
+            let value = saved.get(&key).and_then(|old| old.value.clone());
+            let had_value = current.get(&key).is_some_and(|entry| entry.value.is_some());
+            if value.is_none() && !had_value {
+                continue;
+            }
+            changes.push((key.clone(), value.is_none()));
+            current.insert(
+                key,
+                InputEntry {
+                    value,
+                    changed_at: rev,
+                },
+            );
+        }
+        if !changes.is_empty() {
+            dirty_at.fetch_max(rev.0, Ordering::Release);
+        }
+    }
+
+    debug!(
+        kind = kind.0,
+        rev = rev.0,
+        keys = changes.len(),
+        "input rollback"
+    );
+    for (key, removed) in changes {
+        let Ok(encoded_key) = Key::encode_facet(&key) else {
+            continue;
+        };
+        if removed {
+            runtime.notify_input_removed(rev, kind, encoded_key);
+        } else {
+            runtime.notify_input_set(rev, kind, encoded_key);
         }
     }
 }
 
 #[derive(Debug, Clone, Facet)]
-struct InputRecord<K, V> {
-    key: K,
-    value: Option<V>,
-    changed_at: u64,
+pub(crate) struct InputRecord<K, V> {
+    pub(crate) key: K,
+    pub(crate) value: Option<V>,
+    pub(crate) changed_at: u64,
 }
 
 impl<K, V> PersistableIngredient for InputIngredient<K, V>
@@ -229,13 +561,23 @@ where
     fn clear(&self) {
         let mut entries = self.entries.write();
         *entries = im::HashMap::new();
+        self.dirty_at.store(UNKNOWN_CHANGE, Ordering::Release);
     }
 
     fn save_records(&self) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        self.save_records_yielding(0)
+    }
+
+    fn save_records_yielding(
+        &self,
+        yield_every: usize,
+    ) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
         Box::pin(async move {
-            let entries = self.entries.read();
+            // O(1) structural clone, so the lock isn't held while serializing.
+            let entries = self.entries.read().clone();
             let mut records = Vec::with_capacity(entries.len());
-            for (key, entry) in entries.iter() {
+            for (i, (key, entry)) in entries.iter().enumerate() {
+                yield_point(i, yield_every).await;
                 let rec = InputRecord::<K, V> {
                     key: key.clone(),
                     value: entry.value.clone(),
@@ -245,6 +587,8 @@ where
                     Arc::new(PicanteError::Encode {
                         what: "input record",
                         message: format!("{e:?}"),
+                        kind: Some(self.kind),
+                        kind_name: Some(self.kind_name),
                     })
                 })?;
                 records.push(bytes);
@@ -259,14 +603,30 @@ where
     }
 
     fn load_records(&self, records: Vec<Vec<u8>>) -> PicanteResult<()> {
+        self.load_record_stream(Box::new(records.into_iter()))
+    }
+
+    fn load_record_stream(
+        &self,
+        records: Box<dyn Iterator<Item = Vec<u8>> + '_>,
+    ) -> PicanteResult<()> {
         let mut entries = self.entries.write();
+        // Loaded into an empty ingredient, the contents are exactly the section's, last
+        // changed with its newest record. Loaded on top of other entries (or cut short by
+        // a bad record), they match no saved cache.
+        let fresh = entries.is_empty();
+        self.dirty_at.store(UNKNOWN_CHANGE, Ordering::Release);
+        let mut newest = 0;
         for bytes in records {
             let rec: InputRecord<K, V> = facet_postcard::from_slice(&bytes).map_err(|e| {
                 Arc::new(PicanteError::Decode {
                     what: "input record",
                     message: format!("{e:?}"),
+                    kind: Some(self.kind),
+                    kind_name: Some(self.kind_name),
                 })
             })?;
+            newest = newest.max(rec.changed_at);
             entries.insert(
                 rec.key,
                 InputEntry {
@@ -275,9 +635,16 @@ where
                 },
             );
         }
+        if fresh {
+            self.dirty_at.store(newest, Ordering::Release);
+        }
         Ok(())
     }
 
+    fn is_dirty(&self, since: Revision) -> bool {
+        self.dirty_at.load(Ordering::Acquire) > since.0
+    }
+
     fn save_incremental_records(
         &self,
         since_revision: u64,
@@ -293,6 +660,8 @@ where
                         Arc::new(PicanteError::Encode {
                             what: "input key",
                             message: format!("{e:?}"),
+                            kind: Some(self.kind),
+                            kind_name: Some(self.kind_name),
                         })
                     })?;
 
@@ -301,6 +670,8 @@ where
                             Arc::new(PicanteError::Encode {
                                 what: "input value",
                                 message: format!("{e:?}"),
+                                kind: Some(self.kind),
+                                kind_name: Some(self.kind_name),
                             })
                         })?;
                         Some(bytes)
@@ -333,6 +704,8 @@ where
             Arc::new(PicanteError::Decode {
                 what: "input key from WAL",
                 message: format!("{e:?}"),
+                kind: Some(self.kind),
+                kind_name: Some(self.kind_name),
             })
         })?;
 
@@ -341,6 +714,8 @@ where
                 Arc::new(PicanteError::Decode {
                     what: "input value from WAL",
                     message: format!("{e:?}"),
+                    kind: Some(self.kind),
+                    kind_name: Some(self.kind_name),
                 })
             })?)
         } else {
@@ -356,6 +731,7 @@ where
                 changed_at: Revision(revision),
             },
         );
+        self.mark_dirty(Revision(revision));
 
         Ok(())
     }
@@ -369,7 +745,7 @@ where
 {
     fn touch<'a>(&'a self, _db: &'a DB, key: Key) -> BoxFuture<'a, PicanteResult<Touch>> {
         Box::pin(async move {
-            let key: K = key.decode_facet()?;
+            let key: K = key.decode_for(self.kind, self.kind_name)?;
             let entries = self.entries.read();
             let changed_at = entries
                 .get(&key)
@@ -378,4 +754,8 @@ where
             Ok(Touch { changed_at })
         })
     }
+
+    fn durability(&self, _key: &Key) -> Durability {
+        self.durability
+    }
 }
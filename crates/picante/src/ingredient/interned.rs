@@ -2,14 +2,18 @@ use crate::db::{DynIngredient, Touch};
 use crate::error::{PicanteError, PicanteResult};
 use crate::frame;
 use crate::key::{Dep, Key, QueryKindId};
-use crate::persist::{PersistableIngredient, SectionType};
+use crate::persist::{PersistableIngredient, SectionType, yield_point};
 use crate::revision::Revision;
 use crate::runtime::HasRuntime;
 use dashmap::DashMap;
 use facet::Facet;
 use futures::future::BoxFuture;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicU32, Ordering};
+use parking_lot::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
 use tracing::{debug, trace};
 
 /// An identifier returned from [`InternedIngredient::intern`].
@@ -26,6 +30,15 @@ pub struct InternedIngredient<K> {
     next_id: AtomicU32,
     by_value: DashMap<Key, InternId>,
     by_id: DashMap<InternId, Arc<K>>,
+    /// Number of entries, tracked separately: `DashMap::len` can't be called while an entry
+    /// guard is held.
+    len: AtomicUsize,
+    max_entries: Option<usize>,
+    /// Bumped by every clear, so ids minted before it can be told apart.
+    generation: AtomicU32,
+    /// `(first id, generation)` of each generation since the table was last loaded, oldest
+    /// first. A clear doesn't restart ids, so the id alone says which generation minted it.
+    generation_starts: RwLock<Vec<(u32, u32)>>,
 }
 
 impl<K> InternedIngredient<K>
@@ -40,9 +53,41 @@ where
             next_id: AtomicU32::new(0),
             by_value: DashMap::new(),
             by_id: DashMap::new(),
+            len: AtomicUsize::new(0),
+            max_entries: None,
+            generation: AtomicU32::new(0),
+            generation_starts: RwLock::new(vec![(0, 0)]),
         }
     }
 
+    /// Create an empty interned ingredient holding at most `max_entries` values.
+    ///
+    /// Once full, interning a value that isn't in the table yet fails with
+    /// [`PicanteError::InternCapacityExceeded`]; values already interned keep resolving to
+    /// their ids. Use this when interned values come from untrusted input. Clearing the
+    /// ingredient frees its capacity. Entries restored from a cache file or WAL count
+    /// towards the limit but are never rejected.
+    pub fn with_max_entries(
+        kind: QueryKindId,
+        kind_name: &'static str,
+        max_entries: usize,
+    ) -> Self {
+        Self {
+            max_entries: Some(max_entries),
+            ..Self::new(kind, kind_name)
+        }
+    }
+
+    /// Number of interned values.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+
+    /// Whether no value has been interned.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     /// The stable kind id.
     pub fn kind(&self) -> QueryKindId {
         self.kind
@@ -53,17 +98,143 @@ where
         self.kind_name
     }
 
+    /// The current generation: starts at 0 and goes up by one every time the ingredient is
+    /// cleared, including when a cache is loaded into it.
+    ///
+    /// A clear doesn't restart ids, so [`Self::get`] rejects an id minted before it with
+    /// [`PicanteError::StaleInternId`]. A loaded cache brings its own ids, which may reuse
+    /// ids handed out before the load: remember the generation alongside an id and read it
+    /// back with [`Self::get_in_generation`] to catch those too.
+    pub fn generation(&self) -> u32 {
+        self.generation.load(Ordering::Acquire)
+    }
+
     /// Intern `value` and return its stable id.
     #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
     pub fn intern(&self, value: K) -> PicanteResult<InternId> {
-        let key = Key::encode_facet(&value)?;
+        let key = Key::encode_for(&value, self.kind, self.kind_name)?;
+        Ok(self.intern_encoded(key, || value)?.0)
+    }
+
+    /// Intern `value`, also returning whether this call created the entry.
+    ///
+    /// Exactly one caller sees `true` for a given value, even when several intern it
+    /// concurrently, so it's safe to hang one-time setup off it.
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
+    pub fn intern_status(&self, value: K) -> PicanteResult<(InternId, bool)> {
+        let key = Key::encode_for(&value, self.kind, self.kind_name)?;
+        self.intern_encoded(key, || value)
+    }
+
+    /// Intern a borrowed `value` and return its stable id.
+    ///
+    /// Unlike [`Self::intern`], the value is only cloned when it hasn't been interned yet,
+    /// which makes re-interning already-known values (e.g. symbol lookups) allocation-free
+    /// apart from encoding the key.
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
+    pub fn intern_ref(&self, value: &K) -> PicanteResult<InternId>
+    where
+        K: Clone,
+    {
+        let key = Key::encode_for(value, self.kind, self.kind_name)?;
+        if let Some(id) = self.by_value.get(&key) {
+            return Ok(*id);
+        }
+        Ok(self.intern_encoded(key, || value.clone())?.0)
+    }
+
+    /// Intern every value in `values` and return their ids in input order.
+    ///
+    /// Duplicates within the batch are interned once, and the values new to the table get a
+    /// contiguous range of ids reserved up front with a single capacity check. Each distinct
+    /// value still costs a lookup, plus an insert if it is new, as with [`Self::intern`]; the
+    /// `interned` bench compares the two. If the ingredient has a capacity limit and the new
+    /// values don't all fit, fails with [`PicanteError::InternCapacityExceeded`] without
+    /// interning any of them.
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0, len = values.len()))]
+    pub fn intern_many(&self, values: Vec<K>) -> PicanteResult<Vec<InternId>> {
+        // Each input's index among the distinct values of the batch.
+        let mut slots = Vec::with_capacity(values.len());
+        let mut distinct: HashMap<Key, usize> = HashMap::with_capacity(values.len());
+        let mut ids: Vec<Option<InternId>> = Vec::new();
+        let mut fresh: Vec<(usize, Key, K)> = Vec::new();
+        for value in values {
+            let key = Key::encode_for(&value, self.kind, self.kind_name)?;
+            if let Some(&slot) = distinct.get(&key) {
+                slots.push(slot);
+                continue;
+            }
+            let slot = ids.len();
+            let known = self.by_value.get(&key).map(|id| *id);
+            if known.is_none() {
+                fresh.push((slot, key.clone(), value));
+            }
+            ids.push(known);
+            distinct.insert(key, slot);
+            slots.push(slot);
+        }
+
+        if !fresh.is_empty() {
+            let reserved = fresh.len();
+            self.reserve_slots(reserved)?;
+            let first = self.next_id.fetch_add(reserved as u32, Ordering::AcqRel);
+            let mut raced = 0;
+            for (offset, (slot, key, value)) in fresh.into_iter().enumerate() {
+                let id = match self.by_value.entry(key) {
+                    // Interned concurrently since we looked: keep that id and leave ours unused.
+                    dashmap::mapref::entry::Entry::Occupied(e) => {
+                        raced += 1;
+                        *e.get()
+                    }
+                    dashmap::mapref::entry::Entry::Vacant(e) => {
+                        let id = InternId(first.wrapping_add(offset as u32));
+                        self.by_id.insert(id, Arc::new(value));
+                        e.insert(id);
+                        id
+                    }
+                };
+                ids[slot] = Some(id);
+            }
+            if raced > 0 {
+                self.len.fetch_sub(raced, Ordering::AcqRel);
+            }
+            debug!(
+                kind = self.kind.0,
+                first_id = first,
+                interned = reserved - raced,
+                "interned batch"
+            );
+        }
+
+        Ok(slots
+            .into_iter()
+            .map(|slot| ids[slot].expect("every distinct value has an id"))
+            .collect())
+    }
+
+    /// The id `value` was interned under, or `None` if it hasn't been interned.
+    ///
+    /// Never interns: use it to check whether a value was seen before without minting an id
+    /// for it.
+    pub fn lookup(&self, value: &K) -> PicanteResult<Option<InternId>> {
+        let key = Key::encode_for(value, self.kind, self.kind_name)?;
+        Ok(self.by_value.get(&key).map(|id| *id))
+    }
+
+    /// Returns the id and whether it was freshly minted.
+    fn intern_encoded(
+        &self,
+        key: Key,
+        value: impl FnOnce() -> K,
+    ) -> PicanteResult<(InternId, bool)> {
         let key_hash = key.hash();
 
         match self.by_value.entry(key) {
-            dashmap::mapref::entry::Entry::Occupied(e) => Ok(*e.get()),
+            dashmap::mapref::entry::Entry::Occupied(e) => Ok((*e.get(), false)),
             dashmap::mapref::entry::Entry::Vacant(e) => {
+                self.reserve_slots(1)?;
                 let id = InternId(self.next_id.fetch_add(1, Ordering::AcqRel));
-                self.by_id.insert(id, Arc::new(value));
+                self.by_id.insert(id, Arc::new(value()));
                 e.insert(id);
                 debug!(
                     kind = self.kind.0,
@@ -71,18 +242,177 @@ where
                     id = id.0,
                     "interned"
                 );
-                Ok(id)
+                Ok((id, true))
             }
         }
     }
 
+    /// Count `n` more entries, failing if that would exceed `max_entries`.
+    fn reserve_slots(&self, n: usize) -> PicanteResult<()> {
+        let Some(max) = self.max_entries else {
+            self.len.fetch_add(n, Ordering::AcqRel);
+            return Ok(());
+        };
+        self.len
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |len| {
+                len.checked_add(n).filter(|&new_len| new_len <= max)
+            })
+            .map(|_| ())
+            .map_err(|_| {
+                debug!(kind = self.kind.0, max, "intern rejected: table full");
+                Arc::new(PicanteError::InternCapacityExceeded {
+                    kind: self.kind,
+                    max,
+                })
+            })
+    }
+
     /// Look up an interned value by id.
     ///
+    /// Fails with [`PicanteError::StaleInternId`] for an id minted before the ingredient was
+    /// last cleared, and with [`PicanteError::MissingInternedValue`] for one that was never
+    /// minted or was dropped by [`Self::retain`]. Ids restored by loading a cache are the
+    /// exception: they can coincide with ids handed out before the load, which this can't
+    /// tell apart (see [`Self::get_in_generation`]).
+    ///
     /// If there's an active query frame, records a dependency edge.
     #[tracing::instrument(level = "trace", skip_all, fields(kind = self.kind.0, id = id.0))]
     pub fn get<DB: HasRuntime>(&self, _db: &DB, id: InternId) -> PicanteResult<Arc<K>> {
+        self.record_read(id)?;
+
+        self.by_id
+            .get(&id)
+            .map(|v| v.clone())
+            .ok_or_else(|| self.missing(id))
+    }
+
+    /// The error for an `id` with no value: stale if an earlier generation minted it.
+    fn missing(&self, id: InternId) -> Arc<PicanteError> {
+        let current = self.generation();
+        let minted = self
+            .generation_starts
+            .read()
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= id.0)
+            .map(|&(_, generation)| generation);
+        match minted {
+            Some(generation) if generation != current => {
+                debug!(
+                    kind = self.kind.0,
+                    id = id.0,
+                    generation,
+                    current,
+                    "stale interned id"
+                );
+                Arc::new(PicanteError::StaleInternId {
+                    kind: self.kind,
+                    id: id.0,
+                    generation,
+                    current,
+                })
+            }
+            _ => Arc::new(PicanteError::MissingInternedValue {
+                kind: self.kind,
+                id: id.0,
+            }),
+        }
+    }
+
+    /// Like [`Self::get`], but fails with [`PicanteError::StaleInternId`] if `id` was
+    /// minted in an earlier `generation` (see [`Self::generation`]) instead of silently
+    /// returning whatever value the id names now.
+    #[tracing::instrument(level = "trace", skip_all, fields(kind = self.kind.0, id = id.0))]
+    pub fn get_in_generation<DB: HasRuntime>(
+        &self,
+        db: &DB,
+        id: InternId,
+        generation: u32,
+    ) -> PicanteResult<Arc<K>> {
+        let current = self.generation();
+        if generation != current {
+            debug!(
+                kind = self.kind.0,
+                id = id.0,
+                generation,
+                current,
+                "stale interned id"
+            );
+            return Err(Arc::new(PicanteError::StaleInternId {
+                kind: self.kind,
+                id: id.0,
+                generation,
+                current,
+            }));
+        }
+        self.get(db, id)
+    }
+
+    /// Drop every value whose id isn't in `live` and return how many were dropped: the sweep
+    /// half of a mark-and-sweep over ids reachable from the caller's roots.
+    ///
+    /// Freed ids are never handed out again, so a stale [`InternId`] can't come to mean
+    /// another value: [`Self::get`] on a swept id fails with
+    /// [`PicanteError::MissingInternedValue`], as does revalidating a query that read one.
+    /// A swept value interned again gets a new id. Interning concurrently with a sweep may
+    /// return an id that the sweep is about to drop, so sweep while nothing interns values
+    /// that might be dead. Resolved [`Interned`] handles keep their value.
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
+    pub fn retain(&self, live: &HashSet<InternId>) -> usize {
+        // Unlink values first, so an intern racing the sweep mints a new id rather than
+        // finding one whose value is already gone.
+        self.by_value.retain(|_, id| live.contains(id));
+        let mut swept = 0;
+        self.by_id.retain(|id, _| {
+            let keep = live.contains(id);
+            swept += usize::from(!keep);
+            keep
+        });
+        self.len.fetch_sub(swept, Ordering::AcqRel);
+        debug!(
+            kind = self.kind.0,
+            swept,
+            remaining = self.by_id.len(),
+            "interned retain"
+        );
+        swept
+    }
+
+    /// Every interned value with its id, in id order.
+    ///
+    /// The entries are copied out when this is called, so interning while iterating neither
+    /// blocks nor shows up in the iteration. Values are shared, not cloned.
+    pub fn iter(&self) -> impl Iterator<Item = (InternId, Arc<K>)> + use<K> {
+        self.sorted_entries().into_iter()
+    }
+
+    fn sorted_entries(&self) -> Vec<(InternId, Arc<K>)> {
+        let mut entries: Vec<(InternId, Arc<K>)> = self
+            .by_id
+            .iter()
+            .map(|e| (*e.key(), e.value().clone()))
+            .collect();
+        entries.sort_by_key(|(id, _)| id.0);
+        entries
+    }
+
+    /// Wrap `id` in an [`Interned`] handle that resolves through this ingredient.
+    ///
+    /// The handle is tagged with the current [generation](Self::generation): `id` must come
+    /// from the ingredient's current contents.
+    pub fn handle(self: &Arc<Self>, id: InternId) -> Interned<K> {
+        Interned {
+            id,
+            generation: self.generation(),
+            ingredient: self.clone(),
+            value: OnceLock::new(),
+        }
+    }
+
+    /// If there's an active query frame, records a dependency edge on `id`.
+    fn record_read(&self, id: InternId) -> PicanteResult<()> {
         if frame::has_active_frame() {
-            let key = Key::encode_facet(&id)?;
+            let key = Key::encode_for(&id, self.kind, self.kind_name)?;
             trace!(
                 kind = self.kind.0,
                 key_hash = %format!("{:016x}", key.hash()),
@@ -94,20 +424,103 @@ where
                 key,
             });
         }
+        Ok(())
+    }
+}
 
-        self.by_id.get(&id).map(|v| v.clone()).ok_or_else(|| {
-            Arc::new(PicanteError::MissingInternedValue {
-                kind: self.kind,
-                id: id.0,
-            })
-        })
+/// An [`InternId`] bundled with the ingredient it was minted by.
+///
+/// [`Interned::resolve`] looks the value up on first use and keeps the `Arc<K>` afterwards.
+/// The handle owns an `Arc` of its ingredient and of the resolved value, so it can never
+/// dangle: once resolved it keeps returning that value, even if the ingredient has since
+/// been cleared (e.g. by loading a cache). Ids are only meaningful within the ingredient
+/// state they came from, so a handle that is first resolved after a clear fails with
+/// [`PicanteError::StaleInternId`] rather than following the new contents.
+///
+/// Equality and hashing compare the kind and the id, like [`InternId`].
+pub struct Interned<K> {
+    id: InternId,
+    generation: u32,
+    ingredient: Arc<InternedIngredient<K>>,
+    value: OnceLock<Arc<K>>,
+}
+
+impl<K> Interned<K>
+where
+    K: Facet<'static> + Send + Sync + 'static,
+{
+    /// The wrapped id.
+    pub fn id(&self) -> InternId {
+        self.id
+    }
+
+    /// The ingredient [generation](InternedIngredient::generation) the id was minted in.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// The ingredient this handle resolves through.
+    pub fn ingredient(&self) -> &Arc<InternedIngredient<K>> {
+        &self.ingredient
+    }
+
+    /// Resolve the interned value, looking it up only the first time.
+    ///
+    /// Like [`InternedIngredient::get`], records a dependency edge when called inside a
+    /// query, including when the value is already cached, so every query reading through
+    /// the handle depends on it.
+    pub fn resolve<DB: HasRuntime>(&self, db: &DB) -> PicanteResult<Arc<K>> {
+        if let Some(value) = self.value.get() {
+            self.ingredient.record_read(self.id)?;
+            return Ok(value.clone());
+        }
+        let value = self
+            .ingredient
+            .get_in_generation(db, self.id, self.generation)?;
+        Ok(self.value.get_or_init(|| value).clone())
+    }
+}
+
+impl<K> Clone for Interned<K> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            generation: self.generation,
+            ingredient: self.ingredient.clone(),
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<K> PartialEq for Interned<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.ingredient.kind == other.ingredient.kind
+    }
+}
+
+impl<K> Eq for Interned<K> {}
+
+impl<K> Hash for Interned<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.ingredient.kind.hash(state);
+        self.id.hash(state);
+    }
+}
+
+impl<K> fmt::Debug for Interned<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interned")
+            .field("kind_name", &self.ingredient.kind_name)
+            .field("id", &self.id.0)
+            .field("resolved", &self.value.get().is_some())
+            .finish()
     }
 }
 
 #[derive(Debug, Clone, Facet)]
-struct InternedRecord<K> {
-    id: u32,
-    value: Arc<K>,
+pub(crate) struct InternedRecord<K> {
+    pub(crate) id: u32,
+    pub(crate) value: Arc<K>,
 }
 
 impl<K> PersistableIngredient for InternedIngredient<K>
@@ -129,26 +542,35 @@ where
     fn clear(&self) {
         self.by_value.clear();
         self.by_id.clear();
-        self.next_id.store(0, Ordering::Release);
+        self.len.store(0, Ordering::Release);
+        // Ids carry on from the last generation's, so none of them can name a new value.
+        let generation = self.generation.fetch_add(1, Ordering::AcqRel) + 1;
+        self.generation_starts
+            .write()
+            .push((self.next_id.load(Ordering::Acquire), generation));
     }
 
     fn save_records(&self) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
-        Box::pin(async move {
-            let mut snapshot: Vec<(InternId, Arc<K>)> = self
-                .by_id
-                .iter()
-                .map(|e| (*e.key(), e.value().clone()))
-                .collect();
-            snapshot.sort_by_key(|(id, _)| id.0);
+        self.save_records_yielding(0)
+    }
 
+    fn save_records_yielding(
+        &self,
+        yield_every: usize,
+    ) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        Box::pin(async move {
+            let snapshot = self.sorted_entries();
             let mut records = Vec::with_capacity(snapshot.len());
-            for (id, value) in snapshot {
+            for (i, (id, value)) in snapshot.into_iter().enumerate() {
+                yield_point(i, yield_every).await;
                 let rec = InternedRecord::<K> { id: id.0, value };
 
                 let bytes = facet_postcard::to_vec(&rec).map_err(|e| {
                     Arc::new(PicanteError::Encode {
                         what: "interned record",
                         message: format!("{e:?}"),
+                        kind: Some(self.kind),
+                        kind_name: Some(self.kind_name),
                     })
                 })?;
                 records.push(bytes);
@@ -164,6 +586,13 @@ where
     }
 
     fn load_records(&self, records: Vec<Vec<u8>>) -> PicanteResult<()> {
+        self.load_record_stream(Box::new(records.into_iter()))
+    }
+
+    fn load_record_stream(
+        &self,
+        records: Box<dyn Iterator<Item = Vec<u8>> + '_>,
+    ) -> PicanteResult<()> {
         self.clear();
 
         let mut max_id: u32 = 0;
@@ -173,6 +602,8 @@ where
                 Arc::new(PicanteError::Decode {
                     what: "interned record",
                     message: format!("{e:?}"),
+                    kind: Some(self.kind),
+                    kind_name: Some(self.kind_name),
                 })
             })?;
 
@@ -185,7 +616,7 @@ where
                 }));
             }
 
-            let key = Key::encode_facet(rec.value.as_ref())?;
+            let key = Key::encode_for(rec.value.as_ref(), self.kind, self.kind_name)?;
             if let Some(existing) = self.by_value.insert(key, id) {
                 return Err(Arc::new(PicanteError::Cache {
                     message: format!(
@@ -196,10 +627,14 @@ where
             }
 
             self.by_id.insert(id, rec.value);
+            self.len.fetch_add(1, Ordering::AcqRel);
         }
 
+        // The loaded ids are the new generation's, whatever earlier ones they reuse; values
+        // interned from here on still get ids no earlier generation handed out.
         self.next_id
-            .store(max_id.saturating_add(1), Ordering::Release);
+            .fetch_max(max_id.saturating_add(1), Ordering::AcqRel);
+        *self.generation_starts.write() = vec![(0, self.generation())];
         Ok(())
     }
 
@@ -249,15 +684,19 @@ where
                 Arc::new(PicanteError::Decode {
                     what: "interned record from WAL",
                     message: format!("{e:?}"),
+                    kind: Some(self.kind),
+                    kind_name: Some(self.kind_name),
                 })
             })?;
 
             let id = InternId(rec.id);
-            let key = Key::encode_facet(rec.value.as_ref())?;
+            let key = Key::encode_for(rec.value.as_ref(), self.kind, self.kind_name)?;
 
             // Insert into both maps
             self.by_id.insert(id, rec.value);
-            self.by_value.insert(key, id);
+            if self.by_value.insert(key, id).is_none() {
+                self.len.fetch_add(1, Ordering::AcqRel);
+            }
 
             // Update next_id if necessary
             let current_next = self.next_id.load(Ordering::Acquire);
@@ -279,12 +718,9 @@ where
 {
     fn touch<'a>(&'a self, _db: &'a DB, key: Key) -> BoxFuture<'a, PicanteResult<Touch>> {
         Box::pin(async move {
-            let id: InternId = key.decode_facet()?;
+            let id: InternId = key.decode_for(self.kind, self.kind_name)?;
             if !self.by_id.contains_key(&id) {
-                return Err(Arc::new(PicanteError::MissingInternedValue {
-                    kind: self.kind,
-                    id: id.0,
-                }));
+                return Err(self.missing(id));
             }
             Ok(Touch {
                 changed_at: Revision(0),
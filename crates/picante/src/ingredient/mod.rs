@@ -1,15 +1,32 @@
-//! Query ingredients (inputs, derived queries, and interning).
+//! Query ingredients (inputs, derived and streaming queries, interning, and external inputs).
 
+mod accumulator;
 mod derived;
+mod external;
 mod input;
 mod interned;
+mod multi_input;
+mod sharded;
+mod streaming;
 
-pub use derived::ErasedReadyRecord;
+pub use accumulator::AccumulatorIngredient;
+pub use derived::{AccessInfo, CellState, ErasedReadyRecord, QueryStats};
 /// Re-export `Cell` from `derived` as `DerivedCell` to avoid conflicts with `std::cell::Cell`.
 /// Use `DerivedCell` as the canonical public name when working with derived query cells.
 ///
 /// Note: as of the type-erased derived-query implementation, derived cells are no longer generic
 /// over the stored value type.
-pub use derived::{DerivedIngredient, ErasedCell as DerivedCell};
+pub use derived::{
+    DerivedIngredient, ErasedCell as DerivedCell, ErrorPolicy, PanicPolicy, RetryPolicy,
+    ValidationStrategy,
+};
+pub use external::ExternalInput;
 pub use input::{InputEntry, InputIngredient};
-pub use interned::{InternId, InternedIngredient};
+pub use interned::{InternId, Interned, InternedIngredient};
+pub use multi_input::MultiInputIngredient;
+pub use sharded::ShardedDerived;
+pub use streaming::{StreamCachePolicy, StreamItems, StreamProgress, StreamingDerived};
+
+pub(crate) use derived::{DepRecord, DerivedRecord};
+pub(crate) use input::InputRecord;
+pub(crate) use interned::InternedRecord;
@@ -0,0 +1,293 @@
+use crate::db::{DynIngredient, Touch};
+use crate::error::{PicanteError, PicanteResult};
+use crate::frame;
+use crate::key::{Dep, Key, QueryKindId};
+use crate::persist::{PersistableIngredient, SectionType, yield_point};
+use crate::revision::Revision;
+use crate::runtime::HasRuntime;
+use facet::Facet;
+use futures::future::BoxFuture;
+use parking_lot::RwLock;
+use std::hash::Hash;
+use std::sync::Arc;
+use tracing::{debug, trace};
+
+/// The set of values stored under one key of a [`MultiInputIngredient`].
+#[derive(Clone)]
+struct MultiEntry<V>
+where
+    V: Clone + Eq + Hash,
+{
+    /// Each value with the revision at which it was inserted.
+    values: im::HashMap<V, Revision>,
+    /// The last revision at which the set changed.
+    changed_at: Revision,
+}
+
+/// A set-valued input ingredient: each key maps to a set of values.
+///
+/// Reading a key depends on the whole set for that key, but only on that key: inserting or
+/// removing a value invalidates the readers of that key and nothing else, and re-inserting a
+/// value that is already present is a no-op. This avoids modelling multi-valued inputs as
+/// `InputIngredient<K, Vec<V>>`, where every update rewrites (and invalidates) the whole vector.
+pub struct MultiInputIngredient<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: Clone + Eq + Hash,
+{
+    kind: QueryKindId,
+    kind_name: &'static str,
+    entries: RwLock<im::HashMap<K, MultiEntry<V>>>,
+}
+
+impl<K, V> MultiInputIngredient<K, V>
+where
+    K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+    V: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+{
+    /// Create an empty multi-valued input ingredient.
+    pub fn new(kind: QueryKindId, kind_name: &'static str) -> Self {
+        Self {
+            kind,
+            kind_name,
+            entries: RwLock::new(im::HashMap::new()),
+        }
+    }
+
+    /// The stable kind id.
+    pub fn kind(&self) -> QueryKindId {
+        self.kind
+    }
+
+    /// Debug name for this ingredient.
+    pub fn kind_name(&self) -> &'static str {
+        self.kind_name
+    }
+
+    /// Add `value` to the set for `key`.
+    ///
+    /// Bumps the runtime revision only if the value wasn't already present.
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
+    pub fn insert<DB: HasRuntime>(&self, db: &DB, key: K, value: V) -> Revision {
+        let rev = {
+            let mut entries = self.entries.write();
+            if let Some(existing) = entries.get(&key)
+                && let Some(inserted_at) = existing.values.get(&value)
+            {
+                trace!(
+                    kind = self.kind.0,
+                    inserted_at = inserted_at.0,
+                    "multi input insert no-op (already present)"
+                );
+                return *inserted_at;
+            }
+
+            let rev = db.runtime().bump_revision();
+            let entry = entries.entry(key.clone()).or_insert_with(|| MultiEntry {
+                values: im::HashMap::new(),
+                changed_at: rev,
+            });
+            entry.values.insert(value, rev);
+            entry.changed_at = rev;
+            rev
+        };
+
+        self.notify_changed(db, rev, &key);
+        rev
+    }
+
+    /// Remove `value` from the set for `key`.
+    ///
+    /// Bumps the runtime revision only if the value was present.
+    #[tracing::instrument(level = "debug", skip_all, fields(kind = self.kind.0))]
+    pub fn remove<DB: HasRuntime>(&self, db: &DB, key: &K, value: &V) -> Revision {
+        let rev = {
+            let mut entries = self.entries.write();
+            let Some(entry) = entries.get_mut(key) else {
+                trace!(kind = self.kind.0, "multi input remove no-op (missing key)");
+                return Revision(0);
+            };
+            if !entry.values.contains_key(value) {
+                trace!(
+                    kind = self.kind.0,
+                    changed_at = entry.changed_at.0,
+                    "multi input remove no-op (missing value)"
+                );
+                return entry.changed_at;
+            }
+
+            let rev = db.runtime().bump_revision();
+            entry.values.remove(value);
+            // Keep the (possibly empty) entry so its `changed_at` still reflects the removal.
+            entry.changed_at = rev;
+            rev
+        };
+
+        self.notify_changed(db, rev, key);
+        rev
+    }
+
+    /// Read the set of values for `key` (empty if there are none), in unspecified order.
+    ///
+    /// If there's an active query frame, records a dependency edge on the whole set.
+    #[tracing::instrument(level = "trace", skip_all, fields(kind = self.kind.0))]
+    pub fn get<DB: HasRuntime>(&self, _db: &DB, key: &K) -> PicanteResult<Vec<V>> {
+        if frame::has_active_frame() {
+            let encoded_key = Key::encode_for(key, self.kind, self.kind_name)?;
+            trace!(kind = self.kind.0, key_hash = %format!("{:016x}", encoded_key.hash()), "multi input dep");
+            frame::record_dep(Dep {
+                kind: self.kind,
+                key: encoded_key,
+            });
+        }
+
+        let entries = self.entries.read();
+        Ok(entries
+            .get(key)
+            .map(|e| e.values.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    /// The last revision at which the set for `key` changed.
+    pub fn changed_at(&self, key: &K) -> Option<Revision> {
+        let entries = self.entries.read();
+        entries.get(key).map(|e| e.changed_at)
+    }
+
+    /// The revision at which `value` was inserted into the set for `key`, if present.
+    pub fn value_changed_at(&self, key: &K, value: &V) -> Option<Revision> {
+        let entries = self.entries.read();
+        entries.get(key).and_then(|e| e.values.get(value).copied())
+    }
+
+    fn notify_changed<DB: HasRuntime>(&self, db: &DB, rev: Revision, key: &K) {
+        if let Ok(encoded_key) = Key::encode_facet(key) {
+            db.runtime().notify_input_set(rev, self.kind, encoded_key);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Facet)]
+struct MultiValueRecord<V> {
+    value: V,
+    changed_at: u64,
+}
+
+#[derive(Debug, Clone, Facet)]
+struct MultiInputRecord<K, V> {
+    key: K,
+    values: Vec<MultiValueRecord<V>>,
+    changed_at: u64,
+}
+
+impl<K, V> PersistableIngredient for MultiInputIngredient<K, V>
+where
+    K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+    V: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+{
+    fn kind(&self) -> QueryKindId {
+        self.kind
+    }
+
+    fn kind_name(&self) -> &'static str {
+        self.kind_name
+    }
+
+    fn section_type(&self) -> SectionType {
+        SectionType::Input
+    }
+
+    fn clear(&self) {
+        let mut entries = self.entries.write();
+        *entries = im::HashMap::new();
+    }
+
+    fn save_records(&self) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        self.save_records_yielding(0)
+    }
+
+    fn save_records_yielding(
+        &self,
+        yield_every: usize,
+    ) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        Box::pin(async move {
+            let entries = self.entries.read().clone();
+            let mut records = Vec::with_capacity(entries.len());
+            for (i, (key, entry)) in entries.iter().enumerate() {
+                yield_point(i, yield_every).await;
+                let rec = MultiInputRecord::<K, V> {
+                    key: key.clone(),
+                    values: entry
+                        .values
+                        .iter()
+                        .map(|(value, changed_at)| MultiValueRecord {
+                            value: value.clone(),
+                            changed_at: changed_at.0,
+                        })
+                        .collect(),
+                    changed_at: entry.changed_at.0,
+                };
+                let bytes = facet_postcard::to_vec(&rec).map_err(|e| {
+                    Arc::new(PicanteError::Encode {
+                        what: "multi input record",
+                        message: format!("{e:?}"),
+                        kind: Some(self.kind),
+                        kind_name: Some(self.kind_name),
+                    })
+                })?;
+                records.push(bytes);
+            }
+            debug!(
+                kind = self.kind.0,
+                records = records.len(),
+                "save_records (multi input)"
+            );
+            Ok(records)
+        })
+    }
+
+    fn load_records(&self, records: Vec<Vec<u8>>) -> PicanteResult<()> {
+        let mut entries = self.entries.write();
+        for bytes in records {
+            let rec: MultiInputRecord<K, V> = facet_postcard::from_slice(&bytes).map_err(|e| {
+                Arc::new(PicanteError::Decode {
+                    what: "multi input record",
+                    message: format!("{e:?}"),
+                    kind: Some(self.kind),
+                    kind_name: Some(self.kind_name),
+                })
+            })?;
+            entries.insert(
+                rec.key,
+                MultiEntry {
+                    values: rec
+                        .values
+                        .into_iter()
+                        .map(|v| (v.value, Revision(v.changed_at)))
+                        .collect(),
+                    changed_at: Revision(rec.changed_at),
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+impl<DB, K, V> DynIngredient<DB> for MultiInputIngredient<K, V>
+where
+    DB: HasRuntime + Send + Sync + 'static,
+    K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+    V: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+{
+    fn touch<'a>(&'a self, _db: &'a DB, key: Key) -> BoxFuture<'a, PicanteResult<Touch>> {
+        Box::pin(async move {
+            let key: K = key.decode_for(self.kind, self.kind_name)?;
+            let entries = self.entries.read();
+            let changed_at = entries
+                .get(&key)
+                .map(|e| e.changed_at)
+                .unwrap_or(Revision(0));
+            Ok(Touch { changed_at })
+        })
+    }
+}
@@ -0,0 +1,302 @@
+use crate::db::{DynIngredient, IngredientLookup, Touch};
+use crate::error::PicanteResult;
+use crate::ingredient::{DerivedIngredient, QueryStats};
+use crate::key::{Dep, Key, QueryKindId};
+use crate::persist::{PersistableIngredient, SectionType, decode_all};
+use crate::revision::{Durability, Revision};
+use crate::runtime::{HasRuntime, Runtime};
+use facet::Facet;
+use futures::future::BoxFuture;
+use std::any::Any;
+use std::hash::Hash;
+use std::sync::Arc;
+use tracing::debug;
+
+/// One logical derived query split across several [`DerivedIngredient`] shards.
+///
+/// Keys are routed to shard `key_hash % shard_count`, where `key_hash` is the stable hash of
+/// the encoded key (see [`Key::hash`]). Each shard has its own cell map and lock, which
+/// spreads contention on very hot queries.
+///
+/// All shards share the wrapper's [`QueryKindId`], so dependency edges and persistence
+/// see a single ingredient: register only the wrapper with the database, and it is saved as
+/// one `Derived` section whose records are interchangeable with those of an unsharded
+/// [`DerivedIngredient`] of the same kind. Because routing is recomputed on load, the shard
+/// count may change between runs without invalidating a cache file.
+pub struct ShardedDerived<DB, K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    kind: QueryKindId,
+    kind_name: &'static str,
+    shards: Vec<DerivedIngredient<DB, K, V>>,
+}
+
+impl<DB, K, V> ShardedDerived<DB, K, V>
+where
+    DB: IngredientLookup + Send + Sync + 'static,
+    K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+    V: Clone + Facet<'static> + Send + Sync + 'static,
+{
+    /// Create a sharded derived ingredient with `shard_count` shards (at least one).
+    pub fn new(
+        kind: QueryKindId,
+        kind_name: &'static str,
+        shard_count: usize,
+        compute: impl for<'db> Fn(&'db DB, K) -> BoxFuture<'db, PicanteResult<V>>
+        + Send
+        + Sync
+        + 'static,
+    ) -> Self {
+        let compute = Arc::new(compute);
+        let shards = (0..shard_count.max(1))
+            .map(|_| {
+                let compute = compute.clone();
+                DerivedIngredient::new(kind, kind_name, move |db, key| (*compute)(db, key))
+            })
+            .collect();
+
+        Self {
+            kind,
+            kind_name,
+            shards,
+        }
+    }
+
+    /// Set the logic version of every shard (see [`DerivedIngredient::with_logic_version`]).
+    pub fn with_logic_version(mut self, version: u32) -> Self {
+        self.shards = self
+            .shards
+            .into_iter()
+            .map(|shard| shard.with_logic_version(version))
+            .collect();
+        self
+    }
+
+    /// The stable kind id (shared by every shard).
+    pub fn kind(&self) -> QueryKindId {
+        self.kind
+    }
+
+    /// Debug name for this ingredient.
+    pub fn kind_name(&self) -> &'static str {
+        self.kind_name
+    }
+
+    /// Number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// All shards, in routing order.
+    pub fn shards(&self) -> &[DerivedIngredient<DB, K, V>] {
+        &self.shards
+    }
+
+    /// The shard responsible for `key`.
+    pub fn shard_for(&self, key: &K) -> PicanteResult<&DerivedIngredient<DB, K, V>> {
+        Ok(self.route(&Key::encode_for(key, self.kind, self.kind_name)?))
+    }
+
+    /// Lock wait times merged across all shards.
+    #[cfg(feature = "lock-metrics")]
+    pub fn lock_wait_histogram(&self) -> crate::metrics::LockWaitHistogram {
+        let mut merged = crate::metrics::LockWaitHistogram::default();
+        for shard in &self.shards {
+            merged.merge(&shard.lock_wait_histogram());
+        }
+        merged
+    }
+
+    /// Get the value for `key` at the database's current revision.
+    pub async fn get(&self, db: &DB, key: K) -> PicanteResult<V> {
+        let encoded = Key::encode_for(&key, self.kind, self.kind_name)?;
+        self.route(&encoded).get_encoded(db, encoded, Some(key)).await
+    }
+
+    /// Ensure the value is valid at the current revision and return its `changed_at`.
+    pub async fn touch(&self, db: &DB, key: K) -> PicanteResult<Revision> {
+        let key = Key::encode_for(&key, self.kind, self.kind_name)?;
+        self.route(&key).touch_encoded(db, key).await
+    }
+
+    /// The value for `key` if its shard has it verified at the current revision (see
+    /// [`DerivedIngredient::get_if_ready`]).
+    pub fn get_if_ready(&self, db: &DB, key: &K) -> PicanteResult<Option<V>> {
+        let key = Key::encode_for(key, self.kind, self.kind_name)?;
+        self.route(&key).get_if_ready_encoded(db, key)
+    }
+
+    /// The value for `key` if its shard has it verified at the current revision, without
+    /// recording a dependency (see [`DerivedIngredient::get_if_cached`]).
+    pub fn get_if_cached(&self, db: &DB, key: &K) -> PicanteResult<Option<V>> {
+        let key = Key::encode_for(key, self.kind, self.kind_name)?;
+        self.route(&key).get_if_cached_encoded(db, key)
+    }
+
+    /// Drop the cached value for `key` from its shard (see [`DerivedIngredient::invalidate`]).
+    pub fn invalidate(&self, db: &DB, key: &K) -> PicanteResult<bool> {
+        let key = Key::encode_for(key, self.kind, self.kind_name)?;
+        Ok(self.route(&key).invalidate_encoded(db, key))
+    }
+
+    /// Drop every cached value in every shard, bumping the revision a single time.
+    ///
+    /// Returns the number of cells dropped; the revision is left alone when there were none.
+    pub fn invalidate_all(&self, db: &DB) -> usize {
+        let dropped: usize = self.shards.iter().map(|shard| shard.discard_all(db)).sum();
+        if dropped > 0 {
+            db.runtime().bump_revision();
+        }
+        dropped
+    }
+
+    /// Access counters summed across all shards.
+    pub fn stats(&self) -> QueryStats {
+        self.shards
+            .iter()
+            .map(|shard| shard.stats())
+            .fold(QueryStats::default(), |total, stats| QueryStats {
+                hits: total.hits + stats.hits,
+                revalidations: total.revalidations + stats.revalidations,
+                recomputes: total.recomputes + stats.recomputes,
+                errors: total.errors + stats.errors,
+                panics: total.panics + stats.panics,
+            })
+    }
+
+    /// Zero the counters of every shard.
+    pub fn reset_stats(&self) {
+        for shard in &self.shards {
+            shard.reset_stats();
+        }
+    }
+
+    fn route(&self, key: &Key) -> &DerivedIngredient<DB, K, V> {
+        let idx = (key.hash() % self.shards.len() as u64) as usize;
+        &self.shards[idx]
+    }
+}
+
+impl<DB, K, V> PersistableIngredient for ShardedDerived<DB, K, V>
+where
+    DB: IngredientLookup + Send + Sync + 'static,
+    K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+    V: Clone + Facet<'static> + Send + Sync + 'static,
+{
+    fn kind(&self) -> QueryKindId {
+        self.kind
+    }
+
+    fn kind_name(&self) -> &'static str {
+        self.kind_name
+    }
+
+    fn section_type(&self) -> SectionType {
+        SectionType::Derived
+    }
+
+    fn logic_version(&self) -> u32 {
+        self.shards[0].logic_version()
+    }
+
+    fn clear(&self) {
+        for shard in &self.shards {
+            shard.clear();
+        }
+    }
+
+    fn save_records(&self) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        self.save_records_yielding(0)
+    }
+
+    fn save_records_yielding(
+        &self,
+        yield_every: usize,
+    ) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        Box::pin(async move {
+            let mut records = Vec::new();
+            for shard in &self.shards {
+                records.extend(shard.save_records_yielding(yield_every).await?);
+            }
+            debug!(
+                kind = self.kind.0,
+                shards = self.shards.len(),
+                records = records.len(),
+                "save_records (sharded)"
+            );
+            Ok(records)
+        })
+    }
+
+    fn load_records(&self, records: Vec<Vec<u8>>) -> PicanteResult<()> {
+        // Every shard decodes the same record format; shard 0 is as good as any.
+        let decoded = decode_all(&records, |bytes| self.shards[0].decode_record(bytes))?;
+        for (dyn_key, cell) in decoded {
+            self.route(&dyn_key.key).insert_cell(dyn_key, cell);
+        }
+        Ok(())
+    }
+
+    fn restore_runtime_state<'a>(
+        &'a self,
+        runtime: &'a Runtime,
+    ) -> BoxFuture<'a, PicanteResult<()>> {
+        Box::pin(async move {
+            for shard in &self.shards {
+                shard.restore_runtime_state(runtime).await?;
+            }
+            Ok(())
+        })
+    }
+
+    fn save_incremental_records(
+        &self,
+        since_revision: u64,
+    ) -> BoxFuture<'_, PicanteResult<Vec<(u64, Vec<u8>, Option<Vec<u8>>)>>> {
+        Box::pin(async move {
+            let mut changes = Vec::new();
+            for shard in &self.shards {
+                changes.extend(shard.save_incremental_records(since_revision).await?);
+            }
+            Ok(changes)
+        })
+    }
+
+    fn apply_wal_entry(
+        &self,
+        revision: u64,
+        key: Vec<u8>,
+        value: Option<Vec<u8>>,
+    ) -> PicanteResult<()> {
+        // WAL keys are the postcard encoding of `K`, i.e. the same bytes as `Key`.
+        let shard = self.route(&Key::from_bytes(key.clone()));
+        shard.apply_wal_entry(revision, key, value)
+    }
+}
+
+impl<DB, K, V> DynIngredient<DB> for ShardedDerived<DB, K, V>
+where
+    DB: IngredientLookup + Send + Sync + 'static,
+    K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+    V: Clone + Facet<'static> + Send + Sync + 'static,
+{
+    fn touch<'a>(&'a self, db: &'a DB, key: Key) -> BoxFuture<'a, PicanteResult<Touch>> {
+        Box::pin(async move {
+            let changed_at = self.route(&key).touch_encoded(db, key.clone()).await?;
+            Ok(Touch { changed_at })
+        })
+    }
+
+    fn stale_deps(&self, key: &Key, revision: Revision) -> Option<Arc<[Dep]>> {
+        DynIngredient::<DB>::stale_deps(self.route(key), key, revision)
+    }
+
+    fn durability(&self, key: &Key) -> Durability {
+        DynIngredient::<DB>::durability(self.route(key), key)
+    }
+
+    fn cached_value(&self, db: &DB, key: &Key) -> Option<Arc<dyn Any + Send + Sync>> {
+        DynIngredient::<DB>::cached_value(self.route(key), db, key)
+    }
+}
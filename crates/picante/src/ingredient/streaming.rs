@@ -0,0 +1,309 @@
+use crate::error::PicanteResult;
+use crate::key::QueryKindId;
+use crate::revision::Revision;
+use crate::runtime::HasRuntime;
+use futures::stream::{BoxStream, Stream, StreamExt};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, ready};
+use tracing::{debug, trace};
+
+type ComputeStream<'db, T> = BoxStream<'db, PicanteResult<T>>;
+type StreamComputeFn<DB, K, T> =
+    dyn for<'db> Fn(&'db DB, K, usize) -> ComputeStream<'db, T> + Send + Sync;
+
+/// What a [`StreamingDerived`] keeps when a consumer stops reading before the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StreamCachePolicy {
+    /// Drop the items produced so far; the next request starts over from item 0.
+    #[default]
+    DiscardOnCancel,
+    /// Keep the items produced so far as a partial result; the next request at the same
+    /// revision replays them and resumes the compute from where it stopped.
+    CachePartial,
+}
+
+/// How much of a key's stream is cached at the current revision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamProgress {
+    /// A consumer stopped after `produced` items (only kept under
+    /// [`StreamCachePolicy::CachePartial`]).
+    Partial {
+        /// Number of items cached so far.
+        produced: usize,
+    },
+    /// The compute ran to its end, producing `produced` items.
+    Complete {
+        /// Number of items in the stream.
+        produced: usize,
+    },
+}
+
+/// The items cached for one key.
+struct StreamCell<T> {
+    /// The revision the items were produced at; the cell is dropped once it is stale.
+    revision: Revision,
+    /// Items in production order; their count is where a resumed compute starts.
+    items: Vec<T>,
+    /// Whether the compute reached the end of its stream.
+    complete: bool,
+}
+
+/// A derived ingredient whose compute yields a stream of items instead of a single value.
+///
+/// The compute receives `already_produced`, the number of items the consumer has already
+/// been handed, and must yield the items from that position on; a fresh request passes 0.
+/// A stream read to its end is cached and replayed to later consumers. A stream dropped
+/// midway is discarded or kept as a partial result depending on the
+/// [`StreamCachePolicy`]; a later request replays the partial items and then calls the
+/// compute with their count, so an expensive producer picks up where it stopped.
+///
+/// Cached items (partial or complete) are only valid at the revision they were produced
+/// at: any revision bump discards them, and a stream that outlives its revision is not
+/// cached at all. Reads made by the compute are recorded in whichever query frame polls the
+/// stream. The ingredient is neither registered nor persisted.
+pub struct StreamingDerived<DB, K, T> {
+    kind: QueryKindId,
+    kind_name: &'static str,
+    compute: Arc<StreamComputeFn<DB, K, T>>,
+    policy: StreamCachePolicy,
+    cells: Mutex<HashMap<K, StreamCell<T>>>,
+}
+
+impl<DB, K, T> StreamingDerived<DB, K, T>
+where
+    DB: HasRuntime + Send + Sync + 'static,
+    K: Clone + Eq + Hash + Send + Sync + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    /// Create a streaming ingredient with a resumable compute function.
+    pub fn new<F>(kind: QueryKindId, kind_name: &'static str, compute: F) -> Self
+    where
+        F: for<'db> Fn(&'db DB, K, usize) -> ComputeStream<'db, T> + Send + Sync + 'static,
+    {
+        Self {
+            kind,
+            kind_name,
+            compute: Arc::new(compute),
+            policy: StreamCachePolicy::default(),
+            cells: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Choose what happens to the items produced so far when a consumer cancels.
+    ///
+    /// Defaults to [`StreamCachePolicy::DiscardOnCancel`].
+    pub fn with_cache_policy(mut self, policy: StreamCachePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The stable kind id.
+    pub fn kind(&self) -> QueryKindId {
+        self.kind
+    }
+
+    /// Debug name for this ingredient.
+    pub fn kind_name(&self) -> &'static str {
+        self.kind_name
+    }
+
+    /// The configured cache policy.
+    pub fn cache_policy(&self) -> StreamCachePolicy {
+        self.policy
+    }
+
+    /// Stream the items for `key`.
+    ///
+    /// Cached items from the current revision are replayed first; the compute is only
+    /// called for what is missing. Dropping the returned stream before it ends is a
+    /// cancellation and is handled according to the [`StreamCachePolicy`]. An error item
+    /// ends the stream and nothing is cached for it.
+    pub fn stream<'a>(&'a self, db: &'a DB, key: K) -> StreamItems<'a, DB, K, T> {
+        let revision = db.runtime().current_revision();
+        let (items, complete) = {
+            let mut cells = self.cells.lock();
+            match cells.get(&key) {
+                Some(cell) if cell.revision == revision && cell.complete => {
+                    (cell.items.clone(), true)
+                }
+                Some(cell) if cell.revision == revision => {
+                    // Take the partial items: this consumer resumes them, and puts them
+                    // back (with whatever it adds) if it is cancelled too.
+                    let cell = cells.remove(&key).expect("cell present");
+                    debug!(
+                        kind = self.kind.0,
+                        produced = cell.items.len(),
+                        "stream: resuming partial result"
+                    );
+                    (cell.items, false)
+                }
+                Some(_) => {
+                    trace!(kind = self.kind.0, "stream: discarding stale items");
+                    cells.remove(&key);
+                    (Vec::new(), false)
+                }
+                None => (Vec::new(), false),
+            }
+        };
+
+        StreamItems {
+            ingredient: self,
+            db,
+            key,
+            revision,
+            items,
+            replayed: 0,
+            complete,
+            finished: false,
+            compute: None,
+        }
+    }
+
+    /// How much of `key`'s stream is cached at the current revision, if anything.
+    pub fn progress(&self, db: &DB, key: &K) -> Option<StreamProgress> {
+        let revision = db.runtime().current_revision();
+        let cells = self.cells.lock();
+        let cell = cells.get(key).filter(|cell| cell.revision == revision)?;
+        let produced = cell.items.len();
+        Some(if cell.complete {
+            StreamProgress::Complete { produced }
+        } else {
+            StreamProgress::Partial { produced }
+        })
+    }
+
+    /// Cache `items` for `key` if `revision` is still current.
+    fn store(&self, db: &DB, key: &K, revision: Revision, items: Vec<T>, complete: bool) {
+        if db.runtime().current_revision() != revision {
+            trace!(kind = self.kind.0, "stream: revision moved on, not caching");
+            return;
+        }
+
+        let mut cells = self.cells.lock();
+        if !complete
+            && let Some(existing) = cells.get(key)
+            && existing.revision == revision
+            && (existing.complete || existing.items.len() >= items.len())
+        {
+            // A concurrent consumer of the same key got at least as far.
+            return;
+        }
+        cells.insert(
+            key.clone(),
+            StreamCell {
+                revision,
+                items,
+                complete,
+            },
+        );
+    }
+}
+
+/// The stream returned by [`StreamingDerived::stream`].
+pub struct StreamItems<'a, DB, K, T>
+where
+    DB: HasRuntime + Send + Sync + 'static,
+    K: Clone + Eq + Hash + Send + Sync + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    ingredient: &'a StreamingDerived<DB, K, T>,
+    db: &'a DB,
+    key: K,
+    revision: Revision,
+    /// Every item handed out so far plus the cached ones still to replay.
+    items: Vec<T>,
+    /// How many of `items` were already handed out.
+    replayed: usize,
+    /// `items` is a complete cached stream; the compute is never called.
+    complete: bool,
+    /// The stream ended (or failed); nothing is left to cache on drop.
+    finished: bool,
+    compute: Option<ComputeStream<'a, T>>,
+}
+
+// No field is structurally pinned: the compute stream is boxed.
+impl<DB, K, T> Unpin for StreamItems<'_, DB, K, T>
+where
+    DB: HasRuntime + Send + Sync + 'static,
+    K: Clone + Eq + Hash + Send + Sync + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+}
+
+impl<DB, K, T> Stream for StreamItems<'_, DB, K, T>
+where
+    DB: HasRuntime + Send + Sync + 'static,
+    K: Clone + Eq + Hash + Send + Sync + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    type Item = PicanteResult<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.finished {
+            return Poll::Ready(None);
+        }
+
+        if this.replayed < this.items.len() {
+            let item = this.items[this.replayed].clone();
+            this.replayed += 1;
+            return Poll::Ready(Some(Ok(item)));
+        }
+
+        if this.complete {
+            this.finished = true;
+            return Poll::Ready(None);
+        }
+
+        let compute = this.compute.get_or_insert_with(|| {
+            (this.ingredient.compute)(this.db, this.key.clone(), this.items.len())
+        });
+        match ready!(compute.poll_next_unpin(cx)) {
+            Some(Ok(item)) => {
+                this.items.push(item.clone());
+                this.replayed = this.items.len();
+                Poll::Ready(Some(Ok(item)))
+            }
+            Some(Err(err)) => {
+                this.finished = true;
+                this.compute = None;
+                this.items.clear();
+                Poll::Ready(Some(Err(err)))
+            }
+            None => {
+                this.finished = true;
+                this.compute = None;
+                let items = std::mem::take(&mut this.items);
+                this.ingredient
+                    .store(this.db, &this.key, this.revision, items, true);
+                Poll::Ready(None)
+            }
+        }
+    }
+}
+
+impl<DB, K, T> Drop for StreamItems<'_, DB, K, T>
+where
+    DB: HasRuntime + Send + Sync + 'static,
+    K: Clone + Eq + Hash + Send + Sync + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    fn drop(&mut self) {
+        if self.finished
+            || self.complete
+            || self.items.is_empty()
+            || self.ingredient.policy != StreamCachePolicy::CachePartial
+        {
+            return;
+        }
+        // Release the producer before handing its items back.
+        self.compute = None;
+        let items = std::mem::take(&mut self.items);
+        self.ingredient
+            .store(self.db, &self.key, self.revision, items, false);
+    }
+}
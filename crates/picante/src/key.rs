@@ -4,10 +4,11 @@ use crate::error::{PicanteError, PicanteResult};
 use facet::Facet;
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
 use std::sync::Arc;
 
 /// Stable identifier for a query/input kind.
-#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct QueryKindId(pub u32);
 
 impl QueryKindId {
@@ -23,18 +24,46 @@ impl QueryKindId {
     ///
     /// The hash algorithm is a 32-bit FNV-1a over UTF-8 bytes.
     pub const fn from_str(s: &str) -> Self {
-        let bytes = s.as_bytes();
-        let mut hash: u32 = 0x811c9dc5;
-        let mut i = 0usize;
-        while i < bytes.len() {
-            hash ^= bytes[i] as u32;
-            hash = hash.wrapping_mul(0x0100_0193);
-            i += 1;
-        }
-        QueryKindId(hash)
+        QueryKindId(fnv1a(0x811c9dc5, s.as_bytes()))
+    }
+
+    /// Create a stable id for an ingredient derived from this one, e.g. by
+    /// [`DerivedIngredient::map`](crate::ingredient::DerivedIngredient::map).
+    ///
+    /// The id hashes this id's little-endian bytes, `"::"` and `name` with the same FNV-1a as
+    /// [`Self::from_str`], so it is stable across runs as long as the parent id and `name` are.
+    pub const fn child(self, name: &str) -> Self {
+        let hash = fnv1a(0x811c9dc5, &self.0.to_le_bytes());
+        let hash = fnv1a(hash, b"::");
+        QueryKindId(fnv1a(hash, name.as_bytes()))
+    }
+
+    /// Derive an id from an ingredient's key and value types, so no id has to be picked by
+    /// hand.
+    ///
+    /// Hashes `type_name::<K>()`, `"->"` and `type_name::<V>()` with the FNV-1a of
+    /// [`Self::from_str`]. Two kinds with the same key and value types get the same id;
+    /// [`IngredientRegistry::register`](crate::db::IngredientRegistry::register) panics when
+    /// that happens, and one of them needs an explicit id. Type names are not guaranteed to
+    /// be identical across compiler versions: if a toolchain upgrade changes one, persisted
+    /// sections for that kind are skipped as unknown on load (a cold cache, not a corrupt one).
+    pub fn for_types<K: ?Sized, V: ?Sized>() -> Self {
+        let hash = fnv1a(0x811c9dc5, std::any::type_name::<K>().as_bytes());
+        let hash = fnv1a(hash, b"->");
+        QueryKindId(fnv1a(hash, std::any::type_name::<V>().as_bytes()))
     }
 }
 
+const fn fnv1a(mut hash: u32, bytes: &[u8]) -> u32 {
+    let mut i = 0usize;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+        i += 1;
+    }
+    hash
+}
+
 /// Postcard-encoded bytes for a key, plus a deterministic hash for tracing/debugging.
 #[derive(Clone)]
 pub struct Key {
@@ -44,11 +73,13 @@ pub struct Key {
 
 impl Key {
     /// Encode a key using `facet-postcard`.
-    pub fn encode_facet<T: Facet<'static>>(value: &T) -> PicanteResult<Self> {
+    pub fn encode_facet<T: Facet<'static> + ?Sized>(value: &T) -> PicanteResult<Self> {
         let bytes = facet_postcard::to_vec(value).map_err(|e| {
             Arc::new(PicanteError::Encode {
                 what: "key",
                 message: format!("{e:?}"),
+                kind: None,
+                kind_name: None,
             })
         })?;
         Ok(Self::from_bytes(bytes))
@@ -60,10 +91,41 @@ impl Key {
             Arc::new(PicanteError::Decode {
                 what: "key",
                 message: format!("{e:?}"),
+                kind: None,
+                kind_name: None,
             })
         })
     }
 
+    /// Like [`Self::encode_facet`], attributing a failure to the ingredient `kind`.
+    pub(crate) fn encode_for<T: Facet<'static> + ?Sized>(
+        value: &T,
+        kind: QueryKindId,
+        kind_name: &'static str,
+    ) -> PicanteResult<Self> {
+        Self::encode_facet(value).map_err(|e| PicanteError::in_ingredient(e, kind, kind_name))
+    }
+
+    /// Like [`Self::decode_facet`], attributing a failure to the ingredient `kind`.
+    pub(crate) fn decode_for<T: Facet<'static>>(
+        &self,
+        kind: QueryKindId,
+        kind_name: &'static str,
+    ) -> PicanteResult<T> {
+        self.decode_facet()
+            .map_err(|e| PicanteError::in_ingredient(e, kind, kind_name))
+    }
+
+    /// View this key as the encoding of a `T`, to decode it without naming `T` again.
+    ///
+    /// Nothing is checked here: [`TypedKey::decode`] fails if the bytes aren't a `T`.
+    pub fn typed<T: Facet<'static>>(&self) -> TypedKey<T> {
+        TypedKey {
+            key: self.clone(),
+            _marker: PhantomData,
+        }
+    }
+
     /// Construct from already-encoded bytes.
     pub fn from_bytes(bytes: Vec<u8>) -> Self {
         let hash = stable_hash(&bytes);
@@ -102,6 +164,19 @@ impl PartialEq for Key {
 
 impl Eq for Key {}
 
+/// Orders by encoded bytes, consistently with equality.
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.bytes.cmp(&other.bytes)
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Hash for Key {
     fn hash<H: Hasher>(&self, state: &mut H) {
         state.write_u64(self.hash);
@@ -117,8 +192,138 @@ impl fmt::Debug for Key {
     }
 }
 
+/// Best-effort rendering of the decoded key, for logs and error messages.
+///
+/// The key's type isn't known here, so the bytes are guessed at: a postcard string is shown
+/// quoted, a lone varint as a number (signed integers appear zigzag-encoded), and anything
+/// else as hex, truncated after 16 bytes.
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.bytes();
+        if bytes.is_empty() {
+            return f.write_str("()");
+        }
+        if let Some((n, [])) = read_varint(bytes) {
+            return write!(f, "{n}");
+        }
+        if let Some((len, rest)) = read_varint(bytes)
+            && len == rest.len() as u64
+            && let Ok(text) = std::str::from_utf8(rest)
+            && !text.chars().any(char::is_control)
+        {
+            return write!(f, "{text:?}");
+        }
+
+        f.write_str("0x")?;
+        for byte in bytes.iter().take(16) {
+            write!(f, "{byte:02x}")?;
+        }
+        if bytes.len() > 16 {
+            write!(f, "… ({} bytes)", bytes.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Decode a postcard (LEB128) varint from the front of `bytes`.
+pub(crate) fn read_varint(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f).checked_shl(7 * i as u32)?;
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+/// A [`Key`] that remembers, at compile time, the Rust type it encodes.
+///
+/// Handy when inspecting dependency lists and other places that hand out raw keys: keep the
+/// type next to the bytes instead of repeating it at every `decode_facet` call. Equality,
+/// hashing and ordering are those of the underlying key.
+pub struct TypedKey<T> {
+    key: Key,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Facet<'static>> TypedKey<T> {
+    /// Encode `value` (see [`Key::encode_facet`]).
+    pub fn new(value: &T) -> PicanteResult<Self> {
+        Ok(Key::encode_facet(value)?.typed())
+    }
+
+    /// Decode the key back into a `T` (see [`Key::decode_facet`]).
+    pub fn decode(&self) -> PicanteResult<T> {
+        self.key.decode_facet()
+    }
+}
+
+impl<T> TypedKey<T> {
+    /// The untyped key.
+    pub fn key(&self) -> &Key {
+        &self.key
+    }
+
+    /// Drop the type, keeping the key.
+    pub fn into_key(self) -> Key {
+        self.key
+    }
+}
+
+impl<T> From<TypedKey<T>> for Key {
+    fn from(key: TypedKey<T>) -> Self {
+        key.key
+    }
+}
+
+impl<T> Clone for TypedKey<T> {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for TypedKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<T> Eq for TypedKey<T> {}
+
+impl<T> Ord for TypedKey<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl<T> PartialOrd for TypedKey<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Hash for TypedKey<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Hash::hash(&self.key, state);
+    }
+}
+
+impl<T> fmt::Debug for TypedKey<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedKey")
+            .field("type", &std::any::type_name::<T>())
+            .field("hash", &format_args!("{:016x}", self.key.hash))
+            .field("len", &self.key.bytes.len())
+            .finish()
+    }
+}
+
 /// Erased key for diagnostics/cycle detection.
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct DynKey {
     /// Kind identifier.
     pub kind: QueryKindId,
@@ -126,8 +331,24 @@ pub struct DynKey {
     pub key: Key,
 }
 
+/// Renders as `kind_<id>, key_<hash> (<key>)`, with the key rendered as by `Key`'s
+/// [`Display`](fmt::Display) impl.
+impl fmt::Display for DynKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "kind_{}, key_{:016x} ({})",
+            self.kind.0,
+            self.key.hash(),
+            self.key
+        )
+    }
+}
+
 /// A recorded dependency edge.
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+///
+/// Equality, hashing and ordering compare the kind and the encoded key bytes.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub struct Dep {
     /// The depended-on query kind.
     pub kind: QueryKindId,
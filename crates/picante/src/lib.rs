@@ -58,25 +58,37 @@
 //! # Ok(()) }
 //! ```
 
+mod chrome_trace;
+pub mod clock;
 pub mod db;
 pub mod debug;
+pub mod eager;
 pub mod error;
 mod facet_eq;
 pub mod frame;
 pub(crate) mod inflight;
 pub mod ingredient;
 pub mod key;
+#[cfg(feature = "lock-metrics")]
+pub mod metrics;
 pub mod persist;
+pub mod report;
 pub mod revision;
 pub mod runtime;
+#[cfg(feature = "tower")]
+pub mod service;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod wal;
 
 pub use db::{DynIngredient, IngredientLookup, IngredientRegistry, Touch};
 pub use error::{PicanteError, PicanteResult};
-pub use ingredient::{DerivedIngredient, InputIngredient, InternId, InternedIngredient};
-pub use key::{Dep, DynKey, Key, QueryKindId};
-pub use revision::Revision;
-pub use runtime::{HasRuntime, Runtime, RuntimeEvent, RuntimeId};
+pub use ingredient::{DerivedIngredient, InputIngredient, InternId, Interned, InternedIngredient};
+pub use key::{Dep, DynKey, Key, QueryKindId, TypedKey};
+pub use revision::{Durability, LocalRevisionSource, Revision, RevisionSource};
+pub use runtime::{
+    Checkpoint, EventBatch, HasRuntime, InFlightQuery, Runtime, RuntimeEvent, RuntimeId,
+};
 
 #[cfg(feature = "macros")]
 pub use picante_macros::{db, input, interned, tracked};
@@ -0,0 +1,120 @@
+//! Optional runtime instrumentation (enabled by the `lock-metrics` feature).
+//!
+//! These counters are meant for latency investigations, e.g. confirming or ruling out
+//! contention on derived cells before changing how waiters are woken up.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Number of buckets in a [`LockWaitHistogram`].
+pub const LOCK_WAIT_BUCKETS: usize = 32;
+
+/// Snapshot of how long callers waited to acquire derived cell state locks.
+///
+/// Bucket `i` counts waits in `[2^i, 2^(i+1))` nanoseconds (bucket 0 also holds zero-length
+/// waits, and the last bucket holds everything above its lower bound).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockWaitHistogram {
+    /// Per-bucket sample counts.
+    pub buckets: [u64; LOCK_WAIT_BUCKETS],
+    /// Total number of samples.
+    pub count: u64,
+    /// Sum of all recorded waits.
+    pub total: Duration,
+    /// Longest recorded wait.
+    pub max: Duration,
+}
+
+impl Default for LockWaitHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; LOCK_WAIT_BUCKETS],
+            count: 0,
+            total: Duration::ZERO,
+            max: Duration::ZERO,
+        }
+    }
+}
+
+impl LockWaitHistogram {
+    /// Exclusive upper bound of bucket `index`.
+    pub fn bucket_upper_bound(index: usize) -> Duration {
+        Duration::from_nanos(1u64 << (index.min(LOCK_WAIT_BUCKETS - 1) + 1))
+    }
+
+    /// Mean wait, or zero if nothing was recorded.
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_nanos((self.total.as_nanos() / self.count as u128) as u64)
+    }
+
+    /// Upper bound of the bucket containing the `q`-quantile (`q` in `0.0..=1.0`), capped at
+    /// `max` since no recorded wait is longer. The last bucket has no upper bound: a quantile
+    /// that falls in it is `max`.
+    pub fn quantile(&self, q: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let target = ((self.count as f64) * q.clamp(0.0, 1.0)).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= target {
+                if i == LOCK_WAIT_BUCKETS - 1 {
+                    return self.max;
+                }
+                return Self::bucket_upper_bound(i).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Fold another histogram into this one.
+    pub fn merge(&mut self, other: &LockWaitHistogram) {
+        for (a, b) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *a += b;
+        }
+        self.count += other.count;
+        self.total += other.total;
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// Lock-free recorder behind [`LockWaitHistogram`].
+pub(crate) struct LockWaitRecorder {
+    buckets: [AtomicU64; LOCK_WAIT_BUCKETS],
+    count: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+impl LockWaitRecorder {
+    pub(crate) fn new() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            count: AtomicU64::new(0),
+            total_nanos: AtomicU64::new(0),
+            max_nanos: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record(&self, wait: Duration) {
+        let nanos = u64::try_from(wait.as_nanos()).unwrap_or(u64::MAX);
+        let bucket = (nanos.max(1).ilog2() as usize).min(LOCK_WAIT_BUCKETS - 1);
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> LockWaitHistogram {
+        LockWaitHistogram {
+            buckets: std::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed)),
+            count: self.count.load(Ordering::Relaxed),
+            total: Duration::from_nanos(self.total_nanos.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
@@ -1,18 +1,31 @@
 //! Cache persistence for Picante ingredients.
 
 use crate::error::{PicanteError, PicanteResult};
-use crate::key::QueryKindId;
+use crate::ingredient::{DepRecord, DerivedRecord, InputRecord, InternId, InternedRecord};
+use crate::key::{Dep, QueryKindId, read_varint};
 use crate::revision::Revision;
 use crate::runtime::Runtime;
 use crate::wal::{WalEntry, WalOperation, WalReader, WalWriter};
 use facet::Facet;
 use futures::future::BoxFuture;
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tracing::{debug, info, warn};
 
-const FORMAT_VERSION: u32 = 1;
+const FORMAT_VERSION: u32 = 5;
+
+/// The oldest format version whose layout [`decode_cache_file`] still understands.
+const OLDEST_FORMAT_VERSION: u32 = 1;
+
+/// Version field of a [`CompressedCacheFile`], far above any plain layout's.
+const COMPRESSED_FORMAT_VERSION: u32 = u32::MAX;
+
+/// First bytes of a cache file from format version 5 on. No older layout starts with them:
+/// those start with a small version varint, or [`COMPRESSED_FORMAT_VERSION`]'s.
+const CACHE_MAGIC: [u8; 4] = *b"PCNT";
 
 /// Controls how Picante behaves when a cache file can't be decoded/validated.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -28,7 +41,8 @@ pub enum OnCorruptCache {
 /// Options for loading a cache file.
 #[derive(Debug, Clone)]
 pub struct CacheLoadOptions {
-    /// If set, rejects cache files larger than this.
+    /// If set, rejects cache files larger than this, both as stored and, for compressed
+    /// files, once decompressed.
     pub max_bytes: Option<usize>,
     /// Policy for decode/validation failures.
     pub on_corrupt: OnCorruptCache,
@@ -44,7 +58,10 @@ impl Default for CacheLoadOptions {
 }
 
 /// Options for saving a cache file.
+///
+/// Start from [`CacheSaveOptions::default`] and set what you need with the `with_*` methods.
 #[derive(Debug, Clone, Default)]
+#[non_exhaustive]
 pub struct CacheSaveOptions {
     /// If set, best-effort truncates records to fit within this many bytes.
     ///
@@ -54,10 +71,110 @@ pub struct CacheSaveOptions {
     pub max_records_per_section: Option<usize>,
     /// If set, records larger than this are skipped (best effort).
     pub max_record_bytes: Option<usize>,
+    /// If non-zero, ingredients yield to the executor after serializing this many records, so
+    /// that a large save doesn't starve other tasks on the same runtime.
+    pub yield_every: usize,
+    /// Free-form `(key, value)` pairs stored in the file (build id, toolchain, ...); see
+    /// [`read_cache_metadata`].
+    pub metadata: Vec<(String, String)>,
+    /// Whether large records are kept in the cache file or in sidecar files.
+    pub value_store: ValueStorePolicy,
+    /// Directory for sidecar files; defaults to `<cache file name>.blobs` next to the cache
+    /// file.
+    ///
+    /// The directory belongs to one cache file: every save removes the sidecars its new file
+    /// no longer references.
+    pub sidecar_dir: Option<PathBuf>,
+    /// Compression applied to the whole file once encoded. Size limits apply to the
+    /// uncompressed encoding; sidecar files are never compressed.
+    pub compression: Compression,
+}
+
+impl CacheSaveOptions {
+    /// Set [`Self::max_bytes`].
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Set [`Self::max_records_per_section`].
+    pub fn with_max_records_per_section(mut self, max_records: usize) -> Self {
+        self.max_records_per_section = Some(max_records);
+        self
+    }
+
+    /// Set [`Self::max_record_bytes`].
+    pub fn with_max_record_bytes(mut self, max_record_bytes: usize) -> Self {
+        self.max_record_bytes = Some(max_record_bytes);
+        self
+    }
+
+    /// Set [`Self::yield_every`].
+    pub fn with_yield_every(mut self, yield_every: usize) -> Self {
+        self.yield_every = yield_every;
+        self
+    }
+
+    /// Set [`Self::metadata`].
+    pub fn with_metadata(mut self, metadata: Vec<(String, String)>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Set [`Self::value_store`].
+    pub fn with_value_store(mut self, value_store: ValueStorePolicy) -> Self {
+        self.value_store = value_store;
+        self
+    }
+
+    /// Set [`Self::sidecar_dir`].
+    pub fn with_sidecar_dir(mut self, sidecar_dir: impl Into<PathBuf>) -> Self {
+        self.sidecar_dir = Some(sidecar_dir.into());
+        self
+    }
+
+    /// Set [`Self::compression`].
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+}
+
+/// How [`save_cache_with_options`] compresses the cache file.
+///
+/// Loading detects the codec from the file itself, so it needs no option; a file
+/// compressed with a codec this build doesn't support fails to load with
+/// [`PicanteError::Cache`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Compression {
+    /// Store the encoded file as is, readable by every version of picante.
+    #[default]
+    None,
+    /// Compress with zstd at this level (1 to 22; 0 picks zstd's default). Requires the
+    /// `zstd` feature.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+/// Where [`save_cache_with_options`] stores records.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ValueStorePolicy {
+    /// Every record is stored in the cache file.
+    #[default]
+    Inline,
+    /// Records larger than this many bytes are written to sidecar files named by a hash of
+    /// their contents, and the cache file only references them. Identical records, in one
+    /// section or across sections, share a file.
+    SidecarAbove(usize),
 }
 
 /// Top-level cache file payload (encoded with `facet-postcard`).
+///
+/// On disk it follows a fixed header holding the format version and a CRC-32 of the
+/// encoded payload, checked before anything is decoded; [`CacheFile::to_bytes`] writes both.
+/// Build one by hand with [`CacheFile::new`].
 #[derive(Debug, Clone, Facet)]
+#[non_exhaustive]
 pub struct CacheFile {
     /// Cache format version.
     pub format_version: u32,
@@ -65,6 +182,177 @@ pub struct CacheFile {
     pub current_revision: u64,
     /// Per-ingredient sections.
     pub sections: Vec<Section>,
+    /// User metadata from [`CacheSaveOptions::metadata`]. Picante never interprets it.
+    pub metadata: Vec<(String, String)>,
+    /// Records stored in sidecar files (see [`ValueStorePolicy::SidecarAbove`]), if any.
+    pub sidecars: Option<SidecarIndex>,
+    /// Whether size limits dropped records when this file was saved, so its sections may
+    /// not hold everything their ingredients did.
+    pub truncated: bool,
+}
+
+/// The records of a [`CacheFile`] that live in sidecar files.
+#[derive(Debug, Clone, Facet)]
+pub struct SidecarIndex {
+    /// The sidecar directory, relative to the cache file's directory unless absolute.
+    pub dir: String,
+    /// One entry per record moved out of the file. The record's slot in its section is left
+    /// empty and filled from the sidecar when the file is loaded.
+    pub records: Vec<SidecarRef>,
+}
+
+/// A record stored in a sidecar file.
+#[derive(Debug, Clone, Facet)]
+pub struct SidecarRef {
+    /// Index into [`CacheFile::sections`].
+    pub section: u32,
+    /// Index into that section's records.
+    pub record: u32,
+    /// 64-bit FNV-1a hash of the record bytes.
+    pub hash: u64,
+    /// Length of the record in bytes.
+    pub len: u64,
+}
+
+/// A compressed cache file: the encoded [`CacheFile`], compressed with `codec`.
+#[derive(Facet)]
+struct CompressedCacheFile {
+    /// Always [`COMPRESSED_FORMAT_VERSION`].
+    format_version: u32,
+    /// Name of the codec, e.g. `zstd`.
+    codec: String,
+    payload: Vec<u8>,
+}
+
+impl CacheFile {
+    /// A cache in the current format at `current_revision`, holding `sections` and nothing
+    /// else.
+    pub fn new(current_revision: u64, sections: Vec<Section>) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            current_revision,
+            sections,
+            metadata: Vec::new(),
+            sidecars: None,
+            truncated: false,
+        }
+    }
+
+    /// Encode this cache as [`save_cache`] writes it (uncompressed), header included.
+    pub fn to_bytes(&self) -> PicanteResult<Vec<u8>> {
+        encode_cache_file(self)
+    }
+
+    /// Decode the bytes of a cache file, as [`load_cache`] does before validating them:
+    /// decompressing them, checking the checksum, and reading older layouts.
+    pub fn from_bytes(bytes: &[u8]) -> PicanteResult<Self> {
+        decode_cache_file(bytes)
+    }
+}
+
+/// Layout of format version 4, before [`CacheFile::truncated`] existed.
+#[derive(Facet)]
+struct CacheFileV4 {
+    format_version: u32,
+    current_revision: u64,
+    sections: Vec<Section>,
+    metadata: Vec<(String, String)>,
+    sidecars: Option<SidecarIndex>,
+}
+
+impl From<CacheFileV4> for CacheFile {
+    fn from(old: CacheFileV4) -> Self {
+        Self {
+            format_version: old.format_version,
+            current_revision: old.current_revision,
+            sections: old.sections,
+            metadata: old.metadata,
+            sidecars: old.sidecars,
+            truncated: false,
+        }
+    }
+}
+
+/// Layout of format version 3, before [`CacheFile::sidecars`] existed.
+#[derive(Facet)]
+struct CacheFileV3 {
+    format_version: u32,
+    current_revision: u64,
+    sections: Vec<Section>,
+    metadata: Vec<(String, String)>,
+}
+
+impl From<CacheFileV3> for CacheFile {
+    fn from(old: CacheFileV3) -> Self {
+        Self {
+            format_version: old.format_version,
+            current_revision: old.current_revision,
+            sections: old.sections,
+            metadata: old.metadata,
+            sidecars: None,
+            truncated: false,
+        }
+    }
+}
+
+/// Layout of format version 2, before [`CacheFile::metadata`] existed.
+#[derive(Facet)]
+struct CacheFileV2 {
+    format_version: u32,
+    current_revision: u64,
+    sections: Vec<Section>,
+}
+
+impl From<CacheFileV2> for CacheFile {
+    fn from(old: CacheFileV2) -> Self {
+        Self {
+            format_version: old.format_version,
+            current_revision: old.current_revision,
+            sections: old.sections,
+            metadata: Vec::new(),
+            sidecars: None,
+            truncated: false,
+        }
+    }
+}
+
+/// Layout of format version 1, before [`Section::logic_version`] existed.
+#[derive(Facet)]
+struct CacheFileV1 {
+    format_version: u32,
+    current_revision: u64,
+    sections: Vec<SectionV1>,
+}
+
+/// A section of a version 1 cache.
+#[derive(Facet)]
+struct SectionV1 {
+    kind_id: u32,
+    kind_name: String,
+    section_type: SectionType,
+    records: Vec<Vec<u8>>,
+}
+
+impl From<CacheFileV1> for CacheFile {
+    fn from(old: CacheFileV1) -> Self {
+        CacheFileV2 {
+            format_version: old.format_version,
+            current_revision: old.current_revision,
+            sections: old
+                .sections
+                .into_iter()
+                .map(|section| Section {
+                    kind_id: section.kind_id,
+                    kind_name: section.kind_name,
+                    section_type: section.section_type,
+                    // Saved before ingredients had logic versions: the default one.
+                    logic_version: 0,
+                    records: section.records,
+                })
+                .collect(),
+        }
+        .into()
+    }
 }
 
 /// A per-ingredient cache section.
@@ -76,10 +364,94 @@ pub struct Section {
     pub kind_name: String,
     /// Whether this section is for an input or a derived query.
     pub section_type: SectionType,
+    /// The ingredient's [`logic_version`](PersistableIngredient::logic_version) when saved.
+    pub logic_version: u32,
     /// Ingredient-defined records (each record is its own `facet-postcard` blob).
     pub records: Vec<Vec<u8>>,
 }
 
+impl Section {
+    /// Decode the records of a `Derived` section written by a `DerivedIngredient<_, K, V>`.
+    ///
+    /// Returns `(key, value, changed_at, deps)` for each record.
+    pub fn decode_derived<K, V>(&self) -> PicanteResult<Vec<(K, V, u64, Vec<Dep>)>>
+    where
+        K: Facet<'static>,
+        V: Facet<'static>,
+    {
+        self.decode_records::<DerivedRecord<K, V>>(SectionType::Derived, "derived record")
+            .map(|records| {
+                records
+                    .into_iter()
+                    .map(|rec| {
+                        let deps = rec.deps.into_iter().map(DepRecord::into_dep).collect();
+                        (rec.key, rec.value, rec.changed_at, deps)
+                    })
+                    .collect()
+            })
+    }
+
+    /// Decode the records of an `Input` section written by an `InputIngredient<K, V>`.
+    ///
+    /// Returns `(key, value, changed_at)` for each record; `None` values are removed keys.
+    pub fn decode_input<K, V>(&self) -> PicanteResult<Vec<(K, Option<V>, u64)>>
+    where
+        K: Facet<'static>,
+        V: Facet<'static>,
+    {
+        self.decode_records::<InputRecord<K, V>>(SectionType::Input, "input record")
+            .map(|records| {
+                records
+                    .into_iter()
+                    .map(|rec| (rec.key, rec.value, rec.changed_at))
+                    .collect()
+            })
+    }
+
+    /// Decode the records of an `Interned` section written by an `InternedIngredient<K>`.
+    pub fn decode_interned<K>(&self) -> PicanteResult<Vec<(InternId, Arc<K>)>>
+    where
+        K: Facet<'static>,
+    {
+        self.decode_records::<InternedRecord<K>>(SectionType::Interned, "interned record")
+            .map(|records| {
+                records
+                    .into_iter()
+                    .map(|rec| (InternId(rec.id), rec.value))
+                    .collect()
+            })
+    }
+
+    fn decode_records<T: Facet<'static>>(
+        &self,
+        expected: SectionType,
+        what: &'static str,
+    ) -> PicanteResult<Vec<T>> {
+        if self.section_type != expected {
+            return Err(Arc::new(PicanteError::Cache {
+                message: format!(
+                    "section `{}` (kind {}) is {:?}, not {:?}",
+                    self.kind_name, self.kind_id, self.section_type, expected
+                ),
+            }));
+        }
+
+        self.records
+            .iter()
+            .map(|bytes| {
+                facet_postcard::from_slice(bytes).map_err(|e| {
+                    Arc::new(PicanteError::Decode {
+                        what,
+                        message: format!("{e:?}"),
+                        kind: None,
+                        kind_name: None,
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
 /// Section type for persistence.
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Facet)]
@@ -100,12 +472,41 @@ pub trait PersistableIngredient: Send + Sync {
     fn kind_name(&self) -> &'static str;
     /// Whether this ingredient stores inputs or derived values.
     fn section_type(&self) -> SectionType;
+    /// Version of the logic that produced this ingredient's records.
+    ///
+    /// A section saved under a different version is skipped on load, leaving the ingredient
+    /// cold while the rest of the cache loads normally.
+    fn logic_version(&self) -> u32 {
+        0
+    }
     /// Clear all in-memory data for this ingredient.
     fn clear(&self);
     /// Serialize this ingredient's records.
     fn save_records(&self) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>>;
+    /// Like [`save_records`](Self::save_records), but yields to the executor after every
+    /// `yield_every` records (`0` never yields).
+    ///
+    /// Saving only reads a snapshot of the ingredient, so dropping the returned future at any
+    /// point leaves the ingredient untouched. The default implementation doesn't yield.
+    fn save_records_yielding(
+        &self,
+        _yield_every: usize,
+    ) -> BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        self.save_records()
+    }
     /// Load this ingredient from raw record bytes.
     fn load_records(&self, records: Vec<Vec<u8>>) -> PicanteResult<()>;
+    /// Like [`load_records`](Self::load_records), but takes the records one at a time.
+    ///
+    /// Loading a cache hands records over this way, copying each out of the file as it's
+    /// asked for, so an ingredient that inserts as it goes never holds more than one. The
+    /// default collects them and calls [`load_records`](Self::load_records).
+    fn load_record_stream(
+        &self,
+        records: Box<dyn Iterator<Item = Vec<u8>> + '_>,
+    ) -> PicanteResult<()> {
+        self.load_records(records.collect())
+    }
     /// Restore any runtime-side state derived from loaded records.
     fn restore_runtime_state<'a>(
         &'a self,
@@ -113,6 +514,14 @@ pub trait PersistableIngredient: Send + Sync {
     ) -> BoxFuture<'a, PicanteResult<()>> {
         Box::pin(async { Ok(()) })
     }
+    /// Whether this ingredient's data may have changed after revision `since`.
+    ///
+    /// [`save_cache_incremental`] reuses the previous file's section for an ingredient that
+    /// says no, instead of serializing its records again. A `false` must be certain; the
+    /// default is always `true`.
+    fn is_dirty(&self, _since: Revision) -> bool {
+        true
+    }
 
     // ===== Incremental persistence methods (WAL support) =====
 
@@ -149,6 +558,62 @@ pub trait PersistableIngredient: Send + Sync {
     }
 }
 
+/// Sections with at least this many records are decoded on several threads.
+pub(crate) const PARALLEL_DECODE_THRESHOLD: usize = 4096;
+
+/// Decode every record with `decode`, preserving order.
+///
+/// Decoding is CPU-bound and independent per record, so large sections are split into one
+/// chunk per available core and decoded on scoped threads; smaller ones stay on the calling
+/// thread, where spawning would cost more than it saves. Fails with the first error in
+/// record order.
+pub(crate) fn decode_all<T, F>(records: &[Vec<u8>], decode: F) -> PicanteResult<Vec<T>>
+where
+    T: Send,
+    F: Fn(&[u8]) -> PicanteResult<T> + Sync,
+{
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    if records.len() < PARALLEL_DECODE_THRESHOLD || threads < 2 {
+        return records.iter().map(|bytes| decode(bytes)).collect();
+    }
+
+    let chunk_size = records.len().div_ceil(threads);
+    let decode = &decode;
+    let chunks: Vec<PicanteResult<Vec<T>>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = records
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|bytes| decode(bytes))
+                        .collect::<PicanteResult<Vec<T>>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap_or_else(|p| std::panic::resume_unwind(p)))
+            .collect()
+    });
+
+    let mut out = Vec::with_capacity(records.len());
+    for chunk in chunks {
+        out.extend(chunk?);
+    }
+    debug!(records = records.len(), threads, "decoded records in parallel");
+    Ok(out)
+}
+
+/// Yield to the executor once every `yield_every` records (never if `yield_every` is 0).
+pub(crate) async fn yield_point(index: usize, yield_every: usize) {
+    if yield_every != 0 && index != 0 && index % yield_every == 0 {
+        tokio::task::yield_now().await;
+    }
+}
+
 /// Save `runtime` and `ingredients` to `path`.
 pub async fn save_cache(
     path: impl AsRef<Path>,
@@ -164,20 +629,231 @@ pub async fn save_cache_with_options(
     runtime: &Runtime,
     ingredients: &[&dyn PersistableIngredient],
     options: &CacheSaveOptions,
+) -> PicanteResult<()> {
+    save_cache_file(path.as_ref(), runtime, ingredients, options, None).await
+}
+
+/// Save `runtime` and `ingredients` to `path`, reusing the sections of the cache already
+/// there for ingredients that haven't changed since it was saved.
+///
+/// An ingredient is clean if [`PersistableIngredient::is_dirty`] says so for the existing
+/// file's revision and the file has a section for it with the same kind name, section type,
+/// and logic version; its records are copied over without being serialized again. If there
+/// is no readable cache at `path`, or it is newer than `runtime`, this is a full save.
+///
+/// The existing file must come from this database (saved from it or loaded into it): an
+/// ingredient's dirtiness is judged against its own history.
+pub async fn save_cache_incremental(
+    path: impl AsRef<Path>,
+    runtime: &Runtime,
+    ingredients: &[&dyn PersistableIngredient],
+    options: &CacheSaveOptions,
 ) -> PicanteResult<()> {
     let path = path.as_ref();
+    let previous = match read_previous_cache(path).await {
+        Ok(Some(cache)) if cache.current_revision <= runtime.current_revision().0 => Some(cache),
+        Ok(Some(cache)) => {
+            debug!(
+                rev = cache.current_revision,
+                "save_cache_incremental: existing cache is newer, saving in full"
+            );
+            None
+        }
+        Ok(None) => None,
+        Err(e) => {
+            debug!(error = %e, "save_cache_incremental: existing cache unusable, saving in full");
+            None
+        }
+    };
+    save_cache_file(path, runtime, ingredients, options, previous).await
+}
+
+/// Read the cache at `path` with its sidecar records filled in, if there is one.
+async fn read_previous_cache(path: &Path) -> PicanteResult<Option<CacheFile>> {
+    let bytes = match tokio::fs::read(path).await {
+        Ok(b) => b,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(Arc::new(PicanteError::Cache {
+                message: format!("read {}: {e}", path.display()),
+            }));
+        }
+    };
+    let mut cache = decode_cache_file(&bytes)?;
+    if let Some(index) = cache.sidecars.take() {
+        resolve_sidecars(path, &mut cache.sections, index).await?;
+    }
+    Ok(Some(cache))
+}
+
+async fn save_cache_file(
+    path: &Path,
+    runtime: &Runtime,
+    ingredients: &[&dyn PersistableIngredient],
+    options: &CacheSaveOptions,
+    previous: Option<CacheFile>,
+) -> PicanteResult<()> {
+    let started = runtime.clock().now();
     debug!(path = %path.display(), "save_cache: start");
 
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await.map_err(|e| {
+            Arc::new(PicanteError::Cache {
+                message: format!("create_dir_all {}: {e}", parent.display()),
+            })
+        })?;
+    }
+
+    let sidecar_dir = options
+        .sidecar_dir
+        .clone()
+        .unwrap_or_else(|| default_sidecar_dir(path));
+
+    // Write to a temporary file and rename it into place, so a failed save never leaves a
+    // torn cache behind.
+    let tmp = path.with_extension("tmp");
+    let file = tokio::fs::File::create(&tmp).await.map_err(|e| {
+        Arc::new(PicanteError::Cache {
+            message: format!("create {}: {e}", tmp.display()),
+        })
+    })?;
+    let sidecars = Some((path, sidecar_dir.as_path()));
+    let written = write_cache(
+        file,
+        tmp.display(),
+        runtime,
+        ingredients,
+        options,
+        sidecars,
+        previous,
+    )
+    .await;
+    let saved = match written {
+        Ok(saved) => saved,
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&tmp).await;
+            return Err(e);
+        }
+    };
+
+    tokio::fs::rename(&tmp, path).await.map_err(|e| {
+        Arc::new(PicanteError::Cache {
+            message: format!("rename {} -> {}: {e}", tmp.display(), path.display()),
+        })
+    })?;
+
+    remove_orphaned_sidecars(&sidecar_dir, &saved.blobs).await;
+
+    let elapsed = runtime.clock().now().saturating_duration_since(started);
+    record_saved(runtime, ingredients, &saved, elapsed);
+
+    info!(
+        path = %path.display(),
+        bytes = saved.bytes,
+        rev = runtime.current_revision().0,
+        "save_cache: done"
+    );
+    Ok(())
+}
+
+/// Save `runtime` and `ingredients` to `writer`, e.g. an in-memory buffer or an upload to
+/// object storage.
+///
+/// Writes the bytes [`save_cache_with_options`] would put in the file, then flushes
+/// `writer` (without shutting it down). There is no file to keep sidecars next to, so
+/// [`ValueStorePolicy::SidecarAbove`] is an error here.
+pub async fn save_cache_to(
+    writer: impl AsyncWrite + Unpin,
+    runtime: &Runtime,
+    ingredients: &[&dyn PersistableIngredient],
+    options: &CacheSaveOptions,
+) -> PicanteResult<()> {
+    let started = runtime.clock().now();
+    debug!("save_cache_to: start");
+
+    let saved = write_cache(writer, "cache", runtime, ingredients, options, None, None).await?;
+
+    let elapsed = runtime.clock().now().saturating_duration_since(started);
+    record_saved(runtime, ingredients, &saved, elapsed);
+
+    info!(
+        bytes = saved.bytes,
+        rev = runtime.current_revision().0,
+        "save_cache_to: done"
+    );
+    Ok(())
+}
+
+/// What [`write_cache`] wrote.
+struct SavedCache {
+    /// Records kept in each section, in ingredient order.
+    records: Vec<usize>,
+    /// Size of the encoded cache.
+    bytes: usize,
+    /// The sidecar files the cache references, as `(file name, bytes)`.
+    blobs: Vec<(String, Vec<u8>)>,
+}
+
+/// Encode `runtime` and `ingredients` into `writer` (named `dest` in errors).
+///
+/// `sidecars` is the cache file's final path and its sidecar directory; without them,
+/// [`ValueStorePolicy::SidecarAbove`] is refused. Clean ingredients take their records from
+/// the matching section of `previous`, if given.
+async fn write_cache(
+    mut writer: impl AsyncWrite + Unpin,
+    dest: impl std::fmt::Display,
+    runtime: &Runtime,
+    ingredients: &[&dyn PersistableIngredient],
+    options: &CacheSaveOptions,
+    sidecars: Option<(&Path, &Path)>,
+    previous: Option<CacheFile>,
+) -> PicanteResult<SavedCache> {
     ensure_unique_kinds(ingredients)?;
 
+    // A truncated file's sections may be missing records their ingredients still hold.
+    let previous = previous.filter(|p| {
+        if p.truncated {
+            debug!("save_cache: previous cache was truncated, not reusing its sections");
+        }
+        !p.truncated
+    });
+    let since = previous.as_ref().map(|p| Revision(p.current_revision));
+    let mut reusable: HashMap<u32, Section> = previous
+        .map(|p| p.sections.into_iter().map(|s| (s.kind_id, s)).collect())
+        .unwrap_or_default();
+
+    let mut truncated = false;
     let mut sections = Vec::with_capacity(ingredients.len());
     for ingredient in ingredients {
-        let mut records = ingredient.save_records().await?;
+        let reused = since
+            .filter(|&since| !ingredient.is_dirty(since))
+            .and_then(|_| reusable.remove(&ingredient.kind().as_u32()))
+            .filter(|s| {
+                s.kind_name == ingredient.kind_name()
+                    && s.section_type == ingredient.section_type()
+                    && s.logic_version == ingredient.logic_version()
+            });
+        let mut records = match reused {
+            Some(section) => {
+                debug!(
+                    kind = ingredient.kind().as_u32(),
+                    records = section.records.len(),
+                    "save_cache: reusing clean section"
+                );
+                section.records
+            }
+            None => {
+                ingredient
+                    .save_records_yielding(options.yield_every)
+                    .await?
+            }
+        };
         if let Some(max) = options.max_record_bytes {
             let before = records.len();
             records.retain(|r| r.len() <= max);
             let dropped = before - records.len();
             if dropped != 0 {
+                truncated = true;
                 warn!(
                     kind = ingredient.kind().as_u32(),
                     dropped,
@@ -190,6 +866,7 @@ pub async fn save_cache_with_options(
             kind_id: ingredient.kind().as_u32(),
             kind_name: ingredient.kind_name().to_string(),
             section_type: ingredient.section_type(),
+            logic_version: ingredient.logic_version(),
             records,
         });
     }
@@ -198,50 +875,113 @@ pub async fn save_cache_with_options(
         format_version: FORMAT_VERSION,
         current_revision: runtime.current_revision().0,
         sections,
+        metadata: options.metadata.clone(),
+        sidecars: None,
+        truncated,
     };
 
     if let Some(max) = options.max_records_per_section {
         for section in &mut cache.sections {
             if section.records.len() > max {
                 section.records.truncate(max);
+                cache.truncated = true;
             }
         }
     }
 
     if let Some(max_bytes) = options.max_bytes {
+        let before = record_count(&cache);
         shrink_cache_to_fit(&mut cache, max_bytes)?;
+        cache.truncated |= record_count(&cache) != before;
     }
 
-    let bytes = encode_cache_file(&cache)?;
+    let blobs = match (options.value_store, sidecars) {
+        (ValueStorePolicy::Inline, _) => Vec::new(),
+        (ValueStorePolicy::SidecarAbove(threshold), Some((path, dir))) => {
+            move_to_sidecars(&mut cache, path, dir, threshold)
+        }
+        (ValueStorePolicy::SidecarAbove(_), None) => {
+            return Err(Arc::new(PicanteError::Cache {
+                message: "sidecar value storage needs a cache file path".to_string(),
+            }));
+        }
+    };
 
-    if let Some(parent) = path.parent() {
-        tokio::fs::create_dir_all(parent).await.map_err(|e| {
-            Arc::new(PicanteError::Cache {
-                message: format!("create_dir_all {}: {e}", parent.display()),
-            })
-        })?;
+    let bytes = compress_cache_file(encode_cache_file(&cache)?, options.compression)?;
+
+    // Sidecars go first, so the cache never references one that isn't there.
+    if let Some((_, dir)) = sidecars {
+        write_sidecars(dir, &blobs).await?;
     }
 
-    let tmp = path.with_extension("tmp");
-    tokio::fs::write(&tmp, &bytes).await.map_err(|e| {
+    writer.write_all(&bytes).await.map_err(|e| {
         Arc::new(PicanteError::Cache {
-            message: format!("write {}: {e}", tmp.display()),
+            message: format!("write {dest}: {e}"),
         })
     })?;
-
-    tokio::fs::rename(&tmp, path).await.map_err(|e| {
+    writer.flush().await.map_err(|e| {
         Arc::new(PicanteError::Cache {
-            message: format!("rename {} -> {}: {e}", tmp.display(), path.display()),
+            message: format!("flush {dest}: {e}"),
         })
     })?;
 
-    info!(
-        path = %path.display(),
-        bytes = bytes.len(),
-        rev = runtime.current_revision().0,
-        "save_cache: done"
-    );
-    Ok(())
+    Ok(SavedCache {
+        records: cache.sections.iter().map(|s| s.records.len()).collect(),
+        bytes: bytes.len(),
+        blobs,
+    })
+}
+
+fn record_saved(
+    runtime: &Runtime,
+    ingredients: &[&dyn PersistableIngredient],
+    saved: &SavedCache,
+    elapsed: std::time::Duration,
+) {
+    // Sections stay in ingredient order; shrinking only drops records.
+    for (ingredient, &records) in ingredients.iter().zip(&saved.records) {
+        runtime
+            .tallies()
+            .record_section_saved(ingredient.kind(), ingredient.kind_name(), records);
+    }
+    runtime.tallies().record_save(saved.bytes, elapsed);
+}
+
+/// Save `runtime` and `ingredients` to `path`, stamping the file with `metadata`.
+///
+/// Shorthand for [`save_cache_with_options`] with only [`CacheSaveOptions::metadata`] set.
+pub async fn save_cache_with_metadata(
+    path: impl AsRef<Path>,
+    runtime: &Runtime,
+    ingredients: &[&dyn PersistableIngredient],
+    metadata: Vec<(String, String)>,
+) -> PicanteResult<()> {
+    let options = CacheSaveOptions {
+        metadata,
+        ..CacheSaveOptions::default()
+    };
+    save_cache_with_options(path, runtime, ingredients, &options).await
+}
+
+/// Read the metadata stored in the cache file at `path`, without loading it.
+///
+/// Returns `Ok(None)` if the file does not exist. Records are left undecoded, so this is
+/// cheap enough to run before [`load_cache`] to check provenance or reject a cache built
+/// by an incompatible toolchain. Files written before metadata existed report none.
+pub async fn read_cache_metadata(
+    path: impl AsRef<Path>,
+) -> PicanteResult<Option<Vec<(String, String)>>> {
+    let path = path.as_ref();
+    let bytes = match tokio::fs::read(path).await {
+        Ok(b) => b,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(Arc::new(PicanteError::Cache {
+                message: format!("read {}: {e}", path.display()),
+            }));
+        }
+    };
+    Ok(Some(decode_cache_file(&bytes)?.metadata))
 }
 
 /// Load `runtime` and `ingredients` from `path`.
@@ -255,17 +995,94 @@ pub async fn load_cache(
     load_cache_with_options(path, runtime, ingredients, &CacheLoadOptions::default()).await
 }
 
-/// Load `runtime` and `ingredients` from `path` with a corruption policy.
-///
-/// Returns `Ok(false)` if the cache file does not exist, is ignored, or is deleted.
-pub async fn load_cache_with_options(
-    path: impl AsRef<Path>,
+/// Load `runtime` and `ingredients` from `path` with a corruption policy.
+///
+/// Returns `Ok(false)` if the cache file does not exist, is ignored, or is deleted.
+pub async fn load_cache_with_options(
+    path: impl AsRef<Path>,
+    runtime: &Runtime,
+    ingredients: &[&dyn PersistableIngredient],
+    options: &CacheLoadOptions,
+) -> PicanteResult<bool> {
+    load_cache_migrating(path.as_ref(), runtime, ingredients, options, &[]).await
+}
+
+/// Load `runtime` and `ingredients` from `path`, upgrading a file from another format
+/// version with `migrations` first (see [`Migration`]).
+///
+/// Returns `Ok(false)` if the cache file does not exist.
+pub async fn load_cache_with(
+    path: impl AsRef<Path>,
+    runtime: &Runtime,
+    ingredients: &[&dyn PersistableIngredient],
+    migrations: &[Box<dyn Migration>],
+) -> PicanteResult<bool> {
+    load_cache_migrating(
+        path.as_ref(),
+        runtime,
+        ingredients,
+        &CacheLoadOptions::default(),
+        migrations,
+    )
+    .await
+}
+
+/// A step that upgrades a decoded [`CacheFile`] from one format version to a later one.
+///
+/// Given to [`load_cache_with`]: if a file's version isn't the current one, the migration
+/// whose [`from_version`](Self::from_version) matches it runs, then the one matching that
+/// migration's [`to_version`](Self::to_version), and so on until the current version is
+/// reached or no migration applies. The usual checks then run on the result, so a file
+/// left at a version picante can't load still fails. Files from older versions picante
+/// reads itself are already in the current layout when a migration sees them, with their
+/// original `format_version`: their migrations only need to fix up contents, e.g. rename
+/// or drop sections.
+pub trait Migration: Send + Sync {
+    /// The format version this migration applies to.
+    fn from_version(&self) -> u32;
+
+    /// The format version the migrated file has; must be greater than
+    /// [`from_version`](Self::from_version).
+    fn to_version(&self) -> u32;
+
+    /// Transform `cache`. Its `format_version` is set to [`Self::to_version`] afterwards.
+    fn migrate(&self, cache: CacheFile) -> PicanteResult<CacheFile>;
+}
+
+/// Run the chain of `migrations` that starts at `cache`'s version.
+fn apply_migrations(
+    mut cache: CacheFile,
+    migrations: &[Box<dyn Migration>],
+) -> PicanteResult<CacheFile> {
+    while cache.format_version != FORMAT_VERSION {
+        let from = cache.format_version;
+        let Some(migration) = migrations.iter().find(|m| m.from_version() == from) else {
+            break;
+        };
+        let to = migration.to_version();
+        if to <= from {
+            return Err(Arc::new(PicanteError::Cache {
+                message: format!("cache migration from version {from} to {to} goes backwards"),
+            }));
+        }
+        cache = migration.migrate(cache)?;
+        cache.format_version = to;
+        debug!(from, to, "load_cache: migrated cache file");
+    }
+    Ok(cache)
+}
+
+async fn load_cache_migrating(
+    path: &Path,
     runtime: &Runtime,
     ingredients: &[&dyn PersistableIngredient],
     options: &CacheLoadOptions,
+    migrations: &[Box<dyn Migration>],
 ) -> PicanteResult<bool> {
-    match load_cache_inner(path.as_ref(), runtime, ingredients, options).await {
+    match load_cache_inner(path, runtime, ingredients, options, migrations).await {
         Ok(v) => Ok(v),
+        // A stale cache isn't a corrupt one: don't ignore or delete it.
+        Err(e) if matches!(*e, PicanteError::RevisionRegression { .. }) => Err(e),
         Err(e) => match options.on_corrupt {
             OnCorruptCache::Error => Err(e),
             OnCorruptCache::Ignore => {
@@ -274,7 +1091,6 @@ pub async fn load_cache_with_options(
             }
             OnCorruptCache::Delete => {
                 warn!(error = %e, "load_cache: deleting corrupt cache");
-                let path = path.as_ref();
                 let _ = tokio::fs::remove_file(path).await;
                 Ok(false)
             }
@@ -287,13 +1103,12 @@ async fn load_cache_inner(
     runtime: &Runtime,
     ingredients: &[&dyn PersistableIngredient],
     options: &CacheLoadOptions,
+    migrations: &[Box<dyn Migration>],
 ) -> PicanteResult<bool> {
     debug!(path = %path.display(), "load_cache: start");
 
-    ensure_unique_kinds(ingredients)?;
-
-    let bytes = match tokio::fs::read(path).await {
-        Ok(b) => b,
+    let file = match tokio::fs::File::open(path).await {
+        Ok(f) => f,
         Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
         Err(e) => {
             return Err(Arc::new(PicanteError::Cache {
@@ -301,25 +1116,116 @@ async fn load_cache_inner(
             }));
         }
     };
+    let bytes = read_cache(file, Some(path), runtime, ingredients, options, migrations).await?;
+
+    info!(
+        path = %path.display(),
+        bytes,
+        rev = runtime.current_revision().0,
+        "load_cache: done"
+    );
+    Ok(true)
+}
+
+/// Load `runtime` and `ingredients` from the cache in `reader`, as written by
+/// [`save_cache_to`].
+///
+/// Reads `reader` to the end. Unlike [`load_cache`], a corrupt cache is always an error,
+/// and a cache whose records live in sidecar files can't be loaded this way.
+pub async fn load_cache_from(
+    reader: impl AsyncRead + Unpin,
+    runtime: &Runtime,
+    ingredients: &[&dyn PersistableIngredient],
+) -> PicanteResult<()> {
+    debug!("load_cache_from: start");
+
+    let options = CacheLoadOptions::default();
+    let bytes = read_cache(reader, None, runtime, ingredients, &options, &[]).await?;
+
+    info!(
+        bytes,
+        rev = runtime.current_revision().0,
+        "load_cache_from: done"
+    );
+    Ok(())
+}
+
+/// Decode the cache in `reader` and load it into `runtime` and `ingredients`, returning
+/// its size. `path` is where the cache came from, if it's a file: sidecars are found
+/// relative to it.
+async fn read_cache(
+    reader: impl AsyncRead + Unpin,
+    path: Option<&Path>,
+    runtime: &Runtime,
+    ingredients: &[&dyn PersistableIngredient],
+    options: &CacheLoadOptions,
+    migrations: &[Box<dyn Migration>],
+) -> PicanteResult<usize> {
+    let started = runtime.clock().now();
+
+    ensure_unique_kinds(ingredients)?;
+
+    // Read at most one byte past the limit: enough to tell the cache is too large.
+    let limit = options.max_bytes.map_or(u64::MAX, |max| max as u64 + 1);
+    let mut bytes = Vec::new();
+    reader
+        .take(limit)
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(|e| {
+            let source = path.map_or("cache".into(), |p| p.display().to_string());
+            Arc::new(PicanteError::Cache {
+                message: format!("read {source}: {e}"),
+            })
+        })?;
 
     if let Some(max) = options.max_bytes
         && bytes.len() > max
     {
         return Err(Arc::new(PicanteError::Cache {
-            message: format!("cache file too large ({} bytes > max {max})", bytes.len()),
+            message: format!("cache file too large (more than max {max} bytes)"),
         }));
     }
 
-    let cache: CacheFile = decode_cache_file(&bytes)?;
+    let plain = decompress_cache_bytes(&bytes, options.max_bytes)?;
 
-    if cache.format_version != FORMAT_VERSION {
-        return Err(Arc::new(PicanteError::Cache {
-            message: format!(
-                "unsupported cache format version {}; expected {}",
-                cache.format_version, FORMAT_VERSION
-            ),
-        }));
-    }
+    // A current-version cache without sidecars is loaded straight from its bytes, rather
+    // than first decoded into a second copy of every record.
+    let (current_revision, sections) = match walk_cache_file(&plain)? {
+        Some(walked) => {
+            // Refuse before clearing anything, so a stale cache leaves a live database intact.
+            runtime.check_restore_revision(Revision(walked.current_revision))?;
+            (walked.current_revision, walked.sections)
+        }
+        None => {
+            let mut cache = decode_plain_cache_file(&plain)?;
+            cache = apply_migrations(cache, migrations)?;
+
+            if !(OLDEST_FORMAT_VERSION..=FORMAT_VERSION).contains(&cache.format_version) {
+                return Err(Arc::new(PicanteError::Cache {
+                    message: format!(
+                        "unsupported cache format version {}; expected {}",
+                        cache.format_version, FORMAT_VERSION
+                    ),
+                }));
+            }
+
+            runtime.check_restore_revision(Revision(cache.current_revision))?;
+
+            if let Some(index) = cache.sidecars.take() {
+                let Some(path) = path else {
+                    return Err(Arc::new(PicanteError::Cache {
+                        message: "cache references sidecar files but has no path to find them by"
+                            .to_string(),
+                    }));
+                };
+                resolve_sidecars(path, &mut cache.sections, index).await?;
+            }
+
+            let sections = cache.sections.into_iter().map(SectionSource::from);
+            (cache.current_revision, sections.collect())
+        }
+    };
 
     // Build lookup for provided ingredients.
     let mut by_kind: HashMap<u32, &dyn PersistableIngredient> = HashMap::new();
@@ -334,7 +1240,7 @@ async fn load_cache_inner(
         ingredient.clear();
     }
 
-    for section in cache.sections {
+    for section in sections {
         let Some(ingredient) = by_kind.get(&section.kind_id).copied() else {
             warn!(
                 kind_id = section.kind_id,
@@ -364,22 +1270,33 @@ async fn load_cache_inner(
             }));
         }
 
-        ingredient.load_records(section.records)?;
+        if section.logic_version != ingredient.logic_version() {
+            info!(
+                kind_id = section.kind_id,
+                kind_name = %section.kind_name,
+                cached = section.logic_version,
+                current = ingredient.logic_version(),
+                "load_cache: logic version changed, discarding section"
+            );
+            continue;
+        }
+
+        let records = section.records.len();
+        ingredient.load_record_stream(section.records.into_records())?;
+        runtime
+            .tallies()
+            .record_section_loaded(ingredient.kind(), ingredient.kind_name(), records);
     }
 
     for ingredient in ingredients {
         ingredient.restore_runtime_state(runtime).await?;
     }
 
-    runtime.set_current_revision(Revision(cache.current_revision));
+    runtime.restore_revision(Revision(current_revision))?;
+    let elapsed = runtime.clock().now().saturating_duration_since(started);
+    runtime.tallies().record_load(bytes.len(), elapsed);
 
-    info!(
-        path = %path.display(),
-        bytes = bytes.len(),
-        rev = runtime.current_revision().0,
-        "load_cache: done"
-    );
-    Ok(true)
+    Ok(bytes.len())
 }
 
 fn ensure_unique_kinds(ingredients: &[&dyn PersistableIngredient]) -> PicanteResult<()> {
@@ -395,24 +1312,583 @@ fn ensure_unique_kinds(ingredients: &[&dyn PersistableIngredient]) -> PicanteRes
     Ok(())
 }
 
+/// Encode `cache` behind the fixed header: [`CACHE_MAGIC`], then the format version and
+/// the CRC-32 of the `facet-postcard` payload as little-endian `u32`s, then the payload.
 fn encode_cache_file(cache: &CacheFile) -> PicanteResult<Vec<u8>> {
-    facet_postcard::to_vec(cache).map_err(|e| {
+    let body = facet_postcard::to_vec(cache).map_err(|e| {
         Arc::new(PicanteError::Encode {
             what: "cache file",
             message: format!("{e:?}"),
+            kind: None,
+            kind_name: None,
         })
-    })
+    })?;
+    let mut out = Vec::with_capacity(CACHE_MAGIC.len() + 8 + body.len());
+    out.extend_from_slice(&CACHE_MAGIC);
+    out.extend_from_slice(&cache.format_version.to_le_bytes());
+    out.extend_from_slice(&crc32(&body).to_le_bytes());
+    out.extend_from_slice(&body);
+    Ok(out)
+}
+
+/// The payload of a cache file with the fixed header, once the header's version and
+/// checksum check out; `Ok(None)` for an older, headerless file.
+///
+/// Runs before decoding, so a truncated or corrupted file is reported as such rather than
+/// as whatever decode error the damage happens to cause.
+fn checked_body(bytes: &[u8]) -> PicanteResult<Option<&[u8]>> {
+    let Some(rest) = bytes.strip_prefix(&CACHE_MAGIC) else {
+        return Ok(None);
+    };
+    let header = rest.split_first_chunk::<4>().and_then(|(version, rest)| {
+        let (checksum, body) = rest.split_first_chunk::<4>()?;
+        Some((
+            u32::from_le_bytes(*version),
+            u32::from_le_bytes(*checksum),
+            body,
+        ))
+    });
+    let Some((version, expected, body)) = header else {
+        return Err(Arc::new(PicanteError::Cache {
+            message: "truncated cache file header".to_string(),
+        }));
+    };
+    if version != FORMAT_VERSION {
+        return Err(Arc::new(PicanteError::Cache {
+            message: format!(
+                "unsupported cache format version {version}; expected {FORMAT_VERSION}"
+            ),
+        }));
+    }
+    let actual = crc32(body);
+    if expected != actual {
+        debug!(expected, actual, "cache file checksum mismatch");
+        return Err(Arc::new(PicanteError::Cache {
+            message: "checksum mismatch".to_string(),
+        }));
+    }
+    Ok(Some(body))
+}
+
+fn compress_cache_file(bytes: Vec<u8>, compression: Compression) -> PicanteResult<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(bytes),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(level) => {
+            let payload = zstd::encode_all(bytes.as_slice(), level).map_err(|e| {
+                Arc::new(PicanteError::Cache {
+                    message: format!("zstd compression failed: {e}"),
+                })
+            })?;
+            debug!(
+                uncompressed = bytes.len(),
+                compressed = payload.len(),
+                level,
+                "compressed cache file"
+            );
+            facet_postcard::to_vec(&CompressedCacheFile {
+                format_version: COMPRESSED_FORMAT_VERSION,
+                codec: "zstd".to_string(),
+                payload,
+            })
+            .map_err(|e| {
+                Arc::new(PicanteError::Encode {
+                    what: "compressed cache file",
+                    message: format!("{e:?}"),
+                    kind: None,
+                    kind_name: None,
+                })
+            })
+        }
+    }
+}
+
+/// Decompress the payload of `file`, failing once it grows past `max_bytes`.
+fn decompress_cache_file(
+    file: CompressedCacheFile,
+    max_bytes: Option<usize>,
+) -> PicanteResult<Vec<u8>> {
+    #[cfg(feature = "zstd")]
+    if file.codec == "zstd" {
+        use std::io::Read as _;
+
+        let failed = |e: std::io::Error| {
+            Arc::new(PicanteError::Cache {
+                message: format!("zstd decompression failed: {e}"),
+            })
+        };
+        // Decode at most one byte past the limit: enough to tell the cache is too large.
+        let limit = max_bytes.map_or(u64::MAX, |max| max as u64 + 1);
+        let mut plain = Vec::new();
+        zstd::Decoder::with_buffer(file.payload.as_slice())
+            .map_err(failed)?
+            .take(limit)
+            .read_to_end(&mut plain)
+            .map_err(failed)?;
+        if let Some(max) = max_bytes
+            && plain.len() > max
+        {
+            return Err(Arc::new(PicanteError::Cache {
+                message: format!("decompressed cache too large (more than max {max} bytes)"),
+            }));
+        }
+        return Ok(plain);
+    }
+    #[cfg(not(feature = "zstd"))]
+    let _ = max_bytes;
+    Err(Arc::new(PicanteError::Cache {
+        message: format!(
+            "cache file is compressed with unsupported codec `{}` ({} bytes)",
+            file.codec,
+            file.payload.len()
+        ),
+    }))
 }
 
+/// Decode a cache file, decompressing it and migrating older layouts to the current one.
+///
+/// Fields are only ever appended to [`CacheFile`], so an older file fails to decode as the
+/// current layout and is retried as the layout its version used. The result keeps the
+/// version the file was written with.
 fn decode_cache_file(bytes: &[u8]) -> PicanteResult<CacheFile> {
-    facet_postcard::from_slice(bytes).map_err(|e| {
-        Arc::new(PicanteError::Decode {
-            what: "cache file",
-            message: format!("{e:?}"),
-        })
+    decode_plain_cache_file(&decompress_cache_bytes(bytes, None)?)
+}
+
+/// Undo the compression envelope around an encoded [`CacheFile`], if there is one, refusing
+/// to decompress more than `max_bytes`.
+fn decompress_cache_bytes(bytes: &[u8], max_bytes: Option<usize>) -> PicanteResult<Cow<'_, [u8]>> {
+    match read_varint(bytes) {
+        Some((version, _)) if version == u64::from(COMPRESSED_FORMAT_VERSION) => {
+            let file: CompressedCacheFile = facet_postcard::from_slice(bytes).map_err(|e| {
+                Arc::new(PicanteError::Decode {
+                    what: "compressed cache file",
+                    message: format!("{e:?}"),
+                    kind: None,
+                    kind_name: None,
+                })
+            })?;
+            debug!(codec = %file.codec, "decode_cache_file: decompressing");
+            Ok(Cow::Owned(decompress_cache_file(file, max_bytes)?))
+        }
+        _ => Ok(Cow::Borrowed(bytes)),
+    }
+}
+
+/// Decode an uncompressed [`CacheFile`] (see [`decode_cache_file`]).
+fn decode_plain_cache_file(bytes: &[u8]) -> PicanteResult<CacheFile> {
+    if let Some(body) = checked_body(bytes)? {
+        let cache = facet_postcard::from_slice::<CacheFile>(body).map_err(|e| {
+            Arc::new(PicanteError::Decode {
+                what: "cache file",
+                message: format!("{e:?}"),
+                kind: None,
+                kind_name: None,
+            })
+        })?;
+        if cache.format_version != FORMAT_VERSION {
+            return Err(Arc::new(PicanteError::Cache {
+                message: format!(
+                    "cache header says format version {FORMAT_VERSION}, payload says {}",
+                    cache.format_version
+                ),
+            }));
+        }
+        return Ok(cache);
+    }
+    // The header is what makes the checksum mandatory: don't accept a current-version
+    // payload without one.
+    if let Some((version, _)) = read_varint(bytes)
+        && version >= u64::from(FORMAT_VERSION)
+    {
+        return Err(Arc::new(PicanteError::Cache {
+            message: format!("cache file has format version {version} but no header"),
+        }));
+    }
+    // Version 1 sections lack a field in the middle, so its files could be misread in the
+    // current layout: try theirs first.
+    if let Some((1, _)) = read_varint(bytes)
+        && let Ok(old) = facet_postcard::from_slice::<CacheFileV1>(bytes)
+    {
+        debug!("decode_cache_file: migrating format version 1");
+        return Ok(old.into());
+    }
+    // Headerless: a file a `Migration` understands may have the current payload layout.
+    let err = match facet_postcard::from_slice::<CacheFile>(bytes) {
+        Ok(cache) => return Ok(cache),
+        Err(e) => e,
+    };
+    if let Ok(old) = facet_postcard::from_slice::<CacheFileV4>(bytes)
+        && old.format_version == 4
+    {
+        debug!("decode_cache_file: migrating format version 4");
+        return Ok(old.into());
+    }
+    if let Ok(old) = facet_postcard::from_slice::<CacheFileV3>(bytes)
+        && old.format_version == 3
+    {
+        debug!("decode_cache_file: migrating format version 3");
+        return Ok(old.into());
+    }
+    if let Ok(old) = facet_postcard::from_slice::<CacheFileV2>(bytes)
+        && old.format_version == 2
+    {
+        debug!("decode_cache_file: migrating format version 2");
+        return Ok(old.into());
+    }
+    Err(Arc::new(PicanteError::Decode {
+        what: "cache file",
+        message: format!("{err:?}"),
+        kind: None,
+        kind_name: None,
+    }))
+}
+
+/// A section to load: its header, and its records.
+struct SectionSource<'a> {
+    kind_id: u32,
+    kind_name: Cow<'a, str>,
+    section_type: SectionType,
+    logic_version: u32,
+    records: SectionRecords<'a>,
+}
+
+impl From<Section> for SectionSource<'static> {
+    fn from(section: Section) -> Self {
+        SectionSource {
+            kind_id: section.kind_id,
+            kind_name: Cow::Owned(section.kind_name),
+            section_type: section.section_type,
+            logic_version: section.logic_version,
+            records: SectionRecords::Decoded(section.records),
+        }
+    }
+}
+
+enum SectionRecords<'a> {
+    Decoded(Vec<Vec<u8>>),
+    /// `count` records still encoded in the cache's bytes, each a length-prefixed blob.
+    Encoded {
+        count: usize,
+        bytes: &'a [u8],
+    },
+}
+
+impl<'a> SectionRecords<'a> {
+    fn len(&self) -> usize {
+        match self {
+            SectionRecords::Decoded(records) => records.len(),
+            SectionRecords::Encoded { count, .. } => *count,
+        }
+    }
+
+    /// The records in order, copied out of the cache's bytes one at a time if still encoded.
+    fn into_records(self) -> Box<dyn Iterator<Item = Vec<u8>> + 'a> {
+        match self {
+            SectionRecords::Decoded(records) => Box::new(records.into_iter()),
+            SectionRecords::Encoded { count, mut bytes } => {
+                Box::new((0..count).map_while(move |_| {
+                    let (record, rest) = read_blob(bytes)?;
+                    bytes = rest;
+                    Some(record.to_vec())
+                }))
+            }
+        }
+    }
+}
+
+/// A cache whose sections were found in its bytes by [`walk_cache_file`].
+struct WalkedCache<'a> {
+    current_revision: u64,
+    sections: Vec<SectionSource<'a>>,
+}
+
+/// Find the sections of an uncompressed current-version cache without decoding its records,
+/// after checking its checksum.
+///
+/// `Ok(None)` means the file needs decoding in full: it's from another version, it has
+/// sidecars (whose records have to be filled in first), or its layout isn't what the walk
+/// expects.
+fn walk_cache_file(bytes: &[u8]) -> PicanteResult<Option<WalkedCache<'_>>> {
+    Ok(checked_body(bytes)?.and_then(walk_cache_body))
+}
+
+/// Walk the fields of an encoded [`CacheFile`], as postcard lays them out.
+fn walk_cache_body(body: &[u8]) -> Option<WalkedCache<'_>> {
+    let (format_version, rest) = read_varint(body)?;
+    if format_version != u64::from(FORMAT_VERSION) {
+        return None;
+    }
+    let (current_revision, rest) = read_varint(rest)?;
+    let (count, mut rest) = read_varint(rest)?;
+
+    let mut sections = Vec::new();
+    for _ in 0..count {
+        let (kind_id, r) = read_varint(rest)?;
+        let (kind_name, r) = read_blob(r)?;
+        let (section_type, r) = read_section_type(r)?;
+        let (logic_version, r) = read_varint(r)?;
+        let (records, start) = read_varint(r)?;
+        let mut r = start;
+        for _ in 0..records {
+            r = read_blob(r)?.1;
+        }
+        sections.push(SectionSource {
+            kind_id: u32::try_from(kind_id).ok()?,
+            kind_name: Cow::Borrowed(std::str::from_utf8(kind_name).ok()?),
+            section_type,
+            logic_version: u32::try_from(logic_version).ok()?,
+            records: SectionRecords::Encoded {
+                count: usize::try_from(records).ok()?,
+                bytes: &start[..start.len() - r.len()],
+            },
+        });
+        rest = r;
+    }
+
+    // Metadata isn't needed to load, but is skipped to check the file ends as expected:
+    // with `sidecars: None` and the `truncated` flag.
+    let (entries, mut rest) = read_varint(rest)?;
+    for _ in 0..entries {
+        rest = read_blob(read_blob(rest)?.1)?.1;
+    }
+    matches!(rest, [0, 0 | 1]).then_some(WalkedCache {
+        current_revision,
+        sections,
+    })
+}
+
+/// Split a length-prefixed byte string (a postcard `Vec<u8>` or `String`) off `bytes`.
+fn read_blob(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (len, rest) = read_varint(bytes)?;
+    let len = usize::try_from(len).ok()?;
+    (len <= rest.len()).then(|| rest.split_at(len))
+}
+
+/// Each [`SectionType`] variant with its `facet-postcard` encoding, built on first use.
+static SECTION_TYPE_ENCODINGS: LazyLock<Vec<(SectionType, Vec<u8>)>> = LazyLock::new(|| {
+    [
+        SectionType::Input,
+        SectionType::Derived,
+        SectionType::Interned,
+    ]
+    .into_iter()
+    .filter_map(|ty| Some((ty, facet_postcard::to_vec(&ty).ok()?)))
+    .collect()
+});
+
+/// Split an encoded [`SectionType`] off `bytes`, comparing against each variant's encoding.
+fn read_section_type(bytes: &[u8]) -> Option<(SectionType, &[u8])> {
+    SECTION_TYPE_ENCODINGS
+        .iter()
+        .find_map(|(ty, encoded)| Some((*ty, bytes.strip_prefix(encoded.as_slice())?)))
+}
+
+/// `<file name>.blobs` next to the cache file at `path`.
+fn default_sidecar_dir(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "cache".to_string());
+    path.with_file_name(format!("{name}.blobs"))
+}
+
+/// CRC-32 (IEEE 802.3, the zlib and PNG one).
+fn crc32(bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 0 {
+                    crc >> 1
+                } else {
+                    (crc >> 1) ^ 0xedb8_8320
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    !bytes.iter().fold(!0u32, |crc, &b| {
+        TABLE[((crc ^ u32::from(b)) & 0xff) as usize] ^ (crc >> 8)
     })
 }
 
+/// 64-bit FNV-1a: stable across Rust versions, unlike `DefaultHasher`.
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn sidecar_file_name(hash: u64, len: u64) -> String {
+    format!("{hash:016x}-{len}.blob")
+}
+
+/// Move the records of `cache` larger than `threshold` out of its sections, returning the
+/// distinct sidecar files to write as `(file name, bytes)`.
+fn move_to_sidecars(
+    cache: &mut CacheFile,
+    path: &Path,
+    dir: &Path,
+    threshold: usize,
+) -> Vec<(String, Vec<u8>)> {
+    let mut blobs: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut by_name: HashMap<String, usize> = HashMap::new();
+    let mut refs = Vec::new();
+
+    for (section_idx, section) in cache.sections.iter_mut().enumerate() {
+        for (record_idx, record) in section.records.iter_mut().enumerate() {
+            if record.len() <= threshold {
+                continue;
+            }
+            let hash = content_hash(record);
+            let len = record.len() as u64;
+            let name = sidecar_file_name(hash, len);
+            match by_name.get(&name) {
+                Some(&i) if blobs[i].1 != *record => {
+                    // A hash collision within one save: keep this one inline.
+                    continue;
+                }
+                Some(_) => {}
+                None => {
+                    by_name.insert(name.clone(), blobs.len());
+                    blobs.push((name, record.clone()));
+                }
+            }
+            record.clear();
+            refs.push(SidecarRef {
+                section: section_idx as u32,
+                record: record_idx as u32,
+                hash,
+                len,
+            });
+        }
+    }
+
+    if !refs.is_empty() {
+        // Store the directory relative to the cache file when it lives next to it, so the
+        // pair can be moved together (and `compact_wal` can rename a freshly saved file).
+        let base = path.parent().unwrap_or(Path::new(""));
+        let stored = dir.strip_prefix(base).unwrap_or(dir);
+        cache.sidecars = Some(SidecarIndex {
+            dir: stored.to_string_lossy().into_owned(),
+            records: refs,
+        });
+    }
+    blobs
+}
+
+async fn write_sidecars(dir: &Path, blobs: &[(String, Vec<u8>)]) -> PicanteResult<()> {
+    if blobs.is_empty() {
+        return Ok(());
+    }
+    tokio::fs::create_dir_all(dir).await.map_err(|e| {
+        Arc::new(PicanteError::Cache {
+            message: format!("create_dir_all {}: {e}", dir.display()),
+        })
+    })?;
+
+    for (name, bytes) in blobs {
+        let file = dir.join(name);
+        // Content-addressed: a file with this name already holds these bytes.
+        if let Ok(meta) = tokio::fs::metadata(&file).await
+            && meta.len() == bytes.len() as u64
+        {
+            continue;
+        }
+        let tmp = file.with_extension("tmp");
+        tokio::fs::write(&tmp, bytes).await.map_err(|e| {
+            Arc::new(PicanteError::Cache {
+                message: format!("write {}: {e}", tmp.display()),
+            })
+        })?;
+        tokio::fs::rename(&tmp, &file).await.map_err(|e| {
+            Arc::new(PicanteError::Cache {
+                message: format!("rename {} -> {}: {e}", tmp.display(), file.display()),
+            })
+        })?;
+    }
+    Ok(())
+}
+
+/// Best-effort removal of the sidecars in `dir` that the cache file just saved doesn't use.
+async fn remove_orphaned_sidecars(dir: &Path, blobs: &[(String, Vec<u8>)]) {
+    let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+        return;
+    };
+    let mut removed = 0usize;
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.ends_with(".blob") || blobs.iter().any(|(used, _)| *used == name) {
+            continue;
+        }
+        match tokio::fs::remove_file(entry.path()).await {
+            Ok(()) => removed += 1,
+            Err(e) => warn!(
+                path = %entry.path().display(),
+                error = %e,
+                "save_cache: failed to remove orphaned sidecar"
+            ),
+        }
+    }
+    if removed != 0 {
+        debug!(dir = %dir.display(), removed, "save_cache: removed orphaned sidecars");
+    }
+}
+
+/// Read the sidecar records listed in `index` back into `sections`, checking each against
+/// its recorded length and hash.
+async fn resolve_sidecars(
+    path: &Path,
+    sections: &mut [Section],
+    index: SidecarIndex,
+) -> PicanteResult<()> {
+    let dir = Path::new(&index.dir);
+    let dir = if dir.is_absolute() {
+        dir.to_path_buf()
+    } else {
+        path.parent().unwrap_or(Path::new("")).join(dir)
+    };
+
+    for r in index.records {
+        let file = dir.join(sidecar_file_name(r.hash, r.len));
+        let bytes = tokio::fs::read(&file).await.map_err(|e| {
+            Arc::new(PicanteError::Cache {
+                message: format!("read sidecar {}: {e}", file.display()),
+            })
+        })?;
+        if bytes.len() as u64 != r.len || content_hash(&bytes) != r.hash {
+            return Err(Arc::new(PicanteError::Cache {
+                message: format!("sidecar {} does not match its hash", file.display()),
+            }));
+        }
+        let slot = sections
+            .get_mut(r.section as usize)
+            .and_then(|s| s.records.get_mut(r.record as usize))
+            .ok_or_else(|| {
+                Arc::new(PicanteError::Cache {
+                    message: format!(
+                        "sidecar {} refers to missing record {}/{}",
+                        file.display(),
+                        r.section,
+                        r.record
+                    ),
+                })
+            })?;
+        *slot = bytes;
+    }
+    Ok(())
+}
+
+fn record_count(cache: &CacheFile) -> usize {
+    cache.sections.iter().map(|s| s.records.len()).sum()
+}
+
 fn shrink_cache_to_fit(cache: &mut CacheFile, max_bytes: usize) -> PicanteResult<()> {
     // Encode once to learn the real non-record overhead.
     let bytes = encode_cache_file(cache)?;
@@ -658,7 +2134,7 @@ pub async fn replay_wal(
 
     // Update runtime revision to the latest from the WAL
     if max_revision > base_revision {
-        runtime.set_current_revision(Revision(max_revision));
+        runtime.set_current_revision(Revision(max_revision))?;
         debug!("Set runtime revision to {max_revision} from WAL");
     }
 
@@ -756,3 +2232,66 @@ pub async fn compact_wal(
     info!("WAL compaction complete at revision {new_revision}");
     Ok(new_revision)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walk_follows_the_cache_file_layout() {
+        let section = |kind_id, kind_name: &str, section_type, logic_version, records| Section {
+            kind_id,
+            kind_name: kind_name.to_string(),
+            section_type,
+            logic_version,
+            records,
+        };
+        let cache = CacheFile {
+            format_version: FORMAT_VERSION,
+            current_revision: 300,
+            sections: vec![
+                section(
+                    1,
+                    "Text",
+                    SectionType::Input,
+                    0,
+                    vec![vec![1, 2, 3], vec![]],
+                ),
+                section(200, "Parse", SectionType::Derived, 7, vec![vec![9; 200]]),
+                section(3, "Names", SectionType::Interned, 0, vec![]),
+            ],
+            metadata: vec![("tool".to_string(), "picante".to_string())],
+            sidecars: None,
+            truncated: true,
+        };
+
+        // If `CacheFile` or `Section` gain a field, the walk must be updated with them or
+        // every file silently takes the full decode.
+        let bytes = encode_cache_file(&cache).unwrap();
+        let walked = walk_cache_file(&bytes)
+            .unwrap()
+            .expect("a current-version file without sidecars is walked");
+        assert_eq!(walked.current_revision, cache.current_revision);
+        assert_eq!(walked.sections.len(), cache.sections.len());
+        for (found, section) in walked.sections.into_iter().zip(&cache.sections) {
+            assert_eq!(found.kind_id, section.kind_id);
+            assert_eq!(found.kind_name, section.kind_name);
+            assert_eq!(found.section_type, section.section_type);
+            assert_eq!(found.logic_version, section.logic_version);
+            assert_eq!(
+                found.records.into_records().collect::<Vec<_>>(),
+                section.records
+            );
+        }
+
+        let with_sidecars = CacheFile {
+            sidecars: Some(SidecarIndex {
+                dir: "cache.blobs".to_string(),
+                records: Vec::new(),
+            }),
+            ..cache
+        };
+        let bytes = encode_cache_file(&with_sidecars).unwrap();
+        assert!(walk_cache_file(&bytes).unwrap().is_none());
+    }
+}
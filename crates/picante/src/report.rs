@@ -0,0 +1,225 @@
+//! Machine-readable cache reports.
+//!
+//! [`Runtime::cache_report`](crate::runtime::Runtime::cache_report) gathers the per-ingredient
+//! access tallies, the dependency graph and the last cache save/load into one [`CacheReport`].
+//! It derives [`Facet`], so it can be serialized with any facet format (e.g. JSON) and kept
+//! next to build output as session telemetry.
+
+use crate::frame::CallOutcome;
+use crate::key::QueryKindId;
+use dashmap::DashMap;
+use facet::Facet;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Everything picante knows about one session's cache usage.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Facet)]
+pub struct CacheReport {
+    /// Revision of the runtime when the report was taken.
+    pub revision: u64,
+    /// One entry per ingredient that was accessed, saved or loaded, sorted by kind id.
+    pub ingredients: Vec<IngredientReport>,
+    /// Cache file activity.
+    pub persistence: PersistenceReport,
+}
+
+/// Per-ingredient counters.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Facet)]
+pub struct IngredientReport {
+    /// Stable kind id.
+    pub kind: u32,
+    /// Debug name of the ingredient.
+    pub kind_name: String,
+    /// Derived cells with a recorded dependency set (computed, adopted or loaded).
+    pub cells: u64,
+    /// Accesses answered from a cell already verified at the current revision.
+    pub hits: u64,
+    /// Accesses answered by revalidating dependencies (or adopting another snapshot's result)
+    /// instead of recomputing.
+    pub revalidations: u64,
+    /// Times the compute function actually ran.
+    pub recomputes: u64,
+    /// Records written by the last cache save.
+    pub records_saved: u64,
+    /// Records read by the last cache load.
+    pub records_loaded: u64,
+}
+
+/// Cache file activity.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Facet)]
+pub struct PersistenceReport {
+    /// Number of successful cache saves.
+    pub saves: u64,
+    /// Size of the last saved cache file, in bytes.
+    pub bytes_saved: u64,
+    /// Duration of the last save, in microseconds.
+    pub last_save_micros: u64,
+    /// Number of successful cache loads.
+    pub loads: u64,
+    /// Size of the last loaded cache file, in bytes.
+    pub bytes_loaded: u64,
+    /// Duration of the last load, in microseconds.
+    pub last_load_micros: u64,
+}
+
+/// How a derived query access was answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AccessOutcome {
+    Hit,
+    Revalidated,
+    Recomputed,
+}
+
+impl AccessOutcome {
+    /// The name reported in the `outcome` field of query spans.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            AccessOutcome::Hit => "hit",
+            AccessOutcome::Revalidated => "validated",
+            AccessOutcome::Recomputed => "recomputed",
+        }
+    }
+
+    /// The outcome recorded in call traces (see [`crate::frame::enable_call_trace`]).
+    pub(crate) fn call_outcome(self) -> CallOutcome {
+        match self {
+            AccessOutcome::Hit => CallOutcome::Hit,
+            AccessOutcome::Revalidated => CallOutcome::Validated,
+            AccessOutcome::Recomputed => CallOutcome::Recomputed,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct KindTally {
+    kind_name: &'static str,
+    hits: AtomicU64,
+    revalidations: AtomicU64,
+    recomputes: AtomicU64,
+    records_saved: AtomicU64,
+    records_loaded: AtomicU64,
+}
+
+impl KindTally {
+    fn new(kind_name: &'static str) -> Self {
+        Self {
+            kind_name,
+            hits: AtomicU64::new(0),
+            revalidations: AtomicU64::new(0),
+            recomputes: AtomicU64::new(0),
+            records_saved: AtomicU64::new(0),
+            records_loaded: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Live counters behind [`CacheReport`], owned by the runtime.
+#[derive(Debug, Default)]
+pub(crate) struct CacheTallies {
+    by_kind: DashMap<QueryKindId, KindTally>,
+    persistence: Mutex<PersistenceReport>,
+}
+
+impl CacheTallies {
+    fn with_kind<R>(
+        &self,
+        kind: QueryKindId,
+        kind_name: &'static str,
+        f: impl FnOnce(&KindTally) -> R,
+    ) -> R {
+        if let Some(tally) = self.by_kind.get(&kind) {
+            return f(&tally);
+        }
+        let tally = self
+            .by_kind
+            .entry(kind)
+            .or_insert_with(|| KindTally::new(kind_name));
+        f(&tally)
+    }
+
+    pub(crate) fn record_access(
+        &self,
+        kind: QueryKindId,
+        kind_name: &'static str,
+        outcome: AccessOutcome,
+    ) {
+        self.with_kind(kind, kind_name, |t| {
+            let counter = match outcome {
+                AccessOutcome::Hit => &t.hits,
+                AccessOutcome::Revalidated => &t.revalidations,
+                AccessOutcome::Recomputed => &t.recomputes,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    pub(crate) fn record_section_saved(
+        &self,
+        kind: QueryKindId,
+        kind_name: &'static str,
+        records: usize,
+    ) {
+        self.with_kind(kind, kind_name, |t| {
+            t.records_saved.store(records as u64, Ordering::Relaxed)
+        });
+    }
+
+    pub(crate) fn record_section_loaded(
+        &self,
+        kind: QueryKindId,
+        kind_name: &'static str,
+        records: usize,
+    ) {
+        self.with_kind(kind, kind_name, |t| {
+            t.records_loaded.store(records as u64, Ordering::Relaxed)
+        });
+    }
+
+    pub(crate) fn record_save(&self, bytes: usize, elapsed: Duration) {
+        let mut p = self.persistence.lock();
+        p.saves += 1;
+        p.bytes_saved = bytes as u64;
+        p.last_save_micros = micros(elapsed);
+    }
+
+    pub(crate) fn record_load(&self, bytes: usize, elapsed: Duration) {
+        let mut p = self.persistence.lock();
+        p.loads += 1;
+        p.bytes_loaded = bytes as u64;
+        p.last_load_micros = micros(elapsed);
+    }
+
+    /// Assemble a report, given the number of cells per kind from the dependency graph.
+    pub(crate) fn report(&self, revision: u64, cells: HashMap<QueryKindId, u64>) -> CacheReport {
+        let mut ingredients: Vec<IngredientReport> = self
+            .by_kind
+            .iter()
+            .map(|entry| {
+                let t = entry.value();
+                IngredientReport {
+                    kind: entry.key().as_u32(),
+                    kind_name: t.kind_name.to_string(),
+                    cells: cells.get(entry.key()).copied().unwrap_or(0),
+                    hits: t.hits.load(Ordering::Relaxed),
+                    revalidations: t.revalidations.load(Ordering::Relaxed),
+                    recomputes: t.recomputes.load(Ordering::Relaxed),
+                    records_saved: t.records_saved.load(Ordering::Relaxed),
+                    records_loaded: t.records_loaded.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        ingredients.sort_by_key(|i| i.kind);
+
+        CacheReport {
+            revision,
+            ingredients,
+            persistence: self.persistence.lock().clone(),
+        }
+    }
+}
+
+fn micros(d: Duration) -> u64 {
+    u64::try_from(d.as_micros()).unwrap_or(u64::MAX)
+}
@@ -1,7 +1,90 @@
 //! Revision counters used for global invalidation.
 
 use facet::Facet;
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// A monotonically increasing revision counter.
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Facet)]
 pub struct Revision(pub u64);
+
+/// How rarely an input changes.
+///
+/// The runtime remembers the latest revision at which an input of each durability changed,
+/// so a derived query that only depends on durable inputs can be revalidated without
+/// walking its dependencies while only more volatile inputs are changing (see
+/// [`InputIngredient::with_durability`](crate::ingredient::InputIngredient::with_durability)).
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+pub enum Durability {
+    /// Changes all the time, e.g. the files being edited.
+    #[default]
+    Low,
+    /// Changes now and then, e.g. configuration.
+    Medium,
+    /// Almost never changes, e.g. facts about the standard library.
+    High,
+}
+
+impl Durability {
+    /// Every level, least durable first.
+    pub(crate) const ALL: [Durability; 3] = [Durability::Low, Durability::Medium, Durability::High];
+
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Where a [`Runtime`](crate::runtime::Runtime) gets its revisions from.
+///
+/// The default, [`LocalRevisionSource`], is an in-process counter. A distributed deployment
+/// can plug in an external monotonic source instead (a Raft log index, a Lamport clock, ...)
+/// so that several nodes agree on revision ordering for a shared cache.
+///
+/// Implementations must be monotonic: [`next`](Self::next) returns a revision strictly
+/// greater than any revision previously returned by `current` or `next`.
+pub trait RevisionSource: Send + Sync + std::fmt::Debug {
+    /// The latest revision.
+    fn current(&self) -> Revision;
+
+    /// Allocate and return a new revision, strictly greater than the current one.
+    fn next(&self) -> Revision;
+
+    /// Move the source forward to `revision` (cache loads, snapshots, WAL replay).
+    ///
+    /// Must fail with the current revision, leaving the source untouched, if `revision` is
+    /// lower than it. Sources that can't be moved by the caller at all (e.g. a log index
+    /// owned by a consensus layer) should fail whenever `revision` differs from the current
+    /// one.
+    fn advance_to(&self, revision: Revision) -> Result<(), Revision>;
+}
+
+/// The default [`RevisionSource`]: an atomic counter starting at revision 0.
+#[derive(Debug, Default)]
+pub struct LocalRevisionSource {
+    current: AtomicU64,
+}
+
+impl LocalRevisionSource {
+    /// A counter starting at revision 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RevisionSource for LocalRevisionSource {
+    fn current(&self) -> Revision {
+        Revision(self.current.load(Ordering::Acquire))
+    }
+
+    fn next(&self) -> Revision {
+        Revision(self.current.fetch_add(1, Ordering::AcqRel) + 1)
+    }
+
+    fn advance_to(&self, revision: Revision) -> Result<(), Revision> {
+        self.current
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                (revision.0 >= current).then_some(revision.0)
+            })
+            .map(|_| ())
+            .map_err(Revision)
+    }
+}
@@ -1,12 +1,21 @@
 //! Shared runtime state for a Picante database (revisions, notifications, etc.).
 
+use crate::chrome_trace::{ChromeTrace, ComputeSpan};
+use crate::clock::{Clock, SystemClock};
+use crate::error::{PicanteError, PicanteResult};
 use crate::key::{Dep, DynKey, Key, QueryKindId};
-use crate::revision::Revision;
+use crate::report::{CacheReport, CacheTallies};
+use crate::revision::{Durability, LocalRevisionSource, Revision, RevisionSource};
 use dashmap::{DashMap, DashSet};
-use std::collections::{HashSet, VecDeque};
+use futures::Stream;
+use parking_lot::Mutex;
+use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, Write};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
-use tokio::sync::{broadcast, watch};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, broadcast, watch};
 
 /// Global counter for assigning unique runtime IDs.
 static RUNTIME_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
@@ -27,16 +36,94 @@ impl RuntimeId {
     }
 }
 
+/// A revision pinned for consistent reads across a batch of queries (see
+/// [`Runtime::snapshot`]).
+///
+/// Unlike a database snapshot, this copies nothing: reads through
+/// `DerivedIngredient::get_at` are answered from cells whose value still stands at the
+/// pinned revision, and fail once that value has been replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    revision: Revision,
+}
+
+impl Snapshot {
+    /// The pinned revision.
+    pub fn revision(&self) -> Revision {
+        self.revision
+    }
+}
+
+/// A point to return to with [`Runtime::rollback`], taken by [`Runtime::checkpoint`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    id: u64,
+    revision: Revision,
+}
+
+impl Checkpoint {
+    /// The revision current when the checkpoint was taken.
+    pub fn revision(&self) -> Revision {
+        self.revision
+    }
+}
+
+/// Puts an input ingredient back the way it was when a checkpoint was taken, as a change at
+/// the given revision.
+pub(crate) type Undo = Box<dyn FnOnce(&Runtime, Revision) + Send>;
+
+/// A checkpoint not yet rolled back or released, with the undo of every input ingredient
+/// written since it was taken.
+struct OpenCheckpoint {
+    id: u64,
+    undo: HashMap<QueryKindId, Undo>,
+}
+
+impl std::fmt::Debug for OpenCheckpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpenCheckpoint")
+            .field("id", &self.id)
+            .field("kinds", &self.undo.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
 /// Shared runtime state for a Picante database: primarily the current revision.
 #[derive(Debug)]
 pub struct Runtime {
     /// Unique identifier for this runtime family (shared with snapshots).
     id: RuntimeId,
-    current_revision: AtomicU64,
+    revision_source: Arc<dyn RevisionSource>,
     revision_tx: watch::Sender<Revision>,
+    /// Latest revision at which an input at least as durable as each [`Durability`] changed.
+    /// Locked across bumps, so a revision is never visible before its entry is.
+    durability_changed: Mutex<[Revision; 3]>,
     events_tx: broadcast::Sender<RuntimeEvent>,
+    event_capacity: usize,
     deps_by_query: DashMap<DynKey, Arc<[Dep]>>,
+    /// Debug names of the ingredient kinds (see [`Runtime::register_kind`]).
+    kind_names: DashMap<QueryKindId, &'static str>,
     reverse_deps: DashMap<DynKey, DashSet<DynKey>>,
+    eager_outputs: DashSet<DynKey>,
+    eager_pending: Mutex<HashSet<DynKey>>,
+    eager_scheduled: Notify,
+    tallies: CacheTallies,
+    shutdown_tx: watch::Sender<bool>,
+    running: DashMap<u64, RunningQuery>,
+    next_running_id: AtomicU64,
+    /// Notified when the last running query finishes.
+    idle: Notify,
+    clock: Arc<dyn Clock>,
+    /// `usize::MAX` when unlimited.
+    max_query_depth: AtomicUsize,
+    max_observed_depth: AtomicUsize,
+    event_batch: Mutex<EventBatchState>,
+    /// Mirrors `chrome_trace.is_some()`, so finishing computes skip the lock when off.
+    chrome_tracing: AtomicBool,
+    chrome_trace: Mutex<Option<ChromeTrace>>,
+    /// Open checkpoints, oldest first.
+    checkpoints: Mutex<Vec<OpenCheckpoint>>,
+    next_checkpoint_id: AtomicU64,
 }
 
 impl Runtime {
@@ -52,18 +139,83 @@ impl Runtime {
     /// `RuntimeId` as the parent. This allows in-flight query deduplication to work
     /// across the parent database and all its snapshots.
     pub fn new_for_snapshot(parent_id: RuntimeId) -> Self {
-        let (revision_tx, _) = watch::channel(Revision(0));
-        let (events_tx, _) = broadcast::channel(1024);
+        Self::from_parts(parent_id, Arc::new(LocalRevisionSource::new()))
+    }
+
+    /// Create a new runtime whose revisions come from `source` instead of a local counter.
+    ///
+    /// The runtime starts at `source.current()`. See [`RevisionSource`] for the contract
+    /// a source must uphold.
+    pub fn with_revision_source(source: Arc<dyn RevisionSource>) -> Self {
+        Self::from_parts(RuntimeId::new_unique(), source)
+    }
+
+    /// Measure time with `clock` instead of the system clock (see [`crate::clock`]).
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Buffer up to `capacity` events per subscriber instead of the default 1024.
+    ///
+    /// A subscriber that falls further behind than this misses the oldest events (see
+    /// [`RuntimeEvent::Lagged`]). Call this before subscribing: it replaces the event channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn with_event_capacity(mut self, capacity: usize) -> Self {
+        let (events_tx, _) = broadcast::channel(capacity);
+        self.events_tx = events_tx;
+        self.event_capacity = capacity;
+        self
+    }
+
+    /// How many events the event channel buffers per subscriber.
+    pub fn event_capacity(&self) -> usize {
+        self.event_capacity
+    }
+
+    fn from_parts(id: RuntimeId, revision_source: Arc<dyn RevisionSource>) -> Self {
+        let (revision_tx, _) = watch::channel(revision_source.current());
+        let (events_tx, _) = broadcast::channel(DEFAULT_EVENT_CAPACITY);
+        let (shutdown_tx, _) = watch::channel(false);
         Self {
-            id: parent_id,
-            current_revision: AtomicU64::new(0),
+            id,
+            revision_source,
             revision_tx,
+            durability_changed: Mutex::new([Revision(0); 3]),
             events_tx,
+            event_capacity: DEFAULT_EVENT_CAPACITY,
             deps_by_query: DashMap::new(),
+            kind_names: DashMap::new(),
             reverse_deps: DashMap::new(),
+            eager_outputs: DashSet::new(),
+            eager_pending: Mutex::new(HashSet::new()),
+            eager_scheduled: Notify::new(),
+            tallies: CacheTallies::default(),
+            shutdown_tx,
+            running: DashMap::new(),
+            next_running_id: AtomicU64::new(0),
+            idle: Notify::new(),
+            clock: Arc::new(SystemClock),
+            max_query_depth: AtomicUsize::new(usize::MAX),
+            max_observed_depth: AtomicUsize::new(0),
+            event_batch: Mutex::new(EventBatchState::default()),
+            chrome_tracing: AtomicBool::new(false),
+            chrome_trace: Mutex::new(None),
+            checkpoints: Mutex::new(Vec::new()),
+            next_checkpoint_id: AtomicU64::new(0),
         }
     }
 
+    /// The clock used for every time measurement made by this runtime.
+    ///
+    /// Snapshots should share it: `Runtime::new_for_snapshot(id).with_clock(clock.clone())`.
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
     /// Get this runtime's unique identifier.
     ///
     /// This ID is shared between a database and all snapshots created from it.
@@ -71,9 +223,163 @@ impl Runtime {
         self.id
     }
 
+    /// Record `name` as the debug name of ingredient `kind`, replacing any earlier name.
+    ///
+    /// Databases generated by `#[picante::db]` register every ingredient they create; code
+    /// that assembles a database by hand can call this for its own ingredients.
+    pub fn register_kind(&self, kind: QueryKindId, name: &'static str) {
+        self.kind_names.insert(kind, name);
+    }
+
+    /// The debug name registered for `kind`, if any.
+    pub fn kind_name(&self, kind: QueryKindId) -> Option<&'static str> {
+        self.kind_names.get(&kind).map(|name| *name)
+    }
+
+    /// `kind` for messages: `` `Name` (kind 3) `` if it has a registered name, `kind 3`
+    /// otherwise.
+    ///
+    /// Events and errors only carry kind ids; use this to label them in logs.
+    pub fn describe_kind(&self, kind: QueryKindId) -> String {
+        match self.kind_name(kind) {
+            Some(name) => format!("`{name}` (kind {})", kind.0),
+            None => format!("kind {}", kind.0),
+        }
+    }
+
+    /// Every registered kind name.
+    pub(crate) fn kind_names(&self) -> HashMap<QueryKindId, &'static str> {
+        self.kind_names
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect()
+    }
+
+    /// `event` for logs, with its ingredients labelled as by [`Self::describe_kind`].
+    ///
+    /// The `Debug` output of [`RuntimeEvent`] only has kind ids, since events don't carry the
+    /// runtime they came from.
+    pub fn display_event(&self, event: &RuntimeEvent) -> String {
+        let query = |kind: &QueryKindId, key_hash: &u64| {
+            format!("{} key {key_hash:016x}", self.describe_kind(*kind))
+        };
+        match event {
+            RuntimeEvent::RevisionBumped { revision } => {
+                format!("revision bumped to {}", revision.0)
+            }
+            RuntimeEvent::RevisionSet { revision } => format!("revision set to {}", revision.0),
+            RuntimeEvent::InputSet {
+                revision,
+                kind,
+                key_hash,
+                ..
+            } => format!("input set at {}: {}", revision.0, query(kind, key_hash)),
+            RuntimeEvent::InputRemoved {
+                revision,
+                kind,
+                key_hash,
+                ..
+            } => format!("input removed at {}: {}", revision.0, query(kind, key_hash)),
+            RuntimeEvent::QueryInvalidated {
+                revision,
+                kind,
+                key_hash,
+                by_kind,
+                by_key_hash,
+                ..
+            } => format!(
+                "query invalidated at {}: {}, by {}",
+                revision.0,
+                query(kind, key_hash),
+                query(by_kind, by_key_hash)
+            ),
+            RuntimeEvent::QueryChanged {
+                revision,
+                kind,
+                key_hash,
+                ..
+            } => format!("query changed at {}: {}", revision.0, query(kind, key_hash)),
+            RuntimeEvent::QueryRecomputed {
+                revision,
+                kind,
+                key_hash,
+                ..
+            } => format!(
+                "query recomputed at {}: {}",
+                revision.0,
+                query(kind, key_hash)
+            ),
+            RuntimeEvent::ShuttingDown { revision } => {
+                format!("shutting down at {}", revision.0)
+            }
+            RuntimeEvent::EagerOutputRecomputed {
+                revision,
+                kind,
+                key_hash,
+                changed_at,
+                ..
+            } => format!(
+                "eager output recomputed at {}: {} (changed at {})",
+                revision.0,
+                query(kind, key_hash),
+                changed_at.0
+            ),
+            RuntimeEvent::BatchCommitted {
+                from,
+                to,
+                changed_kinds,
+            } => {
+                let kinds: Vec<String> = changed_kinds
+                    .iter()
+                    .map(|kind| self.describe_kind(*kind))
+                    .collect();
+                format!(
+                    "batch committed from {} to {}, changing {}",
+                    from.0,
+                    to.0,
+                    kinds.join(", ")
+                )
+            }
+            RuntimeEvent::Lagged { skipped } => format!("lagged, missed {skipped} events"),
+        }
+    }
+
+    /// `err` for logs, followed by the registered names of the ingredients it mentions.
+    pub fn display_error(&self, err: &PicanteError) -> String {
+        let names: Vec<String> = err
+            .kinds()
+            .into_iter()
+            .filter_map(|kind| Some(format!("kind {} is `{}`", kind.0, self.kind_name(kind)?)))
+            .collect();
+        if names.is_empty() {
+            err.to_string()
+        } else {
+            format!("{err} ({})", names.join(", "))
+        }
+    }
+
     /// Read the current revision.
+    ///
+    /// This asks the [`RevisionSource`], so it also reflects revisions allocated elsewhere
+    /// (e.g. by another node sharing the source). Such external advances don't emit
+    /// [`RuntimeEvent::RevisionBumped`] on this runtime.
     pub fn current_revision(&self) -> Revision {
-        Revision(self.current_revision.load(Ordering::Acquire))
+        self.revision_source.current()
+    }
+
+    /// Pin the current revision for reads with `DerivedIngredient::get_at`.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            revision: self.current_revision(),
+        }
+    }
+
+    /// The latest revision at which an input of `durability` or higher changed.
+    ///
+    /// Revisions allocated outside this runtime (see [`Self::current_revision`]) are not
+    /// accounted for: durability tracks this runtime's own inputs.
+    pub fn last_changed(&self, durability: Durability) -> Revision {
+        self.durability_changed.lock()[durability.index()]
     }
 
     /// Subscribe to revision changes.
@@ -81,15 +387,83 @@ impl Runtime {
         self.revision_tx.subscribe()
     }
 
+    /// A future that resolves once the current revision is at least `target` (immediately
+    /// if it already is).
+    ///
+    /// Built on the revision watch, so it sees what [`Self::subscribe_revisions`] sees:
+    /// revisions bumped inside a [`Self::batch`] count once the batch commits. Like
+    /// [`Self::shutdown_signal`], it doesn't borrow the runtime.
+    pub fn wait_for_revision(
+        &self,
+        target: Revision,
+    ) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let mut rx = self.revision_tx.subscribe();
+        async move {
+            // The sender lives as long as the runtime; if it's gone, nothing will advance.
+            let _ = rx.wait_for(|revision| *revision >= target).await;
+        }
+    }
+
     /// Subscribe to runtime events.
     pub fn subscribe_events(&self) -> broadcast::Receiver<RuntimeEvent> {
         self.events_tx.subscribe()
     }
 
+    /// Subscribe to runtime events, as a stream.
+    ///
+    /// Unlike [`Self::subscribe_events`], falling behind isn't an error: the stream yields
+    /// a [`RuntimeEvent::Lagged`] in place of the events it missed and carries on. The stream
+    /// ends when the runtime is dropped.
+    pub fn subscribe_event_stream(&self) -> impl Stream<Item = RuntimeEvent> + Send + use<> {
+        event_stream(self.events_tx.subscribe(), |_| true)
+    }
+
+    /// Subscribe to the events about ingredient `kind`, as a stream.
+    ///
+    /// Events that aren't about any one ingredient ([`RevisionBumped`], [`RevisionSet`],
+    /// [`ShuttingDown`], [`Lagged`]) are always passed through. `QueryInvalidated` events
+    /// are matched on the invalidated query, not the input that caused it. Filtering happens
+    /// on the subscriber side of the channel, so a lag may count events for other kinds.
+    /// Otherwise behaves like [`Self::subscribe_event_stream`].
+    ///
+    /// [`RevisionBumped`]: RuntimeEvent::RevisionBumped
+    /// [`RevisionSet`]: RuntimeEvent::RevisionSet
+    /// [`ShuttingDown`]: RuntimeEvent::ShuttingDown
+    /// [`Lagged`]: RuntimeEvent::Lagged
+    pub fn subscribe_events_for(
+        &self,
+        kind: QueryKindId,
+    ) -> impl Stream<Item = RuntimeEvent> + Send + use<> {
+        event_stream(self.events_tx.subscribe(), move |event| {
+            event.kind().is_none_or(|k| k == kind)
+        })
+    }
+
     /// Bump the current revision and return the new value.
+    ///
+    /// The new revision is allocated by the runtime's [`RevisionSource`]. Counts as a change
+    /// to inputs of every durability; input ingredients use [`Self::bump_revision_for`].
     pub fn bump_revision(&self) -> Revision {
-        let next = self.current_revision.fetch_add(1, Ordering::AcqRel) + 1;
-        let rev = Revision(next);
+        self.bump_revision_for(Durability::High)
+    }
+
+    /// Bump the current revision for a change to inputs of `durability`.
+    ///
+    /// Derived queries whose dependencies are all more durable than that are revalidated at
+    /// the new revision without walking their dependencies.
+    pub fn bump_revision_for(&self, durability: Durability) -> Revision {
+        let rev = {
+            let mut changed = self.durability_changed.lock();
+            let rev = self.revision_source.next();
+            for slot in &mut changed[..=durability.index()] {
+                *slot = rev;
+            }
+            rev
+        };
+        // Inside `Runtime::batch`, the bump is announced when the batch commits.
+        if self.event_batch.lock().held_from.is_some() {
+            return rev;
+        }
         self.revision_tx.send_replace(rev);
         let _ = self
             .events_tx
@@ -97,43 +471,270 @@ impl Runtime {
         rev
     }
 
-    /// Set the current revision (intended for cache loading).
-    pub fn set_current_revision(&self, revision: Revision) {
-        self.current_revision.store(revision.0, Ordering::Release);
+    /// Set the current revision.
+    ///
+    /// Revisions of a live runtime never go backwards: setting a revision lower than the
+    /// current one fails with [`PicanteError::RevisionRegression`] and leaves the runtime
+    /// untouched. The request is forwarded to [`RevisionSource::advance_to`]; a source that
+    /// can't be moved from outside reports a regression for any revision but its current one.
+    pub fn set_current_revision(&self, revision: Revision) -> PicanteResult<()> {
+        {
+            let mut changed = self.durability_changed.lock();
+            let previous = self.revision_source.current();
+            self.revision_source
+                .advance_to(revision)
+                .map_err(|current| {
+                    Arc::new(PicanteError::RevisionRegression {
+                        current,
+                        requested: revision,
+                    })
+                })?;
+            // Nothing is known about what changed on the way: treat it as everything.
+            if revision != previous {
+                *changed = [revision; 3];
+            }
+        }
         self.revision_tx.send_replace(revision);
         let _ = self.events_tx.send(RuntimeEvent::RevisionSet { revision });
+        Ok(())
+    }
+
+    /// Restore the revision recorded in persisted state (cache loads, snapshots).
+    ///
+    /// Meant for a fresh or just-cleared runtime. If the runtime has already advanced past
+    /// `revision` (e.g. a stale cache is loaded into a running database), this fails with
+    /// [`PicanteError::RevisionRegression`] instead of rolling the revision back; use
+    /// [`Self::check_restore_revision`] to find out before clearing any state.
+    pub fn restore_revision(&self, revision: Revision) -> PicanteResult<()> {
+        self.set_current_revision(revision)
+    }
+
+    /// Check whether [`Self::restore_revision`] would accept `revision`, without changing
+    /// anything.
+    pub fn check_restore_revision(&self, revision: Revision) -> PicanteResult<()> {
+        let current = self.current_revision();
+        if revision < current {
+            return Err(Arc::new(PicanteError::RevisionRegression {
+                current,
+                requested: revision,
+            }));
+        }
+        Ok(())
     }
 
     /// Emit an input change event (for live reload / diagnostics).
+    ///
+    /// Held back while an [`EventBatch`] is open.
     pub fn notify_input_set(&self, revision: Revision, kind: QueryKindId, key: Key) {
-        let source = DynKey {
-            kind,
-            key: key.clone(),
-        };
-        let _ = self.events_tx.send(RuntimeEvent::InputSet {
+        self.notify_input_change(PendingInputChange {
             revision,
-            kind,
-            key_hash: key.hash(),
-            key,
+            source: DynKey { kind, key },
+            removed: false,
         });
-        self.propagate_invalidation(revision, &source);
     }
 
     /// Emit an input removal event (for live reload / diagnostics).
+    ///
+    /// Held back while an [`EventBatch`] is open.
     pub fn notify_input_removed(&self, revision: Revision, kind: QueryKindId, key: Key) {
-        let source = DynKey {
-            kind,
-            key: key.clone(),
-        };
-        let _ = self.events_tx.send(RuntimeEvent::InputRemoved {
+        self.notify_input_change(PendingInputChange {
             revision,
-            kind,
-            key_hash: key.hash(),
-            key,
+            source: DynKey { kind, key },
+            removed: true,
         });
+    }
+
+    fn notify_input_change(&self, change: PendingInputChange) {
+        {
+            let mut batch = self.event_batch.lock();
+            if batch.open > 0 {
+                batch.pending.push(change);
+                return;
+            }
+        }
+        self.emit_input_change(change);
+    }
+
+    fn emit_input_change(&self, change: PendingInputChange) {
+        let PendingInputChange {
+            revision,
+            source,
+            removed,
+        } = change;
+        let kind = source.kind;
+        let key = source.key.clone();
+        let key_hash = key.hash();
+        let event = if removed {
+            RuntimeEvent::InputRemoved {
+                revision,
+                kind,
+                key_hash,
+                key,
+            }
+        } else {
+            RuntimeEvent::InputSet {
+                revision,
+                kind,
+                key_hash,
+                key,
+            }
+        };
+        let _ = self.events_tx.send(event);
         self.propagate_invalidation(revision, &source);
     }
 
+    /// Hold back input change notifications until the returned guard is dropped.
+    ///
+    /// While any batch is open, `InputSet` / `InputRemoved` events, and the invalidation
+    /// events and eager scheduling they trigger, are buffered instead of sent; the writes
+    /// themselves (and `RevisionBumped`) take effect immediately. When the last open batch
+    /// is dropped the buffered changes are emitted in the order they happened. Batches
+    /// nest and may be opened from several tasks at once: they share one buffer, flushed by
+    /// whichever guard closes last.
+    pub fn batch_events(&self) -> EventBatch<'_> {
+        self.event_batch.lock().open += 1;
+        EventBatch { runtime: self }
+    }
+
+    /// Run `f` with input notifications held back, and announce its revision bumps as one.
+    ///
+    /// Works like [`Self::batch_events`], and additionally holds back the `RevisionBumped`
+    /// events and revision watch updates of the writes made meanwhile. When the last open
+    /// batch closes, the held-back input events are emitted, then the revision watch moves
+    /// to the latest revision and a single [`RuntimeEvent::BatchCommitted`] is sent. Nothing
+    /// is announced for a batch that didn't bump the revision.
+    ///
+    /// Only the announcement is coalesced: each write inside the batch still allocates its
+    /// own revision, so `to - from` counts the bumps. Sharing one revision between writes
+    /// would let a query computed between two of them be verified at that revision and miss
+    /// the second write. To store several values of one ingredient under a single revision,
+    /// use `InputIngredient::set_many`.
+    pub fn batch<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _batch = {
+            let mut batch = self.event_batch.lock();
+            batch.open += 1;
+            if batch.held_from.is_none() {
+                batch.held_from = Some(self.current_revision());
+            }
+            EventBatch { runtime: self }
+        };
+        f()
+    }
+
+    fn close_event_batch(&self) {
+        let (pending, coalesce, held) = {
+            let mut batch = self.event_batch.lock();
+            batch.open -= 1;
+            if batch.open > 0 {
+                return;
+            }
+            // Read the latest revision under the lock: later bumps announce themselves.
+            let held = batch
+                .held_from
+                .take()
+                .map(|from| (from, self.current_revision()));
+            (
+                std::mem::take(&mut batch.pending),
+                std::mem::replace(&mut batch.coalesce, false),
+                held,
+            )
+        };
+        let mut changed_kinds: Vec<QueryKindId> = pending.iter().map(|c| c.source.kind).collect();
+        changed_kinds.sort_unstable();
+        changed_kinds.dedup();
+        let pending = if coalesce {
+            coalesce_input_changes(pending)
+        } else {
+            pending
+        };
+        for change in pending {
+            self.emit_input_change(change);
+        }
+        if let Some((from, to)) = held
+            && to > from
+        {
+            self.revision_tx.send_if_modified(|current| {
+                let advanced = *current < to;
+                if advanced {
+                    *current = to;
+                }
+                advanced
+            });
+            let _ = self.events_tx.send(RuntimeEvent::BatchCommitted {
+                from,
+                to,
+                changed_kinds,
+            });
+        }
+    }
+
+    /// Remember the current state of every input ingredient, to return to with
+    /// [`Self::rollback`].
+    ///
+    /// Meant for speculative edits: take a checkpoint, write inputs, run queries, and roll
+    /// back if the result is rejected. Nothing is copied up front; each input ingredient
+    /// keeps an O(1) copy of its contents the first time it is written after the checkpoint.
+    /// Those copies live until the checkpoint is rolled back or [released](Self::release),
+    /// so release checkpoints that are kept. Checkpoints nest.
+    pub fn checkpoint(&self) -> Checkpoint {
+        let id = self.next_checkpoint_id.fetch_add(1, Ordering::Relaxed);
+        self.checkpoints.lock().push(OpenCheckpoint {
+            id,
+            undo: HashMap::new(),
+        });
+        Checkpoint {
+            id,
+            revision: self.current_revision(),
+        }
+    }
+
+    /// Put every input ingredient written since `checkpoint` back the way it was, and
+    /// discard the checkpoints taken after it.
+    ///
+    /// Revisions never go backwards, so the rollback is itself a change: the revision is
+    /// bumped once, every key that differs from the checkpoint is written back at the new
+    /// revision (with the usual `InputSet` / `InputRemoved` events), and derived queries
+    /// that saw the speculative values recompute (or are cut off early) on their next read,
+    /// giving the old results again.
+    ///
+    /// Returns the new revision, or `None` if no input was written since the checkpoint or
+    /// the checkpoint was already discarded by rolling back an earlier one.
+    pub fn rollback(&self, checkpoint: Checkpoint) -> Option<Revision> {
+        let open = {
+            let mut checkpoints = self.checkpoints.lock();
+            let index = checkpoints
+                .iter()
+                .position(|open| open.id == checkpoint.id)?;
+            checkpoints.drain(index..).next()?
+        };
+        if open.undo.is_empty() {
+            return None;
+        }
+        let revision = self.bump_revision();
+        for undo in open.undo.into_values() {
+            undo(self, revision);
+        }
+        Some(revision)
+    }
+
+    /// Keep the changes made since `checkpoint` and drop what it remembers.
+    ///
+    /// Checkpoints taken before or after it stay open.
+    pub fn release(&self, checkpoint: Checkpoint) {
+        self.checkpoints
+            .lock()
+            .retain(|open| open.id != checkpoint.id);
+    }
+
+    /// Called by an input ingredient of `kind` before it writes: hands every open checkpoint
+    /// that has no undo for `kind` yet a fresh one from `undo`.
+    pub(crate) fn record_undo(&self, kind: QueryKindId, undo: impl Fn() -> Undo) {
+        let mut checkpoints = self.checkpoints.lock();
+        for open in checkpoints.iter_mut() {
+            open.undo.entry(kind).or_insert_with(&undo);
+        }
+    }
+
     /// Update the dependency edges for `query`.
     pub fn update_query_deps(&self, query: DynKey, deps: Arc<[Dep]>) {
         let old = self.deps_by_query.insert(query.clone(), deps.clone());
@@ -181,6 +782,16 @@ impl Runtime {
         });
     }
 
+    /// Emit a derived query recompute event (for diagnostics and incrementality tests).
+    pub fn notify_query_recomputed(&self, revision: Revision, query: DynKey) {
+        let _ = self.events_tx.send(RuntimeEvent::QueryRecomputed {
+            revision,
+            kind: query.kind,
+            key_hash: query.key.hash(),
+            key: query.key,
+        });
+    }
+
     /// Clear the in-memory dependency graph (used during cache loads).
     pub fn clear_dependency_graph(&self) {
         self.deps_by_query.clear();
@@ -212,7 +823,201 @@ impl Runtime {
             .collect()
     }
 
+    /// Check that the forward dependency index (`deps_by_query`) and the reverse index used
+    /// for invalidation describe the same edges.
+    ///
+    /// Every recorded dependency must have its reverse edge and every reverse edge must
+    /// match a recorded dependency; otherwise this fails with
+    /// [`PicanteError::DepIndexMismatch`] listing each missing edge. Meant for tests and
+    /// periodic checks in development builds, so it's only compiled with debug assertions
+    /// or the `testing` feature. Run it while no query is computing: a concurrent
+    /// [`Self::update_query_deps`] can be observed half-applied.
+    #[cfg(any(debug_assertions, feature = "testing"))]
+    pub fn debug_verify_dep_index(&self) -> PicanteResult<()> {
+        fn describe(kind: QueryKindId, key: &Key) -> String {
+            format!("(kind {}, key {:016x})", kind.0, key.hash())
+        }
+
+        let mut problems = Vec::new();
+        for entry in self.deps_by_query.iter() {
+            let query = entry.key();
+            for dep in entry.value().iter() {
+                let dep_key = DynKey {
+                    kind: dep.kind,
+                    key: dep.key.clone(),
+                };
+                let indexed = self
+                    .reverse_deps
+                    .get(&dep_key)
+                    .is_some_and(|dependents| dependents.contains(query));
+                if !indexed {
+                    problems.push(format!(
+                        "{} depends on {} but the reverse index has no such edge",
+                        describe(query.kind, &query.key),
+                        describe(dep.kind, &dep.key)
+                    ));
+                }
+            }
+        }
+        for entry in self.reverse_deps.iter() {
+            let dep = entry.key();
+            for dependent in entry.value().iter() {
+                let recorded = self.deps_by_query.get(&*dependent).is_some_and(|deps| {
+                    deps.iter()
+                        .any(|d| d.kind == dep.kind && d.key == dep.key)
+                });
+                if !recorded {
+                    problems.push(format!(
+                        "reverse index lists {} as a dependent of {} but its deps don't",
+                        describe(dependent.kind, &dependent.key),
+                        describe(dep.kind, &dep.key)
+                    ));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Arc::new(PicanteError::DepIndexMismatch { problems }))
+        }
+    }
+
+    /// Ask background tasks tied to this runtime to stop.
+    ///
+    /// Trips [`Self::shutdown_signal`] and emits [`RuntimeEvent::ShuttingDown`]. Calling it
+    /// again is a no-op. A snapshot has its own signal, independent of its parent's.
+    pub fn begin_shutdown(&self) {
+        if self.shutdown_tx.send_replace(true) {
+            return;
+        }
+        let _ = self.events_tx.send(RuntimeEvent::ShuttingDown {
+            revision: self.current_revision(),
+        });
+    }
+
+    /// Whether [`Self::begin_shutdown`] has been called.
+    pub fn is_shutting_down(&self) -> bool {
+        *self.shutdown_tx.borrow()
+    }
+
+    /// A future that resolves once [`Self::begin_shutdown`] is called (immediately if it
+    /// already was).
+    ///
+    /// It doesn't borrow the runtime, so background tasks can race it against their work,
+    /// e.g. in `tokio::select!`, and return promptly when it fires.
+    pub fn shutdown_signal(&self) -> impl std::future::Future<Output = ()> + Send + 'static {
+        let mut rx = self.shutdown_tx.subscribe();
+        async move {
+            // The sender lives as long as the runtime; if it's gone, so is everything to stop.
+            let _ = rx.wait_for(|shutting_down| *shutting_down).await;
+        }
+    }
+
+    /// Assemble a [`CacheReport`] for this runtime: per-ingredient access tallies and cell
+    /// counts, the last cache save/load, and the current revision.
+    ///
+    /// Tallies are per runtime, so a snapshot starts counting from zero.
+    pub fn cache_report(&self) -> CacheReport {
+        let mut cells = std::collections::HashMap::<QueryKindId, u64>::new();
+        for entry in self.deps_by_query.iter() {
+            *cells.entry(entry.key().kind).or_insert(0) += 1;
+        }
+        self.tallies.report(self.current_revision().0, cells)
+    }
+
+    pub(crate) fn tallies(&self) -> &CacheTallies {
+        &self.tallies
+    }
+
+    /// Mark a derived query as an eager output (push mode).
+    ///
+    /// Whenever an input that the query transitively depends on changes, the query is
+    /// scheduled for recomputation, which [`drive_eager_outputs`](crate::eager::drive_eager_outputs)
+    /// performs without anyone reading it. The query is also scheduled right away, so that its
+    /// first computation records the dependency edges used to find it later.
+    pub fn register_eager_output(&self, query: DynKey) {
+        self.eager_outputs.insert(query.clone());
+        self.schedule_eager_output(query);
+        self.eager_scheduled.notify_one();
+    }
+
+    /// Stop recomputing `query` eagerly. Returns `false` if it was not registered.
+    pub fn unregister_eager_output(&self, query: &DynKey) -> bool {
+        self.eager_pending.lock().remove(query);
+        self.eager_outputs.remove(query).is_some()
+    }
+
+    /// All registered eager outputs.
+    pub fn eager_outputs(&self) -> Vec<DynKey> {
+        self.eager_outputs.iter().map(|k| k.clone()).collect()
+    }
+
+    /// Take the eager outputs scheduled since the last call.
+    ///
+    /// Pending outputs form a set: if inputs change faster than outputs are recomputed, repeated
+    /// invalidations of the same output coalesce into a single recomputation at whatever
+    /// revision is current when it runs, and intermediate revisions are skipped.
+    pub(crate) fn take_pending_eager_outputs(&self) -> Vec<DynKey> {
+        std::mem::take(&mut *self.eager_pending.lock())
+            .into_iter()
+            .collect()
+    }
+
+    /// Wait until at least one eager output has been scheduled.
+    pub(crate) async fn eager_outputs_scheduled(&self) {
+        self.eager_scheduled.notified().await;
+    }
+
+    /// Emit an eager output recomputation event.
+    pub(crate) fn notify_eager_output_recomputed(
+        &self,
+        revision: Revision,
+        query: DynKey,
+        changed_at: Revision,
+        value: Option<Arc<dyn Any + Send + Sync>>,
+    ) {
+        let _ = self.events_tx.send(RuntimeEvent::EagerOutputRecomputed {
+            revision,
+            kind: query.kind,
+            key_hash: query.key.hash(),
+            key: query.key,
+            changed_at,
+            value,
+        });
+    }
+
+    fn schedule_eager_output(&self, query: DynKey) {
+        self.eager_pending.lock().insert(query);
+    }
+
     fn propagate_invalidation(&self, revision: Revision, source: &DynKey) {
+        let mut scheduled = false;
+
+        self.walk_dependents(source, |dependent| {
+            let _ = self.events_tx.send(RuntimeEvent::QueryInvalidated {
+                revision,
+                kind: dependent.kind,
+                key_hash: dependent.key.hash(),
+                key: dependent.key.clone(),
+                by_kind: source.kind,
+                by_key_hash: source.key.hash(),
+                by_key: source.key.clone(),
+            });
+
+            if self.eager_outputs.contains(dependent) {
+                self.schedule_eager_output(dependent.clone());
+                scheduled = true;
+            }
+        });
+
+        if scheduled {
+            self.eager_scheduled.notify_one();
+        }
+    }
+
+    /// Visit every transitive dependent of `source` once, breadth-first.
+    fn walk_dependents(&self, source: &DynKey, mut visit: impl FnMut(&DynKey)) {
         let mut queue = VecDeque::new();
         let mut seen: HashSet<DynKey> = HashSet::new();
 
@@ -229,40 +1034,357 @@ impl Runtime {
                 if !seen.insert(dependent.clone()) {
                     continue;
                 }
+                visit(&dependent);
+                queue.push_back(dependent);
+            }
+        }
+    }
 
-                let _ = self.events_tx.send(RuntimeEvent::QueryInvalidated {
-                    revision,
-                    kind: dependent.kind,
-                    key_hash: dependent.key.hash(),
-                    key: dependent.key.clone(),
-                    by_kind: source.kind,
-                    by_key_hash: source.key.hash(),
-                    by_key: source.key.clone(),
-                });
+    /// The queries that changing `dep` would invalidate, without changing anything.
+    ///
+    /// Walks the reverse-dependency index transitively from `dep`, nearest dependents first,
+    /// and returns exactly the set a write to `dep` would report through
+    /// [`RuntimeEvent::QueryInvalidated`]. Meant for estimating the cost of an edit before
+    /// making it ("this rename affects 412 queries").
+    ///
+    /// The estimate is conservative: a dependent whose recomputed value turns out equal
+    /// (early cutoff) stops the change from reaching its own dependents, which this cannot
+    /// know in advance. The index only covers queries computed or loaded in this runtime;
+    /// queries with no cached value have nothing to invalidate and are not listed.
+    pub fn predict_invalidation(&self, dep: Dep) -> Vec<DynKey> {
+        let source = DynKey {
+            kind: dep.kind,
+            key: dep.key,
+        };
+        let mut affected = Vec::new();
+        self.walk_dependents(&source, |dependent| affected.push(dependent.clone()));
+        affected
+    }
 
-                queue.push_back(dependent);
+    /// Every query currently computing on this runtime, longest-running first.
+    ///
+    /// Meant for diagnosing hangs and deadlocks: a query that stays in this list while its
+    /// `elapsed` keeps growing is the one to look at, and the entries above it in the same task
+    /// (lower `depth`) are the queries waiting on it.
+    pub fn in_flight(&self) -> Vec<InFlightQuery> {
+        let now = self.clock.now();
+        let mut queries: Vec<InFlightQuery> = self
+            .running
+            .iter()
+            .map(|entry| {
+                let q = entry.value();
+                InFlightQuery {
+                    kind: q.kind,
+                    kind_name: q.kind_name,
+                    key_hash: q.key_hash,
+                    started_at: q.started_at,
+                    elapsed: now.saturating_duration_since(q.since),
+                    depth: q.depth,
+                }
+            })
+            .collect();
+        queries.sort_by(|a, b| b.elapsed.cmp(&a.elapsed).then(a.depth.cmp(&b.depth)));
+        queries
+    }
+
+    /// Wait until no query is computing on this runtime.
+    ///
+    /// Resolves once the set reported by [`Self::in_flight`] is empty and is still empty
+    /// after yielding to the executor once, so work that a just-finished query handed off
+    /// (e.g. a spawned prefetch that starts computing right away) gets a chance to show up.
+    /// Useful in tests: set inputs, kick off background work, await `quiescent()`, then
+    /// assert on the cache.
+    ///
+    /// Only computations count: a task that will call `get` but hasn't yet is invisible,
+    /// as are scheduled eager outputs that no driver has picked up. Under continuous
+    /// background churn the runtime may never go idle and this never resolves; wrap it in
+    /// a timeout where that is possible.
+    pub async fn quiescent(&self) {
+        loop {
+            // Create this before checking, so a finish in between isn't missed.
+            let idle = self.idle.notified();
+            if !self.running.is_empty() {
+                idle.await;
+                continue;
+            }
+            tokio::task::yield_now().await;
+            if self.running.is_empty() {
+                return;
             }
         }
     }
+
+    /// Cap how many queries may be nested on one task's query stack; `None` (the default)
+    /// lifts the cap.
+    ///
+    /// A query requested at a depth beyond `limit` fails with [`PicanteError::DepthExceeded`]
+    /// instead of running, which protects a server from untrusted requests that build
+    /// arbitrarily deep query graphs. A query computed outside any other is at depth 1.
+    /// [`Self::max_observed_depth`] helps pick a limit. Snapshots created by `#[picante::db]`
+    /// inherit the limit.
+    pub fn set_max_query_depth(&self, limit: Option<usize>) {
+        self.max_query_depth
+            .store(limit.unwrap_or(usize::MAX), Ordering::Relaxed);
+    }
+
+    /// The limit set by [`Self::set_max_query_depth`], if any.
+    pub fn max_query_depth(&self) -> Option<usize> {
+        match self.max_query_depth.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// The deepest query stack any computation on this runtime has reached so far.
+    ///
+    /// A high-water mark for capacity planning: `1` means no query has read another one yet.
+    pub fn max_observed_depth(&self) -> usize {
+        self.max_observed_depth.load(Ordering::Relaxed)
+    }
+
+    /// Fail if `query`, requested at stack depth `depth`, is beyond the configured limit.
+    pub(crate) fn check_query_depth(&self, query: &DynKey, depth: usize) -> PicanteResult<()> {
+        let limit = self.max_query_depth.load(Ordering::Relaxed);
+        if depth <= limit {
+            return Ok(());
+        }
+        Err(Arc::new(PicanteError::DepthExceeded {
+            kind: query.kind,
+            key_hash: query.key.hash(),
+            limit,
+        }))
+    }
+
+    /// Register a compute for [`Self::in_flight`] until the returned guard is dropped.
+    ///
+    /// `depth` is the number of queries enclosing it; also updates
+    /// [`Self::max_observed_depth`].
+    pub(crate) fn track_running(
+        &self,
+        query: &DynKey,
+        kind_name: &'static str,
+        started_at: Revision,
+        depth: usize,
+    ) -> RunningGuard<'_> {
+        self.max_observed_depth
+            .fetch_max(depth + 1, Ordering::Relaxed);
+        let id = self.next_running_id.fetch_add(1, Ordering::Relaxed);
+        self.running.insert(
+            id,
+            RunningQuery {
+                kind: query.kind,
+                kind_name,
+                key_hash: query.key.hash(),
+                started_at,
+                since: self.clock.now(),
+                depth,
+                task: tokio::task::try_id(),
+            },
+        );
+        RunningGuard { runtime: self, id }
+    }
+
+    /// Start writing every query computation to `writer` in the Chrome Trace Event format.
+    ///
+    /// Each compute becomes one complete event (`"ph":"X"`) named after its ingredient, with
+    /// the kind, key hash, revision and stack depth under `args`. Computes on the same tokio
+    /// task share a `tid`, so nested queries show up stacked under the query that requested
+    /// them when the file is opened in `chrome://tracing` or [Perfetto](https://ui.perfetto.dev).
+    /// Events are written as computes finish; cache hits and revalidations are not recorded.
+    ///
+    /// Replaces (and finishes) any trace already in progress. Call
+    /// [`Self::stop_chrome_trace`] to close the JSON document; a trace that is never stopped
+    /// is left unterminated.
+    pub fn start_chrome_trace(&self, writer: impl Write + Send + 'static) -> io::Result<()> {
+        let trace = ChromeTrace::start(Box::new(writer), self.clock.now())?;
+        let previous = {
+            let mut slot = self.chrome_trace.lock();
+            self.chrome_tracing.store(true, Ordering::Release);
+            slot.replace(trace)
+        };
+        match previous {
+            Some(previous) => previous.finish(),
+            None => Ok(()),
+        }
+    }
+
+    /// Stop the trace started by [`Self::start_chrome_trace`], closing and flushing its output.
+    ///
+    /// Returns the first I/O error hit while writing the trace, if any. Does nothing when no
+    /// trace is running.
+    pub fn stop_chrome_trace(&self) -> io::Result<()> {
+        let trace = {
+            let mut slot = self.chrome_trace.lock();
+            self.chrome_tracing.store(false, Ordering::Release);
+            slot.take()
+        };
+        match trace {
+            Some(trace) => trace.finish(),
+            None => Ok(()),
+        }
+    }
+
+    fn record_chrome_trace(&self, query: RunningQuery) {
+        if !self.chrome_tracing.load(Ordering::Acquire) {
+            return;
+        }
+        let end = self.clock.now();
+        if let Some(trace) = self.chrome_trace.lock().as_mut() {
+            trace.record(&ComputeSpan {
+                kind: query.kind,
+                kind_name: query.kind_name,
+                key_hash: query.key_hash,
+                revision: query.started_at,
+                depth: query.depth,
+                task: query.task,
+                start: query.since,
+                end,
+            });
+        }
+    }
+}
+
+/// A query currently computing, as reported by [`Runtime::in_flight`].
+#[derive(Debug, Clone)]
+pub struct InFlightQuery {
+    /// Kind of the query.
+    pub kind: QueryKindId,
+    /// Debug name of the query's ingredient.
+    pub kind_name: &'static str,
+    /// Hash of the encoded key (see [`Key::hash`]).
+    pub key_hash: u64,
+    /// Revision the compute started at.
+    pub started_at: Revision,
+    /// Wall-clock time since the compute started.
+    pub elapsed: Duration,
+    /// Position in its task's query stack: 0 when requested from outside any query, `n + 1`
+    /// when requested by a query at depth `n`.
+    pub depth: usize,
+}
+
+#[derive(Debug)]
+struct RunningQuery {
+    kind: QueryKindId,
+    kind_name: &'static str,
+    key_hash: u64,
+    started_at: Revision,
+    since: Instant,
+    depth: usize,
+    task: Option<tokio::task::Id>,
+}
+
+/// Removes a compute from [`Runtime::in_flight`] when dropped (on completion, error, panic or
+/// cancellation alike).
+pub(crate) struct RunningGuard<'a> {
+    runtime: &'a Runtime,
+    id: u64,
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        if let Some((_, query)) = self.runtime.running.remove(&self.id) {
+            self.runtime.record_chrome_trace(query);
+        }
+        if self.runtime.running.is_empty() {
+            self.runtime.idle.notify_waiters();
+        }
+    }
 }
 
 impl Default for Runtime {
     fn default() -> Self {
-        let (revision_tx, _) = watch::channel(Revision(0));
-        let (events_tx, _) = broadcast::channel(1024);
-        Self {
-            id: RuntimeId::new_unique(),
-            current_revision: AtomicU64::new(0),
-            revision_tx,
-            events_tx,
-            deps_by_query: DashMap::new(),
-            reverse_deps: DashMap::new(),
+        Self::from_parts(RuntimeId::new_unique(), Arc::new(LocalRevisionSource::new()))
+    }
+}
+
+/// Guard returned by [`Runtime::batch_events`]; emits the held-back events when dropped.
+#[must_use = "events are only held back while the batch guard is alive"]
+pub struct EventBatch<'a> {
+    runtime: &'a Runtime,
+}
+
+impl EventBatch<'_> {
+    /// Collapse the batch's changes to one event per `(kind, key)` before emitting them.
+    ///
+    /// Each key keeps only its last change (last write wins, so a key set and then removed
+    /// yields a single `InputRemoved`), emitted at that change's revision and in the order of
+    /// the keys' last changes. Applies to the whole shared buffer once any open batch asks
+    /// for it.
+    pub fn with_coalesce(self, coalesce: bool) -> Self {
+        if coalesce {
+            self.runtime.event_batch.lock().coalesce = true;
         }
+        self
+    }
+}
+
+impl Drop for EventBatch<'_> {
+    fn drop(&mut self) {
+        self.runtime.close_event_batch();
     }
 }
 
+#[derive(Debug, Default)]
+struct EventBatchState {
+    open: usize,
+    coalesce: bool,
+    /// The revision before the first [`Runtime::batch`] scope among the open batches, if any.
+    held_from: Option<Revision>,
+    pending: Vec<PendingInputChange>,
+}
+
+#[derive(Debug)]
+struct PendingInputChange {
+    revision: Revision,
+    source: DynKey,
+    removed: bool,
+}
+
+/// Keep only the last change per key, preserving the order of those last changes.
+fn coalesce_input_changes(changes: Vec<PendingInputChange>) -> Vec<PendingInputChange> {
+    let mut last = std::collections::HashMap::with_capacity(changes.len());
+    for (i, change) in changes.iter().enumerate() {
+        last.insert(change.source.clone(), i);
+    }
+    changes
+        .into_iter()
+        .enumerate()
+        .filter(|(i, change)| last[&change.source] == *i)
+        .map(|(_, change)| change)
+        .collect()
+}
+
+/// Events buffered per subscriber unless [`Runtime::with_event_capacity`] says otherwise.
+const DEFAULT_EVENT_CAPACITY: usize = 1024;
+
+/// Stream the events from `rx` that pass `filter`, reporting lag as [`RuntimeEvent::Lagged`].
+fn event_stream<F>(
+    rx: broadcast::Receiver<RuntimeEvent>,
+    filter: F,
+) -> impl Stream<Item = RuntimeEvent> + Send
+where
+    F: Fn(&RuntimeEvent) -> bool + Send + 'static,
+{
+    futures::stream::unfold((rx, filter), |(mut rx, filter)| async move {
+        loop {
+            let event = match rx.recv().await {
+                Ok(event) if filter(&event) => event,
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    RuntimeEvent::Lagged { skipped }
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            };
+            return Some((event, (rx, filter)));
+        }
+    })
+}
+
 /// Notifications emitted by a [`Runtime`].
+///
+/// New kinds of events may be added in any release, so matches need a wildcard arm.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum RuntimeEvent {
     /// The global revision counter was bumped.
     RevisionBumped {
@@ -324,6 +1446,83 @@ pub enum RuntimeEvent {
         /// Postcard-encoded key bytes for the changed query.
         key: Key,
     },
+    /// A derived query's compute function started running at `revision`.
+    ///
+    /// Unlike [`RuntimeEvent::QueryChanged`], this fires even when the recomputed value turns
+    /// out equal to the previous one, so it measures work done rather than visible changes.
+    QueryRecomputed {
+        /// Revision the query is being computed at.
+        revision: Revision,
+        /// Kind id of the recomputed query.
+        kind: QueryKindId,
+        /// Stable hash of the recomputed key bytes (for diagnostics).
+        key_hash: u64,
+        /// Postcard-encoded key bytes for the recomputed query.
+        key: Key,
+    },
+    /// [`Runtime::begin_shutdown`] was called; background tasks are stopping.
+    ShuttingDown {
+        /// Revision at which shutdown began.
+        revision: Revision,
+    },
+    /// An eager output was recomputed (or revalidated) in push mode.
+    EagerOutputRecomputed {
+        /// Revision at which the output was brought up to date.
+        revision: Revision,
+        /// Kind id of the eager output.
+        kind: QueryKindId,
+        /// Stable hash of the output key bytes (for diagnostics).
+        key_hash: u64,
+        /// Postcard-encoded key bytes for the output.
+        key: Key,
+        /// The last revision at which the output's value changed.
+        changed_at: Revision,
+        /// The output's value, type-erased: downcast it to the query's value type. `None` if
+        /// it couldn't be read without waiting, e.g. because a newer recompute already holds
+        /// the cell.
+        value: Option<Arc<dyn Any + Send + Sync>>,
+    },
+    /// A [`Runtime::batch`] scope committed, moving the revision from `from` to `to`.
+    ///
+    /// Stands in for the `RevisionBumped` events of the writes made in the batch; their
+    /// `InputSet` / `InputRemoved` events are emitted just before it.
+    BatchCommitted {
+        /// Revision before the batch.
+        from: Revision,
+        /// Latest revision after the batch.
+        to: Revision,
+        /// Kinds of the inputs written in the batch, sorted and deduplicated.
+        changed_kinds: Vec<QueryKindId>,
+    },
+    /// The subscriber fell behind and missed `skipped` events.
+    ///
+    /// Never sent on the channel: [`Runtime::subscribe_event_stream`] and
+    /// [`Runtime::subscribe_events_for`] yield it where the missed events would have been.
+    /// Anything kept in sync from events should resynchronize from scratch.
+    Lagged {
+        /// Number of events missed.
+        skipped: u64,
+    },
+}
+
+impl RuntimeEvent {
+    /// The ingredient this event is about, or `None` for runtime-wide events such as
+    /// revision changes and lag reports.
+    pub fn kind(&self) -> Option<QueryKindId> {
+        match self {
+            RuntimeEvent::RevisionBumped { .. }
+            | RuntimeEvent::RevisionSet { .. }
+            | RuntimeEvent::ShuttingDown { .. }
+            | RuntimeEvent::BatchCommitted { .. }
+            | RuntimeEvent::Lagged { .. } => None,
+            RuntimeEvent::InputSet { kind, .. }
+            | RuntimeEvent::InputRemoved { kind, .. }
+            | RuntimeEvent::QueryInvalidated { kind, .. }
+            | RuntimeEvent::QueryChanged { kind, .. }
+            | RuntimeEvent::QueryRecomputed { kind, .. }
+            | RuntimeEvent::EagerOutputRecomputed { kind, .. } => Some(*kind),
+        }
+    }
 }
 
 /// Trait for database types that expose a [`Runtime`].
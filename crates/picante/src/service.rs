@@ -0,0 +1,76 @@
+//! [`tower`](https://docs.rs/tower) interop (enabled by the `tower` feature).
+//!
+//! A [`QueryService`] exposes one derived query as a `Service<K>` whose `call` is
+//! [`DerivedIngredient::get`], so queries can sit behind tower middleware (timeouts,
+//! concurrency limits, retries, tracing) when served over a network:
+//!
+//! ```ignore
+//! let service = ServiceBuilder::new()
+//!     .timeout(Duration::from_secs(5))
+//!     .service(QueryService::new(db.clone(), len.clone()));
+//! ```
+//!
+//! Response futures must be `'static`, so the service owns an `Arc` of the database rather
+//! than borrowing it. To serve a consistent view while inputs keep changing, hand it an
+//! `Arc` of a snapshot.
+
+use crate::db::IngredientLookup;
+use crate::error::{PicanteError, PicanteResult};
+use crate::ingredient::DerivedIngredient;
+use facet::Facet;
+use futures::future::BoxFuture;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+pub use tower_service::Service;
+
+/// A derived query as a [`Service`] from keys to values.
+///
+/// Always ready: backpressure, if any, comes from middleware such as a concurrency limit.
+pub struct QueryService<DB, K, V> {
+    db: Arc<DB>,
+    ingredient: Arc<DerivedIngredient<DB, K, V>>,
+}
+
+impl<DB, K, V> QueryService<DB, K, V> {
+    /// Serve `ingredient` against `db`.
+    pub fn new(db: Arc<DB>, ingredient: Arc<DerivedIngredient<DB, K, V>>) -> Self {
+        Self { db, ingredient }
+    }
+
+    /// The database queries run against.
+    pub fn db(&self) -> &Arc<DB> {
+        &self.db
+    }
+}
+
+impl<DB, K, V> Clone for QueryService<DB, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+            ingredient: self.ingredient.clone(),
+        }
+    }
+}
+
+impl<DB, K, V> Service<K> for QueryService<DB, K, V>
+where
+    DB: IngredientLookup + Send + Sync + 'static,
+    K: Clone + Eq + Hash + Facet<'static> + Send + Sync + 'static,
+    V: Clone + Facet<'static> + Send + Sync + 'static,
+{
+    type Response = V;
+    type Error = Arc<PicanteError>;
+    type Future = BoxFuture<'static, PicanteResult<V>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<PicanteResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, key: K) -> Self::Future {
+        let db = self.db.clone();
+        let ingredient = self.ingredient.clone();
+        Box::pin(async move { ingredient.get(&db, key).await })
+    }
+}
@@ -0,0 +1,99 @@
+//! Helpers for asserting incrementality in tests (enabled by the `testing` feature).
+//!
+//! A [`RecomputeRecorder`] listens for [`RuntimeEvent::QueryRecomputed`] and lets a test state
+//! exactly which queries ran after a change:
+//!
+//! ```ignore
+//! let mut recorder = RecomputeRecorder::start(db.runtime());
+//! text.set(&db, "a".into(), "changed".into());
+//! len.get(&db, "a".into()).await?;
+//! recorder.assert_recomputed(&[LEN]);
+//! recorder.assert_not_recomputed(&[WORDS]);
+//! ```
+
+use crate::key::{DynKey, QueryKindId};
+use crate::runtime::{Runtime, RuntimeEvent};
+use std::collections::HashSet;
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+/// Records which derived queries recomputed since it started (or was last cleared).
+pub struct RecomputeRecorder {
+    rx: broadcast::Receiver<RuntimeEvent>,
+    recomputed: Vec<DynKey>,
+}
+
+impl RecomputeRecorder {
+    /// Start recording recomputations on `runtime`.
+    pub fn start(runtime: &Runtime) -> Self {
+        Self {
+            rx: runtime.subscribe_events(),
+            recomputed: Vec::new(),
+        }
+    }
+
+    /// Every recomputed query so far, in order (a query that ran twice appears twice).
+    pub fn recomputed(&mut self) -> &[DynKey] {
+        self.drain();
+        &self.recomputed
+    }
+
+    /// The distinct kinds recomputed so far.
+    pub fn recomputed_kinds(&mut self) -> HashSet<QueryKindId> {
+        self.drain();
+        self.recomputed.iter().map(|q| q.kind).collect()
+    }
+
+    /// How many times queries of `kind` recomputed so far.
+    pub fn count(&mut self, kind: QueryKindId) -> usize {
+        self.drain();
+        self.recomputed.iter().filter(|q| q.kind == kind).count()
+    }
+
+    /// Forget everything recorded so far, e.g. after warming the database up.
+    pub fn clear(&mut self) {
+        self.drain();
+        self.recomputed.clear();
+    }
+
+    /// Assert that exactly the kinds in `expected` recomputed (each at least once).
+    #[track_caller]
+    pub fn assert_recomputed(&mut self, expected: &[QueryKindId]) {
+        let actual = self.recomputed_kinds();
+        let expected: HashSet<QueryKindId> = expected.iter().copied().collect();
+        assert_eq!(
+            actual, expected,
+            "unexpected set of recomputed query kinds (recomputed: {:?})",
+            self.recomputed
+        );
+    }
+
+    /// Assert that none of the kinds in `kinds` recomputed.
+    #[track_caller]
+    pub fn assert_not_recomputed(&mut self, kinds: &[QueryKindId]) {
+        self.drain();
+        let offending: Vec<&DynKey> = self
+            .recomputed
+            .iter()
+            .filter(|q| kinds.contains(&q.kind))
+            .collect();
+        assert!(
+            offending.is_empty(),
+            "queries recomputed unexpectedly: {offending:?}"
+        );
+    }
+
+    fn drain(&mut self) {
+        loop {
+            match self.rx.try_recv() {
+                Ok(RuntimeEvent::QueryRecomputed { kind, key, .. }) => {
+                    self.recomputed.push(DynKey { kind, key });
+                }
+                Ok(_) => {}
+                Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+                Err(TryRecvError::Lagged(n)) => {
+                    panic!("RecomputeRecorder missed {n} runtime events; drain it more often")
+                }
+            }
+        }
+    }
+}
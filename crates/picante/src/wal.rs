@@ -172,6 +172,8 @@ impl WalWriter {
             Arc::new(PicanteError::Encode {
                 what: "WAL header",
                 message: format!("{}", e),
+                kind: None,
+                kind_name: None,
             })
         })?;
 
@@ -213,6 +215,8 @@ impl WalWriter {
             Arc::new(PicanteError::Encode {
                 what: "WAL entry",
                 message: format!("{}", e),
+                kind: None,
+                kind_name: None,
             })
         })?;
 
@@ -347,6 +351,8 @@ impl WalReader {
             Arc::new(PicanteError::Decode {
                 what: "WAL header",
                 message: format!("{}", e),
+                kind: None,
+                kind_name: None,
             })
         })?;
 
@@ -417,6 +423,8 @@ impl WalReader {
             Arc::new(PicanteError::Decode {
                 what: "WAL entry",
                 message: format!("{}", e),
+                kind: None,
+                kind_name: None,
             })
         })?;
 
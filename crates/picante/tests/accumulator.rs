@@ -0,0 +1,138 @@
+use picante::db::{DynIngredient, IngredientLookup, IngredientRegistry};
+use picante::ingredient::{AccumulatorIngredient, DerivedIngredient};
+use picante::key::QueryKindId;
+use picante::persist::{load_cache, save_cache};
+use picante::runtime::{HasRuntime, Runtime};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Default)]
+struct TestDb {
+    runtime: Runtime,
+    ingredients: IngredientRegistry<TestDb>,
+}
+
+impl HasRuntime for TestDb {
+    fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+}
+
+impl IngredientLookup for TestDb {
+    fn ingredient(&self, kind: QueryKindId) -> Option<&dyn DynIngredient<Self>> {
+        self.ingredients.ingredient(kind)
+    }
+}
+
+type Lints = AccumulatorIngredient<String, String>;
+
+fn setup() -> (
+    TestDb,
+    Arc<Lints>,
+    Arc<DerivedIngredient<TestDb, String, String>>,
+    Arc<AtomicUsize>,
+) {
+    let mut db = TestDb::default();
+    let lints: Arc<Lints> = Arc::new(AccumulatorIngredient::new(QueryKindId(1), "Lints"));
+    db.ingredients.register(lints.clone());
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let report: Arc<DerivedIngredient<TestDb, String, String>> = {
+        let lints = lints.clone();
+        let executions = executions.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Report",
+            move |db, file| {
+                let lints = lints.clone();
+                let executions = executions.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    Ok(lints.get(db, &file)?.join("; "))
+                })
+            },
+        ))
+    };
+    db.ingredients.register(report.clone());
+
+    (db, lints, report, executions)
+}
+
+#[tokio::test]
+async fn pushes_append_and_only_invalidate_their_key() {
+    let (db, lints, report, executions) = setup();
+
+    lints.push(&db, "a.rs".into(), "unused import".into());
+    lints.push(&db, "b.rs".into(), "dead code".into());
+    assert_eq!(
+        report.get(&db, "a.rs".into()).await.unwrap(),
+        "unused import"
+    );
+    assert_eq!(report.get(&db, "b.rs".into()).await.unwrap(), "dead code");
+    assert_eq!(executions.load(Ordering::SeqCst), 2);
+
+    // Pushes append, duplicates included, and leave other keys alone.
+    lints.push(&db, "a.rs".into(), "missing docs".into());
+    let r = lints.push(&db, "a.rs".into(), "missing docs".into());
+    assert_eq!(
+        report.get(&db, "a.rs".into()).await.unwrap(),
+        "unused import; missing docs; missing docs"
+    );
+    assert_eq!(report.get(&db, "b.rs".into()).await.unwrap(), "dead code");
+    assert_eq!(executions.load(Ordering::SeqCst), 3);
+    assert_eq!(lints.changed_at(&"a.rs".into()), Some(r));
+
+    // Clearing an empty or missing key is a no-op; clearing items invalidates.
+    let rev = db.runtime().current_revision();
+    lints.clear_key(&db, &"c.rs".into());
+    assert_eq!(db.runtime().current_revision(), rev);
+
+    lints.clear_key(&db, &"a.rs".into());
+    assert_eq!(report.get(&db, "a.rs".into()).await.unwrap(), "");
+    assert_eq!(report.get(&db, "b.rs".into()).await.unwrap(), "dead code");
+    assert_eq!(executions.load(Ordering::SeqCst), 4);
+
+    let rev = db.runtime().current_revision();
+    lints.clear_key(&db, &"a.rs".into());
+    assert_eq!(db.runtime().current_revision(), rev);
+}
+
+#[tokio::test]
+async fn persists_items_in_order() {
+    let cache_path = {
+        let pid = std::process::id();
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        std::env::temp_dir().join(format!("picante-accumulator-{pid}-{nanos}.bin"))
+    };
+
+    let (db, lints, report, _) = setup();
+    lints.push(&db, "a.rs".into(), "one".into());
+    let r2 = lints.push(&db, "a.rs".into(), "two".into());
+    assert_eq!(report.get(&db, "a.rs".into()).await.unwrap(), "one; two");
+    save_cache(&cache_path, db.runtime(), &[&*lints, &*report])
+        .await
+        .unwrap();
+
+    let (db2, lints2, report2, executions2) = setup();
+    assert!(
+        load_cache(&cache_path, db2.runtime(), &[&*lints2, &*report2])
+            .await
+            .unwrap()
+    );
+
+    assert_eq!(report2.get(&db2, "a.rs".into()).await.unwrap(), "one; two");
+    assert_eq!(executions2.load(Ordering::SeqCst), 0);
+    assert_eq!(lints2.changed_at(&"a.rs".into()), Some(r2));
+
+    lints2.push(&db2, "a.rs".into(), "three".into());
+    assert_eq!(
+        report2.get(&db2, "a.rs".into()).await.unwrap(),
+        "one; two; three"
+    );
+    assert_eq!(executions2.load(Ordering::SeqCst), 1);
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
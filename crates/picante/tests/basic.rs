@@ -1,8 +1,12 @@
 use picante::db::{DynIngredient, IngredientLookup, IngredientRegistry};
 use picante::error::PicanteError;
-use picante::ingredient::{DerivedIngredient, InputIngredient};
-use picante::key::{DynKey, Key, QueryKindId};
+use picante::ingredient::{
+    CellState, DerivedIngredient, ErrorPolicy, InputIngredient, PanicPolicy, QueryStats,
+    RetryPolicy, ShardedDerived, ValidationStrategy,
+};
+use picante::key::{Dep, DynKey, Key, QueryKindId, TypedKey};
 use picante::persist::{load_cache, save_cache};
+use picante::revision::{Durability, Revision};
 use picante::runtime::{HasRuntime, Runtime};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -151,6 +155,138 @@ async fn detects_cycles_within_task() {
         PicanteError::Cycle { .. } => {}
         other => panic!("expected cycle error, got {other:?}"),
     }
+    // Keys are rendered decoded where their encoding allows it.
+    assert!(
+        err.to_string()
+            .ends_with("(\"k\")  ← cycle (already in stack)"),
+        "{err}"
+    );
+}
+
+#[test]
+fn dyn_keys_display_decoded_keys() {
+    let dyn_key = |key: Key| DynKey {
+        kind: QueryKindId(3),
+        key,
+    };
+    let text = Key::encode_facet(&"a.rs".to_string()).unwrap();
+    assert_eq!(
+        dyn_key(text.clone()).to_string(),
+        format!("kind_3, key_{:016x} (\"a.rs\")", text.hash())
+    );
+    assert_eq!(Key::encode_facet(&300u32).unwrap().to_string(), "300");
+    assert_eq!(Key::encode_facet(&()).unwrap().to_string(), "()");
+    assert_eq!(Key::from_bytes(vec![0xff, 0xff]).to_string(), "0xffff");
+}
+
+#[tokio::test]
+async fn cycle_recovery_seeds_the_cyclic_read() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+
+    // `Step(n)` for n > 0 reads `Step(n - 1)`, and `Step(0)` reads `Step(2)` back.
+    let step: Arc<DerivedIngredient<TestDb, u64, u64>> = Arc::new_cyclic(
+        |weak: &std::sync::Weak<DerivedIngredient<TestDb, u64, u64>>| {
+            let weak = weak.clone();
+            DerivedIngredient::new(QueryKindId(1), "Step", move |db, key| {
+                let weak = weak.clone();
+                Box::pin(async move {
+                    let me = weak.upgrade().expect("ingredient dropped");
+                    let prev = if key == 0 { 2 } else { key - 1 };
+                    Ok(me.get(db, prev).await? + 1)
+                })
+            })
+            .with_cycle_recovery(|_db, key, stack| {
+                assert_eq!(*key, 2);
+                assert_eq!(stack.len(), 3);
+                100
+            })
+        },
+    );
+    db.register(step.clone());
+
+    // Step(2) -> Step(1) -> Step(0) -> Step(2) (seeded with 100).
+    assert_eq!(step.get(&db, 2).await.unwrap(), 103);
+    assert_eq!(step.get(&db, 0).await.unwrap(), 101);
+}
+
+#[tokio::test]
+async fn fixed_point_iteration_converges() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let edges: Arc<InputIngredient<u32, Vec<u32>>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Edges"));
+    db.register(edges.clone());
+
+    // Nodes reachable from `key`, sorted; 0 -> 1 -> 0 is a cycle.
+    let reach: Arc<DerivedIngredient<TestDb, u32, Vec<u32>>> = Arc::new_cyclic(
+        |weak: &std::sync::Weak<DerivedIngredient<TestDb, u32, Vec<u32>>>| {
+            let weak = weak.clone();
+            let edges = edges.clone();
+            DerivedIngredient::new(QueryKindId(2), "Reach", move |db, key| {
+                let weak = weak.clone();
+                let edges = edges.clone();
+                Box::pin(async move {
+                    let me = weak.upgrade().expect("ingredient dropped");
+                    let mut out = vec![key];
+                    for next in edges.get(db, &key)?.unwrap_or_default() {
+                        out.extend(me.get(db, next).await?);
+                    }
+                    out.sort_unstable();
+                    out.dedup();
+                    Ok(out)
+                })
+            })
+            .with_cycle_recovery(|_db, _key, _stack| Vec::new())
+            .with_fixed_point(10)
+        },
+    );
+    db.register(reach.clone());
+
+    edges.set(&db, 0, vec![1]);
+    edges.set(&db, 1, vec![0, 2]);
+    edges.set(&db, 2, vec![]);
+    assert_eq!(reach.get(&db, 0).await.unwrap(), vec![0, 1, 2]);
+    // The other member was recomputed from the converged value, not the seed.
+    assert_eq!(reach.get(&db, 1).await.unwrap(), vec![0, 1, 2]);
+
+    edges.set(&db, 2, vec![3]);
+    assert_eq!(reach.get(&db, 0).await.unwrap(), vec![0, 1, 2, 3]);
+}
+
+#[tokio::test]
+async fn fixed_point_iteration_gives_up() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+
+    // Each round reads one more than the last: never converges.
+    let count: Arc<DerivedIngredient<TestDb, u32, u64>> = Arc::new_cyclic(
+        |weak: &std::sync::Weak<DerivedIngredient<TestDb, u32, u64>>| {
+            let weak = weak.clone();
+            DerivedIngredient::new(QueryKindId(1), "Count", move |db, key| {
+                let weak = weak.clone();
+                Box::pin(async move {
+                    let me = weak.upgrade().expect("ingredient dropped");
+                    Ok(me.get(db, key).await? + 1)
+                })
+            })
+            .with_cycle_recovery(|_db, _key, _stack| 0)
+            .with_fixed_point(5)
+        },
+    );
+    db.register(count.clone());
+
+    let err = count.get(&db, 0).await.unwrap_err();
+    match &*err {
+        PicanteError::CycleDidNotConverge { kind, iterations, .. } => {
+            assert_eq!(*kind, QueryKindId(1));
+            assert_eq!(*iterations, 5);
+        }
+        other => panic!("expected CycleDidNotConverge, got {other:?}"),
+    }
 }
 
 #[tokio::test]
@@ -290,6 +426,477 @@ async fn poisoned_cells_recompute_after_revision_bump() {
     assert_eq!(executions.load(Ordering::SeqCst), 2);
 }
 
+#[tokio::test]
+async fn keep_last_good_serves_previous_value_after_panic() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    input.set(&db, "a".into(), "hello".into());
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let executions_for_compute = executions.clone();
+    let input_for_compute = input.clone();
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(
+        DerivedIngredient::new(QueryKindId(2), "Len", move |db, key| {
+            let input = input_for_compute.clone();
+            let executions = executions_for_compute.clone();
+            Box::pin(async move {
+                let text = input.get(db, &key)?.expect("missing input");
+                if executions.fetch_add(1, Ordering::SeqCst) == 1 {
+                    panic!("boom");
+                }
+                Ok(text.len() as u64)
+            })
+        })
+        .with_panic_policy(PanicPolicy::KeepLastGood),
+    );
+    db.register(len.clone());
+
+    assert_eq!(len.get(&db, "a".into()).await.unwrap(), 5);
+    let first = db.runtime().current_revision();
+
+    // The recompute panics: the stale value is served and the cell is not poisoned.
+    input.set(&db, "a".into(), "hello!!".into());
+    assert_eq!(len.get(&db, "a".into()).await.unwrap(), 5);
+    assert_eq!(
+        len.peek_state(&"a".into()).await.unwrap(),
+        Some(CellState::Ready { verified_at: first })
+    );
+
+    // The restored value is still stale, so the next read recomputes it.
+    assert_eq!(len.get(&db, "a".into()).await.unwrap(), 7);
+    assert_eq!(executions.load(Ordering::SeqCst), 3);
+    assert_eq!(len.stats().panics, 1);
+}
+
+#[tokio::test]
+async fn retry_within_revision_bounds_recomputes() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let source: Arc<InputIngredient<String, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Source"));
+    db.register(source.clone());
+    source.set(&db, "k".into(), 0);
+
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let failures_left = Arc::new(AtomicUsize::new(2));
+    let flaky: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let source = source.clone();
+        let attempts = attempts.clone();
+        let failures_left = failures_left.clone();
+        Arc::new(
+            DerivedIngredient::new(QueryKindId(2), "Flaky", move |db, key| {
+                let source = source.clone();
+                let attempts = attempts.clone();
+                let failures_left = failures_left.clone();
+                Box::pin(async move {
+                    source.get(db, &key)?;
+                    let n = attempts.fetch_add(1, Ordering::SeqCst);
+                    if failures_left.load(Ordering::SeqCst) > 0 {
+                        failures_left.fetch_sub(1, Ordering::SeqCst);
+                        return Err(Arc::new(PicanteError::Cache {
+                            message: format!("attempt {n} failed"),
+                        }));
+                    }
+                    Ok(7)
+                })
+            })
+            .with_error_policy(ErrorPolicy::RetryWithinRevision { max: 2 }),
+        )
+    };
+    db.register(flaky.clone());
+
+    // Two transient failures heal within the revision.
+    assert!(flaky.get(&db, "k".into()).await.is_err());
+    assert!(flaky.get(&db, "k".into()).await.is_err());
+    assert_eq!(flaky.get(&db, "k".into()).await.unwrap(), 7);
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    // A persistent failure latches after `max` retries, with the last attempt's error.
+    failures_left.store(usize::MAX, Ordering::SeqCst);
+    source.set(&db, "k".into(), 1);
+    flaky.get(&db, "k".into()).await.unwrap_err();
+    flaky.get(&db, "k".into()).await.unwrap_err();
+    let err = flaky.get(&db, "k".into()).await.unwrap_err();
+    assert!(err.to_string().contains("attempt 5 failed"), "{err}");
+    assert_eq!(attempts.load(Ordering::SeqCst), 6);
+    let err = flaky.get(&db, "k".into()).await.unwrap_err();
+    assert!(err.to_string().contains("attempt 5 failed"), "{err}");
+    assert_eq!(attempts.load(Ordering::SeqCst), 6);
+
+    // The count starts over at the next revision.
+    source.set(&db, "k".into(), 2);
+    flaky.get(&db, "k".into()).await.unwrap_err();
+    assert_eq!(attempts.load(Ordering::SeqCst), 7);
+}
+
+#[tokio::test]
+async fn retry_policy_only_keeps_permanent_errors() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let flaky: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let attempts = attempts.clone();
+        Arc::new(
+            DerivedIngredient::new(QueryKindId(1), "Flaky", move |_db, key| {
+                let attempts = attempts.clone();
+                Box::pin(async move {
+                    let n = attempts.fetch_add(1, Ordering::SeqCst);
+                    if key == "missing" || n < 3 {
+                        return Err(Arc::new(PicanteError::Cache {
+                            message: format!("{key}: attempt {n} failed"),
+                        }));
+                    }
+                    Ok(7)
+                })
+            })
+            .with_error_policy(ErrorPolicy::Retry)
+            .with_transient_errors(|err| !err.to_string().contains("missing")),
+        )
+    };
+    db.register(flaky.clone());
+
+    // Transient failures are never kept, however many there are.
+    for _ in 0..3 {
+        flaky.get(&db, "k".into()).await.unwrap_err();
+    }
+    assert_eq!(flaky.get(&db, "k".into()).await.unwrap(), 7);
+    assert_eq!(attempts.load(Ordering::SeqCst), 4);
+
+    // Others are cached for the revision.
+    flaky.get(&db, "missing".into()).await.unwrap_err();
+    flaky.get(&db, "missing".into()).await.unwrap_err();
+    assert_eq!(attempts.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn retry_reruns_failed_computes_with_backoff() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let failures_left = Arc::new(AtomicUsize::new(2));
+    let flaky: Arc<DerivedIngredient<TestDb, u32, u64>> = {
+        let attempts = attempts.clone();
+        let failures_left = failures_left.clone();
+        Arc::new(
+            DerivedIngredient::new(QueryKindId(1), "Flaky", move |_db, key| {
+                let attempts = attempts.clone();
+                let failures_left = failures_left.clone();
+                Box::pin(async move {
+                    let n = attempts.fetch_add(1, Ordering::SeqCst);
+                    if failures_left.load(Ordering::SeqCst) > 0 {
+                        failures_left.fetch_sub(1, Ordering::SeqCst);
+                        return Err(Arc::new(PicanteError::Cache {
+                            message: format!("attempt {n} failed"),
+                        }));
+                    }
+                    Ok(u64::from(key) + 1)
+                })
+            })
+            .with_retry(RetryPolicy {
+                max_attempts: 3,
+                base_delay: std::time::Duration::from_millis(1),
+            }),
+        )
+    };
+    db.register(flaky.clone());
+    let db = Arc::new(db);
+
+    // Concurrent readers wait for the one computation and its retries.
+    let joins: Vec<_> = (0..2)
+        .map(|_| {
+            let db = db.clone();
+            let flaky = flaky.clone();
+            tokio::spawn(async move { flaky.get(db.as_ref(), 0).await.unwrap() })
+        })
+        .collect();
+    for j in joins {
+        assert_eq!(j.await.unwrap(), 1);
+    }
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+    // Exhausted retries poison the cell with the last error.
+    failures_left.store(usize::MAX, Ordering::SeqCst);
+    let err = flaky.get(db.as_ref(), 1).await.unwrap_err();
+    assert!(err.to_string().contains("attempt 5 failed"), "{err}");
+    flaky.get(db.as_ref(), 1).await.unwrap_err();
+    assert_eq!(attempts.load(Ordering::SeqCst), 6);
+}
+
+#[tokio::test]
+async fn concurrency_limit_caps_running_computes() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let running = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    // `key` sums `key - 1` first, so computes nest under one permit.
+    let sum: Arc<DerivedIngredient<TestDb, u64, u64>> = Arc::new_cyclic(
+        |weak: &std::sync::Weak<DerivedIngredient<TestDb, u64, u64>>| {
+            let weak = weak.clone();
+            let running = running.clone();
+            let peak = peak.clone();
+            DerivedIngredient::new(QueryKindId(1), "Sum", move |db, key| {
+                let weak = weak.clone();
+                let running = running.clone();
+                let peak = peak.clone();
+                Box::pin(async move {
+                    let me = weak.upgrade().expect("ingredient dropped");
+                    let below = if key % 10 == 0 {
+                        0
+                    } else {
+                        me.get(db, key - 1).await?
+                    };
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Ok(below + key)
+                })
+            })
+            .with_concurrency_limit(2)
+        },
+    );
+    db.register(sum.clone());
+    assert_eq!(sum.concurrency_limit(), Some(2));
+    let db = Arc::new(db);
+
+    let joins: Vec<_> = [2, 12, 22, 32]
+        .into_iter()
+        .map(|key| {
+            let db = db.clone();
+            let sum = sum.clone();
+            tokio::spawn(async move { sum.get(db.as_ref(), key).await.unwrap() })
+        })
+        .collect();
+    let mut results = Vec::new();
+    for j in joins {
+        results.push(j.await.unwrap());
+    }
+    assert_eq!(results, vec![3, 33, 63, 93]);
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn timeouts_poison_the_cell() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Delay"));
+    db.register(input.clone());
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let slow: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        let executions = executions.clone();
+        Arc::new(
+            DerivedIngredient::new(QueryKindId(2), "Slow", move |db, key| {
+                let input = input.clone();
+                let executions = executions.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    let delay = input.get(db, &key)?.unwrap_or_default();
+                    tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+                    Ok(delay)
+                })
+            })
+            .with_timeout(std::time::Duration::from_millis(20)),
+        )
+    };
+    db.register(slow.clone());
+
+    input.set(&db, "k".into(), 10_000);
+    let err = slow.get(&db, "k".into()).await.unwrap_err();
+    match &*err {
+        PicanteError::Timeout { kind, elapsed, .. } => {
+            assert_eq!(*kind, QueryKindId(2));
+            assert_eq!(*elapsed, std::time::Duration::from_millis(20));
+        }
+        other => panic!("expected Timeout, got {other:?}"),
+    }
+
+    // Cached like any other failure until the revision moves on.
+    assert!(slow.get(&db, "k".into()).await.is_err());
+    assert_eq!(executions.load(Ordering::SeqCst), 1);
+
+    input.set(&db, "k".into(), 1);
+    assert_eq!(slow.get(&db, "k".into()).await.unwrap(), 1);
+    assert_eq!(executions.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn timeouts_follow_the_runtime_clock() {
+    use picante::clock::ManualClock;
+    use std::time::Duration;
+
+    init_tracing();
+
+    let clock = Arc::new(ManualClock::new());
+    let mut db = TestDb {
+        runtime: Runtime::new().with_clock(clock.clone()),
+        ingredients: IngredientRegistry::new(),
+    };
+    let stuck: Arc<DerivedIngredient<TestDb, u32, u32>> = Arc::new(
+        DerivedIngredient::new(QueryKindId(1), "Stuck", |_db, _key| {
+            Box::pin(std::future::pending())
+        })
+        .with_timeout(Duration::from_secs(60)),
+    );
+    db.register(stuck.clone());
+    let db = Arc::new(db);
+
+    // A compute that never finishes times out once a minute has passed on the clock.
+    let task = tokio::spawn({
+        let db = db.clone();
+        let stuck = stuck.clone();
+        async move { stuck.get(&*db, 0).await }
+    });
+    while db.runtime().in_flight().is_empty() {
+        tokio::task::yield_now().await;
+    }
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert!(!task.is_finished());
+    clock.advance(Duration::from_secs(60));
+    let err = task.await.unwrap().unwrap_err();
+    match &*err {
+        PicanteError::Timeout { elapsed, .. } => assert_eq!(*elapsed, Duration::from_secs(60)),
+        other => panic!("expected Timeout, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn retry_backoff_follows_the_runtime_clock() {
+    use picante::clock::ManualClock;
+    use std::time::Duration;
+
+    init_tracing();
+
+    let clock = Arc::new(ManualClock::new());
+    let mut db = TestDb {
+        runtime: Runtime::new().with_clock(clock.clone()),
+        ingredients: IngredientRegistry::new(),
+    };
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let flaky: Arc<DerivedIngredient<TestDb, u32, u32>> = {
+        let attempts = attempts.clone();
+        Arc::new(
+            DerivedIngredient::new(QueryKindId(1), "Flaky", move |_db, key| {
+                let attempts = attempts.clone();
+                Box::pin(async move {
+                    if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                        return Err(Arc::new(PicanteError::Cache {
+                            message: "first attempt fails".into(),
+                        }));
+                    }
+                    Ok(key)
+                })
+            })
+            .with_retry(RetryPolicy {
+                max_attempts: 2,
+                base_delay: Duration::from_secs(3600),
+            }),
+        )
+    };
+    db.register(flaky.clone());
+    let db = Arc::new(db);
+
+    // The hour-long backoff only ends when the manual clock gets there.
+    let task = tokio::spawn({
+        let db = db.clone();
+        let flaky = flaky.clone();
+        async move { flaky.get(&*db, 7).await }
+    });
+    while attempts.load(Ordering::SeqCst) == 0 {
+        tokio::task::yield_now().await;
+    }
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert!(!task.is_finished());
+    clock.advance(Duration::from_secs(3600));
+    assert_eq!(task.await.unwrap().unwrap(), 7);
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn get_propagate_panics_resumes_the_panic() {
+    use futures::FutureExt;
+
+    init_tracing();
+
+    let mut db = TestDb::default();
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let executions_for_compute = executions.clone();
+
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(1),
+        "AlwaysPanics",
+        move |_db, _key| {
+            let executions = executions_for_compute.clone();
+            Box::pin(async move {
+                executions.fetch_add(1, Ordering::SeqCst);
+                panic!("boom");
+            })
+        },
+    ));
+    db.register(derived.clone());
+
+    let payload = std::panic::AssertUnwindSafe(derived.get_propagate_panics(&db, "k".into()))
+        .catch_unwind()
+        .await
+        .unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"boom"));
+
+    // Other readers see the cell poisoned, without recomputing.
+    let err = derived.get(&db, "k".into()).await.unwrap_err();
+    match &*err {
+        PicanteError::Panic { message } => assert_eq!(message, "boom"),
+        other => panic!("expected panic error, got {other:?}"),
+    }
+    assert_eq!(executions.load(Ordering::SeqCst), 1);
+
+    // Asking again reruns the compute so the panic is observed again.
+    let again = std::panic::AssertUnwindSafe(derived.get_propagate_panics(&db, "k".into()))
+        .catch_unwind()
+        .await;
+    assert!(again.is_err());
+    assert_eq!(executions.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn input_get_all_lists_current_values() {
+    init_tracing();
+
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+
+    input.set(&db, "a".into(), "hello".into());
+    input.set(&db, "b".into(), "world".into());
+    input.set(&db, "c".into(), "gone".into());
+    input.remove(&db, &"c".into());
+
+    let mut all = input.get_all();
+    all.sort();
+    assert_eq!(
+        all,
+        vec![
+            ("a".to_string(), "hello".to_string()),
+            ("b".to_string(), "world".to_string()),
+        ]
+    );
+}
+
 #[tokio::test]
 async fn input_snapshot_captures_state_at_creation_time() {
     init_tracing();
@@ -510,3 +1117,1801 @@ async fn changed_at_stable_when_value_unchanged() {
         "changed_at should bump when value actually changes"
     );
 }
+
+#[tokio::test]
+async fn iterative_validation_walks_deep_chains() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let seed: Arc<InputIngredient<String, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Seed"));
+    db.register(seed.clone());
+    let noise: Arc<InputIngredient<String, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(2), "Noise"));
+    db.register(noise.clone());
+
+    seed.set(&db, "seed".into(), 1);
+    noise.set(&db, "noise".into(), 0);
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let seed_for_compute = seed.clone();
+    let executions_for_compute = executions.clone();
+
+    let chain: Arc<DerivedIngredient<TestDb, u32, u64>> = Arc::new_cyclic(
+        move |weak: &std::sync::Weak<DerivedIngredient<TestDb, u32, u64>>| {
+            let weak = weak.clone();
+            DerivedIngredient::new(QueryKindId(3), "Chain", move |db, level| {
+                let weak = weak.clone();
+                let seed = seed_for_compute.clone();
+                let executions = executions_for_compute.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    if level == 0 {
+                        return Ok(seed.get(db, &"seed".to_string())?.unwrap_or_default());
+                    }
+                    let me = weak.upgrade().expect("ingredient dropped");
+                    // Depend on the two previous levels so shared dependencies form diamonds.
+                    let prev = me.get(db, level - 1).await?;
+                    let prev2 = if level >= 2 {
+                        me.get(db, level - 2).await?
+                    } else {
+                        0
+                    };
+                    Ok(prev.wrapping_add(prev2))
+                })
+            })
+            .with_validation_strategy(ValidationStrategy::Iterative)
+        },
+    );
+    db.register(chain.clone());
+
+    const DEPTH: u32 = 1_000;
+
+    // Warm bottom-up so the initial computes never nest deeply.
+    for level in 0..=DEPTH {
+        chain.get(&db, level).await.unwrap();
+    }
+    let warm = executions.load(Ordering::SeqCst);
+    assert_eq!(warm, DEPTH as usize + 1);
+    let top = chain.get(&db, DEPTH).await.unwrap();
+
+    // An unrelated change makes every cell stale; validation must not recompute anything.
+    noise.set(&db, "noise".into(), 1);
+    assert_eq!(chain.get(&db, DEPTH).await.unwrap(), top);
+    assert_eq!(executions.load(Ordering::SeqCst), warm);
+
+    // A real change recomputes each level exactly once, bottom-up.
+    seed.set(&db, "seed".into(), 2);
+    assert_ne!(chain.get(&db, DEPTH).await.unwrap(), top);
+    assert_eq!(executions.load(Ordering::SeqCst), warm * 2);
+}
+
+#[tokio::test]
+async fn default_validation_stops_at_the_first_changed_dependency() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let flag: Arc<InputIngredient<String, bool>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Flag"));
+    let source: Arc<InputIngredient<String, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(2), "Source"));
+    db.register(flag.clone());
+    db.register(source.clone());
+
+    let expensive_runs = Arc::new(AtomicUsize::new(0));
+    let expensive: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let source = source.clone();
+        let runs = expensive_runs.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(3),
+            "Expensive",
+            move |db, key| {
+                let source = source.clone();
+                let runs = runs.clone();
+                Box::pin(async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                    Ok(source.get(db, &key)?.unwrap_or_default() * 10)
+                })
+            },
+        ))
+    };
+    db.register(expensive.clone());
+
+    let pick: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let flag = flag.clone();
+        let expensive = expensive.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(4),
+            "Pick",
+            move |db, key| {
+                let flag = flag.clone();
+                let expensive = expensive.clone();
+                Box::pin(async move {
+                    if flag.get(db, &key)?.unwrap_or_default() {
+                        expensive.get(db, key).await
+                    } else {
+                        Ok(0)
+                    }
+                })
+            },
+        ))
+    };
+    db.register(pick.clone());
+    assert_eq!(pick.validation_strategy(), ValidationStrategy::Recursive);
+
+    flag.set(&db, "k".into(), true);
+    source.set(&db, "k".into(), 1);
+    assert_eq!(pick.get(&db, "k".into()).await.unwrap(), 10);
+    assert_eq!(expensive_runs.load(Ordering::SeqCst), 1);
+
+    // The flag is checked first and changed, so the stale `expensive` dependency is never
+    // revalidated: the recompute no longer reads it.
+    source.set(&db, "k".into(), 2);
+    flag.set(&db, "k".into(), false);
+    assert_eq!(pick.get(&db, "k".into()).await.unwrap(), 0);
+    assert_eq!(expensive_runs.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn sharded_derived_routes_and_persists() {
+    init_tracing();
+
+    let cache_path = {
+        let pid = std::process::id();
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        std::env::temp_dir().join(format!("picante-sharded-{pid}-{nanos}.bin"))
+    };
+
+    let make = |input: Arc<InputIngredient<String, String>>, exec: Arc<AtomicUsize>, shards| {
+        Arc::new(ShardedDerived::<TestDb, String, u64>::new(
+            QueryKindId(2),
+            "Len",
+            shards,
+            move |db, key| {
+                let input = input.clone();
+                let exec = exec.clone();
+                Box::pin(async move {
+                    exec.fetch_add(1, Ordering::SeqCst);
+                    let text = input.get(db, &key)?.expect("missing input");
+                    Ok(text.len() as u64)
+                })
+            },
+        ))
+    };
+
+    let keys: Vec<String> = (0..32).map(|i| format!("k{i}")).collect();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    for (i, k) in keys.iter().enumerate() {
+        input.set(&db, k.clone(), "x".repeat(i));
+    }
+
+    let exec1 = Arc::new(AtomicUsize::new(0));
+    let sharded = make(input.clone(), exec1.clone(), 4);
+    db.register(sharded.clone());
+    assert_eq!(sharded.shard_count(), 4);
+
+    for (i, k) in keys.iter().enumerate() {
+        assert_eq!(sharded.get(&db, k.clone()).await.unwrap(), i as u64);
+        assert_eq!(sharded.get(&db, k.clone()).await.unwrap(), i as u64);
+    }
+    assert_eq!(exec1.load(Ordering::SeqCst), keys.len());
+
+    // Every key lives in exactly the shard it routes to.
+    let populated = sharded
+        .shards()
+        .iter()
+        .filter(|s| !s.snapshot().is_empty())
+        .count();
+    assert!(populated > 1, "expected keys to spread across shards");
+    for k in &keys {
+        let shard = sharded.shard_for(k).unwrap();
+        assert!(shard.snapshot().contains_key(&DynKey {
+            kind: QueryKindId(2),
+            key: Key::encode_facet(k).unwrap(),
+        }));
+    }
+
+    save_cache(&cache_path, db.runtime(), &[&*input, &*sharded])
+        .await
+        .unwrap();
+
+    // Reload with a different shard count: routing is recomputed, nothing recomputes.
+    let mut db2 = TestDb::default();
+    let input2: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db2.register(input2.clone());
+    let exec2 = Arc::new(AtomicUsize::new(0));
+    let sharded2 = make(input2.clone(), exec2.clone(), 3);
+    db2.register(sharded2.clone());
+
+    let loaded = load_cache(&cache_path, db2.runtime(), &[&*input2, &*sharded2])
+        .await
+        .unwrap();
+    assert!(loaded);
+
+    for (i, k) in keys.iter().enumerate() {
+        assert_eq!(sharded2.get(&db2, k.clone()).await.unwrap(), i as u64);
+    }
+    assert_eq!(exec2.load(Ordering::SeqCst), 0);
+
+    input2.set(&db2, "k3".into(), "changed".into());
+    assert_eq!(sharded2.get(&db2, "k3".into()).await.unwrap(), 7);
+    assert_eq!(exec2.load(Ordering::SeqCst), 1);
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+type ShardedLen = ShardedDerived<TestDb, String, u64>;
+
+/// A database with `keys` text inputs and a 4-shard query of their lengths.
+fn sharded_len_db(keys: usize) -> (TestDb, Arc<ShardedLen>, Arc<AtomicUsize>) {
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    for i in 0..keys {
+        input.set(&db, format!("k{i}"), "x".repeat(i));
+    }
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let sharded = {
+        let executions = executions.clone();
+        Arc::new(ShardedLen::new(QueryKindId(2), "Len", 4, move |db, key| {
+            let input = input.clone();
+            let executions = executions.clone();
+            Box::pin(async move {
+                executions.fetch_add(1, Ordering::SeqCst);
+                Ok(input.get(db, &key)?.unwrap_or_default().len() as u64)
+            })
+        }))
+    };
+    db.register(sharded.clone());
+    (db, sharded, executions)
+}
+
+#[tokio::test]
+async fn sharded_derived_peeks_at_cached_values() {
+    init_tracing();
+    let (db, sharded, executions) = sharded_len_db(8);
+
+    assert_eq!(sharded.get_if_cached(&db, &"k3".into()).unwrap(), None);
+    assert_eq!(sharded.get_if_ready(&db, &"k3".into()).unwrap(), None);
+    assert_eq!(sharded.get(&db, "k3".into()).await.unwrap(), 3);
+    assert_eq!(sharded.get_if_cached(&db, &"k3".into()).unwrap(), Some(3));
+    assert_eq!(sharded.get_if_ready(&db, &"k3".into()).unwrap(), Some(3));
+    assert_eq!(sharded.get_if_cached(&db, &"k4".into()).unwrap(), None);
+    assert_eq!(executions.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn sharded_derived_invalidates_one_key() {
+    init_tracing();
+    let (db, sharded, executions) = sharded_len_db(8);
+
+    sharded.get(&db, "k3".into()).await.unwrap();
+    sharded.get(&db, "k4".into()).await.unwrap();
+    let before = db.runtime().current_revision();
+
+    assert!(sharded.invalidate(&db, &"k3".into()).unwrap());
+    assert!(db.runtime().current_revision() > before);
+    assert!(!sharded.invalidate(&db, &"k3".into()).unwrap());
+    assert_eq!(sharded.get_if_cached(&db, &"k3".into()).unwrap(), None);
+
+    assert_eq!(sharded.get(&db, "k3".into()).await.unwrap(), 3);
+    assert_eq!(sharded.get(&db, "k4".into()).await.unwrap(), 4);
+    assert_eq!(executions.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn sharded_derived_invalidates_every_shard_with_one_bump() {
+    init_tracing();
+    let (db, sharded, executions) = sharded_len_db(16);
+
+    for i in 0..16 {
+        sharded.get(&db, format!("k{i}")).await.unwrap();
+    }
+    let populated = sharded
+        .shards()
+        .iter()
+        .filter(|s| !s.snapshot().is_empty())
+        .count();
+    assert!(populated > 1, "expected keys to spread across shards");
+
+    let before = db.runtime().current_revision();
+    assert_eq!(sharded.invalidate_all(&db), 16);
+    assert_eq!(db.runtime().current_revision().0, before.0 + 1);
+    assert!(sharded.shards().iter().all(|s| s.snapshot().is_empty()));
+    assert_eq!(sharded.invalidate_all(&db), 0);
+    assert_eq!(db.runtime().current_revision().0, before.0 + 1);
+
+    assert_eq!(sharded.get(&db, "k5".into()).await.unwrap(), 5);
+    assert_eq!(executions.load(Ordering::SeqCst), 17);
+}
+
+#[tokio::test]
+async fn sharded_derived_sums_stats_across_shards() {
+    init_tracing();
+    let (db, sharded, _executions) = sharded_len_db(16);
+
+    for i in 0..16 {
+        sharded.get(&db, format!("k{i}")).await.unwrap();
+        sharded.get(&db, format!("k{i}")).await.unwrap();
+    }
+
+    let stats = sharded.stats();
+    assert_eq!(stats.recomputes, 16);
+    assert_eq!(stats.hits, 16);
+    let per_shard: u64 = sharded.shards().iter().map(|s| s.stats().recomputes).sum();
+    assert_eq!(per_shard, 16);
+
+    sharded.reset_stats();
+    assert_eq!(sharded.stats(), QueryStats::default());
+}
+
+#[tokio::test]
+async fn stale_retries_are_bounded() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Counter"));
+    db.register(input.clone());
+    input.set(&db, "churn".into(), 0);
+
+    // Every computation bumps the revision before finishing, so it is always stale.
+    let executions = Arc::new(AtomicUsize::new(0));
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        let executions = executions.clone();
+        Arc::new(
+            DerivedIngredient::new(QueryKindId(2), "Churn", move |db, key| {
+                let input = input.clone();
+                let executions = executions.clone();
+                Box::pin(async move {
+                    let n = executions.fetch_add(1, Ordering::SeqCst) as u64;
+                    input.set(db, key, n + 1);
+                    Ok(n)
+                })
+            })
+            .with_max_stale_retries(3),
+        )
+    };
+    db.register(derived.clone());
+
+    let err = derived.get(&db, "churn".into()).await.unwrap_err();
+    match &*err {
+        PicanteError::Contended { kind, retries, .. } => {
+            assert_eq!(*kind, QueryKindId(2));
+            assert_eq!(*retries, 4);
+        }
+        other => panic!("expected Contended, got {other:?}"),
+    }
+    assert_eq!(executions.load(Ordering::SeqCst), 4);
+    assert_eq!(derived.stale_retries(), 4);
+}
+
+#[tokio::test]
+async fn key_decode_errors_name_the_ingredient() {
+    init_tracing();
+
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(7), "Text"));
+
+    // A length prefix with no string bytes behind it.
+    let bogus = Key::from_bytes(vec![0x05]);
+    let err = match DynIngredient::<TestDb>::touch(&*input, &db, bogus).await {
+        Ok(_) => panic!("expected a decode error"),
+        Err(err) => err,
+    };
+    match &*err {
+        PicanteError::Decode {
+            what,
+            kind,
+            kind_name,
+            ..
+        } => {
+            assert_eq!(*what, "key");
+            assert_eq!(*kind, Some(QueryKindId(7)));
+            assert_eq!(*kind_name, Some("Text"));
+        }
+        other => panic!("expected Decode, got {other:?}"),
+    }
+    assert!(err.to_string().starts_with("decode key failed in `Text` (kind 7): "));
+}
+
+#[tokio::test]
+async fn checkpoint_cancels_stale_computes() {
+    init_tracing();
+
+    // Outside a query, a checkpoint only yields.
+    picante::frame::checkpoint().await.unwrap();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        let executions = executions.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Len",
+            move |db, key| {
+                let input = input.clone();
+                let executions = executions.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    let text = input.get(db, &key)?.expect("missing input");
+                    // "slow" never finishes on its own: only cancellation gets it out.
+                    while text == "slow" {
+                        picante::frame::checkpoint().await?;
+                        tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+                    }
+                    Ok(text.len() as u64)
+                })
+            },
+        ))
+    };
+    db.register(derived.clone());
+    let db = Arc::new(db);
+
+    input.set(&*db, "a".into(), "slow".into());
+    let pending = tokio::spawn({
+        let db = db.clone();
+        let derived = derived.clone();
+        async move { derived.get(&*db, "a".into()).await }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    assert_eq!(executions.load(Ordering::SeqCst), 1);
+
+    input.set(&*db, "a".into(), "fast".into());
+    let value = tokio::time::timeout(std::time::Duration::from_secs(5), pending)
+        .await
+        .expect("stale compute should have been cancelled")
+        .unwrap()
+        .unwrap();
+    assert_eq!(value, 4);
+    assert_eq!(executions.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn cancelled_computes_keep_the_previous_value() {
+    init_tracing();
+
+    assert!(!picante::frame::is_cancelled());
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Number"));
+    db.register(input.clone());
+
+    // Takes ~50ms, checking for cancellation without awaiting in between steps.
+    let parity: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "Parity", move |db, key| {
+            let input = input.clone();
+            Box::pin(async move {
+                let n = input.get(db, &key)?.unwrap_or_default();
+                for _ in 0..10 {
+                    if picante::frame::is_cancelled() {
+                        picante::frame::check_cancelled()?;
+                    }
+                    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                }
+                Ok(n % 2)
+            })
+        }))
+    };
+    db.register(parity.clone());
+    let db = Arc::new(db);
+
+    input.set(&*db, "k".into(), 2);
+    assert_eq!(parity.get(&*db, "k".into()).await.unwrap(), 0);
+    let changed_at = parity.touch(&*db, "k".into()).await.unwrap();
+
+    input.set(&*db, "k".into(), 4);
+    let pending = tokio::spawn({
+        let db = db.clone();
+        let parity = parity.clone();
+        async move { parity.get(&*db, "k".into()).await }
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    input.set(&*db, "k".into(), 6);
+
+    assert_eq!(pending.await.unwrap().unwrap(), 0);
+    assert!(parity.stale_retries() >= 1);
+    // The cancelled compute restored the old value, so the recompute backdated.
+    assert_eq!(parity.touch(&*db, "k".into()).await.unwrap(), changed_at);
+}
+
+#[tokio::test]
+async fn map_and_and_then_compose_with_backdating() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    input.set(&db, "a".into(), "hello".into());
+
+    let input_for_len = input.clone();
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "Len",
+        move |db, key| {
+            let input = input_for_len.clone();
+            Box::pin(async move {
+                let text = input.get(db, &key)?.expect("missing input");
+                Ok(text.len() as u64)
+            })
+        },
+    ));
+    db.register(len.clone());
+
+    let long_kind = len.kind().child("long");
+    assert_eq!(long_kind, QueryKindId(2).child("long"));
+    assert_ne!(long_kind, len.kind().child("label"));
+
+    let long = Arc::new(len.map(long_kind, "Long", |n| n > 3));
+    db.register(long.clone());
+
+    let labels = Arc::new(AtomicUsize::new(0));
+    let labels_for_compute = labels.clone();
+    let label = Arc::new(long.and_then(len.kind().child("label"), "Label", move |long| {
+        labels_for_compute.fetch_add(1, Ordering::SeqCst);
+        async move { Ok(if long { "long" } else { "short" }.to_string()) }
+    }));
+    db.register(label.clone());
+
+    assert_eq!(label.get(&db, "a".into()).await.unwrap(), "long");
+    assert_eq!(labels.load(Ordering::SeqCst), 1);
+
+    // `Len` changes but `Long` doesn't, so `Label` is backdated instead of recomputed.
+    input.set(&db, "a".into(), "hello!!".into());
+    assert_eq!(label.get(&db, "a".into()).await.unwrap(), "long");
+    assert_eq!(labels.load(Ordering::SeqCst), 1);
+
+    input.set(&db, "a".into(), "hi".into());
+    assert_eq!(label.get(&db, "a".into()).await.unwrap(), "short");
+    assert_eq!(labels.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn zip_combines_two_inputs() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let first: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "First"));
+    let last: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(2), "Last"));
+    db.register(first.clone());
+    db.register(last.clone());
+
+    let full: Arc<DerivedIngredient<TestDb, String, String>> = Arc::new(DerivedIngredient::zip(
+        QueryKindId(3),
+        "FullName",
+        first.clone(),
+        last.clone(),
+        |first, last| format!("{first} {last}"),
+    ));
+    db.register(full.clone());
+
+    first.set(&db, "p".into(), "Ada".into());
+    let err = full.get(&db, "p".into()).await.unwrap_err();
+    match &*err {
+        PicanteError::MissingInputValue { kind, .. } => assert_eq!(*kind, QueryKindId(2)),
+        other => panic!("expected MissingInputValue, got {other:?}"),
+    }
+
+    last.set(&db, "p".into(), "Lovelace".into());
+    assert_eq!(full.get(&db, "p".into()).await.unwrap(), "Ada Lovelace");
+
+    first.set(&db, "p".into(), "Augusta".into());
+    assert_eq!(full.get(&db, "p".into()).await.unwrap(), "Augusta Lovelace");
+}
+
+#[tokio::test]
+async fn pinned_keys_still_recompute() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "Len", move |db, key| {
+            let input = input.clone();
+            Box::pin(async move { Ok(input.get(db, &key)?.unwrap_or_default().len() as u64) })
+        }))
+    };
+    db.register(len.clone());
+
+    // Keys can be pinned before they are computed.
+    assert!(len.pin(&"root".to_string()).unwrap());
+    assert!(!len.pin(&"root".to_string()).unwrap());
+    assert_eq!(len.pinned_count(), 1);
+
+    input.set(&db, "root".into(), "abc".into());
+    assert_eq!(len.get(&db, "root".into()).await.unwrap(), 3);
+    input.set(&db, "root".into(), "abcdef".into());
+    assert_eq!(len.get(&db, "root".into()).await.unwrap(), 6);
+    assert!(len.is_pinned(&"root".to_string()).unwrap());
+
+    assert!(len.unpin(&"root".to_string()).unwrap());
+    assert!(!len.unpin(&"root".to_string()).unwrap());
+    assert_eq!(len.pinned_count(), 0);
+}
+
+#[tokio::test]
+async fn capacity_evicts_least_recently_used_cells() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let executions = Arc::new(AtomicUsize::new(0));
+    let square: Arc<DerivedIngredient<TestDb, u64, u64>> = {
+        let executions = executions.clone();
+        Arc::new(
+            DerivedIngredient::new(QueryKindId(1), "Square", move |_db, key| {
+                let executions = executions.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    Ok(key * key)
+                })
+            })
+            .with_capacity(4),
+        )
+    };
+    db.register(square.clone());
+
+    for key in 0..4 {
+        square.get(&db, key).await.unwrap();
+    }
+    // Key 0 becomes the most recently used; key 1 is now the oldest.
+    square.get(&db, 0).await.unwrap();
+    square.get(&db, 4).await.unwrap();
+    assert_eq!(square.snapshot().len(), 4);
+    assert_eq!(square.evictions(), 1);
+    assert_eq!(executions.load(Ordering::SeqCst), 5);
+
+    assert_eq!(square.get(&db, 0).await.unwrap(), 0);
+    assert_eq!(executions.load(Ordering::SeqCst), 5);
+    assert_eq!(square.get(&db, 1).await.unwrap(), 1);
+    assert_eq!(executions.load(Ordering::SeqCst), 6);
+
+    // Pinned keys survive however cold they get.
+    assert!(square.pin(&0).unwrap());
+    for key in 10..20 {
+        square.get(&db, key).await.unwrap();
+    }
+    assert_eq!(square.snapshot().len(), 4);
+    let before = executions.load(Ordering::SeqCst);
+    assert_eq!(square.get(&db, 0).await.unwrap(), 0);
+    assert_eq!(executions.load(Ordering::SeqCst), before);
+}
+
+#[tokio::test]
+async fn stats_count_hits_revalidations_and_failures() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<u32, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Input"));
+    db.register(input.clone());
+
+    let parity: Arc<DerivedIngredient<TestDb, u32, u64>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Parity",
+            move |db, key| {
+                let input = input.clone();
+                Box::pin(async move {
+                    let Some(n) = input.get(db, &key)? else {
+                        return Err(Arc::new(PicanteError::Cache {
+                            message: format!("no input for {key}"),
+                        }));
+                    };
+                    Ok(n % 2)
+                })
+            },
+        ))
+    };
+    db.register(parity.clone());
+
+    let label: Arc<DerivedIngredient<TestDb, u32, String>> = {
+        let parity = parity.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(3),
+            "Label",
+            move |db, key| {
+                let parity = parity.clone();
+                Box::pin(async move { Ok(format!("parity {}", parity.get(db, key).await?)) })
+            },
+        ))
+    };
+    db.register(label.clone());
+
+    input.set(&db, 0, 1);
+    label.get(&db, 0).await.unwrap();
+    label.get(&db, 0).await.unwrap();
+    // Parity stays 1, so the label backdates instead of recomputing.
+    input.set(&db, 0, 3);
+    label.get(&db, 0).await.unwrap();
+
+    let stats = label.stats();
+    assert_eq!(stats.recomputes, 1);
+    assert_eq!(stats.hits, 1);
+    assert_eq!(stats.revalidations, 1);
+    assert_eq!(stats.errors, 0);
+    assert_eq!(parity.stats().recomputes, 2);
+
+    assert!(parity.get(&db, 9).await.is_err());
+    assert_eq!(parity.stats().errors, 1);
+
+    label.reset_stats();
+    assert_eq!(label.stats(), QueryStats::default());
+}
+
+#[tokio::test]
+async fn durable_dependencies_skip_validation_across_volatile_changes() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let std_lib: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "StdLib").with_durability(Durability::High));
+    db.register(std_lib.clone());
+    let files: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(2), "File"));
+    db.register(files.clone());
+
+    let std_len: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let std_lib = std_lib.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(3),
+            "StdLen",
+            move |db, key| {
+                let std_lib = std_lib.clone();
+                Box::pin(async move { Ok(std_lib.get(db, &key)?.unwrap_or_default().len() as u64) })
+            },
+        ))
+    };
+    db.register(std_len.clone());
+
+    let doubled: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let std_len = std_len.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(4),
+            "Doubled",
+            move |db, key| {
+                let std_len = std_len.clone();
+                Box::pin(async move { Ok(std_len.get(db, key).await? * 2) })
+            },
+        ))
+    };
+    db.register(doubled.clone());
+
+    std_lib.set(&db, "core".into(), "abc".into());
+    assert_eq!(doubled.get(&db, "core".into()).await.unwrap(), 6);
+    let std_stats = std_len.stats();
+
+    // A low-durability edit: `Doubled` is revalidated without touching `StdLen`.
+    let rev = files.set(&db, "main.rs".into(), "fn main() {}".into());
+    assert_eq!(db.runtime().last_changed(Durability::Low), rev);
+    assert!(db.runtime().last_changed(Durability::High) < rev);
+    assert_eq!(doubled.get(&db, "core".into()).await.unwrap(), 6);
+    assert_eq!(doubled.stats().revalidations, 1);
+    assert_eq!(std_len.stats(), std_stats);
+
+    // A high-durability edit invalidates as usual.
+    let rev = std_lib.set(&db, "core".into(), "abcd".into());
+    assert_eq!(db.runtime().last_changed(Durability::Low), rev);
+    assert_eq!(db.runtime().last_changed(Durability::High), rev);
+    assert_eq!(doubled.get(&db, "core".into()).await.unwrap(), 8);
+    assert_eq!(std_len.stats().recomputes, 2);
+}
+
+#[tokio::test]
+async fn invalidate_recomputes_out_of_band_values() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    // Stands in for a resource the database can't see changing.
+    let external = Arc::new(AtomicUsize::new(1));
+
+    let read: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let external = external.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(1), "Read", move |_db, _key| {
+            let external = external.clone();
+            Box::pin(async move { Ok(external.load(Ordering::SeqCst) as u64) })
+        }))
+    };
+    db.register(read.clone());
+
+    let doubled: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let read = read.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "Doubled", move |db, key| {
+            let read = read.clone();
+            Box::pin(async move { Ok(read.get(db, key).await? * 2) })
+        }))
+    };
+    db.register(doubled.clone());
+
+    let key = "k".to_string();
+    assert_eq!(doubled.get(&db, key.clone()).await.unwrap(), 2);
+
+    external.store(5, Ordering::SeqCst);
+    assert_eq!(doubled.get(&db, key.clone()).await.unwrap(), 2);
+
+    let before = db.runtime().current_revision();
+    assert!(read.invalidate(&db, &key).unwrap());
+    assert!(db.runtime().current_revision() > before);
+    assert_eq!(doubled.get(&db, key.clone()).await.unwrap(), 10);
+
+    // Nothing cached: no revision bump.
+    let before = db.runtime().current_revision();
+    assert!(!read.invalidate(&db, &"other".to_string()).unwrap());
+    assert_eq!(db.runtime().current_revision(), before);
+
+    external.store(7, Ordering::SeqCst);
+    assert_eq!(read.get(&db, "other".into()).await.unwrap(), 7);
+    assert_eq!(read.invalidate_all(&db), 2);
+    assert_eq!(db.runtime().current_revision(), Revision(before.0 + 1));
+    assert_eq!(read.invalidate_all(&db), 0);
+    assert_eq!(doubled.get(&db, key).await.unwrap(), 14);
+}
+
+#[tokio::test]
+async fn invalidate_discards_in_flight_result() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let external = Arc::new(AtomicUsize::new(1));
+
+    let slow: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let external = external.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(1), "Slow", move |_db, _key| {
+            let external = external.clone();
+            Box::pin(async move {
+                let value = external.load(Ordering::SeqCst) as u64;
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                Ok(value)
+            })
+        }))
+    };
+    db.register(slow.clone());
+    let db = Arc::new(db);
+
+    let running = {
+        let db = db.clone();
+        let slow = slow.clone();
+        tokio::spawn(async move { slow.get(db.as_ref(), "k".into()).await.unwrap() })
+    };
+    tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+
+    // The compute already read 1; its result must not be kept.
+    external.store(2, Ordering::SeqCst);
+    assert!(slow.invalidate(&db, &"k".to_string()).unwrap());
+
+    assert_eq!(running.await.unwrap(), 2);
+    assert_eq!(slow.get(db.as_ref(), "k".into()).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn access_info_tracks_reads() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    input.set(&db, "a".into(), "hello".into());
+
+    let input_for_compute = input.clone();
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "Len",
+        move |db, key| {
+            let input = input_for_compute.clone();
+            Box::pin(async move {
+                let text = input.get(db, &key)?.expect("missing input");
+                Ok(text.len() as u64)
+            })
+        },
+    ));
+    db.register(derived.clone());
+
+    assert_eq!(derived.access_info(&"a".to_string()).await.unwrap(), None);
+
+    derived.get(&db, "a".into()).await.unwrap();
+    derived.get(&db, "a".into()).await.unwrap();
+    let info = derived.access_info(&"a".to_string()).await.unwrap().unwrap();
+    assert_eq!(info.read_count, 2);
+    assert_eq!(info.last_read, Some(Revision(1)));
+    assert_eq!(info.verified_at, Some(Revision(1)));
+
+    input.set(&db, "b".into(), "unrelated".into());
+    derived.touch(&db, "a".into()).await.unwrap();
+    let info = derived.access_info(&"a".to_string()).await.unwrap().unwrap();
+    assert_eq!(info.read_count, 3);
+    assert_eq!(info.last_read, Some(Revision(2)));
+    assert_eq!(info.verified_at, Some(Revision(2)));
+}
+
+#[tokio::test]
+async fn dependencies_lists_reads_of_ready_cells() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    input.set(&db, "a".into(), "hello".into());
+    input.set(&db, "b".into(), "world".into());
+
+    let input_for_compute = input.clone();
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "Len",
+        move |db, key| {
+            let input = input_for_compute.clone();
+            Box::pin(async move {
+                let text = input.get(db, &key)?.expect("missing input");
+                // A repeated read is recorded once.
+                input.get(db, &"b".to_string())?;
+                input.get(db, &key)?;
+                Ok(text.len() as u64)
+            })
+        },
+    ));
+    db.register(derived.clone());
+
+    assert_eq!(derived.dependencies(&"a".to_string()).await.unwrap(), None);
+
+    derived.get(&db, "a".into()).await.unwrap();
+    let deps = derived.dependencies(&"a".to_string()).await.unwrap().unwrap();
+    let text_dep = |key: &str| Dep {
+        kind: QueryKindId(1),
+        key: Key::encode_facet(&key.to_string()).unwrap(),
+    };
+    assert_eq!(deps, vec![text_dep("a"), text_dep("b")]);
+}
+
+#[tokio::test]
+async fn typed_keys_round_trip_dependency_keys() {
+    let key = TypedKey::<String>::new(&"a".to_string()).unwrap();
+    assert_eq!(key.decode().unwrap(), "a");
+    assert_eq!(key.key(), &Key::encode_facet(&"a".to_string()).unwrap());
+
+    let dep = Dep {
+        kind: QueryKindId(1),
+        key: key.clone().into_key(),
+    };
+    let typed = dep.key.typed::<String>();
+    assert_eq!(typed, key);
+    assert_eq!(typed.decode().unwrap(), "a");
+    assert!(dep.key.typed::<(String, u64)>().decode().is_err());
+}
+
+#[tokio::test]
+async fn get_many_fetches_distinct_keys_concurrently() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let executions = Arc::new(AtomicUsize::new(0));
+    let square: Arc<DerivedIngredient<TestDb, u64, u64>> = {
+        let executions = executions.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(1),
+            "Square",
+            move |_db, key| {
+                let executions = executions.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    tokio::task::yield_now().await;
+                    if key == 13 {
+                        return Err(Arc::new(PicanteError::Cache {
+                            message: "unlucky".into(),
+                        }));
+                    }
+                    Ok(key * key)
+                })
+            },
+        ))
+    };
+    db.register(square.clone());
+
+    let sum: Arc<DerivedIngredient<TestDb, u64, u64>> = {
+        let square = square.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "SumOfSquares",
+            move |db, n| {
+                let square = square.clone();
+                Box::pin(async move {
+                    let squares = square.get_many(db, (1..=n).collect()).await?;
+                    Ok(squares.iter().sum())
+                })
+            },
+        ))
+    };
+    db.register(sum.clone());
+
+    assert_eq!(
+        square.get_many(&db, vec![3, 1, 3, 2]).await.unwrap(),
+        vec![9, 1, 9, 4]
+    );
+    assert_eq!(executions.load(Ordering::SeqCst), 3);
+
+    assert_eq!(sum.get(&db, 4).await.unwrap(), 30);
+    assert_eq!(executions.load(Ordering::SeqCst), 4);
+    let mut deps = sum.dependencies(&4).await.unwrap().unwrap();
+    deps.sort();
+    let mut expected: Vec<Dep> = (1..=4u64)
+        .map(|k| Dep {
+            kind: QueryKindId(1),
+            key: Key::encode_facet(&k).unwrap(),
+        })
+        .collect();
+    expected.sort();
+    assert_eq!(deps, expected);
+
+    // The rest settle before the error is returned.
+    assert!(square.get_many(&db, vec![5, 13, 6]).await.is_err());
+    assert_eq!(square.get_if_ready(&db, &6).unwrap(), Some(36));
+}
+
+#[tokio::test]
+async fn prefetch_warms_cells_without_recording_deps() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let executions = Arc::new(AtomicUsize::new(0));
+    let square: Arc<DerivedIngredient<TestDb, u64, u64>> = {
+        let executions = executions.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(1),
+            "Square",
+            move |_db, key| {
+                let executions = executions.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    Ok(key * key)
+                })
+            },
+        ))
+    };
+    db.register(square.clone());
+
+    // Warms the next key while computing this one.
+    let warm: Arc<DerivedIngredient<TestDb, u64, u64>> = {
+        let square = square.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Warm",
+            move |db, key| {
+                let square = square.clone();
+                Box::pin(async move {
+                    square.prefetch(db, key + 1).await?;
+                    Ok(key)
+                })
+            },
+        ))
+    };
+    db.register(warm.clone());
+
+    square.prefetch(&db, 2).await.unwrap();
+    assert_eq!(executions.load(Ordering::SeqCst), 1);
+    square.prefetch(&db, 2).await.unwrap();
+    assert_eq!(square.get(&db, 2).await.unwrap(), 4);
+    assert_eq!(executions.load(Ordering::SeqCst), 1);
+
+    assert_eq!(warm.get(&db, 4).await.unwrap(), 4);
+    assert_eq!(warm.dependencies(&4).await.unwrap(), Some(Vec::new()));
+    assert_eq!(square.get_if_ready(&db, &5).unwrap(), Some(25));
+    assert_eq!(executions.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn subscribe_receives_new_values() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    input.set(&db, "a".into(), "hello".into());
+
+    let input_for_compute = input.clone();
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "Len",
+        move |db, key| {
+            let input = input_for_compute.clone();
+            Box::pin(async move {
+                let text = input.get(db, &key)?.expect("missing input");
+                Ok(text.len() as u64)
+            })
+        },
+    ));
+    db.register(len.clone());
+
+    let mut rx = len.subscribe(&"a".to_string()).unwrap();
+    assert_eq!(*rx.borrow(), None);
+
+    len.get(&db, "a".into()).await.unwrap();
+    assert!(rx.has_changed().unwrap());
+    assert_eq!(*rx.borrow_and_update(), Some(5));
+
+    // Same length: early cutoff, nothing sent.
+    input.set(&db, "a".into(), "world".into());
+    len.get(&db, "a".into()).await.unwrap();
+    assert!(!rx.has_changed().unwrap());
+
+    input.set(&db, "a".into(), "hi".into());
+    len.get(&db, "a".into()).await.unwrap();
+    assert_eq!(*rx.borrow_and_update(), Some(2));
+
+    // Late subscribers start from the latest value.
+    assert_eq!(*len.subscribe(&"a".to_string()).unwrap().borrow(), Some(2));
+
+    assert!(len.invalidate(&db, &"a".to_string()).unwrap());
+    assert!(rx.has_changed().unwrap());
+    assert_eq!(*rx.borrow_and_update(), None);
+}
+
+#[tokio::test]
+async fn subscribers_see_values_adopted_from_another_runtime() {
+    init_tracing();
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let gate = Arc::new(tokio::sync::Notify::new());
+    let started = Arc::new(tokio::sync::Notify::new());
+    let len = || {
+        let executions = executions.clone();
+        let gate = gate.clone();
+        let started = started.clone();
+        Arc::new(DerivedIngredient::<TestDb, String, u64>::new(
+            QueryKindId(1),
+            "Len",
+            move |_db, key| {
+                let executions = executions.clone();
+                let gate = gate.clone();
+                let started = started.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    if key == "slow" {
+                        started.notify_one();
+                        gate.notified().await;
+                    }
+                    Ok(key.len() as u64)
+                })
+            },
+        ))
+    };
+
+    // Two runtimes sharing an identity, as a database and its snapshot do.
+    let mut db = TestDb::default();
+    let first = len();
+    db.register(first.clone());
+    let mut snapshot = TestDb {
+        runtime: Runtime::new_for_snapshot(db.runtime.id()),
+        ingredients: IngredientRegistry::default(),
+    };
+    let second = len();
+    snapshot.register(second.clone());
+
+    // Adopted from the shared cache of completed results.
+    let mut rx = second.subscribe(&"ab".to_string()).unwrap();
+    assert_eq!(first.get(&db, "ab".into()).await.unwrap(), 2);
+    assert_eq!(second.get(&snapshot, "ab".into()).await.unwrap(), 2);
+    assert_eq!(executions.load(Ordering::SeqCst), 1);
+    assert!(rx.has_changed().unwrap());
+    assert_eq!(*rx.borrow_and_update(), Some(2));
+
+    // Handed over by a leader computing in the other runtime.
+    let mut rx = second.subscribe(&"slow".to_string()).unwrap();
+    let (led, followed) = futures::join!(first.get(&db, "slow".into()), async {
+        started.notified().await;
+        let (followed, ()) = futures::join!(second.get(&snapshot, "slow".into()), async {
+            gate.notify_one();
+        });
+        followed
+    });
+    assert_eq!(led.unwrap(), 4);
+    assert_eq!(followed.unwrap(), 4);
+    assert_eq!(executions.load(Ordering::SeqCst), 2);
+    assert!(rx.has_changed().unwrap());
+    assert_eq!(*rx.borrow_and_update(), Some(4));
+}
+
+#[tokio::test]
+async fn peek_state_reports_lifecycle() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+
+    let input_for_compute = input.clone();
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "Len",
+        move |db, key| {
+            let input = input_for_compute.clone();
+            Box::pin(async move {
+                let Some(text) = input.get(db, &key)? else {
+                    return Err(Arc::new(PicanteError::Cache {
+                        message: format!("no text for {key}"),
+                    }));
+                };
+                Ok(text.len() as u64)
+            })
+        },
+    ));
+    db.register(len.clone());
+
+    let a = "a".to_string();
+    assert_eq!(len.peek_state(&a).await.unwrap(), None);
+
+    len.get(&db, a.clone()).await.unwrap_err();
+    let failed_at = db.runtime().current_revision();
+    assert_eq!(
+        len.peek_state(&a).await.unwrap(),
+        Some(CellState::Poisoned {
+            verified_at: failed_at
+        })
+    );
+
+    input.set(&db, a.clone(), "hello".into());
+    // Peeking doesn't revalidate.
+    assert_eq!(
+        len.peek_state(&a).await.unwrap(),
+        Some(CellState::Poisoned {
+            verified_at: failed_at
+        })
+    );
+    len.get(&db, a.clone()).await.unwrap();
+    assert_eq!(
+        len.peek_state(&a).await.unwrap(),
+        Some(CellState::Ready {
+            verified_at: db.runtime().current_revision()
+        })
+    );
+}
+
+#[tokio::test]
+async fn on_evict_sees_each_value_leave_once() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+
+    let evicted = Arc::new(parking_lot::Mutex::new(Vec::new()));
+    let upper: Arc<DerivedIngredient<TestDb, String, String>> = {
+        let input = input.clone();
+        let evicted = evicted.clone();
+        Arc::new(
+            DerivedIngredient::new(QueryKindId(2), "Upper", move |db, key| {
+                let input = input.clone();
+                Box::pin(async move {
+                    let Some(text) = input.get(db, &key)? else {
+                        return Err(Arc::new(PicanteError::Cache {
+                            message: format!("no text for {key}"),
+                        }));
+                    };
+                    Ok(text.to_uppercase())
+                })
+            })
+            .on_evict(move |key: &String, value: &String| {
+                evicted.lock().push(format!("{key}={value}"));
+            }),
+        )
+    };
+    db.register(upper.clone());
+
+    input.set(&db, "a".into(), "x".into());
+    upper.get(&db, "a".into()).await.unwrap();
+    input.set(&db, "a".into(), "y".into());
+    upper.get(&db, "a".into()).await.unwrap();
+    assert_eq!(*evicted.lock(), ["a=X"]);
+
+    // A failed recompute drops the value; the error itself is never reported.
+    input.remove(&db, &"a".to_string());
+    upper.get(&db, "a".into()).await.unwrap_err();
+    assert_eq!(*evicted.lock(), ["a=X", "a=Y"]);
+    assert!(upper.invalidate(&db, &"a".to_string()).unwrap());
+    assert_eq!(evicted.lock().len(), 2);
+
+    input.set(&db, "b".into(), "z".into());
+    upper.get(&db, "b".into()).await.unwrap();
+    assert_eq!(upper.invalidate_all(&db), 1);
+    assert_eq!(*evicted.lock(), ["a=X", "a=Y", "b=Z"]);
+}
+
+#[tokio::test]
+async fn get_at_reads_values_as_of_a_pinned_revision() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    input.set(&db, "a".into(), "hello".into());
+
+    let input_for_compute = input.clone();
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "Len",
+        move |db, key| {
+            let input = input_for_compute.clone();
+            Box::pin(async move {
+                let text = input.get(db, &key)?.expect("missing input");
+                Ok(text.len() as u64)
+            })
+        },
+    ));
+    db.register(len.clone());
+
+    let snapshot = db.runtime().snapshot();
+    assert_eq!(len.get_at(&db, "a".into(), &snapshot).await.unwrap(), 5);
+
+    // A writer moves on; the pinned read still sees the old value until it is replaced.
+    input.set(&db, "a".into(), "hi".into());
+    assert_eq!(len.get_at(&db, "a".into(), &snapshot).await.unwrap(), 5);
+    assert_eq!(len.get(&db, "a".into()).await.unwrap(), 2);
+    let err = len.get_at(&db, "a".into(), &snapshot).await.unwrap_err();
+    assert!(
+        matches!(&*err, PicanteError::SnapshotExpired { snapshot: pinned, .. } if *pinned == snapshot.revision()),
+        "{err}"
+    );
+
+    // Revalidation at a later revision keeps a value readable at the revision it dates from.
+    let snapshot = db.runtime().snapshot();
+    input.set(&db, "b".into(), "unrelated".into());
+    assert_eq!(len.get(&db, "a".into()).await.unwrap(), 2);
+    assert_eq!(len.get_at(&db, "a".into(), &snapshot).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn rolling_back_a_checkpoint_undoes_speculative_edits() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    let other: Arc<InputIngredient<String, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(3), "Other"));
+    db.register(input.clone());
+    db.register(other.clone());
+    input.set(&db, "a".into(), "hello".into());
+    input.set(&db, "gone".into(), "soon".into());
+
+    let input_for_compute = input.clone();
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "Len",
+        move |db, key| {
+            let input = input_for_compute.clone();
+            Box::pin(async move {
+                let text = input.get(db, &key)?.unwrap_or_default();
+                Ok(text.len() as u64)
+            })
+        },
+    ));
+    db.register(len.clone());
+    assert_eq!(len.get(&db, "a".into()).await.unwrap(), 5);
+
+    let checkpoint = db.runtime().checkpoint();
+    assert_eq!(checkpoint.revision(), db.runtime().current_revision());
+    input.set(&db, "a".into(), "speculative".into());
+    input.set(&db, "b".into(), "new".into());
+    input.remove(&db, &"gone".into());
+    other.set(&db, "x".into(), 1);
+    assert_eq!(len.get(&db, "a".into()).await.unwrap(), 11);
+    assert_eq!(len.get(&db, "b".into()).await.unwrap(), 3);
+
+    // Revisions never regress: the rollback is one more change.
+    let before = db.runtime().current_revision();
+    let rev = db
+        .runtime()
+        .rollback(checkpoint)
+        .expect("inputs were written since the checkpoint");
+    assert_eq!(rev.0, before.0 + 1);
+    assert_eq!(db.runtime().current_revision(), rev);
+    assert_eq!(
+        input.get(&db, &"a".into()).unwrap().as_deref(),
+        Some("hello")
+    );
+    assert_eq!(input.get(&db, &"b".into()).unwrap(), None);
+    assert_eq!(
+        input.get(&db, &"gone".into()).unwrap().as_deref(),
+        Some("soon")
+    );
+    assert_eq!(other.get(&db, &"x".into()).unwrap(), None);
+    assert_eq!(len.get(&db, "a".into()).await.unwrap(), 5);
+    assert_eq!(len.get(&db, "b".into()).await.unwrap(), 0);
+
+    // A checkpoint nothing was written after rolls back to nothing.
+    let unused = db.runtime().checkpoint();
+    assert_eq!(db.runtime().rollback(unused), None);
+    assert_eq!(db.runtime().current_revision(), rev);
+}
+
+#[tokio::test]
+async fn nested_checkpoints_roll_back_and_release_independently() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Count"));
+    db.register(input.clone());
+    input.set(&db, "n".into(), 0);
+
+    let outer = db.runtime().checkpoint();
+    input.set(&db, "n".into(), 1);
+    let inner = db.runtime().checkpoint();
+    input.set(&db, "n".into(), 2);
+    assert!(db.runtime().rollback(inner).is_some());
+    assert_eq!(input.get(&db, &"n".into()).unwrap(), Some(1));
+    assert!(db.runtime().rollback(outer).is_some());
+    assert_eq!(input.get(&db, &"n".into()).unwrap(), Some(0));
+
+    // Rolling back an outer checkpoint discards the ones taken after it.
+    let outer = db.runtime().checkpoint();
+    let inner = db.runtime().checkpoint();
+    input.set(&db, "n".into(), 3);
+    assert!(db.runtime().rollback(outer).is_some());
+    assert_eq!(db.runtime().rollback(inner), None);
+    assert_eq!(input.get(&db, &"n".into()).unwrap(), Some(0));
+
+    // A released checkpoint keeps the changes; the one before it can still undo them.
+    let outer = db.runtime().checkpoint();
+    let inner = db.runtime().checkpoint();
+    input.set(&db, "n".into(), 4);
+    db.runtime().release(inner);
+    assert_eq!(input.get(&db, &"n".into()).unwrap(), Some(4));
+    assert!(db.runtime().rollback(outer).is_some());
+    assert_eq!(input.get(&db, &"n".into()).unwrap(), Some(0));
+}
+
+#[tokio::test]
+async fn current_dep_count_tracks_reads_so_far() {
+    init_tracing();
+
+    assert_eq!(picante::frame::current_dep_count(), None);
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    for key in ["a", "b", "c"] {
+        input.set(&db, key.into(), key.into());
+    }
+
+    // Reads keys until it has seen `limit` of them, then gives up on the rest.
+    let derived: Arc<DerivedIngredient<TestDb, usize, Vec<usize>>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Capped",
+            move |db, limit| {
+                let input = input.clone();
+                Box::pin(async move {
+                    let mut counts = vec![picante::frame::current_dep_count().unwrap()];
+                    for key in ["a", "b", "c"] {
+                        if picante::frame::current_dep_count().unwrap() >= limit {
+                            break;
+                        }
+                        input.get(db, &key.to_string())?;
+                        counts.push(picante::frame::current_dep_count().unwrap());
+                    }
+                    Ok(counts)
+                })
+            },
+        ))
+    };
+    db.register(derived.clone());
+
+    assert_eq!(derived.get(&db, 10).await.unwrap(), vec![0, 1, 2, 3]);
+    assert_eq!(derived.get(&db, 2).await.unwrap(), vec![0, 1, 2]);
+    assert_eq!(picante::frame::current_dep_count(), None);
+}
+
+#[tokio::test]
+async fn repeated_reads_record_one_dep() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    input.set(&db, "a".into(), "x".into());
+    input.set(&db, "b".into(), "y".into());
+
+    let derived: Arc<DerivedIngredient<TestDb, String, usize>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Loop",
+            move |db, _key| {
+                let input = input.clone();
+                Box::pin(async move {
+                    let mut total = 0;
+                    for key in ["a", "b", "a", "a", "b"] {
+                        total += input.get(db, &key.to_string())?.unwrap_or_default().len();
+                    }
+                    Ok(total)
+                })
+            },
+        ))
+    };
+    db.register(derived.clone());
+
+    assert_eq!(derived.get(&db, "k".into()).await.unwrap(), 5);
+
+    let graph = picante::debug::DependencyGraph::from_runtime(db.runtime());
+    let query = DynKey {
+        kind: QueryKindId(2),
+        key: Key::encode_facet(&"k".to_string()).unwrap(),
+    };
+    let deps: Vec<String> = graph.forward_deps[&query]
+        .iter()
+        .map(|dep| dep.key.decode_facet::<String>().unwrap())
+        .collect();
+    // Distinct deps only, in first-read order.
+    assert_eq!(deps, vec!["a", "b"]);
+}
+
+#[tokio::test]
+async fn get_if_ready_never_computes() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    input.set(&db, "a".into(), "hello".into());
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        let executions = executions.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Len",
+            move |db, key| {
+                let input = input.clone();
+                let executions = executions.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    let text = input.get(db, &key)?.expect("missing input");
+                    Ok(text.len() as u64)
+                })
+            },
+        ))
+    };
+    db.register(derived.clone());
+
+    let key = "a".to_string();
+    assert_eq!(derived.get_if_ready(&db, &key).unwrap(), None);
+    assert_eq!(executions.load(Ordering::SeqCst), 0);
+
+    derived.get(&db, key.clone()).await.unwrap();
+    assert_eq!(derived.get_if_ready(&db, &key).unwrap(), Some(5));
+
+    // Stale after a new revision, until someone brings it up to date.
+    input.set(&db, "a".into(), "hello!".into());
+    assert_eq!(derived.get_if_ready(&db, &key).unwrap(), None);
+    assert_eq!(executions.load(Ordering::SeqCst), 1);
+
+    derived.get(&db, key.clone()).await.unwrap();
+    assert_eq!(derived.get_if_ready(&db, &key).unwrap(), Some(6));
+}
+
+#[tokio::test]
+async fn get_if_cached_records_no_dependency() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    input.set(&db, "a".into(), "hello".into());
+
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "Len", move |db, key| {
+            let input = input.clone();
+            Box::pin(async move { Ok(input.get(db, &key)?.expect("missing input").len() as u64) })
+        }))
+    };
+    db.register(len.clone());
+
+    // Peeks at `Len` without depending on it.
+    let peek: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let len = len.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(3), "Peek", move |db, key| {
+            let len = len.clone();
+            Box::pin(async move { Ok(len.get_if_cached(db, &key)?.unwrap_or(0)) })
+        }))
+    };
+    db.register(peek.clone());
+
+    let key = "a".to_string();
+    assert_eq!(len.get_if_cached(&db, &key).unwrap(), None);
+    assert_eq!(len.get(&db, key.clone()).await.unwrap(), 5);
+    assert_eq!(len.get_if_cached(&db, &key).unwrap(), Some(5));
+    assert_eq!(peek.get(&db, key.clone()).await.unwrap(), 5);
+
+    input.set(&db, "a".into(), "hello!".into());
+    assert_eq!(len.get_if_cached(&db, &key).unwrap(), None);
+    assert_eq!(len.get(&db, key.clone()).await.unwrap(), 6);
+
+    // `Peek` recorded no deps, so it is backdated rather than recomputed.
+    assert_eq!(peek.get(&db, key.clone()).await.unwrap(), 5);
+}
+
+#[tokio::test]
+async fn borrowed_keys_share_cells_with_owned_keys() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.register(input.clone());
+    input.set(&db, "a".into(), "hello".into());
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        let executions = executions.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "Len", move |db, key| {
+            let input = input.clone();
+            let executions = executions.clone();
+            Box::pin(async move {
+                executions.fetch_add(1, Ordering::SeqCst);
+                let text = input.get(db, &key)?.expect("missing input");
+                Ok(text.len() as u64)
+            })
+        }))
+    };
+    db.register(derived.clone());
+
+    // A miss through a `&str` computes with the decoded owned key.
+    assert_eq!(derived.get_borrowed(&db, "a").await.unwrap(), 5);
+    assert_eq!(derived.get(&db, "a".to_string()).await.unwrap(), 5);
+    assert_eq!(derived.get_if_ready_borrowed(&db, "a").unwrap(), Some(5));
+    assert_eq!(executions.load(Ordering::SeqCst), 1);
+
+    input.set(&db, "a".into(), "hello!".into());
+    assert_eq!(derived.get_if_ready_borrowed(&db, "a").unwrap(), None);
+    assert_eq!(derived.get_borrowed(&db, "a").await.unwrap(), 6);
+    assert_eq!(executions.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn borrowed_keys_peek_and_invalidate_owned_cells() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let executions = Arc::new(AtomicUsize::new(0));
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let executions = executions.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(1),
+            "Len",
+            move |_db, key| {
+                let executions = executions.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    Ok(key.len() as u64)
+                })
+            },
+        ))
+    };
+    db.register(derived.clone());
+
+    assert_eq!(derived.get_if_cached_borrowed(&db, "abc").unwrap(), None);
+    assert_eq!(derived.get(&db, "abc".to_string()).await.unwrap(), 3);
+    assert_eq!(derived.get_if_cached_borrowed(&db, "abc").unwrap(), Some(3));
+    assert_eq!(derived.get_if_cached_borrowed(&db, "ab").unwrap(), None);
+
+    // Invalidating through a `&str` drops the cell the owned key computed.
+    let before = db.runtime().current_revision();
+    assert!(derived.invalidate_borrowed(&db, "abc").unwrap());
+    assert!(db.runtime().current_revision() > before);
+    assert!(!derived.invalidate_borrowed(&db, "abc").unwrap());
+    assert_eq!(
+        derived.get_if_cached(&db, &"abc".to_string()).unwrap(),
+        None
+    );
+    assert_eq!(derived.get(&db, "abc".to_string()).await.unwrap(), 3);
+    assert_eq!(executions.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn type_derived_kind_ids_are_stable_and_collisions_panic() {
+    let len = QueryKindId::for_types::<String, u64>();
+    assert_eq!(len, QueryKindId::for_types::<String, u64>());
+    assert_ne!(len, QueryKindId::for_types::<u64, String>());
+    assert_ne!(len, QueryKindId::for_types::<String, String>());
+
+    let mut db = TestDb::default();
+    let text: Arc<InputIngredient<String, u64>> = Arc::new(InputIngredient::new(len, "Text"));
+    db.register(text.clone());
+    // Registering the same kind again replaces it.
+    db.register(text);
+
+    let words: Arc<InputIngredient<String, u64>> = Arc::new(InputIngredient::new(len, "Words"));
+    let collision = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| db.register(words)))
+        .unwrap_err();
+    let message = collision.downcast_ref::<String>().unwrap();
+    assert!(message.contains("`Text` and `Words`"), "{message}");
+}
+
+#[tokio::test]
+async fn max_query_depth_limits_nesting() {
+    init_tracing();
+
+    let mut db = TestDb::default();
+
+    let leaf: Arc<DerivedIngredient<TestDb, u32, u32>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(1),
+        "Leaf",
+        |_db, key| Box::pin(async move { Ok(key) }),
+    ));
+    let middle: Arc<DerivedIngredient<TestDb, u32, u32>> = {
+        let leaf = leaf.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "Middle", move |db, key| {
+            let leaf = leaf.clone();
+            Box::pin(async move { Ok(leaf.get(db, key).await? + 1) })
+        }))
+    };
+    let top: Arc<DerivedIngredient<TestDb, u32, u32>> = {
+        let middle = middle.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(3), "Top", move |db, key| {
+            let middle = middle.clone();
+            Box::pin(async move { Ok(middle.get(db, key).await? + 1) })
+        }))
+    };
+    db.register(leaf.clone());
+    db.register(middle.clone());
+    db.register(top.clone());
+
+    assert_eq!(db.runtime().max_observed_depth(), 0);
+    db.runtime().set_max_query_depth(Some(2));
+    assert_eq!(db.runtime().max_query_depth(), Some(2));
+
+    let err = top.get(&db, 1).await.unwrap_err();
+    match &*err {
+        PicanteError::DepthExceeded { kind, limit, .. } => {
+            assert_eq!(*kind, QueryKindId(1));
+            assert_eq!(*limit, 2);
+        }
+        other => panic!("expected DepthExceeded, got {other:?}"),
+    }
+    assert_eq!(db.runtime().max_observed_depth(), 2);
+
+    // Shallower entry points stay within the limit.
+    assert_eq!(middle.get(&db, 2).await.unwrap(), 3);
+
+    db.runtime().set_max_query_depth(None);
+    assert_eq!(top.get(&db, 3).await.unwrap(), 5);
+    assert_eq!(db.runtime().max_observed_depth(), 3);
+}
@@ -0,0 +1,100 @@
+//! Tests for ordered call traces (`frame::enable_call_trace`).
+//!
+//! Lives in its own binary because the switch is process-wide.
+
+use picante::frame::{self, CallOutcome};
+use picante::{
+    DerivedIngredient, DynIngredient, HasRuntime, IngredientLookup, IngredientRegistry,
+    InputIngredient, Key, PicanteError, QueryKindId, Runtime,
+};
+use std::sync::Arc;
+
+#[derive(Default)]
+struct TestDb {
+    runtime: Runtime,
+    ingredients: IngredientRegistry<TestDb>,
+}
+
+impl HasRuntime for TestDb {
+    fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+}
+
+impl IngredientLookup for TestDb {
+    fn ingredient(&self, kind: QueryKindId) -> Option<&dyn DynIngredient<Self>> {
+        self.ingredients.ingredient(kind)
+    }
+}
+
+#[tokio::test]
+async fn call_traces_keep_order_and_repetition() {
+    let mut db = TestDb::default();
+
+    let text: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.ingredients.register(text.clone());
+
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let text = text.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "Len", move |db, key| {
+            let text = text.clone();
+            Box::pin(async move {
+                match text.get(db, &key)? {
+                    Some(s) => Ok(s.len() as u64),
+                    None => Err(Arc::new(PicanteError::Cache {
+                        message: format!("no text for {key}"),
+                    })),
+                }
+            })
+        }))
+    };
+    db.ingredients.register(len.clone());
+
+    // Reads `Len` twice and the input once, then a missing key.
+    let summary: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let text = text.clone();
+        let len = len.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(3), "Summary", move |db, key| {
+            let text = text.clone();
+            let len = len.clone();
+            Box::pin(async move {
+                let a = len.get(db, key.clone()).await?;
+                let b = len.get(db, key.clone()).await?;
+                text.get(db, &key)?;
+                let missing = len.get(db, "missing".to_string()).await.unwrap_or(0);
+                Ok(a + b + missing)
+            })
+        }))
+    };
+    db.ingredients.register(summary.clone());
+
+    text.set(&db, "a".into(), "hello".into());
+
+    // Nothing is recorded while tracing is off.
+    summary.get(&db, "a".into()).await.unwrap();
+    assert!(summary.last_call_trace(&"a".into()).unwrap().is_none());
+
+    frame::enable_call_trace();
+    text.set(&db, "a".into(), "hello!".into());
+    assert_eq!(summary.get(&db, "a".into()).await.unwrap(), 12);
+    frame::disable_call_trace();
+
+    let trace = summary.last_call_trace(&"a".into()).unwrap().unwrap();
+    let key = |s: &str| Key::encode_facet(&s.to_string()).unwrap();
+    let calls: Vec<_> = trace.iter().map(|c| (c.kind, c.key.clone(), c.outcome)).collect();
+    assert_eq!(
+        calls,
+        vec![
+            (QueryKindId(2), key("a"), CallOutcome::Recomputed),
+            (QueryKindId(2), key("a"), CallOutcome::Hit),
+            (QueryKindId(1), key("a"), CallOutcome::Read),
+            (QueryKindId(2), key("missing"), CallOutcome::Error),
+        ]
+    );
+
+    // Nested computations keep their own traces.
+    let inner = len.last_call_trace(&"a".into()).unwrap().unwrap();
+    assert_eq!(inner.len(), 1);
+    assert_eq!(inner[0].outcome, CallOutcome::Read);
+}
@@ -1,9 +1,10 @@
 //! Integration tests for debugging and observability tools.
 
 use picante::debug::{CacheStats, DependencyGraph, TraceAnalysis, TraceCollector};
+use picante::runtime::RuntimeEvent;
 use picante::{
-    DerivedIngredient, HasRuntime, IngredientLookup, IngredientRegistry, InputIngredient,
-    QueryKindId, Runtime,
+    Dep, DerivedIngredient, DynKey, HasRuntime, IngredientLookup, IngredientRegistry,
+    InputIngredient, Key, PicanteError, QueryKindId, Runtime,
 };
 use std::sync::Arc;
 
@@ -74,6 +75,56 @@ async fn test_dependency_graph_visualization() {
     assert!(dot.contains("->"));
 }
 
+#[tokio::test]
+async fn test_registered_kind_names_label_events_errors_and_graphs() {
+    let mut db = TestDb::default();
+    db.runtime.register_kind(QueryKindId(1), "Text");
+    db.runtime.register_kind(QueryKindId(2), "Len");
+
+    let input: Arc<InputIngredient<u32, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    let derived: Arc<DerivedIngredient<TestDb, u32, usize>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Len",
+            move |db, key| {
+                let input = input.clone();
+                Box::pin(async move { Ok(input.get(db, &key)?.unwrap_or_default().len()) })
+            },
+        ))
+    };
+    db.ingredients.register(input.clone());
+    db.ingredients.register(derived.clone());
+
+    let mut events = db.runtime.subscribe_events();
+    input.set(&db, 1, "hello".to_string());
+    let event = loop {
+        let event = events.recv().await.unwrap();
+        if matches!(event, RuntimeEvent::InputSet { .. }) {
+            break event;
+        }
+    };
+    let shown = db.runtime.display_event(&event);
+    assert!(shown.contains("`Text` (kind 1)"), "{shown}");
+
+    let err = PicanteError::MissingInputValue {
+        kind: QueryKindId(1),
+        key_hash: 0,
+    };
+    let shown = db.runtime.display_error(&err);
+    assert!(shown.contains("`Text`"), "{shown}");
+
+    derived.get(&db, 1).await.unwrap();
+    let mut output = Vec::new();
+    DependencyGraph::from_runtime(db.runtime())
+        .write_dot_to(&mut output)
+        .unwrap();
+    let dot = String::from_utf8(output).unwrap();
+    assert!(dot.contains("label=\"Len\\n"), "{dot}");
+    assert!(dot.contains("label=\"Text\\n"), "{dot}");
+}
+
 #[tokio::test]
 async fn test_cache_statistics() {
     let mut db = TestDb::default();
@@ -276,3 +327,327 @@ async fn test_dependency_graph_path_finding() {
         "Should have chain of dependencies"
     );
 }
+
+#[tokio::test]
+async fn test_in_flight_lists_running_queries() {
+    let mut db = TestDb::default();
+    let gate = Arc::new(tokio::sync::Notify::new());
+
+    let inner: Arc<DerivedIngredient<TestDb, u32, u32>> = {
+        let gate = gate.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(1),
+            "inner",
+            move |_db, key| {
+                let gate = gate.clone();
+                Box::pin(async move {
+                    gate.notified().await;
+                    Ok(key + 1)
+                })
+            },
+        ))
+    };
+    let outer: Arc<DerivedIngredient<TestDb, u32, u32>> = {
+        let inner = inner.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "outer",
+            move |db, key| {
+                let inner = inner.clone();
+                Box::pin(async move { Ok(inner.get(db, key).await? * 2) })
+            },
+        ))
+    };
+    db.ingredients.register(inner.clone());
+    db.ingredients.register(outer.clone());
+    let db = Arc::new(db);
+
+    assert!(db.runtime().in_flight().is_empty());
+
+    let task = tokio::spawn({
+        let db = db.clone();
+        let outer = outer.clone();
+        async move { outer.get(&*db, 20).await }
+    });
+
+    while db.runtime().in_flight().len() < 2 {
+        tokio::task::yield_now().await;
+    }
+
+    let running = db.runtime().in_flight();
+    let names: Vec<(&str, usize)> = running.iter().map(|q| (q.kind_name, q.depth)).collect();
+    // Longest-running first: the outer query started before the inner one.
+    assert_eq!(names, vec![("outer", 0), ("inner", 1)]);
+    assert!(running[0].elapsed >= running[1].elapsed);
+
+    gate.notify_one();
+    assert_eq!(task.await.unwrap().unwrap(), 42);
+    assert!(db.runtime().in_flight().is_empty());
+}
+
+#[tokio::test]
+async fn test_in_flight_elapsed_uses_runtime_clock() {
+    use picante::clock::ManualClock;
+    use std::time::Duration;
+
+    let clock = Arc::new(ManualClock::new());
+    let mut db = TestDb {
+        runtime: Runtime::new().with_clock(clock.clone()),
+        ingredients: IngredientRegistry::new(),
+    };
+    let gate = Arc::new(tokio::sync::Notify::new());
+    let slow: Arc<DerivedIngredient<TestDb, u32, u32>> = {
+        let gate = gate.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(1),
+            "slow",
+            move |_db, key| {
+                let gate = gate.clone();
+                Box::pin(async move {
+                    gate.notified().await;
+                    Ok(key)
+                })
+            },
+        ))
+    };
+    db.ingredients.register(slow.clone());
+    let db = Arc::new(db);
+
+    let task = tokio::spawn({
+        let db = db.clone();
+        let slow = slow.clone();
+        async move { slow.get(&*db, 7).await }
+    });
+    while db.runtime().in_flight().is_empty() {
+        tokio::task::yield_now().await;
+    }
+
+    assert_eq!(db.runtime().in_flight()[0].elapsed, Duration::ZERO);
+    clock.advance(Duration::from_secs(90));
+    assert_eq!(db.runtime().in_flight()[0].elapsed, Duration::from_secs(90));
+
+    gate.notify_one();
+    assert_eq!(task.await.unwrap().unwrap(), 7);
+}
+
+#[tokio::test]
+async fn test_predict_invalidation_is_read_only() {
+    let mut db = TestDb::default();
+
+    let input: Arc<InputIngredient<u32, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "input"));
+
+    let len: Arc<DerivedIngredient<TestDb, u32, usize>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "len", move |db, key| {
+            let input = input.clone();
+            Box::pin(async move { Ok(input.get(db, &key)?.unwrap_or_default().len()) })
+        }))
+    };
+
+    let double: Arc<DerivedIngredient<TestDb, u32, usize>> = {
+        let len = len.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(3), "double", move |db, key| {
+            let len = len.clone();
+            Box::pin(async move { Ok(len.get(db, key).await? * 2) })
+        }))
+    };
+
+    db.ingredients.register(input.clone());
+    db.ingredients.register(len.clone());
+    db.ingredients.register(double.clone());
+
+    input.set(&db, 1, "hello".to_string());
+    input.set(&db, 2, "world".to_string());
+    double.get(&db, 1).await.unwrap();
+    double.get(&db, 2).await.unwrap();
+
+    let key = |n: u32| Key::encode_facet(&n).unwrap();
+    let rev = db.runtime().current_revision();
+    let mut events = db.runtime().subscribe_events();
+
+    let affected = db.runtime().predict_invalidation(Dep {
+        kind: QueryKindId(1),
+        key: key(1),
+    });
+    assert_eq!(
+        affected,
+        vec![
+            DynKey {
+                kind: QueryKindId(2),
+                key: key(1),
+            },
+            DynKey {
+                kind: QueryKindId(3),
+                key: key(1),
+            },
+        ]
+    );
+
+    // Nothing depends on an unread key.
+    let unread = Dep {
+        kind: QueryKindId(1),
+        key: key(3),
+    };
+    assert!(db.runtime().predict_invalidation(unread).is_empty());
+
+    assert_eq!(db.runtime().current_revision(), rev);
+    assert!(events.try_recv().is_err());
+    assert_eq!(double.get(&db, 1).await.unwrap(), 10);
+}
+
+#[tokio::test]
+async fn test_quiescent_waits_for_running_queries() {
+    use std::time::Duration;
+
+    let mut db = TestDb::default();
+    let gate = Arc::new(tokio::sync::Notify::new());
+
+    let slow: Arc<DerivedIngredient<TestDb, u32, u32>> = {
+        let gate = gate.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(1), "slow", move |_db, key| {
+            let gate = gate.clone();
+            Box::pin(async move {
+                gate.notified().await;
+                Ok(key * 10)
+            })
+        }))
+    };
+    db.ingredients.register(slow.clone());
+    let db = Arc::new(db);
+
+    // Nothing running: resolves right away.
+    db.runtime().quiescent().await;
+
+    let task = tokio::spawn({
+        let db = db.clone();
+        let slow = slow.clone();
+        async move { slow.get(&*db, 4).await }
+    });
+    while db.runtime().in_flight().is_empty() {
+        tokio::task::yield_now().await;
+    }
+
+    let waiting = tokio::time::timeout(Duration::from_millis(20), db.runtime().quiescent()).await;
+    assert!(waiting.is_err(), "quiescent resolved while a query was running");
+
+    let quiescent = tokio::spawn({
+        let db = db.clone();
+        async move { db.runtime().quiescent().await }
+    });
+    gate.notify_one();
+    quiescent.await.unwrap();
+    assert!(db.runtime().in_flight().is_empty());
+    assert_eq!(task.await.unwrap().unwrap(), 40);
+}
+
+#[tokio::test]
+async fn test_dep_index_stays_consistent_as_deps_change() {
+    let mut db = TestDb::default();
+
+    let input: Arc<InputIngredient<u32, u32>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "input"));
+
+    // Reads key 0, then the key it points to, so its deps change with the data.
+    let follow: Arc<DerivedIngredient<TestDb, u32, u32>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "follow", move |db, _key| {
+            let input = input.clone();
+            Box::pin(async move {
+                let next = input.get(db, &0)?.unwrap_or_default();
+                Ok(input.get(db, &next)?.unwrap_or_default())
+            })
+        }))
+    };
+
+    db.ingredients.register(input.clone());
+    db.ingredients.register(follow.clone());
+
+    db.runtime().debug_verify_dep_index().unwrap();
+
+    input.set(&db, 0, 1);
+    input.set(&db, 1, 10);
+    input.set(&db, 2, 20);
+    assert_eq!(follow.get(&db, 0).await.unwrap(), 10);
+    db.runtime().debug_verify_dep_index().unwrap();
+
+    input.set(&db, 0, 2);
+    assert_eq!(follow.get(&db, 0).await.unwrap(), 20);
+    db.runtime().debug_verify_dep_index().unwrap();
+
+    let query = DynKey {
+        kind: QueryKindId(2),
+        key: Key::encode_facet(&0u32).unwrap(),
+    };
+    db.runtime().update_query_deps(query, Arc::from([]));
+    db.runtime().debug_verify_dep_index().unwrap();
+    assert!(db.runtime().reverse_deps_snapshot().is_empty());
+}
+
+/// A `Write` whose bytes can be inspected after the runtime takes ownership of it.
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_chrome_trace_records_nested_computes() {
+    let mut db = TestDb::default();
+
+    let input: Arc<InputIngredient<u32, u32>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "input"));
+    let inner: Arc<DerivedIngredient<TestDb, u32, u32>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "inner", move |db, key| {
+            let input = input.clone();
+            Box::pin(async move { Ok(input.get(db, &key)?.unwrap_or_default() * 2) })
+        }))
+    };
+    let outer: Arc<DerivedIngredient<TestDb, u32, u32>> = {
+        let inner = inner.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(3), "outer \"quoted\"", move |db, key| {
+            let inner = inner.clone();
+            Box::pin(async move { Ok(inner.get(db, key).await? + 1) })
+        }))
+    };
+    db.ingredients.register(input.clone());
+    db.ingredients.register(inner.clone());
+    db.ingredients.register(outer.clone());
+
+    input.set(&db, 1, 20);
+    let buf = SharedBuf::default();
+    db.runtime().start_chrome_trace(buf.clone()).unwrap();
+    assert_eq!(outer.get(&db, 1).await.unwrap(), 41);
+    // Cache hits are not computes.
+    assert_eq!(outer.get(&db, 1).await.unwrap(), 41);
+    db.runtime().stop_chrome_trace().unwrap();
+
+    // Not recorded once stopped.
+    input.set(&db, 1, 30);
+    assert_eq!(outer.get(&db, 1).await.unwrap(), 61);
+
+    let text = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+    assert!(text.starts_with("{\"traceEvents\":["), "{text}");
+    assert!(text.trim_end().ends_with("]}"), "{text}");
+    assert_eq!(text.matches("\"ph\":\"X\"").count(), 2, "{text}");
+
+    // The inner compute finishes first and sits one level deeper, on the same task.
+    let inner_at = text.find("\"name\":\"inner\"").unwrap();
+    let outer_at = text.find("\"name\":\"outer \\\"quoted\\\"\"").unwrap();
+    assert!(inner_at < outer_at, "{text}");
+    assert!(text.contains("\"kind\":2,"), "{text}");
+    assert!(text.contains("\"depth\":1}"), "{text}");
+    assert!(text.contains("\"depth\":0}"), "{text}");
+
+    // Stopping again is a no-op.
+    db.runtime().stop_chrome_trace().unwrap();
+}
@@ -0,0 +1,193 @@
+use picante::db::{DynIngredient, IngredientLookup, IngredientRegistry};
+use picante::eager::{drive_eager_outputs, recompute_eager_outputs};
+use picante::ingredient::{DerivedIngredient, InputIngredient};
+use picante::key::{DynKey, Key, QueryKindId};
+use picante::runtime::{HasRuntime, Runtime, RuntimeEvent};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+#[derive(Default)]
+struct TestDb {
+    runtime: Runtime,
+    ingredients: IngredientRegistry<TestDb>,
+}
+
+impl HasRuntime for TestDb {
+    fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+}
+
+impl IngredientLookup for TestDb {
+    fn ingredient(&self, kind: QueryKindId) -> Option<&dyn DynIngredient<Self>> {
+        self.ingredients.ingredient(kind)
+    }
+}
+
+type Setup = (
+    TestDb,
+    Arc<InputIngredient<String, String>>,
+    Arc<DerivedIngredient<TestDb, String, u64>>,
+    Arc<AtomicUsize>,
+);
+
+fn setup() -> Setup {
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.ingredients.register(input.clone());
+
+    let exec = Arc::new(AtomicUsize::new(0));
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        let exec = exec.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Len",
+            move |db, key| {
+                let input = input.clone();
+                let exec = exec.clone();
+                Box::pin(async move {
+                    exec.fetch_add(1, Ordering::SeqCst);
+                    let text = input.get(db, &key)?.unwrap_or_default();
+                    Ok(text.len() as u64)
+                })
+            },
+        ))
+    };
+    db.ingredients.register(derived.clone());
+
+    (db, input, derived, exec)
+}
+
+fn len_key(key: &str) -> DynKey {
+    DynKey {
+        kind: QueryKindId(2),
+        key: Key::encode_facet(&key.to_string()).unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn input_changes_schedule_dependent_eager_outputs() {
+    let (db, input, derived, exec) = setup();
+    input.set(&db, "a".into(), "hello".into());
+    input.set(&db, "b".into(), "bye".into());
+
+    db.runtime().register_eager_output(len_key("a"));
+    assert_eq!(db.runtime().eager_outputs(), vec![len_key("a")]);
+
+    // Registration schedules the first computation, which records dependency edges.
+    assert_eq!(recompute_eager_outputs(&db).await, 1);
+    assert_eq!(exec.load(Ordering::SeqCst), 1);
+
+    // Unrelated input: nothing to do.
+    input.set(&db, "b".into(), "goodbye".into());
+    assert_eq!(recompute_eager_outputs(&db).await, 0);
+    assert_eq!(exec.load(Ordering::SeqCst), 1);
+
+    // A burst of changes coalesces into a single recomputation at the latest revision.
+    input.set(&db, "a".into(), "hello!".into());
+    input.set(&db, "a".into(), "hello!!".into());
+    assert_eq!(recompute_eager_outputs(&db).await, 1);
+    assert_eq!(exec.load(Ordering::SeqCst), 2);
+
+    // Reading the output is now a cache hit.
+    assert_eq!(derived.get(&db, "a".into()).await.unwrap(), 7);
+    assert_eq!(exec.load(Ordering::SeqCst), 2);
+
+    assert!(db.runtime().unregister_eager_output(&len_key("a")));
+    input.set(&db, "a".into(), "x".into());
+    assert_eq!(recompute_eager_outputs(&db).await, 0);
+}
+
+#[tokio::test]
+async fn driver_emits_recomputed_events() {
+    let (db, input, _derived, exec) = setup();
+    let db = Arc::new(db);
+    input.set(&*db, "a".into(), "hello".into());
+
+    db.runtime().register_eager_output(len_key("a"));
+
+    let driver = tokio::spawn({
+        let db = db.clone();
+        async move { drive_eager_outputs(&*db).await }
+    });
+
+    // Subscribes eagerly, so events emitted before the returned future is polled are seen.
+    let next_recomputed = |db: &TestDb| {
+        let mut events = db.runtime().subscribe_events();
+        async move {
+            loop {
+                if let RuntimeEvent::EagerOutputRecomputed {
+                    key,
+                    changed_at,
+                    value,
+                    ..
+                } = events.recv().await.unwrap()
+                {
+                    let len = value.and_then(|value| value.downcast_ref::<u64>().copied());
+                    return (key, changed_at, len);
+                }
+            }
+        }
+    };
+
+    let (key, first_changed_at, len) =
+        tokio::time::timeout(Duration::from_secs(5), next_recomputed(&db))
+            .await
+            .unwrap();
+    assert_eq!(key, len_key("a").key);
+    assert_eq!(len, Some(5));
+    assert_eq!(exec.load(Ordering::SeqCst), 1);
+
+    let waiter = tokio::spawn(next_recomputed(&db));
+    let rev = input.set(&*db, "a".into(), "hello world".into());
+    let (_, changed_at, len) = tokio::time::timeout(Duration::from_secs(5), waiter)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(changed_at, rev);
+    assert_eq!(len, Some(11));
+    assert!(changed_at > first_changed_at);
+    assert_eq!(exec.load(Ordering::SeqCst), 2);
+
+    driver.abort();
+}
+
+#[tokio::test]
+async fn driver_stops_on_shutdown() {
+    let (db, _input, _derived, _exec) = setup();
+    let db = Arc::new(db);
+    let mut events = db.runtime().subscribe_events();
+
+    let driver = tokio::spawn({
+        let db = db.clone();
+        async move { drive_eager_outputs(&*db).await }
+    });
+    let signal = db.runtime().shutdown_signal();
+
+    assert!(!db.runtime().is_shutting_down());
+    db.runtime().begin_shutdown();
+    db.runtime().begin_shutdown();
+    assert!(db.runtime().is_shutting_down());
+
+    tokio::time::timeout(Duration::from_secs(5), driver)
+        .await
+        .expect("driver should stop after shutdown")
+        .unwrap();
+    tokio::time::timeout(Duration::from_secs(5), signal)
+        .await
+        .unwrap();
+    // Signals taken after shutdown resolve immediately.
+    tokio::time::timeout(Duration::from_secs(5), db.runtime().shutdown_signal())
+        .await
+        .unwrap();
+
+    // Only the first call emits an event.
+    assert!(matches!(
+        events.try_recv(),
+        Ok(RuntimeEvent::ShuttingDown { .. })
+    ));
+    assert!(events.try_recv().is_err());
+}
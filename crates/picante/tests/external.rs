@@ -0,0 +1,112 @@
+use picante::db::{DynIngredient, IngredientLookup, IngredientRegistry};
+use picante::ingredient::{DerivedIngredient, ExternalInput, InputIngredient};
+use picante::key::QueryKindId;
+use picante::runtime::{HasRuntime, Runtime};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+#[derive(Default)]
+struct TestDb {
+    runtime: Runtime,
+    ingredients: IngredientRegistry<TestDb>,
+}
+
+impl HasRuntime for TestDb {
+    fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+}
+
+impl IngredientLookup for TestDb {
+    fn ingredient(&self, kind: QueryKindId) -> Option<&dyn DynIngredient<Self>> {
+        self.ingredients.ingredient(kind)
+    }
+}
+
+fn temp_file(name: &str) -> std::path::PathBuf {
+    let pid = std::process::id();
+    let nanos = SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    std::env::temp_dir().join(format!("{name}-{pid}-{nanos}"))
+}
+
+/// Rewrite `path` and move its mtime forward, so the change is visible even on filesystems
+/// with coarse timestamps.
+fn rewrite(path: &std::path::Path, contents: &str, mtime: SystemTime) {
+    std::fs::write(path, contents).unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(mtime)
+        .unwrap();
+}
+
+#[tokio::test]
+async fn file_changes_invalidate_dependents() {
+    let path = temp_file("picante-external.txt");
+    let path_str = path.to_str().unwrap().to_string();
+    let base = SystemTime::now() - Duration::from_secs(3600);
+    rewrite(&path, "hello", base);
+
+    let mut db = TestDb::default();
+    let files = Arc::new(ExternalInput::new(QueryKindId(1), "Files"));
+    db.ingredients.register(files.clone());
+    let other: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(2), "Other"));
+    db.ingredients.register(other.clone());
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let file_len: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let files = files.clone();
+        let executions = executions.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(3),
+            "FileLen",
+            move |db, path| {
+                let files = files.clone();
+                let executions = executions.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    files.track(db, &path).await?;
+                    let text = tokio::fs::read_to_string(&path).await.unwrap_or_default();
+                    Ok(text.len() as u64)
+                })
+            },
+        ))
+    };
+    db.ingredients.register(file_len.clone());
+
+    assert_eq!(file_len.get(&db, path_str.clone()).await.unwrap(), 5);
+    assert_eq!(file_len.get(&db, path_str.clone()).await.unwrap(), 5);
+    assert_eq!(executions.load(Ordering::SeqCst), 1);
+    assert_eq!(files.last_seen(&path_str), Some(base));
+
+    // Unrelated revision bump with an untouched file: revalidation stats it and keeps the value.
+    other.set(&db, "x".into(), "y".into());
+    assert_eq!(file_len.get(&db, path_str.clone()).await.unwrap(), 5);
+    assert_eq!(executions.load(Ordering::SeqCst), 1);
+
+    // Revalidation notices the new mtime.
+    rewrite(&path, "hello world", base + Duration::from_secs(1));
+    other.set(&db, "x".into(), "z".into());
+    assert_eq!(file_len.get(&db, path_str.clone()).await.unwrap(), 11);
+    assert_eq!(executions.load(Ordering::SeqCst), 2);
+
+    // With no other input changing, `refresh` is what surfaces the change.
+    rewrite(&path, "hi", base + Duration::from_secs(2));
+    assert_eq!(file_len.get(&db, path_str.clone()).await.unwrap(), 11);
+    assert_eq!(files.refresh(&db).await, 1);
+    assert_eq!(files.refresh(&db).await, 0);
+    assert_eq!(file_len.get(&db, path_str.clone()).await.unwrap(), 2);
+    assert_eq!(executions.load(Ordering::SeqCst), 3);
+
+    // Deleting the file is a change too.
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(files.refresh(&db).await, 1);
+    assert_eq!(files.last_seen(&path_str), None);
+    assert_eq!(file_len.get(&db, path_str.clone()).await.unwrap(), 0);
+}
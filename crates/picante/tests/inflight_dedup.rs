@@ -3,7 +3,7 @@
 //! These tests verify that concurrent requests for the same tracked query
 //! with identical parameters coalesce into a single computation.
 
-use picante::PicanteResult;
+use picante::{HasRuntime, PicanteResult};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
@@ -326,3 +326,53 @@ async fn error_propagation_to_followers() -> PicanteResult<()> {
 
     Ok(())
 }
+
+/// A caller at a newer revision waits for a compute still running at an older revision, and
+/// reuses its result when the change in between didn't touch the query's dependencies.
+#[tokio::test(flavor = "current_thread")]
+async fn newer_revision_waits_for_older_inflight_compute() -> PicanteResult<()> {
+    static OLDER_COMPUTE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[picante::input]
+    pub struct OlderConfig {
+        #[key]
+        pub id: u32,
+        pub value: u32,
+    }
+
+    #[picante::tracked]
+    pub async fn older_slow_compute<DB: OlderDatabaseTrait>(
+        db: &DB,
+        config: OlderConfig,
+    ) -> PicanteResult<u64> {
+        OLDER_COMPUTE_COUNT.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let value = config.value(db)?;
+        Ok(value as u64 * 2)
+    }
+
+    #[picante::db(inputs(OlderConfig), tracked(older_slow_compute))]
+    pub struct OlderDatabase {}
+
+    OLDER_COMPUTE_COUNT.store(0, Ordering::SeqCst);
+
+    let db = Arc::new(OlderDatabase::new());
+    let config = OlderConfig::new(&*db, 1, 42)?;
+
+    // Start computing on a snapshot taken at the current revision.
+    let snapshot = Arc::new(OlderDatabaseSnapshot::from_database(&db).await);
+    let older = tokio::spawn(async move { older_slow_compute(&*snapshot, config).await });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+
+    // Move the live database to a newer revision without touching `config`.
+    let before = db.runtime().current_revision();
+    OlderConfig::new(&*db, 2, 7)?;
+    assert!(db.runtime().current_revision() > before);
+
+    // The newer caller waits for the older compute and revalidates its result.
+    assert_eq!(older_slow_compute(&*db, config).await?, 84);
+    assert_eq!(older.await.expect("task panicked")?, 84);
+    assert_eq!(OLDER_COMPUTE_COUNT.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
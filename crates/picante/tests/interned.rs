@@ -1,8 +1,10 @@
 use picante::db::{DynIngredient, IngredientLookup, IngredientRegistry};
-use picante::ingredient::InternedIngredient;
+use picante::error::PicanteError;
+use picante::ingredient::{InternId, InternedIngredient};
 use picante::key::QueryKindId;
 use picante::persist::{load_cache, save_cache};
 use picante::runtime::{HasRuntime, Runtime};
+use std::collections::HashSet;
 use std::sync::Arc;
 
 fn init_tracing() {
@@ -88,6 +90,312 @@ async fn interned_dedups_and_persists() {
     let _ = tokio::fs::remove_file(&cache_path).await;
 }
 
+#[test]
+fn intern_ref_clones_only_on_miss() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug, PartialEq, Eq, facet::Facet)]
+    struct Symbol {
+        name: String,
+    }
+
+    impl Clone for Symbol {
+        fn clone(&self) -> Self {
+            CLONES.fetch_add(1, Ordering::SeqCst);
+            Self {
+                name: self.name.clone(),
+            }
+        }
+    }
+
+    let symbols: InternedIngredient<Symbol> = InternedIngredient::new(QueryKindId(1), "Symbols");
+    let foo = Symbol { name: "foo".into() };
+
+    let id = symbols.intern_ref(&foo).unwrap();
+    assert_eq!(CLONES.load(Ordering::SeqCst), 1);
+
+    for _ in 0..10 {
+        assert_eq!(symbols.intern_ref(&foo).unwrap(), id);
+    }
+    assert_eq!(CLONES.load(Ordering::SeqCst), 1);
+
+    // Shares the id space with by-value interning.
+    assert_eq!(symbols.intern(foo).unwrap(), id);
+    let bar = symbols.intern(Symbol { name: "bar".into() }).unwrap();
+    assert_ne!(bar, id);
+    assert_eq!(symbols.intern_ref(&Symbol { name: "bar".into() }).unwrap(), bar);
+    assert_eq!(CLONES.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn intern_status_reports_new_entries_once() {
+    let words: Arc<InternedIngredient<String>> =
+        Arc::new(InternedIngredient::new(QueryKindId(1), "Words"));
+
+    let (hello, is_new) = words.intern_status("hello".to_string()).unwrap();
+    assert!(is_new);
+    assert_eq!(words.intern_status("hello".to_string()).unwrap(), (hello, false));
+    assert_eq!(words.intern("hello".to_string()).unwrap(), hello);
+
+    // Racing interners: exactly one of them creates the entry.
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let words = words.clone();
+            std::thread::spawn(move || words.intern_status("world".to_string()).unwrap())
+        })
+        .collect();
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(results.iter().filter(|(_, is_new)| *is_new).count(), 1);
+    assert!(results.iter().all(|(id, _)| *id == results[0].0));
+    assert_ne!(results[0].0, hello);
+}
+
+#[test]
+fn lookup_finds_interned_values_without_interning() {
+    let words: InternedIngredient<String> = InternedIngredient::new(QueryKindId(1), "Words");
+
+    assert_eq!(words.lookup(&"hello".to_string()).unwrap(), None);
+    assert!(words.is_empty());
+
+    let hello = words.intern("hello".to_string()).unwrap();
+    assert_eq!(words.lookup(&"hello".to_string()).unwrap(), Some(hello));
+    assert_eq!(words.lookup(&"world".to_string()).unwrap(), None);
+    assert_eq!(words.len(), 1);
+
+    // Looking values up doesn't use up ids.
+    let world = words.intern("world".to_string()).unwrap();
+    assert_eq!(world.0, hello.0 + 1);
+}
+
+#[test]
+fn retain_sweeps_dead_values_without_reusing_ids() {
+    let db = TestDb::default();
+    let words: InternedIngredient<String> =
+        InternedIngredient::with_max_entries(QueryKindId(1), "Words", 2);
+    let hello = words.intern("hello".to_string()).unwrap();
+    let world = words.intern("world".to_string()).unwrap();
+
+    assert_eq!(words.retain(&HashSet::from([hello])), 1);
+    assert_eq!(words.len(), 1);
+    assert_eq!(*words.get(&db, hello).unwrap(), "hello");
+    let err = words.get(&db, world).unwrap_err();
+    assert!(
+        matches!(&*err, PicanteError::MissingInternedValue { id, .. } if *id == world.0),
+        "{err}"
+    );
+    assert_eq!(words.lookup(&"world".to_string()).unwrap(), None);
+
+    // The freed slot can be reused, the freed id can't.
+    let again = words.intern("world".to_string()).unwrap();
+    assert_ne!(again, world);
+    assert_eq!(*words.get(&db, again).unwrap(), "world");
+}
+
+#[test]
+fn bounded_interning_rejects_new_values_when_full() {
+    use picante::PicanteError;
+    use picante::persist::PersistableIngredient;
+
+    let words: InternedIngredient<String> =
+        InternedIngredient::with_max_entries(QueryKindId(1), "Words", 2);
+
+    let a = words.intern("a".to_string()).unwrap();
+    let b = words.intern_ref(&"b".to_string()).unwrap();
+    assert_eq!(words.len(), 2);
+
+    let err = words.intern("c".to_string()).unwrap_err();
+    match &*err {
+        PicanteError::InternCapacityExceeded { kind, max } => {
+            assert_eq!(*kind, QueryKindId(1));
+            assert_eq!(*max, 2);
+        }
+        other => panic!("expected InternCapacityExceeded, got {other:?}"),
+    }
+    assert!(words.intern_status("c".to_string()).is_err());
+    assert_eq!(words.len(), 2);
+
+    // Values already in the table still resolve when it's full.
+    assert_eq!(words.intern("a".to_string()).unwrap(), a);
+    assert_eq!(words.intern_status("b".to_string()).unwrap(), (b, false));
+
+    // Clearing frees the capacity again.
+    words.clear();
+    assert!(words.is_empty());
+    words.intern("c".to_string()).unwrap();
+}
+
+#[test]
+fn interned_handle_caches_the_resolved_value() {
+    use picante::persist::PersistableIngredient;
+
+    let db = TestDb::default();
+    let strings: Arc<InternedIngredient<String>> =
+        Arc::new(InternedIngredient::new(QueryKindId(1), "Strings"));
+
+    let id = strings.intern("hello".to_string()).unwrap();
+    let handle = strings.handle(id);
+    assert_eq!(handle.id(), id);
+    assert_eq!(handle, strings.handle(id).clone());
+
+    let first = handle.resolve(&db).unwrap();
+    assert_eq!(first.as_str(), "hello");
+    assert!(Arc::ptr_eq(&first, &handle.resolve(&db).unwrap()));
+
+    // A resolved handle keeps its value alive after the ingredient is cleared; an
+    // unresolved one reports its id as stale.
+    let unresolved = strings.handle(id);
+    strings.clear();
+    assert_eq!(handle.resolve(&db).unwrap().as_str(), "hello");
+    assert!(unresolved.resolve(&db).is_err());
+}
+
+#[test]
+fn ids_from_before_a_clear_are_rejected_as_stale() {
+    use picante::persist::PersistableIngredient;
+
+    let db = TestDb::default();
+    let strings: Arc<InternedIngredient<String>> =
+        Arc::new(InternedIngredient::new(QueryKindId(1), "Strings"));
+
+    let old = strings.intern("old".to_string()).unwrap();
+    let generation = strings.generation();
+    let handle = strings.handle(old);
+    assert_eq!(handle.generation(), generation);
+
+    // Ids keep counting up across a clear, so the old id names nothing new.
+    strings.clear();
+    assert_eq!(strings.generation(), generation + 1);
+    let new = strings.intern("new".to_string()).unwrap();
+    assert_ne!(new, old);
+    assert_eq!(strings.get(&db, new).unwrap().as_str(), "new");
+
+    for err in [
+        strings.get(&db, old).unwrap_err(),
+        strings.get_in_generation(&db, old, generation).unwrap_err(),
+        handle.resolve(&db).unwrap_err(),
+    ] {
+        match &*err {
+            PicanteError::StaleInternId {
+                id,
+                generation: minted,
+                current,
+                ..
+            } => {
+                assert_eq!(*id, old.0);
+                assert_eq!(*minted, generation);
+                assert_eq!(*current, generation + 1);
+            }
+            other => panic!("expected StaleInternId, got {other:?}"),
+        }
+    }
+
+    // Ids the current generation never minted are just missing.
+    assert!(matches!(
+        &*strings.get(&db, InternId(new.0 + 1)).unwrap_err(),
+        PicanteError::MissingInternedValue { .. }
+    ));
+
+    let current = strings.generation();
+    let value = strings.get_in_generation(&db, new, current).unwrap();
+    assert_eq!(value.as_str(), "new");
+    assert_eq!(strings.handle(new).resolve(&db).unwrap().as_str(), "new");
+}
+
+#[tokio::test]
+async fn ids_reused_by_a_loaded_cache_are_caught_by_their_generation() {
+    use picante::persist::PersistableIngredient;
+
+    let db = TestDb::default();
+    let strings: Arc<InternedIngredient<String>> =
+        Arc::new(InternedIngredient::new(QueryKindId(1), "Strings"));
+    let old = strings.intern("old".to_string()).unwrap();
+    let generation = strings.generation();
+
+    let saved: InternedIngredient<String> = InternedIngredient::new(QueryKindId(1), "Strings");
+    let loaded = saved.intern("loaded".to_string()).unwrap();
+    assert_eq!(loaded, old);
+    strings
+        .load_records(saved.save_records().await.unwrap())
+        .unwrap();
+
+    // The loaded table reuses the id: only the generation tells the two apart.
+    assert_eq!(strings.get(&db, old).unwrap().as_str(), "loaded");
+    assert!(matches!(
+        &*strings.get_in_generation(&db, old, generation).unwrap_err(),
+        PicanteError::StaleInternId { .. }
+    ));
+
+    // Values interned after the load don't reuse ids from before it either.
+    let fresh = strings.intern("fresh".to_string()).unwrap();
+    assert!(fresh.0 > old.0);
+}
+
+#[test]
+fn intern_many_dedups_and_keeps_input_order() {
+    let db = TestDb::default();
+    let words: InternedIngredient<String> = InternedIngredient::new(QueryKindId(1), "Words");
+    let known = words.intern("b".to_string()).unwrap();
+
+    let batch = ["a", "b", "c", "a", "c"].map(String::from).to_vec();
+    let ids = words.intern_many(batch).unwrap();
+    assert_eq!(ids.len(), 5);
+    assert_eq!(ids[1], known);
+    assert_eq!(ids[3], ids[0]);
+    assert_eq!(ids[4], ids[2]);
+    assert_ne!(ids[0], ids[2]);
+    // The two new values got consecutive ids.
+    assert_eq!(ids[2].0, ids[0].0 + 1);
+    assert_eq!(words.len(), 3);
+    for (id, word) in ids.iter().zip(["a", "b", "c", "a", "c"]) {
+        assert_eq!(words.get(&db, *id).unwrap().as_str(), word);
+    }
+    assert_eq!(words.intern("c".to_string()).unwrap(), ids[2]);
+    assert!(words.intern_many(Vec::new()).unwrap().is_empty());
+
+    // A batch that doesn't fit is rejected as a whole.
+    let small: InternedIngredient<String> =
+        InternedIngredient::with_max_entries(QueryKindId(2), "Small", 2);
+    small.intern("x".to_string()).unwrap();
+    let err = small
+        .intern_many(["x", "y", "z"].map(String::from).to_vec())
+        .unwrap_err();
+    assert!(matches!(
+        &*err,
+        PicanteError::InternCapacityExceeded { max: 2, .. }
+    ));
+    assert_eq!(small.len(), 1);
+    assert_eq!(small.lookup(&"y".to_string()).unwrap(), None);
+    let ids = small
+        .intern_many(["x", "y", "y"].map(String::from).to_vec())
+        .unwrap();
+    assert_eq!(ids[1], ids[2]);
+    assert_eq!(small.len(), 2);
+}
+
+#[test]
+fn iter_lists_values_in_id_order() {
+    let words: InternedIngredient<String> = InternedIngredient::new(QueryKindId(1), "Words");
+    let ids = words
+        .intern_many(["c", "a", "b"].map(String::from).to_vec())
+        .unwrap();
+
+    let entries = words.iter();
+    // Interning after the snapshot was taken doesn't show up in it.
+    words.intern("d".to_string()).unwrap();
+    let entries: Vec<(InternId, String)> = entries.map(|(id, v)| (id, (*v).clone())).collect();
+    assert_eq!(
+        entries,
+        vec![
+            (ids[0], "c".to_string()),
+            (ids[1], "a".to_string()),
+            (ids[2], "b".to_string()),
+        ]
+    );
+    assert_eq!(words.iter().count(), 4);
+}
+
 fn temp_file(name: &str) -> std::path::PathBuf {
     let pid = std::process::id();
     let nanos = std::time::SystemTime::now()
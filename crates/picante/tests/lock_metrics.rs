@@ -0,0 +1,90 @@
+#![cfg(feature = "lock-metrics")]
+
+use picante::db::{DynIngredient, IngredientLookup, IngredientRegistry};
+use picante::ingredient::{DerivedIngredient, InputIngredient};
+use picante::key::QueryKindId;
+use picante::metrics::{LOCK_WAIT_BUCKETS, LockWaitHistogram};
+use picante::runtime::{HasRuntime, Runtime};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Default)]
+struct TestDb {
+    runtime: Runtime,
+    ingredients: IngredientRegistry<TestDb>,
+}
+
+impl HasRuntime for TestDb {
+    fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+}
+
+impl IngredientLookup for TestDb {
+    fn ingredient(&self, kind: QueryKindId) -> Option<&dyn DynIngredient<Self>> {
+        self.ingredients.ingredient(kind)
+    }
+}
+
+#[tokio::test]
+async fn records_lock_waits_on_get() {
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.ingredients.register(input.clone());
+
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Len",
+            move |db, key| {
+                let input = input.clone();
+                Box::pin(async move {
+                    let text = input.get(db, &key)?.unwrap_or_default();
+                    Ok(text.len() as u64)
+                })
+            },
+        ))
+    };
+    db.ingredients.register(derived.clone());
+
+    assert_eq!(derived.lock_wait_histogram(), LockWaitHistogram::default());
+
+    input.set(&db, "a".into(), "hello".into());
+    assert_eq!(derived.get(&db, "a".into()).await.unwrap(), 5);
+    let first = derived.lock_wait_histogram();
+    // Fast-path read plus the lock taken to start computing.
+    assert!(first.count >= 2, "{first:?}");
+
+    assert_eq!(derived.get(&db, "a".into()).await.unwrap(), 5);
+    let second = derived.lock_wait_histogram();
+    assert!(second.count > first.count);
+    assert_eq!(second.buckets.iter().sum::<u64>(), second.count);
+    assert!(second.max <= second.total);
+    assert_eq!(second.quantile(1.0), second.max);
+    assert!(second.quantile(0.5) <= second.max);
+}
+
+#[test]
+fn quantiles_never_exceed_the_longest_wait() {
+    let last = LOCK_WAIT_BUCKETS - 1;
+    let mut histogram = LockWaitHistogram::default();
+    histogram.buckets[3] = 1;
+    histogram.buckets[last] = 1;
+    histogram.count = 2;
+    histogram.max = Duration::from_secs(3600);
+    histogram.total = histogram.max + Duration::from_nanos(10);
+
+    assert_eq!(
+        histogram.quantile(0.5),
+        LockWaitHistogram::bucket_upper_bound(3)
+    );
+    // The last bucket's nominal bound is about 4.3s: report the real maximum instead.
+    assert_eq!(histogram.quantile(1.0), histogram.max);
+
+    histogram.max = Duration::from_nanos(10);
+    histogram.buckets[last] = 0;
+    histogram.count = 1;
+    assert_eq!(histogram.quantile(1.0), Duration::from_nanos(10));
+}
@@ -92,6 +92,42 @@ async fn macros_interned_works() -> PicanteResult<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "current_thread")]
+async fn macros_register_kind_names() -> PicanteResult<()> {
+    use picante::HasRuntime;
+    use picante::persist::PersistableIngredient;
+
+    let db = Db::new(0, false);
+    let ingredients = db.persistable_ingredients();
+    assert!(!ingredients.is_empty());
+    for ingredient in ingredients {
+        assert_eq!(
+            db.runtime().kind_name(ingredient.kind()),
+            Some(ingredient.kind_name())
+        );
+    }
+
+    let unknown = picante::QueryKindId(u32::MAX);
+    assert_eq!(db.runtime().kind_name(unknown), None);
+    assert_eq!(
+        db.runtime().describe_kind(unknown),
+        format!("kind {}", u32::MAX)
+    );
+
+    let snapshot = DbSnapshot::from_database(&db).await;
+    let ingredient = db.persistable_ingredients()[0];
+    assert_eq!(
+        snapshot.runtime().describe_kind(ingredient.kind()),
+        format!(
+            "`{}` (kind {})",
+            ingredient.kind_name(),
+            ingredient.kind().0
+        )
+    );
+
+    Ok(())
+}
+
 mod db_paths {
     use super::*;
     use std::sync::atomic::{AtomicUsize, Ordering};
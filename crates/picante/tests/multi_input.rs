@@ -0,0 +1,146 @@
+use picante::db::{DynIngredient, IngredientLookup, IngredientRegistry};
+use picante::ingredient::{DerivedIngredient, MultiInputIngredient};
+use picante::key::QueryKindId;
+use picante::persist::{load_cache, save_cache};
+use picante::runtime::{HasRuntime, Runtime};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Default)]
+struct TestDb {
+    runtime: Runtime,
+    ingredients: IngredientRegistry<TestDb>,
+}
+
+impl HasRuntime for TestDb {
+    fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+}
+
+impl IngredientLookup for TestDb {
+    fn ingredient(&self, kind: QueryKindId) -> Option<&dyn DynIngredient<Self>> {
+        self.ingredients.ingredient(kind)
+    }
+}
+
+type Diagnostics = MultiInputIngredient<String, String>;
+
+fn setup() -> (
+    TestDb,
+    Arc<Diagnostics>,
+    Arc<DerivedIngredient<TestDb, String, u64>>,
+    Arc<AtomicUsize>,
+) {
+    let mut db = TestDb::default();
+    let diagnostics: Arc<Diagnostics> =
+        Arc::new(MultiInputIngredient::new(QueryKindId(1), "Diagnostics"));
+    db.ingredients.register(diagnostics.clone());
+
+    let executions = Arc::new(AtomicUsize::new(0));
+    let count: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let diagnostics = diagnostics.clone();
+        let executions = executions.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Count",
+            move |db, file| {
+                let diagnostics = diagnostics.clone();
+                let executions = executions.clone();
+                Box::pin(async move {
+                    executions.fetch_add(1, Ordering::SeqCst);
+                    Ok(diagnostics.get(db, &file)?.len() as u64)
+                })
+            },
+        ))
+    };
+    db.ingredients.register(count.clone());
+
+    (db, diagnostics, count, executions)
+}
+
+#[tokio::test]
+async fn set_changes_only_invalidate_their_key() {
+    let (db, diagnostics, count, executions) = setup();
+
+    let r1 = diagnostics.insert(&db, "a.rs".into(), "unused import".into());
+    diagnostics.insert(&db, "b.rs".into(), "dead code".into());
+    assert_eq!(count.get(&db, "a.rs".into()).await.unwrap(), 1);
+    assert_eq!(count.get(&db, "b.rs".into()).await.unwrap(), 1);
+    assert_eq!(count.get(&db, "c.rs".into()).await.unwrap(), 0);
+    assert_eq!(executions.load(Ordering::SeqCst), 3);
+
+    // Re-inserting a present value is a no-op.
+    let rev = db.runtime().current_revision();
+    assert_eq!(
+        diagnostics.insert(&db, "a.rs".into(), "unused import".into()),
+        r1
+    );
+    assert_eq!(db.runtime().current_revision(), rev);
+
+    // Changing a.rs's set only recomputes a.rs.
+    let r2 = diagnostics.insert(&db, "a.rs".into(), "missing docs".into());
+    assert_eq!(count.get(&db, "a.rs".into()).await.unwrap(), 2);
+    assert_eq!(count.get(&db, "b.rs".into()).await.unwrap(), 1);
+    assert_eq!(executions.load(Ordering::SeqCst), 4);
+    assert_eq!(diagnostics.changed_at(&"a.rs".into()), Some(r2));
+    assert_eq!(
+        diagnostics.value_changed_at(&"a.rs".into(), &"unused import".into()),
+        Some(r1)
+    );
+
+    let mut values = diagnostics.get(&db, &"a.rs".into()).unwrap();
+    values.sort();
+    assert_eq!(values, vec!["missing docs", "unused import"]);
+
+    // Removing a missing value is a no-op; removing a present one invalidates.
+    diagnostics.remove(&db, &"b.rs".into(), &"nope".into());
+    assert_eq!(count.get(&db, "b.rs".into()).await.unwrap(), 1);
+    assert_eq!(executions.load(Ordering::SeqCst), 4);
+
+    diagnostics.remove(&db, &"b.rs".into(), &"dead code".into());
+    assert_eq!(count.get(&db, "b.rs".into()).await.unwrap(), 0);
+    assert_eq!(count.get(&db, "a.rs".into()).await.unwrap(), 2);
+    assert_eq!(executions.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn persists_sets_and_revisions() {
+    let cache_path = {
+        let pid = std::process::id();
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        std::env::temp_dir().join(format!("picante-multi-input-{pid}-{nanos}.bin"))
+    };
+
+    let (db, diagnostics, count, _) = setup();
+    let r1 = diagnostics.insert(&db, "a.rs".into(), "one".into());
+    let r2 = diagnostics.insert(&db, "a.rs".into(), "two".into());
+    assert_eq!(count.get(&db, "a.rs".into()).await.unwrap(), 2);
+    save_cache(&cache_path, db.runtime(), &[&*diagnostics, &*count])
+        .await
+        .unwrap();
+
+    let (db2, diagnostics2, count2, executions2) = setup();
+    assert!(
+        load_cache(&cache_path, db2.runtime(), &[&*diagnostics2, &*count2])
+            .await
+            .unwrap()
+    );
+
+    assert_eq!(count2.get(&db2, "a.rs".into()).await.unwrap(), 2);
+    assert_eq!(executions2.load(Ordering::SeqCst), 0);
+    assert_eq!(diagnostics2.changed_at(&"a.rs".into()), Some(r2));
+    assert_eq!(
+        diagnostics2.value_changed_at(&"a.rs".into(), &"one".into()),
+        Some(r1)
+    );
+
+    diagnostics2.remove(&db2, &"a.rs".into(), &"one".into());
+    assert_eq!(count2.get(&db2, "a.rs".into()).await.unwrap(), 1);
+    assert_eq!(executions2.load(Ordering::SeqCst), 1);
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
@@ -1,7 +1,8 @@
-use picante::Revision;
+use picante::{PicanteError, Revision, RevisionSource};
 use picante::ingredient::InputIngredient;
 use picante::key::QueryKindId;
 use picante::runtime::{HasRuntime, Runtime, RuntimeEvent};
+use std::sync::Arc;
 use tokio::sync::broadcast::error::TryRecvError;
 
 fn init_tracing() {
@@ -117,6 +118,409 @@ async fn input_set_same_value_is_noop() {
     assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
 }
 
+#[tokio::test]
+async fn input_set_if_changed_reports_writes() {
+    init_tracing();
+
+    let db = TestDb::default();
+    let mut events = db.runtime().subscribe_events();
+
+    let input: InputIngredient<String, String> = InputIngredient::new(QueryKindId(1), "Text");
+
+    assert!(input.set_if_changed(&db, "a".into(), "hello".into()));
+    assert_eq!(db.runtime().current_revision(), Revision(1));
+    let _ = events.recv().await.unwrap();
+    let _ = events.recv().await.unwrap();
+
+    assert!(!input.set_if_changed(&db, "a".into(), "hello".into()));
+    assert_eq!(db.runtime().current_revision(), Revision(1));
+    assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+
+    assert!(input.set_if_changed(&db, "a".into(), "world".into()));
+    assert_eq!(db.runtime().current_revision(), Revision(2));
+}
+
+#[tokio::test]
+async fn input_replace_all_applies_only_the_diff() {
+    init_tracing();
+
+    let db = TestDb::default();
+    let input: InputIngredient<String, String> = InputIngredient::new(QueryKindId(1), "Text");
+    input.set(&db, "a".into(), "same".into());
+    input.set(&db, "b".into(), "old".into());
+    input.set(&db, "c".into(), "gone".into());
+    assert_eq!(db.runtime().current_revision(), Revision(3));
+
+    let mut events = db.runtime().subscribe_events();
+    let rev = input.replace_all(
+        &db,
+        vec![
+            ("a".to_string(), "same".to_string()),
+            ("b".to_string(), "new".to_string()),
+            ("d".to_string(), "added".to_string()),
+        ],
+    );
+    assert_eq!(rev, Some(Revision(4)));
+    assert_eq!(db.runtime().current_revision(), Revision(4));
+
+    let mut changes = Vec::new();
+    loop {
+        match events.try_recv() {
+            Ok(RuntimeEvent::InputSet { revision, key, .. }) => {
+                assert_eq!(revision, Revision(4));
+                changes.push(("set", key.decode_facet::<String>().unwrap()));
+            }
+            Ok(RuntimeEvent::InputRemoved { revision, key, .. }) => {
+                assert_eq!(revision, Revision(4));
+                changes.push(("removed", key.decode_facet::<String>().unwrap()));
+            }
+            Ok(_) => {}
+            Err(TryRecvError::Empty) => break,
+            Err(e) => panic!("unexpected receive error: {e:?}"),
+        }
+    }
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("removed", "c".to_string()),
+            ("set", "b".to_string()),
+            ("set", "d".to_string()),
+        ]
+    );
+
+    assert_eq!(input.changed_at(&"a".into()), Some(Revision(1)));
+    assert_eq!(input.get(&db, &"b".into()).unwrap(), Some("new".to_string()));
+    assert_eq!(input.get(&db, &"c".into()).unwrap(), None);
+
+    // Replacing with the same contents is a no-op.
+    let same = vec![
+        ("a".to_string(), "same".to_string()),
+        ("b".to_string(), "new".to_string()),
+        ("d".to_string(), "added".to_string()),
+    ];
+    assert_eq!(input.replace_all(&db, same), None);
+    assert_eq!(db.runtime().current_revision(), Revision(4));
+    assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+}
+
+/// Input changes received so far, as `(kind of event, key, revision)`.
+fn drain_input_changes(
+    events: &mut tokio::sync::broadcast::Receiver<RuntimeEvent>,
+) -> Vec<(&'static str, String, Revision)> {
+    let mut changes = Vec::new();
+    loop {
+        match events.try_recv() {
+            Ok(RuntimeEvent::InputSet { revision, key, .. }) => {
+                changes.push(("set", key.decode_facet::<String>().unwrap(), revision));
+            }
+            Ok(RuntimeEvent::InputRemoved { revision, key, .. }) => {
+                changes.push(("removed", key.decode_facet::<String>().unwrap(), revision));
+            }
+            Ok(_) => {}
+            Err(TryRecvError::Empty) => return changes,
+            Err(e) => panic!("unexpected receive error: {e:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn input_set_many_bumps_the_revision_once() {
+    init_tracing();
+
+    let db = TestDb::default();
+    let input: InputIngredient<String, String> = InputIngredient::new(QueryKindId(1), "Text");
+    input.set(&db, "a".into(), "same".into());
+    input.set(&db, "b".into(), "old".into());
+    assert_eq!(db.runtime().current_revision(), Revision(2));
+
+    let mut events = db.runtime().subscribe_events();
+    let rev = input.set_many(
+        &db,
+        vec![
+            ("a".to_string(), "same".to_string()),
+            ("b".to_string(), "new".to_string()),
+            ("c".to_string(), "first".to_string()),
+            ("c".to_string(), "added".to_string()),
+        ],
+    );
+    assert_eq!(rev, Some(Revision(3)));
+    assert_eq!(db.runtime().current_revision(), Revision(3));
+
+    let mut changes = drain_input_changes(&mut events);
+    changes.sort();
+    assert_eq!(
+        changes,
+        vec![
+            ("set", "b".to_string(), Revision(3)),
+            ("set", "c".to_string(), Revision(3)),
+        ]
+    );
+    assert_eq!(input.changed_at(&"a".into()), Some(Revision(1)));
+    assert_eq!(input.get(&db, &"c".into()).unwrap(), Some("added".to_string()));
+
+    // Unlike `replace_all`, keys left out are kept, and an unchanged batch is a no-op.
+    assert_eq!(
+        input.set_many(&db, vec![("b".to_string(), "new".to_string())]),
+        None
+    );
+    assert_eq!(input.get(&db, &"a".into()).unwrap(), Some("same".to_string()));
+    assert_eq!(db.runtime().current_revision(), Revision(3));
+}
+
+#[tokio::test]
+async fn input_modify_applies_concurrent_updates_atomically() {
+    init_tracing();
+
+    let db = Arc::new(TestDb::default());
+    let input: Arc<InputIngredient<String, u64>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Counter"));
+
+    // Modifying a missing key changes nothing.
+    assert_eq!(input.modify(&*db, "hits".into(), |n| *n += 1), None);
+    assert_eq!(db.runtime().current_revision(), Revision(0));
+
+    let tasks: Vec<_> = (0..8)
+        .map(|_| {
+            let db = db.clone();
+            let input = input.clone();
+            tokio::spawn(async move {
+                for _ in 0..100 {
+                    input.modify_or_insert(&*db, "hits".into(), |n| *n += 1);
+                    tokio::task::yield_now().await;
+                }
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await.unwrap();
+    }
+    assert_eq!(input.get(&*db, &"hits".into()).unwrap(), Some(800));
+    assert_eq!(db.runtime().current_revision(), Revision(800));
+
+    assert_eq!(input.modify(&*db, "hits".into(), |n| *n *= 2), Some(1600));
+    assert_eq!(db.runtime().current_revision(), Revision(801));
+
+    // An update that leaves the value as it was doesn't bump the revision.
+    assert_eq!(input.modify(&*db, "hits".into(), |_| {}), Some(1600));
+    assert_eq!(db.runtime().current_revision(), Revision(801));
+}
+
+#[tokio::test]
+async fn event_batches_hold_and_coalesce_input_events() {
+    init_tracing();
+
+    let db = TestDb::default();
+    let mut events = db.runtime().subscribe_events();
+    let input: InputIngredient<String, String> = InputIngredient::new(QueryKindId(1), "Text");
+
+    {
+        let _batch = db.runtime().batch_events();
+        input.set(&db, "a".into(), "1".into());
+        input.set(&db, "a".into(), "2".into());
+        assert!(drain_input_changes(&mut events).is_empty());
+    }
+    assert_eq!(
+        drain_input_changes(&mut events),
+        vec![
+            ("set", "a".to_string(), Revision(1)),
+            ("set", "a".to_string(), Revision(2)),
+        ]
+    );
+
+    {
+        let _batch = db.runtime().batch_events().with_coalesce(true);
+        input.set(&db, "a".into(), "3".into());
+        input.set(&db, "b".into(), "1".into());
+        {
+            // Nested batches flush with the outermost one.
+            let _inner = db.runtime().batch_events();
+            input.set(&db, "a".into(), "4".into());
+            input.remove(&db, &"b".into());
+            input.set(&db, "c".into(), "1".into());
+        }
+        assert!(drain_input_changes(&mut events).is_empty());
+    }
+    assert_eq!(
+        drain_input_changes(&mut events),
+        vec![
+            ("set", "a".to_string(), Revision(5)),
+            ("removed", "b".to_string(), Revision(6)),
+            ("set", "c".to_string(), Revision(7)),
+        ]
+    );
+
+    // Coalescing doesn't carry over to the next batch.
+    {
+        let _batch = db.runtime().batch_events();
+        input.set(&db, "c".into(), "2".into());
+        input.set(&db, "c".into(), "3".into());
+    }
+    assert_eq!(drain_input_changes(&mut events).len(), 2);
+}
+
+#[tokio::test]
+async fn wait_for_revision_resolves_once_reached() {
+    init_tracing();
+
+    let db = TestDb::default();
+    let input: InputIngredient<String, String> = InputIngredient::new(QueryKindId(1), "Text");
+    input.set(&db, "a".into(), "0".into());
+
+    // Already reached: resolves right away.
+    db.runtime().wait_for_revision(Revision(1)).await;
+    db.runtime().wait_for_revision(Revision(0)).await;
+
+    let waiter = tokio::spawn(db.runtime().wait_for_revision(Revision(3)));
+    input.set(&db, "a".into(), "1".into());
+    tokio::task::yield_now().await;
+    assert!(!waiter.is_finished());
+
+    input.set(&db, "a".into(), "2".into());
+    tokio::time::timeout(std::time::Duration::from_secs(5), waiter)
+        .await
+        .expect("waiter resolves once revision 3 is reached")
+        .unwrap();
+}
+
+#[tokio::test]
+async fn batch_scopes_announce_one_revision_change() {
+    init_tracing();
+
+    let db = TestDb::default();
+    let mut events = db.runtime().subscribe_events();
+    let revisions = db.runtime().subscribe_revisions();
+    let text: InputIngredient<String, String> = InputIngredient::new(QueryKindId(1), "Text");
+    let other: InputIngredient<String, String> = InputIngredient::new(QueryKindId(2), "Other");
+    text.set(&db, "a".into(), "0".into());
+    while events.try_recv().is_ok() {}
+
+    let out = db.runtime().batch(|| {
+        text.set(&db, "a".into(), "1".into());
+        text.set(&db, "b".into(), "1".into());
+        other.set(&db, "c".into(), "1".into());
+        // Writes are visible right away; their announcements wait for the commit.
+        assert_eq!(text.get(&db, &"a".into()).unwrap().as_deref(), Some("1"));
+        assert_eq!(*revisions.borrow(), Revision(1));
+        assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+        "done"
+    });
+    assert_eq!(out, "done");
+    assert_eq!(*revisions.borrow(), Revision(4));
+
+    let mut received = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        received.push(event);
+    }
+    assert_eq!(received.len(), 4);
+    assert!(
+        received[..3]
+            .iter()
+            .all(|e| matches!(e, RuntimeEvent::InputSet { .. }))
+    );
+    match &received[3] {
+        RuntimeEvent::BatchCommitted {
+            from,
+            to,
+            changed_kinds,
+        } => {
+            assert_eq!((*from, *to), (Revision(1), Revision(4)));
+            assert_eq!(changed_kinds, &vec![QueryKindId(1), QueryKindId(2)]);
+        }
+        other => panic!("expected BatchCommitted, got {other:?}"),
+    }
+
+    // A batch that writes nothing new announces nothing.
+    db.runtime().batch(|| text.set(&db, "a".into(), "1".into()));
+    assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
+
+    // Outside a batch, every write is announced again.
+    text.set(&db, "a".into(), "2".into());
+    assert!(matches!(
+        events.try_recv(),
+        Ok(RuntimeEvent::RevisionBumped {
+            revision: Revision(5)
+        })
+    ));
+}
+
+#[tokio::test]
+async fn filtered_event_stream_only_yields_matching_kinds() {
+    use futures::StreamExt;
+
+    init_tracing();
+
+    let db = TestDb::default();
+    let mut events = Box::pin(db.runtime().subscribe_events_for(QueryKindId(2)));
+
+    let first: InputIngredient<String, String> = InputIngredient::new(QueryKindId(1), "First");
+    let second: InputIngredient<String, String> = InputIngredient::new(QueryKindId(2), "Second");
+    first.set(&db, "a".into(), "hello".into());
+    second.set(&db, "b".into(), "world".into());
+
+    // Revision events pass through; the first input's `InputSet` doesn't.
+    let received: Vec<RuntimeEvent> = (&mut events).take(3).collect().await;
+    assert!(matches!(
+        received[0],
+        RuntimeEvent::RevisionBumped {
+            revision: Revision(1)
+        }
+    ));
+    assert!(matches!(
+        received[1],
+        RuntimeEvent::RevisionBumped {
+            revision: Revision(2)
+        }
+    ));
+    match &received[2] {
+        event @ RuntimeEvent::InputSet { key, .. } => {
+            assert_eq!(event.kind(), Some(QueryKindId(2)));
+            assert_eq!(key.decode_facet::<String>().unwrap(), "b");
+        }
+        other => panic!("expected InputSet, got {other:?}"),
+    }
+
+    drop(db);
+    assert!(events.next().await.is_none());
+}
+
+#[tokio::test]
+async fn lagging_event_streams_report_skipped_events() {
+    use futures::StreamExt;
+
+    init_tracing();
+
+    assert_eq!(Runtime::new().event_capacity(), 1024);
+    let db = TestDb {
+        runtime: Runtime::new().with_event_capacity(2),
+    };
+    assert_eq!(db.runtime().event_capacity(), 2);
+    let mut events = Box::pin(db.runtime().subscribe_event_stream());
+
+    // Three sets send six events; only the last two fit in the channel.
+    let input: InputIngredient<String, String> = InputIngredient::new(QueryKindId(1), "Text");
+    for key in ["a", "b", "c"] {
+        input.set(&db, key.into(), "hello".into());
+    }
+
+    assert!(matches!(
+        events.next().await,
+        Some(RuntimeEvent::Lagged { skipped: 4 })
+    ));
+    assert!(matches!(
+        events.next().await,
+        Some(RuntimeEvent::RevisionBumped {
+            revision: Revision(3)
+        })
+    ));
+    match events.next().await {
+        Some(RuntimeEvent::InputSet { key, .. }) => {
+            assert_eq!(key.decode_facet::<String>().unwrap(), "c");
+        }
+        other => panic!("expected InputSet, got {other:?}"),
+    }
+}
+
 #[tokio::test]
 async fn input_remove_missing_is_noop() {
     init_tracing();
@@ -160,3 +564,62 @@ async fn input_remove_twice_second_is_noop() {
     assert_eq!(db.runtime().current_revision(), rev_before);
     assert!(matches!(events.try_recv(), Err(TryRecvError::Empty)));
 }
+
+/// A source that hands out every tenth revision, like a clock shared with other nodes.
+#[derive(Debug, Default)]
+struct SteppedSource {
+    current: std::sync::Mutex<u64>,
+}
+
+impl RevisionSource for SteppedSource {
+    fn current(&self) -> Revision {
+        Revision(*self.current.lock().unwrap())
+    }
+
+    fn next(&self) -> Revision {
+        let mut current = self.current.lock().unwrap();
+        *current += 10;
+        Revision(*current)
+    }
+
+    fn advance_to(&self, revision: Revision) -> Result<(), Revision> {
+        let mut current = self.current.lock().unwrap();
+        if revision.0 < *current {
+            return Err(Revision(*current));
+        }
+        *current = revision.0;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn custom_revision_source_drives_bumps() {
+    init_tracing();
+
+    let source = Arc::new(SteppedSource::default());
+    let db = TestDb {
+        runtime: Runtime::with_revision_source(source.clone()),
+    };
+    let mut revisions = db.runtime().subscribe_revisions();
+
+    let input: InputIngredient<String, String> = InputIngredient::new(QueryKindId(1), "Text");
+    input.set(&db, "a".into(), "hello".into());
+
+    revisions.changed().await.unwrap();
+    assert_eq!(*revisions.borrow(), Revision(10));
+    assert_eq!(db.runtime().current_revision(), Revision(10));
+
+    // Advances made directly on the shared source are visible to the runtime.
+    source.advance_to(Revision(25)).unwrap();
+    assert_eq!(db.runtime().current_revision(), Revision(25));
+    assert_eq!(db.runtime().bump_revision(), Revision(35));
+
+    let err = db.runtime().set_current_revision(Revision(30)).unwrap_err();
+    match &*err {
+        PicanteError::RevisionRegression { current, requested } => {
+            assert_eq!(*current, Revision(35));
+            assert_eq!(*requested, Revision(30));
+        }
+        other => panic!("expected RevisionRegression, got {other:?}"),
+    }
+}
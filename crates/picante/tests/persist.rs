@@ -4,8 +4,8 @@ use picante::error::PicanteError;
 use picante::ingredient::{DerivedIngredient, InputIngredient};
 use picante::key::QueryKindId;
 use picante::persist::{
-    CacheFile, CacheLoadOptions, CacheSaveOptions, OnCorruptCache, Section, SectionType,
-    load_cache_with_options, save_cache_with_options,
+    CacheFile, CacheLoadOptions, CacheSaveOptions, Compression, OnCorruptCache, Section,
+    SectionType, ValueStorePolicy, load_cache_with_options, save_cache_with_options,
 };
 use picante::runtime::{HasRuntime, Runtime};
 use std::sync::Arc;
@@ -150,157 +150,1420 @@ async fn save_cache_respects_max_bytes() {
         &cache_path,
         db.runtime(),
         &[&*input, &*derived],
-        &CacheSaveOptions {
-            max_bytes: Some(max_bytes),
-            max_records_per_section: None,
-            max_record_bytes: None,
+        &CacheSaveOptions::default().with_max_bytes(max_bytes),
+    )
+    .await
+    .unwrap();
+
+    let bytes = tokio::fs::read(&cache_path).await.unwrap();
+    assert!(
+        bytes.len() <= max_bytes,
+        "cache was {} bytes, expected <= {max_bytes}",
+        bytes.len()
+    );
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn load_cache_respects_max_bytes() {
+    init_tracing();
+
+    let cache_path = temp_file("picante-too-big-cache.bin");
+    tokio::fs::write(&cache_path, vec![0u8; 16]).await.unwrap();
+
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "Len",
+        |_db, _key| Box::pin(async { Ok(0) }),
+    ));
+
+    let err = load_cache_with_options(
+        &cache_path,
+        db.runtime(),
+        &[&*input, &*derived],
+        &CacheLoadOptions {
+            max_bytes: Some(8),
+            on_corrupt: OnCorruptCache::Error,
         },
     )
     .await
+    .unwrap_err();
+
+    match &*err {
+        PicanteError::Cache { message } => {
+            assert!(message.contains("cache file too large"));
+        }
+        other => panic!("expected cache error, got {other:?}"),
+    }
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn load_cache_ignores_unknown_sections() {
+    init_tracing();
+
+    let cache_path = temp_file("picante-unknown-section.bin");
+
+    let cache = CacheFile::new(
+        123,
+        vec![
+            Section {
+                kind_id: 999,
+                kind_name: "Unknown".to_string(),
+                section_type: SectionType::Input,
+                logic_version: 0,
+                records: vec![b"ignored".to_vec()],
+            },
+            Section {
+                kind_id: 1,
+                kind_name: "Text".to_string(),
+                section_type: SectionType::Input,
+                logic_version: 0,
+                records: Vec::new(),
+            },
+        ],
+    );
+
+    let bytes = cache.to_bytes().unwrap();
+    tokio::fs::write(&cache_path, bytes).await.unwrap();
+
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "Len",
+        |_db, _key| Box::pin(async { Ok(0) }),
+    ));
+
+    let ok = load_cache_with_options(
+        &cache_path,
+        db.runtime(),
+        &[&*input, &*derived],
+        &CacheLoadOptions {
+            max_bytes: None,
+            on_corrupt: OnCorruptCache::Error,
+        },
+    )
+    .await
+    .unwrap();
+
+    assert!(ok);
+    assert_eq!(db.runtime().current_revision(), Revision(123));
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn load_cache_kind_name_mismatch_is_warning() {
+    init_tracing();
+
+    let cache_path = temp_file("picante-kind-name-mismatch.bin");
+
+    let cache = CacheFile::new(
+        1,
+        vec![Section {
+            kind_id: 1,
+            kind_name: "NotText".to_string(),
+            section_type: SectionType::Input,
+            logic_version: 0,
+            records: Vec::new(),
+        }],
+    );
+
+    let bytes = cache.to_bytes().unwrap();
+    tokio::fs::write(&cache_path, bytes).await.unwrap();
+
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+
+    // Kind name mismatches should be treated as corrupt cache
+    let result = load_cache_with_options(
+        &cache_path,
+        db.runtime(),
+        &[&*input],
+        &CacheLoadOptions {
+            max_bytes: None,
+            on_corrupt: OnCorruptCache::Ignore,
+        },
+    )
+    .await
+    .unwrap();
+
+    // With OnCorruptCache::Ignore, we get Ok(false)
+    assert!(!result);
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn stale_cache_cannot_roll_back_a_live_runtime() {
+    init_tracing();
+
+    let cache_path = temp_file("picante-stale-cache.bin");
+
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    input.set(&db, "a".into(), "old".into());
+    save_cache_with_options(
+        &cache_path,
+        db.runtime(),
+        &[&*input],
+        &CacheSaveOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let db2 = TestDb::default();
+    let input2: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    input2.set(&db2, "a".into(), "new".into());
+    input2.set(&db2, "b".into(), "newer".into());
+    assert_eq!(db2.runtime().current_revision(), Revision(2));
+
+    // Even with a lenient corruption policy, a stale cache is an error, not a silent skip.
+    let err = load_cache_with_options(
+        &cache_path,
+        db2.runtime(),
+        &[&*input2],
+        &CacheLoadOptions {
+            max_bytes: None,
+            on_corrupt: OnCorruptCache::Ignore,
+        },
+    )
+    .await
+    .unwrap_err();
+    match &*err {
+        PicanteError::RevisionRegression { current, requested } => {
+            assert_eq!(*current, Revision(2));
+            assert_eq!(*requested, Revision(1));
+        }
+        other => panic!("expected RevisionRegression, got {other:?}"),
+    }
+
+    // Nothing was cleared or overwritten.
+    assert_eq!(db2.runtime().current_revision(), Revision(2));
+    assert_eq!(input2.get(&db2, &"a".into()).unwrap().as_deref(), Some("new"));
+    assert_eq!(input2.get(&db2, &"b".into()).unwrap().as_deref(), Some("newer"));
+    assert!(cache_path.exists());
+
+    // The runtime-level setter enforces the same invariant.
+    assert!(db2.runtime().set_current_revision(Revision(1)).is_err());
+    db2.runtime().set_current_revision(Revision(5)).unwrap();
+    assert_eq!(db2.runtime().current_revision(), Revision(5));
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn cache_report_aggregates_tallies_and_persistence() {
+    init_tracing();
+
+    let cache_path = temp_file("picante-cache-report.bin");
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.ingredients.register(input.clone());
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "Len", move |db, key| {
+            let input = input.clone();
+            Box::pin(async move { Ok(input.get(db, &key)?.unwrap_or_default().len() as u64) })
+        }))
+    };
+    db.ingredients.register(len.clone());
+
+    input.set(&db, "a".into(), "hello".into());
+    input.set(&db, "b".into(), "hi".into());
+    assert_eq!(len.get(&db, "a".into()).await.unwrap(), 5);
+    assert_eq!(len.get(&db, "a".into()).await.unwrap(), 5);
+    assert_eq!(len.get(&db, "b".into()).await.unwrap(), 2);
+
+    // An unrelated change: "a" only needs to be revalidated.
+    input.set(&db, "c".into(), "unused".into());
+    assert_eq!(len.get(&db, "a".into()).await.unwrap(), 5);
+
+    save_cache_with_options(
+        &cache_path,
+        db.runtime(),
+        &[&*input, &*len],
+        &CacheSaveOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let report = db.runtime().cache_report();
+    assert_eq!(report.revision, 3);
+    assert_eq!(report.persistence.saves, 1);
+    assert!(report.persistence.bytes_saved > 0);
+    assert_eq!(report.persistence.loads, 0);
+
+    let kinds: Vec<_> = report.ingredients.iter().map(|i| i.kind_name.as_str()).collect();
+    assert_eq!(kinds, ["Text", "Len"]);
+    let text = &report.ingredients[0];
+    assert_eq!(text.records_saved, 3);
+    let len_report = &report.ingredients[1];
+    assert_eq!(len_report.cells, 2);
+    assert_eq!(len_report.recomputes, 2);
+    assert_eq!(len_report.hits, 1);
+    assert_eq!(len_report.revalidations, 1);
+    assert_eq!(len_report.records_saved, 2);
+
+    // Loading into a fresh database starts its own tallies.
+    let db2 = TestDb::default();
+    let input2: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    let len2: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
+        QueryKindId(2),
+        "Len",
+        |_db, _key| Box::pin(async { Ok(0) }),
+    ));
+    assert!(
+        load_cache_with_options(
+            &cache_path,
+            db2.runtime(),
+            &[&*input2, &*len2],
+            &CacheLoadOptions::default(),
+        )
+        .await
+        .unwrap()
+    );
+
+    let report = db2.runtime().cache_report();
+    assert_eq!(report.revision, 3);
+    assert_eq!(report.persistence.loads, 1);
+    assert!(report.persistence.bytes_loaded > 0);
+    assert_eq!(report.ingredients[0].records_loaded, 3);
+    assert_eq!(report.ingredients[1].records_loaded, 2);
+    assert_eq!(report.ingredients[1].cells, 2);
+    assert_eq!(report.ingredients[1].recomputes, 0);
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn logic_version_change_discards_only_that_section() {
+    init_tracing();
+
+    let cache_path = temp_file("picante-logic-version.bin");
+
+    let make = |version: u32| {
+        let input: Arc<InputIngredient<String, String>> =
+            Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+        let len: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(
+            DerivedIngredient::new(QueryKindId(2), "Len", |_db, key: String| {
+                Box::pin(async move { Ok(key.len() as u64) })
+            })
+            .with_logic_version(version),
+        );
+        (input, len)
+    };
+
+    let db = TestDb::default();
+    let (input, len) = make(1);
+    input.set(&db, "a".into(), "hello".into());
+    len.get(&db, "abc".into()).await.unwrap();
+    save_cache_with_options(
+        &cache_path,
+        db.runtime(),
+        &[&*input, &*len],
+        &CacheSaveOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    // Same version: both sections load.
+    let db2 = TestDb::default();
+    let (input2, len2) = make(1);
+    assert!(
+        load_cache_with_options(
+            &cache_path,
+            db2.runtime(),
+            &[&*input2, &*len2],
+            &CacheLoadOptions::default(),
+        )
+        .await
+        .unwrap()
+    );
+    assert!(len2.cell_for_key(&"abc".into()).unwrap().is_some());
+
+    // Bumped version: the derived section is discarded, inputs still load.
+    let db3 = TestDb::default();
+    let (input3, len3) = make(2);
+    assert_eq!(len3.logic_version(), 2);
+    assert!(
+        load_cache_with_options(
+            &cache_path,
+            db3.runtime(),
+            &[&*input3, &*len3],
+            &CacheLoadOptions::default(),
+        )
+        .await
+        .unwrap()
+    );
+    assert!(len3.cell_for_key(&"abc".into()).unwrap().is_none());
+    assert_eq!(
+        input3.get(&db3, &"a".into()).unwrap().as_deref(),
+        Some("hello")
+    );
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn large_derived_sections_load_in_order() {
+    use picante::persist::PersistableIngredient;
+
+    init_tracing();
+
+    let make = || -> DerivedIngredient<TestDb, String, u64> {
+        DerivedIngredient::new(QueryKindId(2), "KeyLen", |_db, key| {
+            Box::pin(async move { Ok(key.len() as u64) })
+        })
+    };
+
+    // Enough records to take the parallel decoding path.
+    let db = TestDb::default();
+    let source = make();
+    for i in 0..10_000 {
+        source.get(&db, format!("key-{i}")).await.unwrap();
+    }
+    let mut records = source.save_records().await.unwrap();
+    assert_eq!(records.len(), 10_000);
+
+    let target = make();
+    target.load_records(records.clone()).unwrap();
+    assert_eq!(target.snapshot().len(), 10_000);
+    for i in [0, 4_999, 9_999] {
+        assert!(target.cell_for_key(&format!("key-{i}")).unwrap().is_some());
+    }
+
+    // A corrupt record anywhere fails the whole section without loading part of it.
+    records.insert(7_500, b"not a record".to_vec());
+    let target = make();
+    assert!(target.load_records(records).is_err());
+    assert!(target.snapshot().is_empty());
+}
+
+#[tokio::test]
+async fn load_filter_leaves_rejected_keys_cold() {
+    use picante::persist::PersistableIngredient;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    init_tracing();
+
+    let computes = Arc::new(AtomicUsize::new(0));
+    let make = || -> DerivedIngredient<TestDb, String, u64> {
+        let computes = computes.clone();
+        DerivedIngredient::new(QueryKindId(2), "KeyLen", move |_db, key| {
+            computes.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move { Ok(key.len() as u64) })
+        })
+    };
+
+    let db = TestDb::default();
+    let source = make();
+    for key in ["a", "bb", "ccc"] {
+        source.get(&db, key.to_string()).await.unwrap();
+    }
+    let records = source.save_records().await.unwrap();
+
+    let target = make();
+    let skipped = target
+        .load_records_with_filter(records, |key, len| key != "bb" && *len < 10)
+        .unwrap();
+    assert_eq!(skipped, 1);
+    assert!(target.cell_for_key(&"a".to_string()).unwrap().is_some());
+    assert!(target.cell_for_key(&"bb".to_string()).unwrap().is_none());
+
+    let computed = computes.load(Ordering::SeqCst);
+    assert_eq!(target.get(&db, "ccc".to_string()).await.unwrap(), 3);
+    assert_eq!(computes.load(Ordering::SeqCst), computed);
+    assert_eq!(target.get(&db, "bb".to_string()).await.unwrap(), 2);
+    assert_eq!(computes.load(Ordering::SeqCst), computed + 1);
+}
+
+#[tokio::test]
+async fn sections_decode_typed_records() {
+    use picante::ingredient::InternedIngredient;
+    use picante::key::Key;
+
+    init_tracing();
+
+    let cache_path = temp_file("picante-decode-sections.bin");
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.ingredients.register(input.clone());
+    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(2),
+            "Len",
+            move |db, key| {
+                let input = input.clone();
+                Box::pin(async move {
+                    let text = input.get(db, &key)?.unwrap_or_default();
+                    Ok(text.len() as u64)
+                })
+            },
+        ))
+    };
+    db.ingredients.register(derived.clone());
+    let words: Arc<InternedIngredient<String>> =
+        Arc::new(InternedIngredient::new(QueryKindId(3), "Word"));
+
+    let rev = input.set(&db, "a".into(), "hello".into());
+    assert_eq!(derived.get(&db, "a".into()).await.unwrap(), 5);
+    let id = words.intern("hello".into()).unwrap();
+
+    save_cache_with_options(
+        &cache_path,
+        db.runtime(),
+        &[&*input, &*derived, &*words],
+        &CacheSaveOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let bytes = tokio::fs::read(&cache_path).await.unwrap();
+    let cache = CacheFile::from_bytes(&bytes).unwrap();
+    let section = |kind_id| {
+        cache
+            .sections
+            .iter()
+            .find(|s| s.kind_id == kind_id)
+            .unwrap()
+    };
+
+    let inputs = section(1).decode_input::<String, String>().unwrap();
+    assert_eq!(inputs.len(), 1);
+    assert_eq!(inputs[0].0, "a");
+    assert_eq!(inputs[0].1.as_deref(), Some("hello"));
+    assert_eq!(inputs[0].2, rev.0);
+
+    let derived_records = section(2).decode_derived::<String, u64>().unwrap();
+    assert_eq!(derived_records.len(), 1);
+    let (key, value, changed_at, deps) = &derived_records[0];
+    assert_eq!(key, "a");
+    assert_eq!(*value, 5);
+    assert_eq!(*changed_at, rev.0);
+    assert_eq!(deps.len(), 1);
+    assert_eq!(deps[0].kind, QueryKindId(1));
+    assert_eq!(deps[0].key, Key::encode_facet(&"a".to_string()).unwrap());
+
+    let interned = section(3).decode_interned::<String>().unwrap();
+    assert_eq!(interned.len(), 1);
+    assert_eq!(interned[0].0, id);
+    assert_eq!(*interned[0].1, "hello");
+
+    // Asking for the wrong section type is an error, not a garbage decode.
+    match section(1).decode_interned::<String>() {
+        Err(e) => assert!(matches!(&*e, PicanteError::Cache { .. })),
+        Ok(records) => panic!("expected section type error, got {records:?}"),
+    }
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn save_records_yielding_lets_other_tasks_run() {
+    use picante::persist::PersistableIngredient;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    init_tracing();
+
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    for i in 0..100 {
+        input.set(&db, format!("k{i}"), format!("v{i}"));
+    }
+
+    let mut expected = input.save_records().await.unwrap();
+    expected.sort();
+
+    // Dropping a save mid-way leaves the ingredient untouched.
+    {
+        let save = input.save_records_yielding(1);
+        futures::pin_mut!(save);
+        assert!(futures::poll!(save.as_mut()).is_pending());
+    }
+
+    // On the current-thread runtime, the ticker only runs when the save yields.
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let ticker = tokio::spawn({
+        let ticks = ticks.clone();
+        async move {
+            loop {
+                ticks.fetch_add(1, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+            }
+        }
+    });
+
+    let mut records = input.save_records_yielding(10).await.unwrap();
+    ticker.abort();
+    records.sort();
+
+    assert_eq!(records, expected);
+    assert!(ticks.load(Ordering::SeqCst) > 0);
+}
+
+#[tokio::test]
+async fn cache_metadata_round_trips_and_old_files_migrate() {
+    use picante::persist::{load_cache, read_cache_metadata, save_cache_with_metadata};
+
+    init_tracing();
+
+    let cache_path = temp_file("picante-metadata.bin");
+    assert_eq!(read_cache_metadata(&cache_path).await.unwrap(), None);
+
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    input.set(&db, "a".into(), "hello".into());
+
+    let metadata = vec![
+        ("git_sha".to_string(), "abc123".to_string()),
+        ("rustc".to_string(), "1.85.0".to_string()),
+    ];
+    save_cache_with_metadata(&cache_path, db.runtime(), &[&*input], metadata.clone())
+        .await
+        .unwrap();
+    assert_eq!(read_cache_metadata(&cache_path).await.unwrap(), Some(metadata));
+
+    // Metadata doesn't get in the way of loading.
+    let db2 = TestDb::default();
+    let input2: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    let loaded = load_cache(&cache_path, db2.runtime(), &[&*input2])
+        .await
+        .unwrap();
+    assert!(loaded);
+    assert_eq!(input2.get(&db2, &"a".to_string()).unwrap(), Some("hello".to_string()));
+
+    // A version 2 file (no metadata field) still loads, and reports no metadata.
+    #[derive(facet::Facet)]
+    struct CacheFileV2 {
+        format_version: u32,
+        current_revision: u64,
+        sections: Vec<Section>,
+    }
+    let old = CacheFileV2 {
+        format_version: 2,
+        current_revision: 5,
+        sections: vec![Section {
+            kind_id: 1,
+            kind_name: "Text".to_string(),
+            section_type: SectionType::Input,
+            logic_version: 0,
+            records: Vec::new(),
+        }],
+    };
+    tokio::fs::write(&cache_path, facet_postcard::to_vec(&old).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(read_cache_metadata(&cache_path).await.unwrap(), Some(Vec::new()));
+
+    let db3 = TestDb::default();
+    let input3: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    let loaded = load_cache(&cache_path, db3.runtime(), &[&*input3])
+        .await
+        .unwrap();
+    assert!(loaded);
+    assert_eq!(db3.runtime().current_revision(), Revision(5));
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn unit_queries_persist_without_a_value() {
+    use picante::persist::PersistableIngredient;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    init_tracing();
+
+    let mut db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.ingredients.register(input.clone());
+    input.set(&db, "a".into(), "hello".into());
+
+    let runs = Arc::new(AtomicUsize::new(0));
+    let validate = |kind, name, runs: Arc<AtomicUsize>| {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::<TestDb, String, ()>::new(kind, name, move |db, key| {
+            let input = input.clone();
+            let runs = runs.clone();
+            Box::pin(async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                input.get(db, &key)?;
+                Ok(())
+            })
+        }))
+    };
+    let unit = validate(QueryKindId(2), "Validate", runs.clone());
+    db.ingredients.register(unit.clone());
+    // Same key and deps, but with a one-byte value.
+    let zero: Arc<DerivedIngredient<TestDb, String, u8>> = {
+        let input = input.clone();
+        Arc::new(DerivedIngredient::new(
+            QueryKindId(3),
+            "Zero",
+            move |db, key| {
+                let input = input.clone();
+                Box::pin(async move {
+                    input.get(db, &key)?;
+                    Ok(0)
+                })
+            },
+        ))
+    };
+    db.ingredients.register(zero.clone());
+
+    unit.get(&db, "a".into()).await.unwrap();
+    unit.get(&db, "a".into()).await.unwrap();
+    zero.get(&db, "a".into()).await.unwrap();
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+    let unit_records = unit.save_records().await.unwrap();
+    let zero_records = zero.save_records().await.unwrap();
+    assert_eq!(unit_records.len(), 1);
+    assert_eq!(unit_records[0].len() + 1, zero_records[0].len());
+
+    // The loaded cell is memoized: validating it again doesn't rerun the query.
+    let runs2 = Arc::new(AtomicUsize::new(0));
+    let unit2 = validate(QueryKindId(2), "Validate", runs2.clone());
+    db.ingredients.register(unit2.clone());
+    unit2.load_records(unit_records).unwrap();
+    unit2.get(&db, "a".into()).await.unwrap();
+    assert_eq!(runs2.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn large_records_move_to_sidecars() {
+    use picante::persist::load_cache;
+
+    init_tracing();
+
+    let cache_path = temp_file("picante-sidecars.bin");
+    let blob_dir = std::path::PathBuf::from(format!("{}.blobs", cache_path.display()));
+    let blob_count = |dir: std::path::PathBuf| async move {
+        let mut entries = tokio::fs::read_dir(dir).await.unwrap();
+        let mut n = 0;
+        while entries.next_entry().await.unwrap().is_some() {
+            n += 1;
+        }
+        n
+    };
+
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    let big = "x".repeat(10_000);
+    input.set(&db, "big".into(), big.clone());
+    input.set(&db, "small".into(), "tiny".into());
+
+    let mut options =
+        CacheSaveOptions::default().with_value_store(ValueStorePolicy::SidecarAbove(1024));
+    save_cache_with_options(&cache_path, db.runtime(), &[&*input], &options)
+        .await
+        .unwrap();
+    let file_len = tokio::fs::metadata(&cache_path).await.unwrap().len();
+    assert!(file_len < 1024, "cache file is {file_len} bytes");
+    assert_eq!(blob_count(blob_dir.clone()).await, 1);
+
+    let db2 = TestDb::default();
+    let input2: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    assert!(load_cache(&cache_path, db2.runtime(), &[&*input2]).await.unwrap());
+    assert_eq!(input2.get(&db2, &"big".to_string()).unwrap(), Some(big.clone()));
+    assert_eq!(input2.get(&db2, &"small".to_string()).unwrap(), Some("tiny".to_string()));
+
+    // Saving inline again leaves the old sidecar orphaned, so it is removed.
+    options.value_store = ValueStorePolicy::Inline;
+    save_cache_with_options(&cache_path, db.runtime(), &[&*input], &options)
+        .await
+        .unwrap();
+    assert_eq!(blob_count(blob_dir.clone()).await, 0);
+
+    let db3 = TestDb::default();
+    let input3: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    assert!(load_cache(&cache_path, db3.runtime(), &[&*input3]).await.unwrap());
+    assert_eq!(input3.get(&db3, &"big".to_string()).unwrap(), Some(big));
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+    let _ = tokio::fs::remove_dir_all(&blob_dir).await;
+}
+
+#[tokio::test]
+async fn corrupt_cache_files_fail_the_checksum() {
+    use picante::persist::load_cache;
+
+    init_tracing();
+
+    let cache_path = temp_file("picante-checksum.bin");
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    input.set(&db, "a".into(), "hello".into());
+    save_cache_with_options(
+        &cache_path,
+        db.runtime(),
+        &[&*input],
+        &CacheSaveOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let bytes = tokio::fs::read(&cache_path).await.unwrap();
+    let cache = CacheFile::from_bytes(&bytes).unwrap();
+
+    // Flip one bit of a value, leaving the file decodable.
+    let at = bytes
+        .windows(5)
+        .position(|w| w == b"hello")
+        .expect("value is stored inline");
+    let mut corrupt = bytes.clone();
+    corrupt[at] ^= 0x20;
+    tokio::fs::write(&cache_path, &corrupt).await.unwrap();
+
+    let db2 = TestDb::default();
+    let input2: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    let err = load_cache(&cache_path, db2.runtime(), &[&*input2])
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&*err, PicanteError::Cache { message } if message == "checksum mismatch"),
+        "{err}"
+    );
+
+    // A truncated file is caught the same way.
+    tokio::fs::write(&cache_path, &bytes[..bytes.len() - 3])
+        .await
+        .unwrap();
+    let err = load_cache(&cache_path, db2.runtime(), &[&*input2])
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&*err, PicanteError::Cache { message } if message == "checksum mismatch"),
+        "{err}"
+    );
+
+    // Zeroing the checksum doesn't switch verification off.
+    let mut zeroed = bytes.clone();
+    zeroed[8..12].fill(0);
+    tokio::fs::write(&cache_path, &zeroed).await.unwrap();
+    let err = load_cache(&cache_path, db2.runtime(), &[&*input2])
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(&*err, PicanteError::Cache { message } if message == "checksum mismatch"),
+        "{err}"
+    );
+
+    // Nor does dropping the header: a current-version payload needs one.
+    tokio::fs::write(&cache_path, facet_postcard::to_vec(&cache).unwrap())
+        .await
+        .unwrap();
+    let err = load_cache(&cache_path, db2.runtime(), &[&*input2])
+        .await
+        .unwrap_err();
+    assert!(matches!(&*err, PicanteError::Cache { .. }), "{err}");
+
+    // Version 4 files predate the header and still load.
+    #[derive(facet::Facet)]
+    struct CacheFileV4 {
+        format_version: u32,
+        current_revision: u64,
+        sections: Vec<Section>,
+        metadata: Vec<(String, String)>,
+        sidecars: Option<picante::persist::SidecarIndex>,
+    }
+    let old = CacheFileV4 {
+        format_version: 4,
+        current_revision: cache.current_revision,
+        sections: cache.sections,
+        metadata: Vec::new(),
+        sidecars: None,
+    };
+    tokio::fs::write(&cache_path, facet_postcard::to_vec(&old).unwrap())
+        .await
+        .unwrap();
+    assert!(
+        load_cache(&cache_path, db2.runtime(), &[&*input2])
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        input2.get(&db2, &"a".to_string()).unwrap().as_deref(),
+        Some("hello")
+    );
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn unsupported_compression_codecs_are_cache_errors() {
+    use picante::persist::read_cache_metadata;
+
+    init_tracing();
+
+    // Same layout as a compressed file, with a codec picante doesn't know.
+    #[derive(facet::Facet)]
+    struct CompressedCacheFile {
+        format_version: u32,
+        codec: String,
+        payload: Vec<u8>,
+    }
+    let cache_path = temp_file("picante-unknown-codec.bin");
+    let file = CompressedCacheFile {
+        format_version: u32::MAX,
+        codec: "brotli".to_string(),
+        payload: vec![1, 2, 3],
+    };
+    tokio::fs::write(&cache_path, facet_postcard::to_vec(&file).unwrap())
+        .await
+        .unwrap();
+
+    let err = read_cache_metadata(&cache_path).await.unwrap_err();
+    assert!(
+        matches!(&*err, PicanteError::Cache { message } if message.contains("`brotli`")),
+        "{err}"
+    );
+
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    let err = load_cache_with_options(
+        &cache_path,
+        db.runtime(),
+        &[&*input],
+        &CacheLoadOptions::default(),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(&*err, PicanteError::Cache { .. }), "{err}");
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[cfg(feature = "zstd")]
+#[tokio::test]
+async fn zstd_compressed_caches_round_trip() {
+    use picante::persist::load_cache;
+
+    init_tracing();
+
+    let cache_path = temp_file("picante-zstd.bin");
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    for i in 0..100 {
+        input.set(&db, format!("key-{i}"), "repetitive value ".repeat(20));
+    }
+
+    save_cache_with_options(
+        &cache_path,
+        db.runtime(),
+        &[&*input],
+        &CacheSaveOptions::default(),
+    )
+    .await
     .unwrap();
+    let plain_len = tokio::fs::metadata(&cache_path).await.unwrap().len();
 
-    let bytes = tokio::fs::read(&cache_path).await.unwrap();
+    let options = CacheSaveOptions::default()
+        .with_compression(Compression::Zstd(3))
+        .with_metadata(vec![("codec".to_string(), "zstd".to_string())]);
+    save_cache_with_options(&cache_path, db.runtime(), &[&*input], &options)
+        .await
+        .unwrap();
+    let compressed_len = tokio::fs::metadata(&cache_path).await.unwrap().len();
     assert!(
-        bytes.len() <= max_bytes,
-        "cache was {} bytes, expected <= {max_bytes}",
-        bytes.len()
+        compressed_len * 4 < plain_len,
+        "{compressed_len} bytes compressed vs {plain_len} plain"
+    );
+    assert_eq!(
+        picante::persist::read_cache_metadata(&cache_path)
+            .await
+            .unwrap(),
+        Some(options.metadata.clone())
+    );
+
+    let db2 = TestDb::default();
+    let input2: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    assert!(
+        load_cache(&cache_path, db2.runtime(), &[&*input2])
+            .await
+            .unwrap()
+    );
+    assert_eq!(input2.get_all().len(), 100);
+    assert_eq!(
+        db2.runtime().current_revision(),
+        db.runtime().current_revision()
     );
 
     let _ = tokio::fs::remove_file(&cache_path).await;
 }
 
+#[cfg(feature = "zstd")]
 #[tokio::test]
-async fn load_cache_respects_max_bytes() {
+async fn zstd_caches_are_size_checked_once_decompressed() {
     init_tracing();
 
-    let cache_path = temp_file("picante-too-big-cache.bin");
-    tokio::fs::write(&cache_path, vec![0u8; 16]).await.unwrap();
-
+    let cache_path = temp_file("picante-zstd-bomb.bin");
     let db = TestDb::default();
     let input: Arc<InputIngredient<String, String>> =
         Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
-    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
-        QueryKindId(2),
-        "Len",
-        |_db, _key| Box::pin(async { Ok(0) }),
-    ));
+    input.set(&db, "big".into(), "x".repeat(1 << 20));
+
+    let options = CacheSaveOptions::default().with_compression(Compression::Zstd(3));
+    save_cache_with_options(&cache_path, db.runtime(), &[&*input], &options)
+        .await
+        .unwrap();
+    let compressed_len = tokio::fs::metadata(&cache_path).await.unwrap().len() as usize;
+    assert!(
+        compressed_len < 64 * 1024,
+        "{compressed_len} bytes compressed"
+    );
 
+    // Small enough on disk, but a megabyte once decompressed.
+    let input2: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
     let err = load_cache_with_options(
         &cache_path,
-        db.runtime(),
-        &[&*input, &*derived],
+        TestDb::default().runtime(),
+        &[&*input2],
         &CacheLoadOptions {
-            max_bytes: Some(8),
+            max_bytes: Some(64 * 1024),
             on_corrupt: OnCorruptCache::Error,
         },
     )
     .await
     .unwrap_err();
-
     match &*err {
         PicanteError::Cache { message } => {
-            assert!(message.contains("cache file too large"));
+            assert!(
+                message.contains("decompressed cache too large"),
+                "{message}"
+            );
         }
         other => panic!("expected cache error, got {other:?}"),
     }
+    assert!(input2.get_all().is_empty());
 
     let _ = tokio::fs::remove_file(&cache_path).await;
 }
 
 #[tokio::test]
-async fn load_cache_ignores_unknown_sections() {
-    init_tracing();
+async fn version_one_caches_load_with_logic_version_zero() {
+    use picante::persist::{load_cache, save_cache};
 
-    let cache_path = temp_file("picante-unknown-section.bin");
+    init_tracing();
 
-    let cache = CacheFile {
+    let cache_path = temp_file("picante-v1.bin");
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    input.set(&db, "a".into(), "hello".into());
+    save_cache(&cache_path, db.runtime(), &[&*input])
+        .await
+        .unwrap();
+    let saved = CacheFile::from_bytes(&tokio::fs::read(&cache_path).await.unwrap()).unwrap();
+
+    // Rewrite it in the version 1 layout, whose sections had no logic version.
+    #[derive(facet::Facet)]
+    struct SectionV1 {
+        kind_id: u32,
+        kind_name: String,
+        section_type: SectionType,
+        records: Vec<Vec<u8>>,
+    }
+    #[derive(facet::Facet)]
+    struct CacheFileV1 {
+        format_version: u32,
+        current_revision: u64,
+        sections: Vec<SectionV1>,
+    }
+    let old = CacheFileV1 {
         format_version: 1,
-        current_revision: 123,
-        sections: vec![
-            Section {
-                kind_id: 999,
-                kind_name: "Unknown".to_string(),
-                section_type: SectionType::Input,
-                records: vec![b"ignored".to_vec()],
-            },
-            Section {
-                kind_id: 1,
-                kind_name: "Text".to_string(),
-                section_type: SectionType::Input,
-                records: Vec::new(),
-            },
-        ],
+        current_revision: saved.current_revision,
+        sections: saved
+            .sections
+            .into_iter()
+            .map(|section| SectionV1 {
+                kind_id: section.kind_id,
+                kind_name: section.kind_name,
+                section_type: section.section_type,
+                records: section.records,
+            })
+            .collect(),
     };
+    let bytes = facet_postcard::to_vec(&old).unwrap();
 
-    let bytes = facet_postcard::to_vec(&cache).unwrap();
-    tokio::fs::write(&cache_path, bytes).await.unwrap();
+    let cache = CacheFile::from_bytes(&bytes).unwrap();
+    assert_eq!(cache.format_version, 1);
+    assert_eq!(cache.sections.len(), 1);
+    assert_eq!(cache.sections[0].kind_name, "Text");
+    assert_eq!(cache.sections[0].logic_version, 0);
+
+    tokio::fs::write(&cache_path, &bytes).await.unwrap();
+    let db2 = TestDb::default();
+    let input2: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    assert!(
+        load_cache(&cache_path, db2.runtime(), &[&*input2])
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        db2.runtime().current_revision(),
+        db.runtime().current_revision()
+    );
+    assert_eq!(
+        input2.get(&db2, &"a".to_string()).unwrap(),
+        Some("hello".to_string())
+    );
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn migrations_upgrade_unsupported_format_versions() {
+    use picante::persist::{Migration, load_cache, load_cache_with};
+
+    init_tracing();
+
+    // A hypothetical version 0 file, where the ingredient was still called "Txt".
+    struct RenameTxt;
+    impl Migration for RenameTxt {
+        fn from_version(&self) -> u32 {
+            0
+        }
+        fn to_version(&self) -> u32 {
+            5
+        }
+        fn migrate(&self, mut cache: CacheFile) -> picante::PicanteResult<CacheFile> {
+            for section in &mut cache.sections {
+                if section.kind_name == "Txt" {
+                    section.kind_name = "Text".to_string();
+                }
+            }
+            Ok(cache)
+        }
+    }
+
+    let cache_path = temp_file("picante-migration.bin");
+    let mut cache = CacheFile::new(
+        1,
+        vec![Section {
+            kind_id: 1,
+            kind_name: "Txt".to_string(),
+            section_type: SectionType::Input,
+            logic_version: 0,
+            records: Vec::new(),
+        }],
+    );
+    cache.format_version = 0;
+    tokio::fs::write(&cache_path, facet_postcard::to_vec(&cache).unwrap())
+        .await
+        .unwrap();
 
     let db = TestDb::default();
     let input: Arc<InputIngredient<String, String>> =
         Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
-    let derived: Arc<DerivedIngredient<TestDb, String, u64>> = Arc::new(DerivedIngredient::new(
-        QueryKindId(2),
-        "Len",
-        |_db, _key| Box::pin(async { Ok(0) }),
-    ));
 
-    let ok = load_cache_with_options(
-        &cache_path,
+    // Without migrations the version is still rejected.
+    let err = load_cache(&cache_path, db.runtime(), &[&*input])
+        .await
+        .unwrap_err();
+    assert!(matches!(&*err, PicanteError::Cache { .. }), "{err}");
+
+    let migrations: Vec<Box<dyn Migration>> = vec![Box::new(RenameTxt)];
+    assert!(
+        load_cache_with(&cache_path, db.runtime(), &[&*input], &migrations)
+            .await
+            .unwrap()
+    );
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn caches_round_trip_through_in_memory_buffers() {
+    use picante::persist::{load_cache_from, save_cache_to};
+
+    init_tracing();
+
+    let db = TestDb::default();
+    let input: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    input.set(&db, "a".into(), "hello".into());
+
+    let mut buf: Vec<u8> = Vec::new();
+    save_cache_to(
+        &mut buf,
         db.runtime(),
-        &[&*input, &*derived],
-        &CacheLoadOptions {
-            max_bytes: None,
-            on_corrupt: OnCorruptCache::Error,
-        },
+        &[&*input],
+        &CacheSaveOptions::default(),
     )
     .await
     .unwrap();
+    assert!(!buf.is_empty());
 
-    assert!(ok);
-    assert_eq!(db.runtime().current_revision(), Revision(123));
+    let db2 = TestDb::default();
+    let input2: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    load_cache_from(buf.as_slice(), db2.runtime(), &[&*input2])
+        .await
+        .unwrap();
+    assert_eq!(
+        input2.get(&db2, &"a".to_string()).unwrap().as_deref(),
+        Some("hello")
+    );
+    assert_eq!(
+        db2.runtime().current_revision(),
+        db.runtime().current_revision()
+    );
+
+    // Sidecars need a file to live next to.
+    let options = CacheSaveOptions::default().with_value_store(ValueStorePolicy::SidecarAbove(0));
+    let err = save_cache_to(Vec::new(), db.runtime(), &[&*input], &options)
+        .await
+        .unwrap_err();
+    assert!(matches!(&*err, PicanteError::Cache { .. }), "{err}");
+}
+
+/// An input that counts how often its records are serialized.
+struct CountingInput {
+    inner: Arc<InputIngredient<String, String>>,
+    saves: std::sync::atomic::AtomicUsize,
+}
+
+impl CountingInput {
+    fn new(id: u32, name: &'static str) -> Self {
+        Self {
+            inner: Arc::new(InputIngredient::new(QueryKindId(id), name)),
+            saves: std::sync::atomic::AtomicUsize::new(0),
+        }
+    }
+
+    fn saves(&self) -> usize {
+        self.saves.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+impl picante::persist::PersistableIngredient for CountingInput {
+    fn kind(&self) -> QueryKindId {
+        self.inner.kind()
+    }
+    fn kind_name(&self) -> &'static str {
+        self.inner.kind_name()
+    }
+    fn section_type(&self) -> SectionType {
+        SectionType::Input
+    }
+    fn clear(&self) {
+        picante::persist::PersistableIngredient::clear(&*self.inner)
+    }
+    fn save_records(&self) -> futures::future::BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+        self.saves.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        self.inner.save_records()
+    }
+    fn load_records(&self, records: Vec<Vec<u8>>) -> PicanteResult<()> {
+        self.inner.load_records(records)
+    }
+    fn is_dirty(&self, since: Revision) -> bool {
+        self.inner.is_dirty(since)
+    }
+}
+
+#[tokio::test]
+async fn incremental_saves_reuse_clean_sections() {
+    use picante::persist::{load_cache, save_cache_incremental};
+
+    init_tracing();
+
+    let cache_path = temp_file("picante-incremental.bin");
+    let _ = tokio::fs::remove_file(&cache_path).await;
+
+    let db = TestDb::default();
+    let (a, b) = (CountingInput::new(1, "A"), CountingInput::new(2, "B"));
+    a.inner.set(&db, "k".into(), "a1".into());
+    b.inner.set(&db, "k".into(), "b1".into());
+
+    // Nothing to reuse yet.
+    let options = CacheSaveOptions::default();
+    save_cache_incremental(&cache_path, db.runtime(), &[&a, &b], &options)
+        .await
+        .unwrap();
+    assert_eq!((a.saves(), b.saves()), (1, 1));
+
+    a.inner.set(&db, "k".into(), "a2".into());
+    save_cache_incremental(&cache_path, db.runtime(), &[&a, &b], &options)
+        .await
+        .unwrap();
+    assert_eq!((a.saves(), b.saves()), (2, 1));
+
+    // A freshly loaded ingredient is clean against the file it came from.
+    let db2 = TestDb::default();
+    let (a2, b2) = (CountingInput::new(1, "A"), CountingInput::new(2, "B"));
+    assert!(
+        load_cache(&cache_path, db2.runtime(), &[&a2, &b2])
+            .await
+            .unwrap()
+    );
+    assert_eq!(
+        a2.inner.get(&db2, &"k".to_string()).unwrap().as_deref(),
+        Some("a2")
+    );
+    assert_eq!(
+        b2.inner.get(&db2, &"k".to_string()).unwrap().as_deref(),
+        Some("b1")
+    );
+    save_cache_incremental(&cache_path, db2.runtime(), &[&a2, &b2], &options)
+        .await
+        .unwrap();
+    assert_eq!((a2.saves(), b2.saves()), (0, 0));
 
     let _ = tokio::fs::remove_file(&cache_path).await;
 }
 
 #[tokio::test]
-async fn load_cache_kind_name_mismatch_is_warning() {
+async fn incremental_saves_redo_cleared_and_truncated_sections() {
+    use picante::persist::{PersistableIngredient, load_cache, save_cache_incremental};
+
     init_tracing();
 
-    let cache_path = temp_file("picante-kind-name-mismatch.bin");
+    let cache_path = temp_file("picante-incremental-redo.bin");
+    let _ = tokio::fs::remove_file(&cache_path).await;
 
-    let cache = CacheFile {
-        format_version: 1,
-        current_revision: 1,
-        sections: vec![Section {
-            kind_id: 1,
-            kind_name: "NotText".to_string(),
-            section_type: SectionType::Input,
-            records: Vec::new(),
-        }],
-    };
+    let db = TestDb::default();
+    let a = CountingInput::new(1, "A");
+    a.inner.set(&db, "k1".into(), "v1".into());
+    a.inner.set(&db, "k2".into(), "v2".into());
+    // How many entries a fresh database gets from the file.
+    async fn loaded_len(path: &std::path::Path) -> usize {
+        let db = TestDb::default();
+        let a = CountingInput::new(1, "A");
+        assert!(load_cache(path, db.runtime(), &[&a]).await.unwrap());
+        a.inner.get_all().len()
+    }
 
-    let bytes = facet_postcard::to_vec(&cache).unwrap();
-    tokio::fs::write(&cache_path, bytes).await.unwrap();
+    // A save cut down by size limits isn't reused once the limits are lifted.
+    let limited = CacheSaveOptions::default().with_max_records_per_section(1);
+    save_cache_incremental(&cache_path, db.runtime(), &[&a], &limited)
+        .await
+        .unwrap();
+    assert_eq!(loaded_len(&cache_path).await, 1);
+    let options = CacheSaveOptions::default();
+    save_cache_incremental(&cache_path, db.runtime(), &[&a], &options)
+        .await
+        .unwrap();
+    assert_eq!(a.saves(), 2);
+    assert_eq!(loaded_len(&cache_path).await, 2);
+
+    // Clearing leaves no tombstones, but still makes the ingredient dirty.
+    PersistableIngredient::clear(&a);
+    save_cache_incremental(&cache_path, db.runtime(), &[&a], &options)
+        .await
+        .unwrap();
+    assert_eq!(a.saves(), 3);
+    assert_eq!(loaded_len(&cache_path).await, 0);
+
+    let _ = tokio::fs::remove_file(&cache_path).await;
+}
+
+#[tokio::test]
+async fn loading_streams_records_into_ingredients() {
+    use picante::persist::{PersistableIngredient, load_cache};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    init_tracing();
+
+    // Only accepts records one at a time, counting them.
+    struct Streaming {
+        inner: Arc<InputIngredient<String, String>>,
+        streamed: AtomicUsize,
+    }
+    impl PersistableIngredient for Streaming {
+        fn kind(&self) -> QueryKindId {
+            self.inner.kind()
+        }
+        fn kind_name(&self) -> &'static str {
+            self.inner.kind_name()
+        }
+        fn section_type(&self) -> SectionType {
+            SectionType::Input
+        }
+        fn clear(&self) {
+            PersistableIngredient::clear(&*self.inner)
+        }
+        fn save_records(&self) -> futures::future::BoxFuture<'_, PicanteResult<Vec<Vec<u8>>>> {
+            self.inner.save_records()
+        }
+        fn load_records(&self, _records: Vec<Vec<u8>>) -> PicanteResult<()> {
+            panic!("records should be streamed");
+        }
+        fn load_record_stream(
+            &self,
+            records: Box<dyn Iterator<Item = Vec<u8>> + '_>,
+        ) -> PicanteResult<()> {
+            let records = records.inspect(|_| {
+                self.streamed.fetch_add(1, Ordering::SeqCst);
+            });
+            self.inner.load_record_stream(Box::new(records))
+        }
+    }
 
+    let cache_path = temp_file("picante-streaming.bin");
     let db = TestDb::default();
     let input: Arc<InputIngredient<String, String>> =
         Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
-
-    // Kind name mismatches should be treated as corrupt cache
-    let result = load_cache_with_options(
+    for i in 0..100 {
+        input.set(&db, format!("k{i}"), format!("v{i}"));
+    }
+    save_cache_with_options(
         &cache_path,
         db.runtime(),
         &[&*input],
-        &CacheLoadOptions {
-            max_bytes: None,
-            on_corrupt: OnCorruptCache::Ignore,
-        },
+        &CacheSaveOptions::default(),
     )
     .await
     .unwrap();
 
-    // With OnCorruptCache::Ignore, we get Ok(false)
-    assert!(!result);
+    let db2 = TestDb::default();
+    let streaming = Streaming {
+        inner: Arc::new(InputIngredient::new(QueryKindId(1), "Text")),
+        streamed: AtomicUsize::new(0),
+    };
+    assert!(
+        load_cache(&cache_path, db2.runtime(), &[&streaming])
+            .await
+            .unwrap()
+    );
+    assert_eq!(streaming.streamed.load(Ordering::SeqCst), 100);
+    let value = streaming.inner.get(&db2, &"k42".to_string()).unwrap();
+    assert_eq!(value.as_deref(), Some("v42"));
+    assert_eq!(
+        db2.runtime().current_revision(),
+        db.runtime().current_revision()
+    );
+
     let _ = tokio::fs::remove_file(&cache_path).await;
 }
 
@@ -403,11 +1666,7 @@ async fn save_with_options_using_convenience_methods() -> PicanteResult<()> {
     // Save with size limit
     db.save_to_cache_with_options(
         &cache_path,
-        &CacheSaveOptions {
-            max_bytes: Some(2048),
-            max_records_per_section: None,
-            max_record_bytes: None,
-        },
+        &CacheSaveOptions::default().with_max_bytes(2048),
     )
     .await?;
 
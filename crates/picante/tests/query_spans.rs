@@ -0,0 +1,178 @@
+//! Tests for the `picante.query` spans emitted around derived query accesses.
+
+use parking_lot::Mutex;
+use picante::{
+    DerivedIngredient, DynIngredient, HasRuntime, IngredientLookup, IngredientRegistry,
+    InputIngredient, PicanteError, QueryKindId, Runtime,
+};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use tracing::Subscriber;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+#[derive(Default)]
+struct TestDb {
+    runtime: Runtime,
+    ingredients: IngredientRegistry<TestDb>,
+}
+
+impl HasRuntime for TestDb {
+    fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+}
+
+impl IngredientLookup for TestDb {
+    fn ingredient(&self, kind: QueryKindId) -> Option<&dyn DynIngredient<Self>> {
+        self.ingredients.ingredient(kind)
+    }
+}
+
+#[derive(Debug, Default)]
+struct RecordedSpan {
+    parent: Option<usize>,
+    fields: HashMap<&'static str, String>,
+}
+
+#[derive(Default)]
+struct Recorded {
+    spans: Vec<RecordedSpan>,
+    /// Live span id -> index into `spans` (ids are reused once a span closes).
+    live: HashMap<u64, usize>,
+}
+
+/// Records every `picante.query` span with its fields and parent.
+#[derive(Clone, Default)]
+struct SpanRecorder(Arc<Mutex<Recorded>>);
+
+impl SpanRecorder {
+    fn take(&self) -> Vec<RecordedSpan> {
+        std::mem::take(&mut self.0.lock().spans)
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut HashMap<&'static str, String>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name(), format!("{value:?}"));
+    }
+}
+
+impl<S> Layer<S> for SpanRecorder
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != "picante.query" {
+            return;
+        }
+        let parent_id = attrs.parent().cloned().or_else(|| {
+            attrs
+                .is_contextual()
+                .then(|| ctx.current_span().id().cloned())
+                .flatten()
+        });
+
+        let mut recorded = self.0.lock();
+        let parent = parent_id.and_then(|p| recorded.live.get(&p.into_u64()).copied());
+        let mut span = RecordedSpan {
+            parent,
+            ..Default::default()
+        };
+        attrs.record(&mut FieldVisitor(&mut span.fields));
+        let index = recorded.spans.len();
+        recorded.spans.push(span);
+        recorded.live.insert(id.into_u64(), index);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+        let mut recorded = self.0.lock();
+        let Some(&index) = recorded.live.get(&id.into_u64()) else {
+            return;
+        };
+        values.record(&mut FieldVisitor(&mut recorded.spans[index].fields));
+    }
+
+    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+        self.0.lock().live.remove(&id.into_u64());
+    }
+}
+
+#[tokio::test]
+async fn query_spans_record_outcome_and_nest() {
+    let recorder = SpanRecorder::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+    let mut db = TestDb::default();
+
+    let text: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.ingredients.register(text.clone());
+
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let text = text.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "Len", move |db, key| {
+            let text = text.clone();
+            Box::pin(async move {
+                match text.get(db, &key)? {
+                    Some(s) => Ok(s.len() as u64),
+                    None => Err(Arc::new(PicanteError::Cache {
+                        message: format!("no text for {key}"),
+                    })),
+                }
+            })
+        }))
+    };
+    db.ingredients.register(len.clone());
+
+    let double: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let len = len.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(3), "Double", move |db, key| {
+            let len = len.clone();
+            Box::pin(async move { Ok(len.get(db, key).await? * 2) })
+        }))
+    };
+    db.ingredients.register(double.clone());
+
+    text.set(&db, "a".into(), "hello".into());
+
+    assert_eq!(double.get(&db, "a".into()).await.unwrap(), 10);
+    let spans = recorder.take();
+    assert_eq!(spans.len(), 2);
+    let (outer, inner) = (&spans[0], &spans[1]);
+    assert_eq!(outer.fields["kind_name"], "Double");
+    assert_eq!(outer.fields["outcome"], "recomputed");
+    assert!(outer.fields.contains_key("duration_us"));
+    assert_eq!(outer.parent, None);
+    assert_eq!(inner.fields["kind_name"], "Len");
+    assert_eq!(inner.fields["outcome"], "recomputed");
+    assert_eq!(inner.parent, Some(0));
+
+    assert_eq!(double.get(&db, "a".into()).await.unwrap(), 10);
+    let spans = recorder.take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].fields["outcome"], "hit");
+
+    // An unrelated input write leaves the cached values valid.
+    text.set(&db, "b".into(), "other".into());
+    assert_eq!(double.get(&db, "a".into()).await.unwrap(), 10);
+    let spans = recorder.take();
+    assert_eq!(spans[0].fields["outcome"], "validated");
+
+    assert!(len.get(&db, "missing".into()).await.is_err());
+    let spans = recorder.take();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].fields["outcome"], "error");
+    assert_eq!(spans[0].fields["otel.status_code"], "ERROR");
+    assert!(spans[0].fields["otel.status_message"].contains("no text for missing"));
+}
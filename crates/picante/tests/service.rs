@@ -0,0 +1,70 @@
+#![cfg(feature = "tower")]
+
+use picante::db::{DynIngredient, IngredientLookup, IngredientRegistry};
+use picante::error::PicanteError;
+use picante::ingredient::{DerivedIngredient, InputIngredient};
+use picante::key::QueryKindId;
+use picante::runtime::{HasRuntime, Runtime};
+use picante::service::{QueryService, Service};
+use std::sync::Arc;
+
+#[derive(Default)]
+struct TestDb {
+    runtime: Runtime,
+    ingredients: IngredientRegistry<TestDb>,
+}
+
+impl HasRuntime for TestDb {
+    fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+}
+
+impl IngredientLookup for TestDb {
+    fn ingredient(&self, kind: QueryKindId) -> Option<&dyn DynIngredient<Self>> {
+        self.ingredients.ingredient(kind)
+    }
+}
+
+#[tokio::test]
+async fn query_service_calls_get() {
+    let mut db = TestDb::default();
+
+    let text: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(QueryKindId(1), "Text"));
+    db.ingredients.register(text.clone());
+
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let text = text.clone();
+        Arc::new(DerivedIngredient::new(QueryKindId(2), "Len", move |db, key| {
+            let text = text.clone();
+            Box::pin(async move {
+                match text.get(db, &key)? {
+                    Some(s) => Ok(s.len() as u64),
+                    None => Err(Arc::new(PicanteError::Cache {
+                        message: format!("no text for {key}"),
+                    })),
+                }
+            })
+        }))
+    };
+    db.ingredients.register(len.clone());
+
+    text.set(&db, "a".into(), "hello".into());
+
+    let db = Arc::new(db);
+    let mut service = QueryService::new(db.clone(), len.clone());
+
+    std::future::poll_fn(|cx| service.poll_ready(cx))
+        .await
+        .unwrap();
+    // The response future owns everything it needs, so it can be spawned.
+    let response = tokio::spawn(service.call("a".into()));
+    assert_eq!(response.await.unwrap().unwrap(), 5);
+
+    let err = service.clone().call("missing".into()).await.unwrap_err();
+    assert!(matches!(&*err, PicanteError::Cache { .. }));
+
+    text.set(&*db, "a".into(), "hello world".into());
+    assert_eq!(service.call("a".into()).await.unwrap(), 11);
+}
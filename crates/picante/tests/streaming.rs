@@ -0,0 +1,169 @@
+use futures::StreamExt;
+use picante::PicanteError;
+use picante::ingredient::{StreamCachePolicy, StreamProgress, StreamingDerived};
+use picante::key::QueryKindId;
+use picante::runtime::{HasRuntime, Runtime};
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct TestDb {
+    runtime: Runtime,
+}
+
+impl HasRuntime for TestDb {
+    fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+}
+
+type Counter = StreamingDerived<TestDb, String, u32>;
+
+/// A stream of 0..5 that records the `already_produced` of every call.
+fn counter(policy: StreamCachePolicy) -> (Counter, Arc<Mutex<Vec<usize>>>) {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let ingredient = {
+        let calls = calls.clone();
+        StreamingDerived::new(
+            QueryKindId(1),
+            "Counter",
+            move |_db: &TestDb, _key: String, already_produced: usize| {
+                calls.lock().unwrap().push(already_produced);
+                futures::stream::iter((already_produced as u32..5).map(Ok)).boxed()
+            },
+        )
+        .with_cache_policy(policy)
+    };
+    (ingredient, calls)
+}
+
+async fn collect(ingredient: &Counter, db: &TestDb) -> Vec<u32> {
+    ingredient
+        .stream(db, "k".into())
+        .map(|item| item.unwrap())
+        .collect()
+        .await
+}
+
+/// Read `n` items, then drop the stream as a cancelled consumer would.
+async fn take(ingredient: &Counter, db: &TestDb, n: usize) -> Vec<u32> {
+    let mut stream = ingredient.stream(db, "k".into());
+    let mut items = Vec::new();
+    for _ in 0..n {
+        items.push(stream.next().await.unwrap().unwrap());
+    }
+    items
+}
+
+#[tokio::test]
+async fn completed_streams_are_replayed_at_the_same_revision() {
+    let db = TestDb::default();
+    let (counter, calls) = counter(StreamCachePolicy::DiscardOnCancel);
+
+    assert_eq!(collect(&counter, &db).await, vec![0, 1, 2, 3, 4]);
+    assert_eq!(
+        counter.progress(&db, &"k".into()),
+        Some(StreamProgress::Complete { produced: 5 })
+    );
+    assert_eq!(collect(&counter, &db).await, vec![0, 1, 2, 3, 4]);
+    assert_eq!(*calls.lock().unwrap(), vec![0]);
+
+    db.runtime.bump_revision();
+    assert_eq!(counter.progress(&db, &"k".into()), None);
+    assert_eq!(collect(&counter, &db).await, vec![0, 1, 2, 3, 4]);
+    assert_eq!(*calls.lock().unwrap(), vec![0, 0]);
+}
+
+#[tokio::test]
+async fn cancelled_streams_restart_by_default() {
+    let db = TestDb::default();
+    let (counter, calls) = counter(StreamCachePolicy::default());
+
+    assert_eq!(take(&counter, &db, 2).await, vec![0, 1]);
+    assert_eq!(counter.progress(&db, &"k".into()), None);
+    assert_eq!(collect(&counter, &db).await, vec![0, 1, 2, 3, 4]);
+    assert_eq!(*calls.lock().unwrap(), vec![0, 0]);
+}
+
+#[tokio::test]
+async fn cached_partial_streams_resume_where_they_stopped() {
+    let db = TestDb::default();
+    let (counter, calls) = counter(StreamCachePolicy::CachePartial);
+
+    assert_eq!(take(&counter, &db, 2).await, vec![0, 1]);
+    assert_eq!(
+        counter.progress(&db, &"k".into()),
+        Some(StreamProgress::Partial { produced: 2 })
+    );
+
+    // Cancelled again, partway through the replay: nothing is lost.
+    assert_eq!(take(&counter, &db, 1).await, vec![0]);
+    assert_eq!(
+        counter.progress(&db, &"k".into()),
+        Some(StreamProgress::Partial { produced: 2 })
+    );
+
+    assert_eq!(take(&counter, &db, 3).await, vec![0, 1, 2]);
+    assert_eq!(
+        counter.progress(&db, &"k".into()),
+        Some(StreamProgress::Partial { produced: 3 })
+    );
+
+    assert_eq!(collect(&counter, &db).await, vec![0, 1, 2, 3, 4]);
+    assert_eq!(
+        counter.progress(&db, &"k".into()),
+        Some(StreamProgress::Complete { produced: 5 })
+    );
+    assert_eq!(*calls.lock().unwrap(), vec![0, 2, 3]);
+}
+
+#[tokio::test]
+async fn partial_streams_are_discarded_once_their_revision_is_stale() {
+    let db = TestDb::default();
+    let (counter, calls) = counter(StreamCachePolicy::CachePartial);
+
+    assert_eq!(take(&counter, &db, 2).await, vec![0, 1]);
+    db.runtime.bump_revision();
+    assert_eq!(counter.progress(&db, &"k".into()), None);
+    assert_eq!(collect(&counter, &db).await, vec![0, 1, 2, 3, 4]);
+    assert_eq!(*calls.lock().unwrap(), vec![0, 0]);
+
+    // A stream cancelled after the revision moved on is not cached either.
+    let stale = StreamingDerived::new(
+        QueryKindId(2),
+        "Stale",
+        |db: &TestDb, _key: String, already_produced: usize| {
+            db.runtime.bump_revision();
+            futures::stream::iter((already_produced as u32..5).map(Ok)).boxed()
+        },
+    )
+    .with_cache_policy(StreamCachePolicy::CachePartial);
+    let mut stream = stale.stream(&db, "k".into());
+    assert_eq!(stream.next().await.unwrap().unwrap(), 0);
+    drop(stream);
+    assert_eq!(stale.progress(&db, &"k".into()), None);
+}
+
+#[tokio::test]
+async fn failed_streams_are_not_cached() {
+    let db = TestDb::default();
+    let failing: Counter = StreamingDerived::new(
+        QueryKindId(3),
+        "Failing",
+        |_db: &TestDb, _key: String, _already_produced: usize| {
+            futures::stream::iter(vec![
+                Ok(0),
+                Err(Arc::new(PicanteError::Cache {
+                    message: "producer failed".into(),
+                })),
+                Ok(1),
+            ])
+            .boxed()
+        },
+    )
+    .with_cache_policy(StreamCachePolicy::CachePartial);
+
+    let items: Vec<_> = failing.stream(&db, "k".into()).collect().await;
+    assert_eq!(items.len(), 2);
+    assert!(items[1].is_err());
+    assert_eq!(failing.progress(&db, &"k".into()), None);
+}
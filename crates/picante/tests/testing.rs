@@ -0,0 +1,94 @@
+#![cfg(feature = "testing")]
+
+use picante::db::{DynIngredient, IngredientLookup, IngredientRegistry};
+use picante::ingredient::{DerivedIngredient, InputIngredient};
+use picante::key::QueryKindId;
+use picante::runtime::{HasRuntime, Runtime};
+use picante::testing::RecomputeRecorder;
+use std::sync::Arc;
+
+const TEXT: QueryKindId = QueryKindId(1);
+const LEN: QueryKindId = QueryKindId(2);
+const PARITY: QueryKindId = QueryKindId(3);
+const CONFIG: QueryKindId = QueryKindId(4);
+const SHOUT: QueryKindId = QueryKindId(5);
+
+#[derive(Default)]
+struct TestDb {
+    runtime: Runtime,
+    ingredients: IngredientRegistry<TestDb>,
+}
+
+impl HasRuntime for TestDb {
+    fn runtime(&self) -> &Runtime {
+        &self.runtime
+    }
+}
+
+impl IngredientLookup for TestDb {
+    fn ingredient(&self, kind: QueryKindId) -> Option<&dyn DynIngredient<Self>> {
+        self.ingredients.ingredient(kind)
+    }
+}
+
+#[tokio::test]
+async fn recorder_reports_exactly_what_recomputed() {
+    let mut db = TestDb::default();
+
+    let text: Arc<InputIngredient<String, String>> = Arc::new(InputIngredient::new(TEXT, "Text"));
+    db.ingredients.register(text.clone());
+    let config: Arc<InputIngredient<String, String>> =
+        Arc::new(InputIngredient::new(CONFIG, "Config"));
+    db.ingredients.register(config.clone());
+
+    let len: Arc<DerivedIngredient<TestDb, String, u64>> = {
+        let text = text.clone();
+        Arc::new(DerivedIngredient::new(LEN, "Len", move |db, key| {
+            let text = text.clone();
+            Box::pin(async move { Ok(text.get(db, &key)?.unwrap_or_default().len() as u64) })
+        }))
+    };
+    db.ingredients.register(len.clone());
+
+    let parity: Arc<DerivedIngredient<TestDb, String, bool>> = {
+        let len = len.clone();
+        Arc::new(DerivedIngredient::new(PARITY, "Parity", move |db, key| {
+            let len = len.clone();
+            Box::pin(async move { Ok(len.get(db, key).await? % 2 == 0) })
+        }))
+    };
+    db.ingredients.register(parity.clone());
+
+    let shout: Arc<DerivedIngredient<TestDb, String, String>> = {
+        let config = config.clone();
+        Arc::new(DerivedIngredient::new(SHOUT, "Shout", move |db, key| {
+            let config = config.clone();
+            Box::pin(async move { Ok(config.get(db, &key)?.unwrap_or_default().to_uppercase()) })
+        }))
+    };
+    db.ingredients.register(shout.clone());
+
+    text.set(&db, "a".into(), "hello".into());
+    config.set(&db, "x".into(), "hey".into());
+
+    let mut recorder = RecomputeRecorder::start(db.runtime());
+    assert!(!parity.get(&db, "a".into()).await.unwrap());
+    assert_eq!(shout.get(&db, "x".into()).await.unwrap(), "HEY");
+    recorder.assert_recomputed(&[LEN, PARITY, SHOUT]);
+    recorder.clear();
+
+    // Same length: `Len` reruns, but its value is unchanged so `Parity` is only revalidated,
+    // and `Shout` doesn't depend on `Text` at all.
+    text.set(&db, "a".into(), "world".into());
+    assert!(!parity.get(&db, "a".into()).await.unwrap());
+    assert_eq!(shout.get(&db, "x".into()).await.unwrap(), "HEY");
+    recorder.assert_recomputed(&[LEN]);
+    recorder.assert_not_recomputed(&[PARITY, SHOUT]);
+    assert_eq!(recorder.count(LEN), 1);
+    recorder.clear();
+
+    // Nothing changed: nothing runs.
+    assert!(!parity.get(&db, "a".into()).await.unwrap());
+    recorder.assert_recomputed(&[]);
+    assert!(recorder.recomputed().is_empty());
+}